@@ -1,3 +1,91 @@
 fn main() {
-    tauri_build::build()
+    // Commands listed here need a permission from a capability; see `permissions/`
+    // for the groups an embedding app can grant.
+    tauri_build::try_build(tauri_build::Attributes::new().app_manifest(
+        tauri_build::AppManifest::new().commands(&[
+            "connect",
+            "test_connection",
+            "disconnect",
+            "reconnect",
+            "clone_connection",
+            "switch_database",
+            "ping",
+            "set_health_monitor",
+            "warmup",
+            "get_acquire_stats",
+            "list_connections",
+            "pool_status",
+            "get_capability",
+            "get_server_fingerprint",
+            "parse_jdbc_url",
+            "quote_identifier",
+            "quote_literal",
+            "touch_activity",
+            "list_profiles",
+            "connect_profile",
+            "forget_session_password",
+            "execute",
+            "request_confirmation",
+            "invalidate_cache",
+            "execute_many",
+            "begin_transaction",
+            "execute_in_transaction",
+            "commit",
+            "rollback",
+            "savepoint",
+            "rollback_to",
+            "release",
+            "open_session",
+            "close_session",
+            "cancel_query",
+            "submit_query",
+            "job_status",
+            "job_result",
+            "cancel_job",
+            "open_cursor",
+            "fetch_next",
+            "close_cursor",
+            "explain",
+            "call_procedure",
+            "validate",
+            "watch_query",
+            "unwatch_query",
+            "execute_binary",
+            "execute_stream",
+            "ack_stream",
+            "cancel_stream",
+            "fetch_spilled",
+            "release_spilled",
+            "get_tables",
+            "get_columns",
+            "get_sequences",
+            "refresh_schema",
+            "get_table_data",
+            "safe_select",
+            "estimate_count",
+            "get_active_sessions",
+            "get_locks",
+            "run_maintenance",
+            "diff_schemas",
+            "export_audit_log",
+            "verify_audit_log",
+            "set_auto_lock",
+            "lock_now",
+            "credentials_status",
+            "unlock_credentials",
+            "lock_credentials",
+            "change_master_password",
+            "save_credential",
+            "get_credential",
+            "delete_credential",
+            "save_keychain_secret",
+            "get_keychain_secret",
+            "delete_keychain_secret",
+            "set_vault_config",
+            "save_profile",
+            "delete_profile",
+            "terminate_session",
+        ]),
+    ))
+    .expect("failed to run tauri-build");
 }
@@ -1,28 +1,162 @@
 #![allow(dead_code)]
+pub mod arrow_ipc;
+pub mod audit;
+pub mod autolock;
+pub mod azure_ad;
+pub mod batch;
+mod bigquery;
+pub mod browse;
+pub mod cancel;
+mod cassandra;
+mod clickhouse;
+pub mod clone;
+pub mod cloudsql;
+pub mod column_meta;
+mod config;
+mod convert;
+pub mod count;
+pub mod credentials;
+pub mod cursor;
+pub mod dedicated;
+mod duckdb;
+pub mod explain;
+mod firebird;
+pub mod flavor;
+mod gcp;
+pub mod health;
+pub mod introspect;
+pub mod jdbc;
+pub mod jobs;
+pub mod keychain;
+pub mod maintenance;
+pub mod masking;
+mod mongo;
+mod odbc;
+mod oracle;
+mod params;
+pub mod payload;
+pub mod pinning;
+pub mod policy;
+pub mod pool_stats;
+pub mod pooling;
+pub mod probe;
+pub mod procedures;
+pub mod profiles;
+pub mod proxy;
+pub mod reconnect;
+mod redact;
+mod redis;
+pub mod registry;
+pub mod result_cache;
+pub mod retry;
+pub mod sandbox;
+mod schema_cache;
+pub mod schema_diff;
+pub mod secrets;
+pub mod sessions;
+mod single_flight;
+pub mod spill;
+pub mod sql;
+pub mod stream;
+mod surreal;
+pub mod switch;
+pub mod timeout;
+pub mod timing;
+pub mod tls;
+pub mod transactions;
+mod tunnel;
+pub mod validate;
+pub mod watch;
+
+use audit::AuditLog;
+use autolock::AutoLock;
+use azure_ad::AzureAdOptions;
+use cancel::RunningQuery;
+use cloudsql::CloudSqlOptions;
+use column_meta::ColumnMeta;
+use convert::{
+    collect_rows, CollectLimits, Collected, QueryOutput, ResultFormat, RowSet, Truncation,
+};
+use credentials::CredentialStore;
+use cursor::CursorSlot;
+use dedicated::{DedicatedConnection, SessionSlot};
+use flavor::Flavor;
+use futures_util::future::{join_all, try_join_all};
+use health::HealthMonitor;
+use introspect::ColumnInfo;
+use jobs::JobQueue;
+use masking::MaskingOptions;
+use params::bind_value;
+use policy::{Capability, Confirmations, ConnectScope, StatementPolicy};
+use pool_stats::AcquireStats;
+use pooling::PoolSettings;
+use profiles::SessionSecrets;
+use proxy::ProxyOptions;
+use reconnect::ReconnectPolicy;
+use registry::{ConnectionMeta, Environment};
+use result_cache::{CacheKey, ResultCache};
+use retry::RetryPolicy;
+use sandbox::SandboxOptions;
+use schema_cache::SchemaCache;
+use secrets::VaultConfig;
 use serde::{Deserialize, Serialize};
-use serde_json::{Map, Value};
-use sqlx::mysql::{MySqlPool, MySqlPoolOptions};
-use sqlx::postgres::{PgPool, PgPoolOptions};
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
-use sqlx::{Column, Row};
-use std::collections::HashMap;
-use std::sync::Mutex;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tauri::State;
-
-#[allow(dead_code)]
-#[derive(Debug, Serialize, Deserialize)]
+use single_flight::SingleFlight;
+use spill::SpillFile;
+use sql::StatementKind;
+use sqlx::mysql::{MySqlConnectOptions, MySqlPool, MySqlPoolOptions};
+use sqlx::pool::PoolOptions;
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::{Database, Pool};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::ipc::CommandScope;
+use tauri::{AppHandle, Emitter, State};
+use timeout::{PgStatementTimeout, QueryControl};
+use timing::QueryTimer;
+use tls::TlsOptions;
+use tokio::sync::Semaphore;
+use transactions::{OpenTransaction, TransactionSlot};
+use tunnel::Tunnel;
+use uuid::Uuid;
+use watch::WatchSlot;
+use zeroize::Zeroizing;
+
+/// A connection described field by field, as an alternative to a connection string.
+/// `params` become the URL's query string (attributes for ODBC). For SQLite and DuckDB,
+/// `database` is the file path; for BigQuery, `host` is the project.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DbConfig {
-    pub conn_string: String,
-    pub name: Option<String>,
+    pub kind: DbKind,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    /// Sent separately from the connection string, so it needs no escaping. May be a
+    /// `vault:path#field` reference; `options.password` takes precedence.
+    pub password: Option<String>,
+    pub database: Option<String>,
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DbKind {
     Postgres,
     MySql,
     Sqlite,
-    Unknown,
+    Oracle,
+    Mongo,
+    DuckDb,
+    ClickHouse,
+    Redis,
+    Cassandra,
+    Surreal,
+    BigQuery,
+    Firebird,
+    Odbc,
 }
 
 #[derive(Clone)]
@@ -30,115 +164,1053 @@ pub enum Connection {
     Postgres(PgPool),
     MySql(MySqlPool),
     Sqlite(SqlitePool),
+    Oracle(::oracle::pool::Pool),
+    Mongo(mongodb::Database),
+    DuckDb(duckdb::Database),
+    ClickHouse(clickhouse::Client),
+    Redis(::redis::aio::ConnectionManager),
+    Cassandra(Arc<scylla::client::session::Session>),
+    Surreal(surreal::Client),
+    BigQuery(bigquery::Client),
+    Firebird(firebird::Pool),
+    Odbc(odbc::Pool),
+}
+
+impl Connection {
+    pub fn kind(&self) -> DbKind {
+        match self {
+            Connection::Postgres(_) => DbKind::Postgres,
+            Connection::MySql(_) => DbKind::MySql,
+            Connection::Sqlite(_) => DbKind::Sqlite,
+            Connection::Oracle(_) => DbKind::Oracle,
+            Connection::Mongo(_) => DbKind::Mongo,
+            Connection::DuckDb(_) => DbKind::DuckDb,
+            Connection::ClickHouse(_) => DbKind::ClickHouse,
+            Connection::Redis(_) => DbKind::Redis,
+            Connection::Cassandra(_) => DbKind::Cassandra,
+            Connection::Surreal(_) => DbKind::Surreal,
+            Connection::BigQuery(_) => DbKind::BigQuery,
+            Connection::Firebird(_) => DbKind::Firebird,
+            Connection::Odbc(_) => DbKind::Odbc,
+        }
+    }
+
+    /// Error for a feature only implemented for the sqlx-backed databases.
+    pub fn unsupported(&self, feature: &str) -> String {
+        format!("{} is not available on {:?}", feature, self.kind())
+    }
+
+    /// Acquire `n` connections at once and release them, leaving them idle in the pool.
+    /// `n` should not exceed the pool maximum.
+    pub async fn warm(&self, n: u32) -> Result<u32, String> {
+        match self {
+            Connection::Postgres(pool) => warm_pool(pool, n).await,
+            Connection::MySql(pool) => warm_pool(pool, n).await,
+            Connection::Sqlite(pool) => warm_pool(pool, n).await,
+            Connection::Oracle(pool) => oracle::warm(pool, n).await,
+            Connection::Mongo(db) => mongo::warm(db, n).await,
+            Connection::ClickHouse(client) => clickhouse::warm(client, n).await,
+            Connection::Surreal(client) => surreal::warm(client, n).await,
+            Connection::Firebird(pool) => firebird::warm(pool, n).await,
+            Connection::Odbc(pool) => odbc::warm(pool, n).await,
+            // In-process: there is nothing to open ahead of time.
+            Connection::DuckDb(_) => Ok(1),
+            // One multiplexed connection, opened by `connect`.
+            Connection::Redis(_) => Ok(1),
+            // The driver keeps a connection per shard of every node open on its own.
+            Connection::Cassandra(_) => Ok(1),
+            // Queries run as jobs on Google's side; there is nothing to hold open.
+            Connection::BigQuery(_) => Ok(1),
+        }
+    }
+}
+
+async fn warm_pool<DB: Database>(pool: &Pool<DB>, n: u32) -> Result<u32, String> {
+    let n = n.min(pool.options().get_max_connections());
+    let held = try_join_all((0..n).map(|_| pool.acquire()))
+        .await
+        .map_err(|e| e.to_string())?;
+    drop(held);
+    Ok(pool.size())
 }
 
 pub struct AppState {
     pub connections: Mutex<HashMap<String, Connection>>,
+    /// Ids of connections still being opened, so an alias can't be claimed twice.
+    opening: Mutex<HashSet<String>>,
+    /// The connection string, secrets unresolved, and options each connection was
+    /// opened with, for `reconnect`.
+    connect_specs: Mutex<HashMap<String, (String, ConnectionOptions)>>,
+    /// Local relays of connections made through a proxy or to Cloud SQL, by connection id.
+    tunnels: Mutex<HashMap<String, Tunnel>>,
+    /// Open transactions by transaction id.
+    transactions: Mutex<HashMap<String, TransactionSlot>>,
+    /// Dedicated connections by session id.
+    dedicated: Mutex<HashMap<String, SessionSlot>>,
+    /// Queries run with a `query_id`, by query id, while they run.
+    running: Mutex<HashMap<String, RunningQuery>>,
+    /// Open cursors by cursor id.
+    cursors: Mutex<HashMap<String, CursorSlot>>,
+    /// Running watches by watch id.
+    watches: Mutex<HashMap<String, WatchSlot>>,
+    pub streams: Mutex<HashMap<String, Arc<Semaphore>>>,
+    pub jobs: JobQueue,
+    pub schema_cache: SchemaCache,
+    pub result_cache: ResultCache,
+    pub table_flights: SingleFlight<Vec<String>>,
+    pub column_flights: SingleFlight<Vec<ColumnInfo>>,
+    /// Spilled results by spill id, with the id of the connection that produced them.
+    pub spills: Mutex<HashMap<String, (String, SpillFile)>>,
+    pub acquire_stats: Mutex<HashMap<String, Arc<AcquireStats>>>,
+    pub credentials: CredentialStore,
+    pub capabilities: Mutex<HashMap<String, Capability>>,
+    pub confirmations: Confirmations,
+    /// Connections opened with `read_only`.
+    pub read_only: Mutex<HashSet<String>>,
+    /// Connections opened with `single_statement`.
+    pub single_statement: Mutex<HashSet<String>>,
+    /// Lazy connections whose flavor and server version are still to be read.
+    pub undescribed: Mutex<HashSet<String>>,
+    pub policies: Mutex<HashMap<String, StatementPolicy>>,
+    pub sandboxes: Mutex<HashMap<String, SandboxOptions>>,
+    pub masking: Mutex<HashMap<String, MaskingOptions>>,
+    pub flavors: Mutex<HashMap<String, Flavor>>,
+    pub pool_settings: Mutex<HashMap<String, PoolSettings>>,
+    pub connection_meta: Mutex<HashMap<String, ConnectionMeta>>,
+    pub auto_lock: AutoLock,
+    pub health: HealthMonitor,
+    pub vault: Mutex<VaultConfig>,
+    pub audit_log: AuditLog,
+    pub session_secrets: SessionSecrets,
+    data_dir: OnceLock<PathBuf>,
+    /// Set once during app setup; used to emit events from outside command handlers.
+    app: OnceLock<AppHandle>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
             connections: Mutex::new(HashMap::new()),
+            opening: Mutex::new(HashSet::new()),
+            connect_specs: Mutex::new(HashMap::new()),
+            tunnels: Mutex::new(HashMap::new()),
+            transactions: Mutex::new(HashMap::new()),
+            dedicated: Mutex::new(HashMap::new()),
+            running: Mutex::new(HashMap::new()),
+            cursors: Mutex::new(HashMap::new()),
+            watches: Mutex::new(HashMap::new()),
+            streams: Mutex::new(HashMap::new()),
+            jobs: JobQueue::default(),
+            schema_cache: SchemaCache::default(),
+            result_cache: ResultCache::default(),
+            table_flights: SingleFlight::default(),
+            column_flights: SingleFlight::default(),
+            spills: Mutex::new(HashMap::new()),
+            acquire_stats: Mutex::new(HashMap::new()),
+            credentials: CredentialStore::default(),
+            capabilities: Mutex::new(HashMap::new()),
+            confirmations: Confirmations::default(),
+            read_only: Mutex::new(HashSet::new()),
+            single_statement: Mutex::new(HashSet::new()),
+            undescribed: Mutex::new(HashSet::new()),
+            policies: Mutex::new(HashMap::new()),
+            sandboxes: Mutex::new(HashMap::new()),
+            masking: Mutex::new(HashMap::new()),
+            flavors: Mutex::new(HashMap::new()),
+            pool_settings: Mutex::new(HashMap::new()),
+            connection_meta: Mutex::new(HashMap::new()),
+            auto_lock: AutoLock::new(),
+            health: HealthMonitor::default(),
+            vault: Mutex::new(VaultConfig::default()),
+            audit_log: AuditLog::default(),
+            session_secrets: SessionSecrets::default(),
+            data_dir: OnceLock::new(),
+            app: OnceLock::new(),
         }
     }
+
+    pub fn set_app_handle(&self, app: AppHandle) {
+        let _ = self.app.set(app);
+    }
+
+    pub fn set_data_dir(&self, dir: PathBuf) {
+        let _ = self.data_dir.set(dir);
+    }
+
+    /// Path of a file in the app data directory, creating the directory if needed.
+    pub fn data_path(&self, name: &str) -> Result<PathBuf, String> {
+        let dir = self
+            .data_dir
+            .get()
+            .ok_or("App data directory is not available")?;
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        Ok(dir.join(name))
+    }
+
+    /// Emit an event to the frontend. A no-op before setup has run.
+    pub fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        if let Some(app) = self.app.get() {
+            let _ = app.emit(event, payload);
+        }
+    }
+
+    /// Clone the pool for `id` out of the mutex so callers never hold the guard across awaits.
+    pub fn connection(&self, id: &str) -> Result<Connection, String> {
+        self.auto_lock.touch();
+        self.peek_connection(id)
+    }
+
+    /// The connection `id`, without counting the lookup as user activity.
+    pub fn peek_connection(&self, id: &str) -> Result<Connection, String> {
+        let guard = self.connections.lock().unwrap();
+        guard
+            .get(id)
+            .cloned()
+            .ok_or_else(|| "Connection not found".to_string())
+    }
 }
 
-fn detect_db_kind(conn_string: &str) -> DbKind {
-    let s = conn_string.to_lowercase();
-    if s.starts_with("postgres://")
-        || s.starts_with("postgresql://")
-        || s.contains("postgresql")
-        || s.contains("postgres")
-    {
-        DbKind::Postgres
-    } else if s.starts_with("mysql://") || s.contains("mysql") {
-        DbKind::MySql
-    } else if s.starts_with("sqlite://")
-        || s.starts_with("file:")
-        || s.contains(".sqlite")
-        || s.ends_with(".db")
-    {
-        DbKind::Sqlite
-    } else {
-        DbKind::Unknown
+fn generate_id(prefix: &str) -> String {
+    format!("{}_{}", prefix, Uuid::new_v4().simple())
+}
+
+/// Connection string schemes and the backend each selects.
+const SCHEMES: &[(&str, DbKind)] = &[
+    ("postgres", DbKind::Postgres),
+    ("postgresql", DbKind::Postgres),
+    ("mysql", DbKind::MySql),
+    ("sqlite", DbKind::Sqlite),
+    ("file", DbKind::Sqlite),
+    ("oracle", DbKind::Oracle),
+    ("mongodb", DbKind::Mongo),
+    ("mongodb+srv", DbKind::Mongo),
+    ("duckdb", DbKind::DuckDb),
+    ("clickhouse", DbKind::ClickHouse),
+    ("clickhouses", DbKind::ClickHouse),
+    ("redis", DbKind::Redis),
+    ("rediss", DbKind::Redis),
+    ("cassandra", DbKind::Cassandra),
+    ("scylla", DbKind::Cassandra),
+    ("surreal", DbKind::Surreal),
+    ("surreals", DbKind::Surreal),
+    ("bigquery", DbKind::BigQuery),
+    ("firebird", DbKind::Firebird),
+    ("odbc", DbKind::Odbc),
+];
+
+/// The backend for `conn_string`, from its URL scheme. A bare file path ending in
+/// `.duckdb`, `.db`, `.sqlite` or `.sqlite3` opens that file.
+fn detect_db_kind(conn_string: &str) -> Result<DbKind, String> {
+    let conn_string = conn_string.trim();
+    // RFC 3986 schemes; a single letter is a Windows drive, not a scheme.
+    let scheme = conn_string
+        .split_once(':')
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| {
+            scheme.len() > 1
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        });
+    if let Some(scheme) = scheme {
+        return SCHEMES
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(scheme))
+            .map(|(_, kind)| *kind)
+            .ok_or_else(|| unsupported_scheme(&format!("Unsupported scheme {}://", scheme)));
     }
+    let extension = std::path::Path::new(conn_string)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("duckdb") => Ok(DbKind::DuckDb),
+        Some("db" | "sqlite" | "sqlite3") => Ok(DbKind::Sqlite),
+        _ => Err(unsupported_scheme("The connection string has no scheme")),
+    }
+}
+
+fn unsupported_scheme(problem: &str) -> String {
+    let schemes: Vec<&str> = SCHEMES.iter().map(|(scheme, _)| *scheme).collect();
+    format!(
+        "{}; supported schemes are {}, or set the kind explicitly",
+        problem,
+        schemes.join(", ")
+    )
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionOptions {
+    /// Stable id for the connection, e.g. `reporting`, instead of a generated one.
+    /// Rejected while another open connection has it.
+    pub alias: Option<String>,
+    /// Display name for the connection list.
+    pub name: Option<String>,
+    /// CSS color for the connection's badge, e.g. `#d33`.
+    pub color: Option<String>,
+    /// Deployment the connection points at.
+    pub environment: Option<Environment>,
+    /// Backend to use, overriding detection from the connection string's scheme.
+    pub kind: Option<DbKind>,
+    /// Database to open instead of the one named in the connection string; see
+    /// `switch_database`.
+    pub database: Option<String>,
+    /// Postgres schema to resolve unqualified names in, set as the `search_path`.
+    pub schema: Option<String>,
+    /// Connections the pool keeps open at all times. They are opened before `connect`
+    /// returns so the first query doesn't pay for the handshake.
+    pub min_connections: Option<u32>,
+    /// Most connections the pool opens at once (5 by default). For HTTP backends this
+    /// caps the idle connections kept per host.
+    pub max_connections: Option<u32>,
+    /// How long a query waits for a free pooled connection (5 seconds by default).
+    /// Applies to the sqlx backends and Oracle.
+    pub acquire_timeout_ms: Option<u64>,
+    /// Close pooled connections idle this long, so the server doesn't drop them
+    /// first. Applies to the sqlx backends, Oracle, MongoDB, Firebird and ODBC; the
+    /// last two default to 10 minutes.
+    pub idle_timeout_ms: Option<u64>,
+    /// Close pooled connections this old once they are returned. Applies to the sqlx
+    /// backends, Oracle, Firebird and ODBC; the last two default to 30 minutes.
+    pub max_lifetime_ms: Option<u64>,
+    /// Prepared statements cached per pooled connection (sqlx defaults to 100).
+    pub statement_cache_capacity: Option<usize>,
+    /// Password overriding the one in the DSN. May be a `vault:path#field` reference,
+    /// as may the connection string itself.
+    pub password: Option<String>,
+    /// What the connection may do; `admin` (no restriction) when unset.
+    pub capability: Option<Capability>,
+    /// Refuse writes whatever `capability` says: `execute` rejects them, and the
+    /// session itself is read-only on Postgres, MySQL, SQLite and DuckDB files.
+    pub read_only: bool,
+    /// Reject any script holding more than one statement, or on Redis more than one
+    /// command, so input interpolated into a statement by the frontend can't append
+    /// another after a `;` or a line break.
+    pub single_statement: bool,
+    /// Statements run on every new pooled connection, e.g. `SET search_path TO app`
+    /// or `PRAGMA foreign_keys = ON`, so session settings survive pool recycling.
+    /// Postgres, MySQL and SQLite only.
+    pub init_sql: Vec<String>,
+    /// Register the connection without contacting the server; the pool connects on
+    /// first use, when the server's flavor and version are read too. Postgres, MySQL
+    /// and SQLite only.
+    pub lazy: bool,
+    /// Statement kinds allowed or denied on this connection, on top of `capability`.
+    pub policy: Option<StatementPolicy>,
+    /// TLS mode, CA bundle and client certificate, overriding any `sslmode`/`ssl-mode`
+    /// and certificate parameters in the DSN.
+    pub tls: Option<TlsOptions>,
+    /// SOCKS5 or HTTP CONNECT proxy to connect through. Supported for Postgres, MySQL
+    /// and the HTTP backends: ClickHouse, SurrealDB and BigQuery.
+    pub proxy: Option<ProxyOptions>,
+    /// Connect to a Cloud SQL instance by its connection name, with a service account
+    /// and short-lived client certificates. Postgres and MySQL only.
+    pub cloud_sql: Option<CloudSqlOptions>,
+    /// Sign in with Azure AD and use the access token as the password, renewing it
+    /// before it expires. Postgres and MySQL only.
+    pub azure_ad: Option<AzureAdOptions>,
+    /// Inject row limits and a statement timeout for exploratory use.
+    pub sandbox: Option<SandboxOptions>,
+    /// Record every statement run on this connection in the tamper-evident audit log.
+    pub audit: bool,
+    /// Columns whose values are masked in results.
+    pub masking: Option<MaskingOptions>,
+    /// Reconnect and retry when a query fails because the connection was lost.
+    pub auto_reconnect: Option<ReconnectPolicy>,
+    /// Retry scripts that fail with a transient error, such as a deadlock or a
+    /// serialization failure. Off when unset.
+    pub retry: Option<RetryPolicy>,
+}
+
+/// Connection errors, with TLS failures rephrased and credentials scrubbed.
+fn connect_error(e: sqlx::Error) -> String {
+    redact::redact(&tls::describe_error(&e).unwrap_or_else(|| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn connect(state: State<'_, AppState>, conn_string: String) -> Result<String, String> {
-    let kind = detect_db_kind(&conn_string);
+pub async fn connect(
+    state: State<'_, AppState>,
+    scope: CommandScope<ConnectScope>,
+    conn_string: Option<String>,
+    config: Option<DbConfig>,
+    options: Option<ConnectionOptions>,
+) -> Result<String, String> {
+    let mut options = options.unwrap_or_default();
+    policy::limit_capability(&mut options, &scope);
+    let conn_string = connection_target(conn_string, config, &mut options)?;
+    open_connection(&state, &conn_string, options).await
+}
+
+/// The connection string `connect` was given, or one built from `config` or a JDBC URL,
+/// whose password then becomes the default for `options.password`.
+fn connection_target(
+    conn_string: Option<String>,
+    config: Option<DbConfig>,
+    options: &mut ConnectionOptions,
+) -> Result<String, String> {
+    let config = match (conn_string, config) {
+        (Some(conn_string), None) if !jdbc::is_jdbc(&conn_string) => return Ok(conn_string),
+        (Some(url), None) => jdbc::parse(&url)?,
+        (None, Some(config)) => config,
+        _ => return Err("Pass either a connection string or a config".to_string()),
+    };
+    if options.password.is_none() {
+        options.password = config.password.clone();
+    }
+    config.to_conn_string()
+}
 
-    let id = format!(
-        "conn_{}",
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
+/// Open a pool for `conn_string` and register it, returning the new connection id:
+/// the alias from `options`, or a generated one.
+pub async fn open_connection(
+    state: &AppState,
+    conn_string: &str,
+    options: ConnectionOptions,
+) -> Result<String, String> {
+    let id = match options.alias.as_deref().map(str::trim) {
+        Some("") => return Err("Connection aliases cannot be empty".to_string()),
+        Some(alias) => alias.to_string(),
+        None => generate_id("conn"),
+    };
+    {
+        let connections = state.connections.lock().unwrap();
+        let mut opening = state.opening.lock().unwrap();
+        if connections.contains_key(&id) || !opening.insert(id.clone()) {
+            return Err(format!("A connection named '{}' is already open", id));
+        }
+    }
+    let opened = open_as(state, &id, conn_string, options).await;
+    state.opening.lock().unwrap().remove(&id);
+    opened.map(|()| id)
+}
+
+async fn open_as(
+    state: &AppState,
+    id: &str,
+    conn_string: &str,
+    options: ConnectionOptions,
+) -> Result<(), String> {
+    let (conn, resolved, tunnel) = open_pool(state, conn_string, &options).await?;
+    let spec = (conn_string.to_string(), options.clone());
+    state
+        .pool_settings
+        .lock()
+        .unwrap()
+        .insert(id.to_string(), PoolSettings::from_options(&options)?);
+    if options.lazy {
+        state.undescribed.lock().unwrap().insert(id.to_string());
+    } else if let Some(flavor) = flavor::detect(&conn).await? {
+        state.flavors.lock().unwrap().insert(id.to_string(), flavor);
+    }
+    let meta = registry::describe(state, id, &conn, &resolved, &options).await;
+    state
+        .connection_meta
+        .lock()
+        .unwrap()
+        .insert(id.to_string(), meta);
+
+    let capability = match options.read_only {
+        true => Some(Capability::ReadOnly),
+        false => options.capability,
+    };
+    if let Some(capability) = capability {
+        state
+            .capabilities
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), capability);
+    }
+    if options.read_only {
+        state.read_only.lock().unwrap().insert(id.to_string());
+    }
+    if options.single_statement {
+        state
+            .single_statement
+            .lock()
+            .unwrap()
+            .insert(id.to_string());
+    }
+    if let Some(masking) = options.masking {
+        state
+            .masking
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), masking);
+    }
+    if options.audit {
+        state.audit_log.enable(id);
+    }
+    if let Some(sandbox) = options.sandbox {
+        state
+            .sandboxes
+            .lock()
             .unwrap()
-            .as_millis()
-    );
+            .insert(id.to_string(), sandbox);
+    }
+    if let Some(policy) = options.policy {
+        state.set_policy(id, policy);
+    }
+    state
+        .connect_specs
+        .lock()
+        .unwrap()
+        .insert(id.to_string(), spec);
+    if let Some(tunnel) = tunnel {
+        state.tunnels.lock().unwrap().insert(id.to_string(), tunnel);
+    }
+    state
+        .connections
+        .lock()
+        .unwrap()
+        .insert(id.to_string(), conn);
+
+    Ok(())
+}
+
+/// Run `statements` on each connection the pool opens, before it is first used.
+fn run_on_connect<DB: Database>(pool: PoolOptions<DB>, statements: Vec<String>) -> PoolOptions<DB>
+where
+    for<'c> &'c mut DB::Connection: sqlx::Executor<'c, Database = DB>,
+{
+    if statements.is_empty() {
+        return pool;
+    }
+    let statements = Arc::new(statements);
+    pool.after_connect(move |conn, _| {
+        let statements = statements.clone();
+        Box::pin(async move {
+            for statement in statements.iter() {
+                sqlx::Executor::execute(&mut *conn, statement.as_str()).await?;
+            }
+            Ok(())
+        })
+    })
+}
+
+/// Open the pool, or with `lazy` only set it up: its first connection is then made
+/// when it is first used.
+async fn connect_pool<DB: Database>(
+    pool: PoolOptions<DB>,
+    connect_options: <DB::Connection as sqlx::Connection>::Options,
+    lazy: bool,
+) -> Result<Pool<DB>, String> {
+    if lazy {
+        return Ok(pool.connect_lazy_with(connect_options));
+    }
+    pool.connect_with(connect_options)
+        .await
+        .map_err(connect_error)
+}
+
+/// Resolve secrets in `conn_string` and open its pool or client without registering
+/// it, returning the connection, the resolved connection string and, when connecting
+/// through a proxy or to Cloud SQL needs one, the relay the connection depends on.
+async fn open_pool(
+    state: &AppState,
+    conn_string: &str,
+    options: &ConnectionOptions,
+) -> Result<(Connection, Zeroizing<String>, Option<Tunnel>), String> {
+    let conn_string = secrets::resolve(state, conn_string).await?;
+    let password = match &options.password {
+        Some(password) => Some(secrets::resolve(state, password).await?),
+        None => None,
+    };
+    let kind = match options.kind {
+        Some(kind) => kind,
+        None => detect_db_kind(&conn_string)?,
+    };
+    let conn_string = match &options.database {
+        Some(database) => Zeroizing::new(switch::with_database(kind, &conn_string, database)?),
+        None => conn_string,
+    };
+    if options.schema.is_some() && kind != DbKind::Postgres {
+        return Err("A default schema can only be set for Postgres".to_string());
+    }
+    let pool = PoolSettings::from_options(options)?;
+    let statement_timeout_ms = options
+        .sandbox
+        .as_ref()
+        .and_then(|sandbox| sandbox.statement_timeout_ms);
+    let proxy = match &options.proxy {
+        Some(proxy) => Some(proxy.resolve(state).await?),
+        None => None,
+    };
+    if proxy.is_some() {
+        if !matches!(
+            kind,
+            DbKind::Postgres
+                | DbKind::MySql
+                | DbKind::ClickHouse
+                | DbKind::Surreal
+                | DbKind::BigQuery
+        ) {
+            return Err(format!(
+                "Proxies are not supported for {:?} connections",
+                kind
+            ));
+        }
+        // Pinning reads the certificate over a direct connection.
+        if options.tls.as_ref().is_some_and(|tls| tls.pin.is_some()) {
+            return Err("Certificate pinning can't be used through a proxy".to_string());
+        }
+    }
+    if options.cloud_sql.is_some() {
+        if !matches!(kind, DbKind::Postgres | DbKind::MySql) {
+            return Err(format!(
+                "Cloud SQL is not supported for {:?} connections",
+                kind
+            ));
+        }
+        // The connector sets up TLS itself and dials the instance directly.
+        if options.tls.is_some() || proxy.is_some() {
+            return Err("Cloud SQL connections can't use tls or proxy options".to_string());
+        }
+    }
+    if options.azure_ad.is_some() {
+        if !matches!(kind, DbKind::Postgres | DbKind::MySql) {
+            return Err(format!(
+                "Azure AD sign-in is not supported for {:?} connections",
+                kind
+            ));
+        }
+        if password.is_some() {
+            return Err("Azure AD sign-in replaces the password; remove it".to_string());
+        }
+    }
+    if !options.init_sql.is_empty() {
+        if !matches!(kind, DbKind::Postgres | DbKind::MySql | DbKind::Sqlite) {
+            return Err(format!(
+                "Init statements are not supported for {:?} connections",
+                kind
+            ));
+        }
+        if options.read_only {
+            for statement in &options.init_sql {
+                policy::check_read_only(statement)?;
+            }
+        }
+    }
+    if options.lazy {
+        if !matches!(kind, DbKind::Postgres | DbKind::MySql | DbKind::Sqlite) {
+            return Err(format!(
+                "Lazy connections are not supported for {:?} connections",
+                kind
+            ));
+        }
+        // Pinning reads the certificate from the server up front.
+        if options.tls.as_ref().is_some_and(|tls| tls.pin.is_some()) {
+            return Err("Certificate pinning can't be used with a lazy connection".to_string());
+        }
+    }
+    let azure_ad = match &options.azure_ad {
+        Some(azure_ad) => Some(azure_ad::Credential::sign_in(state, azure_ad).await?),
+        None => None,
+    };
+    let mut tunnel = None;
 
-    match kind {
+    let conn = match kind {
         DbKind::Postgres => {
-            let pool = PgPoolOptions::new()
-                .max_connections(5)
-                .acquire_timeout(Duration::from_secs(5))
-                .connect(&conn_string)
-                .await
-                .map_err(|e| e.to_string())?;
-            state
-                .connections
-                .lock()
-                .unwrap()
-                .insert(id.clone(), Connection::Postgres(pool));
+            let mut connect_options =
+                PgConnectOptions::from_str(&conn_string).map_err(redact::error)?;
+            if let Some(password) = &password {
+                connect_options = connect_options.password(password);
+            }
+            if let Some((_, token)) = &azure_ad {
+                connect_options = connect_options.password(&token.value);
+            }
+            if let Some(tls) = &options.tls {
+                connect_options = tls.apply_pg(connect_options)?;
+                if let (Some(pin), None) = (&tls.pin, connect_options.get_socket()) {
+                    let (host, port) = (connect_options.get_host(), connect_options.get_port());
+                    pinning::verify(kind, host, port, pin).await?;
+                }
+            }
+            if let Some(capacity) = options.statement_cache_capacity {
+                connect_options = connect_options.statement_cache_capacity(capacity);
+            }
+            if let Some(ms) = statement_timeout_ms {
+                connect_options = connect_options.options([("statement_timeout", ms.to_string())]);
+            }
+            if options.read_only {
+                connect_options =
+                    connect_options.options([("default_transaction_read_only", "on")]);
+            }
+            if let Some(schema) = &options.schema {
+                // Spaces separate startup options unless escaped.
+                let search_path = sql::quote_ident(kind, schema).replace(' ', "\\ ");
+                connect_options = connect_options.options([("search_path", search_path)]);
+            }
+            if let Some(proxy) = &proxy {
+                let (tunneled, opened) = proxy::tunnel_pg(proxy, connect_options).await?;
+                connect_options = tunneled;
+                tunnel = Some(opened);
+            }
+            if let Some(cloud_sql) = &options.cloud_sql {
+                let (tunneled, opened) =
+                    cloudsql::tunnel_pg(state, cloud_sql, connect_options).await?;
+                connect_options = tunneled;
+                tunnel = Some(opened);
+            }
+            let pool_options =
+                run_on_connect(pool.apply(PgPoolOptions::new()), options.init_sql.clone());
+            let pool = connect_pool(pool_options, connect_options, options.lazy).await?;
+            if let Some((credential, token)) = azure_ad {
+                azure_ad::keep_fresh(
+                    pool.clone(),
+                    credential,
+                    token.expires,
+                    PgConnectOptions::password,
+                );
+            }
+            Connection::Postgres(pool)
         }
         DbKind::MySql => {
-            let pool = MySqlPoolOptions::new()
-                .max_connections(5)
-                .acquire_timeout(Duration::from_secs(5))
-                .connect(&conn_string)
-                .await
-                .map_err(|e| e.to_string())?;
-            state
-                .connections
-                .lock()
-                .unwrap()
-                .insert(id.clone(), Connection::MySql(pool));
+            let mut connect_options =
+                MySqlConnectOptions::from_str(&conn_string).map_err(redact::error)?;
+            if let Some(password) = &password {
+                connect_options = connect_options.password(password);
+            }
+            if let Some((_, token)) = &azure_ad {
+                connect_options = connect_options.password(&token.value);
+            }
+            if let Some(tls) = &options.tls {
+                connect_options = tls.apply_mysql(connect_options)?;
+                if let (Some(pin), None) = (&tls.pin, connect_options.get_socket()) {
+                    let (host, port) = (connect_options.get_host(), connect_options.get_port());
+                    pinning::verify(kind, host, port, pin).await?;
+                }
+            }
+            if let Some(capacity) = options.statement_cache_capacity {
+                connect_options = connect_options.statement_cache_capacity(capacity);
+            }
+            if let Some(proxy) = &proxy {
+                let (tunneled, opened) = proxy::tunnel_mysql(proxy, connect_options).await?;
+                connect_options = tunneled;
+                tunnel = Some(opened);
+            }
+            if let Some(cloud_sql) = &options.cloud_sql {
+                let (tunneled, opened) =
+                    cloudsql::tunnel_mysql(state, cloud_sql, connect_options).await?;
+                connect_options = tunneled;
+                tunnel = Some(opened);
+            }
+            let mut session = Vec::new();
+            if let Some(ms) = statement_timeout_ms {
+                session.push(format!("SET SESSION max_execution_time = {}", ms));
+            }
+            session.extend(options.init_sql.iter().cloned());
+            if options.read_only {
+                session.push("SET SESSION TRANSACTION READ ONLY".to_string());
+            }
+            let pool_options = run_on_connect(pool.apply(MySqlPoolOptions::new()), session);
+            let pool = connect_pool(pool_options, connect_options, options.lazy).await?;
+            if let Some((credential, token)) = azure_ad {
+                azure_ad::keep_fresh(
+                    pool.clone(),
+                    credential,
+                    token.expires,
+                    MySqlConnectOptions::password,
+                );
+            }
+            Connection::MySql(pool)
         }
         DbKind::Sqlite => {
-            let pool = SqlitePoolOptions::new()
-                .max_connections(5)
-                .connect(&conn_string)
+            let mut connect_options =
+                SqliteConnectOptions::from_str(&conn_string).map_err(redact::error)?;
+            if let Some(capacity) = options.statement_cache_capacity {
+                connect_options = connect_options.statement_cache_capacity(capacity);
+            }
+            if options.read_only {
+                connect_options = connect_options.read_only(true);
+            }
+            let pool_options = run_on_connect(
+                pool.apply(SqlitePoolOptions::new()),
+                options.init_sql.clone(),
+            );
+            let pool = connect_pool(pool_options, connect_options, options.lazy).await?;
+            Connection::Sqlite(pool)
+        }
+        DbKind::Oracle => {
+            if options.tls.is_some() {
+                return Err(
+                    "Configure TLS for Oracle in the connect string, e.g. tcps://host:2484/service"
+                        .to_string(),
+                );
+            }
+            let pool = oracle::connect(
+                &conn_string,
+                password.as_deref().map(String::as_str),
+                &pool,
+                options.statement_cache_capacity,
+            )
+            .await
+            .map_err(|e| redact::redact(&e))?;
+            Connection::Oracle(pool)
+        }
+        DbKind::Mongo => {
+            if options.tls.is_some() {
+                return Err(
+                    "Configure TLS for MongoDB in the connection string, e.g. ?tls=true"
+                        .to_string(),
+                );
+            }
+            let db = mongo::connect(&conn_string, password.as_deref().map(String::as_str), &pool)
                 .await
-                .map_err(|e| e.to_string())?;
-            state
-                .connections
-                .lock()
-                .unwrap()
-                .insert(id.clone(), Connection::Sqlite(pool));
+                .map_err(|e| redact::redact(&e))?;
+            Connection::Mongo(db)
         }
-        DbKind::Unknown => return Err("Unsupported database type".to_string()),
+        DbKind::DuckDb => {
+            Connection::DuckDb(duckdb::connect(&conn_string, options.read_only).await?)
+        }
+        DbKind::ClickHouse => {
+            let client = clickhouse::connect(
+                &conn_string,
+                password.as_deref().map(String::as_str),
+                options.tls.as_ref(),
+                proxy.as_ref(),
+                pool.max_connections,
+            )
+            .await
+            .map_err(|e| redact::redact(&e))?;
+            Connection::ClickHouse(client)
+        }
+        DbKind::Redis => {
+            if options.tls.is_some() {
+                return Err(
+                    "Configure TLS for Redis with a rediss:// connection string".to_string()
+                );
+            }
+            let conn = redis::connect(&conn_string, password.as_deref().map(String::as_str))
+                .await
+                .map_err(|e| redact::redact(&e))?;
+            Connection::Redis(conn)
+        }
+        DbKind::Cassandra => {
+            if options.tls.is_some() {
+                return Err("TLS is not supported for Cassandra connections".to_string());
+            }
+            let session = cassandra::connect(&conn_string, password.as_deref().map(String::as_str))
+                .await
+                .map_err(|e| redact::redact(&e))?;
+            Connection::Cassandra(session)
+        }
+        DbKind::Surreal => {
+            let client = surreal::connect(
+                &conn_string,
+                password.as_deref().map(String::as_str),
+                options.tls.as_ref(),
+                proxy.as_ref(),
+                pool.max_connections,
+            )
+            .await
+            .map_err(|e| redact::redact(&e))?;
+            Connection::Surreal(client)
+        }
+        DbKind::BigQuery => {
+            if options.tls.is_some() {
+                return Err("BigQuery connections always use verified TLS".to_string());
+            }
+            let client = bigquery::connect(
+                &conn_string,
+                password.as_deref().map(String::as_str),
+                proxy.as_ref(),
+            )
+            .await
+            .map_err(|e| redact::redact(&e))?;
+            Connection::BigQuery(client)
+        }
+        DbKind::Firebird => {
+            if options.tls.is_some() {
+                return Err("TLS is not supported for Firebird connections".to_string());
+            }
+            let pool =
+                firebird::connect(&conn_string, password.as_deref().map(String::as_str), &pool)
+                    .await
+                    .map_err(|e| redact::redact(&e))?;
+            Connection::Firebird(pool)
+        }
+        DbKind::Odbc => {
+            if options.tls.is_some() {
+                return Err(
+                    "Configure TLS for ODBC in the connection string, as the driver documents"
+                        .to_string(),
+                );
+            }
+            let pool = odbc::connect(&conn_string, password.as_deref().map(String::as_str), &pool)
+                .await
+                .map_err(|e| redact::redact(&e))?;
+            Connection::Odbc(pool)
+        }
+    };
+
+    if pool.min_connections > 0 && !options.lazy {
+        conn.warm(pool.min_connections).await?;
     }
+    Ok((conn, conn_string, tunnel))
+}
 
-    Ok(id)
+/// Pre-open up to `n` pooled connections, e.g. right after `connect` on a high-latency
+/// link. Returns the number of connections the pool holds afterwards.
+#[tauri::command]
+pub async fn warmup(state: State<'_, AppState>, id: String, n: u32) -> Result<u32, String> {
+    let conn = state.connection(&id)?;
+    conn.warm(n.min(state.max_connections(&id, &conn))).await
 }
 
 #[tauri::command]
 pub async fn disconnect(state: State<'_, AppState>, id: String) -> Result<bool, String> {
+    if close_connection(&state, &id).await {
+        Ok(true)
+    } else {
+        Err("Connection not found".to_string())
+    }
+}
+
+/// Close the pool for `id` and drop everything held for it. Returns `false` if there
+/// was no such connection.
+pub async fn close_connection(state: &AppState, id: &str) -> bool {
     // Remove the connection while the mutex is held, then drop the guard before awaiting.
     let conn_to_close = {
         let mut connections = state.connections.lock().unwrap();
-        connections.remove(&id)
+        connections.remove(id)
     };
+    state.end_transactions(id);
+    state.end_sessions(id);
+    state.end_cursors(id);
+    state.end_watches(id);
+    state.schema_cache.invalidate(id);
+    state.result_cache.invalidate(id, None);
+    state.acquire_stats.lock().unwrap().remove(id);
+    state.capabilities.lock().unwrap().remove(id);
+    state.read_only.lock().unwrap().remove(id);
+    state.single_statement.lock().unwrap().remove(id);
+    state.undescribed.lock().unwrap().remove(id);
+    state.policies.lock().unwrap().remove(id);
+    state.sandboxes.lock().unwrap().remove(id);
+    state.audit_log.disable(id);
+    state.masking.lock().unwrap().remove(id);
+    state.flavors.lock().unwrap().remove(id);
+    state.pool_settings.lock().unwrap().remove(id);
+    state.connection_meta.lock().unwrap().remove(id);
+    state.connect_specs.lock().unwrap().remove(id);
+    state.tunnels.lock().unwrap().remove(id);
+    state.health.forget(id);
+    spill::release_for_connection(state, id).await;
+
+    match conn_to_close {
+        Some(conn) => conn.close().await,
+        None => return false,
+    }
+    true
+}
 
-    if let Some(conn) = conn_to_close {
-        match conn {
+/// Close every connection so servers end their sessions now rather than when they
+/// notice the sockets are gone. Gives up after `timeout`, leaving the rest to be
+/// dropped with the process.
+pub async fn shutdown_all(state: &AppState, timeout: std::time::Duration) {
+    let ids: Vec<String> = state.connections.lock().unwrap().keys().cloned().collect();
+    let closing = join_all(ids.iter().map(|id| close_connection(state, id)));
+    let _ = tokio::time::timeout(timeout, closing).await;
+}
+
+impl Connection {
+    /// Close the pool or client; queries still running on it may fail.
+    pub async fn close(self) {
+        match self {
             Connection::Postgres(pool) => pool.close().await,
             Connection::MySql(pool) => pool.close().await,
             Connection::Sqlite(pool) => pool.close().await,
+            Connection::Oracle(pool) => oracle::close(pool).await,
+            // Closed once the last handle is dropped.
+            Connection::Mongo(_)
+            | Connection::DuckDb(_)
+            | Connection::ClickHouse(_)
+            | Connection::Redis(_)
+            | Connection::Cassandra(_)
+            | Connection::Surreal(_)
+            | Connection::BigQuery(_)
+            | Connection::Firebird(_)
+            | Connection::Odbc(_) => {}
         }
-        Ok(true)
-    } else {
-        Err("Connection not found".to_string())
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ExecuteOptions {
+    pub format: ResultFormat,
+    /// Approximate bytes of result data held in memory before the rest is spilled to disk.
+    pub memory_budget: Option<usize>,
+    /// A token from `request_confirmation` for this script, needed to run DROP,
+    /// TRUNCATE, ALTER and unfiltered DELETE/UPDATE statements, and any write on a
+    /// connection tagged `production`. Without it such scripts return
+    /// `NeedsConfirmation` instead of running.
+    pub confirmation_token: Option<String>,
+    /// Show masked columns in clear, if the connection allows it.
+    pub unmask: bool,
+    /// Values for `:name` (or `@name`) placeholders, bound rather than spliced into
+    /// the statement. Postgres, MySQL and SQLite only, one statement at a time. A value
+    /// may name its type, as in `{"value": "2024-01-01", "type": "date"}`.
+    pub params: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Run on this session's dedicated connection (see `open_session`) instead of
+    /// whichever pooled connection is free.
+    pub session: Option<String>,
+    /// Register the script under this id while it runs, so `cancel_query` can stop it.
+    pub query_id: Option<String>,
+    /// Cancel the script if it hasn't finished after this many ms, returning
+    /// `TimedOut`. The server is told the limit too where it can be set per query.
+    pub timeout_ms: Option<u64>,
+    /// The LIMIT added to a single SELECT that has none. Defaults to
+    /// `DEFAULT_ROW_LIMIT`; a sandboxed connection uses its own.
+    pub row_limit: Option<u64>,
+    /// Run SELECTs as written, without an injected LIMIT. Ignored on sandboxed
+    /// connections.
+    pub no_limit: bool,
+    /// Rows returned at most; the result is cut short there and reported `Truncated`.
+    /// Defaults to `DEFAULT_MAX_ROWS`.
+    pub max_rows: Option<u64>,
+    /// Approximate bytes of values returned at most, as with `max_rows`. Defaults to
+    /// `DEFAULT_MAX_BYTES`.
+    pub max_bytes: Option<u64>,
+    /// Return the result `Described`, with each column's database type, JSON type and
+    /// nullability. Postgres, MySQL and SQLite only, one statement at a time; the
+    /// statement is described on the server before it runs.
+    pub column_meta: bool,
+    /// Return the result `Timed`, with how long each phase of the query took.
+    pub timings: bool,
+    /// Retry policy for this script, in place of the connection's `retry`. Retries
+    /// are only made on pooled connections; the result is `Retried` when any were.
+    pub retry: Option<RetryPolicy>,
+    /// Serve a read-only script from the result cache when it ran with the same
+    /// parameters and options less than this many ms ago, returning it `Cached`;
+    /// otherwise run it and cache the result for this long. Pooled connections only.
+    pub cache_ttl_ms: Option<u64>,
+    /// Set by the backend for scripts it runs on its own, such as watches: the run
+    /// doesn't count as activity for `auto_lock`, and the result is capped to what
+    /// fits in memory instead of spilling, since nobody pages through it.
+    #[serde(skip)]
+    pub background: bool,
+}
+
+impl ExecuteOptions {
+    fn limits(&self) -> CollectLimits {
+        let mut limits = CollectLimits::default();
+        if let Some(budget) = self.memory_budget {
+            limits.memory_budget = budget;
+        }
+        if let Some(max_rows) = self.max_rows {
+            limits.row_cap = max_rows.min(spill::MAX_SPILLED_ROWS);
+        }
+        if let Some(max_bytes) = self.max_bytes {
+            limits.byte_cap = max_bytes;
+        }
+        if self.background {
+            limits.row_cap = limits.row_cap.min(limits.max_rows as u64);
+            limits.byte_cap = limits.byte_cap.min(limits.memory_budget as u64);
+        }
+        limits
     }
 }
 
@@ -147,174 +1219,414 @@ pub async fn execute(
     state: State<'_, AppState>,
     id: String,
     sql: String,
-) -> Result<Vec<Map<String, Value>>, String> {
-    let conn = {
-        let guard = state.connections.lock().unwrap();
-        guard.get(&id).cloned().ok_or("Connection not found")?
+    options: Option<ExecuteOptions>,
+) -> Result<QueryOutput, String> {
+    execute_on(&state, &id, &sql, &options.unwrap_or_default()).await
+}
+
+/// The shared body of every command that runs caller-supplied SQL.
+async fn execute_on(
+    state: &AppState,
+    id: &str,
+    sql: &str,
+    options: &ExecuteOptions,
+) -> Result<QueryOutput, String> {
+    let Some(session_id) = &options.session else {
+        return execute_via(state, id, sql, options, Route::Pool).await;
     };
+    let slot = state.dedicated_session(session_id, id)?;
+    let mut dedicated = slot.lock().await;
+    let dedicated = dedicated.as_mut().ok_or("The session has been closed")?;
+    execute_via(state, id, sql, options, Route::Dedicated(dedicated)).await
+}
 
-    // Execute the query using the appropriate typed pool and produce a concrete Vec<Map<String, Value>>
-    let results = match conn {
-        Connection::Postgres(pool) => {
-            let rows = sqlx::query(&sql)
-                .fetch_all(&pool)
-                .await
-                .map_err(|e| e.to_string())?;
-
-            let mut results = Vec::new();
-            for row in rows {
-                let mut map = Map::new();
-                for col in row.columns() {
-                    let col_name = col.name();
-
-                    let val: Value = if let Ok(v) = row.try_get::<i64, _>(col.ordinal()) {
-                        Value::Number(v.into())
-                    } else if let Ok(v) = row.try_get::<f64, _>(col.ordinal()) {
-                        if let Some(n) = serde_json::Number::from_f64(v) {
-                            Value::Number(n)
-                        } else {
-                            Value::Null
-                        }
-                    } else if let Ok(v) = row.try_get::<bool, _>(col.ordinal()) {
-                        Value::Bool(v)
-                    } else if let Ok(v) = row.try_get::<String, _>(col.ordinal()) {
-                        Value::String(v)
-                    } else {
-                        Value::Null
-                    };
-
-                    map.insert(col_name.to_string(), val);
-                }
-                results.push(map);
+/// Where `execute_via` runs a script.
+enum Route<'a> {
+    Pool,
+    Transaction(&'a mut OpenTransaction),
+    Dedicated(&'a mut DedicatedConnection),
+}
+
+/// `execute_on`, run on the connection `route` names.
+async fn execute_via(
+    state: &AppState,
+    id: &str,
+    sql: &str,
+    options: &ExecuteOptions,
+    route: Route<'_>,
+) -> Result<QueryOutput, String> {
+    let conn = match options.background {
+        true => state.peek_connection(id)?,
+        false => state.connection(id)?,
+    };
+    registry::describe_on_first_use(state, id, &conn).await?;
+    if options.params.is_some()
+        && !matches!(
+            conn,
+            Connection::Postgres(_) | Connection::MySql(_) | Connection::Sqlite(_)
+        )
+    {
+        return Err(conn.unsupported("Named parameters"));
+    }
+    if options.column_meta
+        && !matches!(
+            conn,
+            Connection::Postgres(_) | Connection::MySql(_) | Connection::Sqlite(_)
+        )
+    {
+        return Err(conn.unsupported("Column metadata"));
+    }
+    if options.timeout_ms.is_some()
+        && matches!(
+            conn,
+            Connection::Mongo(_) | Connection::Redis(_) | Connection::Surreal(_)
+        )
+    {
+        return Err(conn.unsupported("Query timeouts"));
+    }
+    if let Connection::Mongo(db) = &conn {
+        return mongo::execute(state, id, db, sql, options).await;
+    }
+    if let Connection::Redis(redis) = &conn {
+        return redis::execute(state, id, redis, sql, options).await;
+    }
+    if let Connection::Surreal(client) = &conn {
+        return surreal::execute(state, id, client, sql, options).await;
+    }
+    state.check_policy(id, sql)?;
+    flavor::check_script(&conn, state.flavor(id), sql)?;
+    if !state.confirmed(id, sql, options) {
+        let statements = policy::destructive_statements(sql, state.is_production(id));
+        if !statements.is_empty() {
+            return Ok(QueryOutput::NeedsConfirmation {
+                needs_confirmation: true,
+                statements,
+            });
+        }
+    }
+    let cache = match (options.cache_ttl_ms, &route) {
+        (Some(ttl_ms), Route::Pool) if sql::is_read_only(sql) => Some((
+            CacheKey::new(id, sql, options),
+            std::time::Duration::from_millis(ttl_ms),
+        )),
+        _ => None,
+    };
+    if let Some((key, ttl)) = &cache {
+        if let Some((output, cached_age_ms)) = state.result_cache.get(key, *ttl) {
+            return Ok(QueryOutput::Cached {
+                cached_age_ms,
+                result: Box::new(output),
+            });
+        }
+    }
+    let (sql, binds) = match &options.params {
+        Some(params) => {
+            if sql::split_statements(sql).len() > 1 {
+                return Err("Parameters can only be bound to a single statement".to_string());
             }
-            results
+            sql::positional(conn.kind(), sql, params)?
         }
-        Connection::MySql(pool) => {
-            let rows = sqlx::query(&sql)
-                .fetch_all(&pool)
+        None => (sql.to_string(), Vec::new()),
+    };
+    let sql = sql.as_str();
+    let limited = state.limited(id, sql, options);
+    let query = limited.as_ref().map_or(sql, |(rewritten, _)| rewritten);
+    let mut limits = options.limits();
+    limits.masking = state.masking_rules(id, options.unmask)?;
+    let hinted = match (&conn, state.query_timeout_ms(id, options.timeout_ms)) {
+        (Connection::MySql(_), Some(ms)) if options.timeout_ms.is_some() => Some(
+            sql::hint_selects(query, &format!("MAX_EXECUTION_TIME({})", ms)),
+        ),
+        _ => None,
+    };
+    let query = hinted.as_deref().unwrap_or(query);
+    let mut route = route;
+    let column_meta = if options.column_meta {
+        if sql::split_statements(sql).len() > 1 {
+            return Err("Column metadata can only be read for a single statement".to_string());
+        }
+        let described = describe_via(&conn, &mut route, query).await;
+        Some(described.map_err(|e| redact::redact(&e))?)
+    } else {
+        None
+    };
+    // A timeout is enforced by cancelling, so such queries are registered too.
+    let query_id = options
+        .query_id
+        .clone()
+        .or_else(|| options.timeout_ms.map(|_| generate_id("query")));
+    let tracked = query_id
+        .as_deref()
+        .map(|query_id| state.track(query_id, id))
+        .transpose()?;
+    let control = QueryControl {
+        query_id: query_id.as_deref(),
+        timeout_ms: options.timeout_ms,
+    };
+    let timer = options.timings.then(QueryTimer::start);
+    limits.timer = timer.clone();
+    let retry = options.retry.or_else(|| {
+        state
+            .connect_specs
+            .lock()
+            .unwrap()
+            .get(id)
+            .and_then(|(_, options)| options.retry)
+    });
+    let mut retries = 0;
+    let run = async {
+        match route {
+            Route::Pool => {
+                retry::run_with_retry(retry, sql, &mut retries, || {
+                    reconnect::run_with_reconnect(
+                        state,
+                        id,
+                        &conn,
+                        query,
+                        &binds,
+                        limits.clone(),
+                        control,
+                    )
+                })
                 .await
-                .map_err(|e| e.to_string())?;
-
-            let mut results = Vec::new();
-            for row in rows {
-                let mut map = Map::new();
-                for col in row.columns() {
-                    let col_name = col.name();
-
-                    let val: Value = if let Ok(v) = row.try_get::<i64, _>(col.ordinal()) {
-                        Value::Number(v.into())
-                    } else if let Ok(v) = row.try_get::<f64, _>(col.ordinal()) {
-                        if let Some(n) = serde_json::Number::from_f64(v) {
-                            Value::Number(n)
-                        } else {
-                            Value::Null
-                        }
-                    } else if let Ok(v) = row.try_get::<bool, _>(col.ordinal()) {
-                        Value::Bool(v)
-                    } else if let Ok(v) = row.try_get::<String, _>(col.ordinal()) {
-                        Value::String(v)
-                    } else {
-                        Value::Null
-                    };
-
-                    map.insert(col_name.to_string(), val);
-                }
-                results.push(map);
             }
-            results
+            Route::Transaction(transaction) => {
+                let _interrupt = transaction.interruptible(state, control.query_id).await?;
+                limits.acquired();
+                transaction.run(query, &binds, limits).await
+            }
+            Route::Dedicated(dedicated) => {
+                let _interrupt = dedicated.interruptible(state, control.query_id).await?;
+                limits.acquired();
+                dedicated.run(query, &binds, limits).await
+            }
         }
-        Connection::Sqlite(pool) => {
-            let rows = sqlx::query(&sql)
-                .fetch_all(&pool)
-                .await
-                .map_err(|e| e.to_string())?;
-
-            let mut results = Vec::new();
-            for row in rows {
-                let mut map = Map::new();
-                for col in row.columns() {
-                    let col_name = col.name();
-
-                    let val: Value = if let Ok(v) = row.try_get::<i64, _>(col.ordinal()) {
-                        Value::Number(v.into())
-                    } else if let Ok(v) = row.try_get::<f64, _>(col.ordinal()) {
-                        if let Some(n) = serde_json::Number::from_f64(v) {
-                            Value::Number(n)
-                        } else {
-                            Value::Null
-                        }
-                    } else if let Ok(v) = row.try_get::<bool, _>(col.ordinal()) {
-                        Value::Bool(v)
-                    } else if let Ok(v) = row.try_get::<String, _>(col.ordinal()) {
-                        Value::String(v)
-                    } else {
-                        Value::Null
-                    };
-
-                    map.insert(col_name.to_string(), val);
-                }
-                results.push(map);
+    };
+    // Cancels the query once the timeout passes, before `run` lets go of its connection.
+    let deadline = async {
+        match (options.timeout_ms, control.query_id) {
+            (Some(ms), Some(query_id)) => {
+                tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
+                let _ = state.cancel(query_id).await;
             }
-            results
+            _ => std::future::pending().await,
         }
     };
+    let started = std::time::Instant::now();
+    let results = match &tracked {
+        Some((_, cancelled)) => tokio::select! {
+            results = run => results,
+            _ = cancelled.notified() => Err(cancel::CANCELLED.to_string()),
+            _ = deadline => Err(cancel::CANCELLED.to_string()),
+        },
+        None => run.await,
+    };
+    drop(tracked);
+    let timed_out =
+        results.is_err() && timeout::timed_out(options.timeout_ms, started.elapsed().as_millis());
+    let results = match results {
+        Err(_) if timed_out => Err(format!(
+            "The query timed out after {} ms",
+            options.timeout_ms.unwrap_or_default()
+        )),
+        results => results.map_err(|e| redact::redact(&e)),
+    };
+    state.audit(id, query, results.as_ref().err().map(String::as_str));
+    if sql::is_ddl(sql) {
+        state.schema_cache.invalidate(id);
+    }
+    if !sql::is_read_only(sql) {
+        state.result_cache.invalidate(id, None);
+    }
+    if let (true, Some(timeout_ms)) = (timed_out, options.timeout_ms) {
+        return Ok(QueryOutput::TimedOut {
+            timed_out: true,
+            timeout_ms,
+        });
+    }
+    let mut results = results?;
+    if let Collected::Memory(set) = &mut results {
+        keep_affected(set, sql);
+    }
+    let (returned, truncated) = match &results {
+        Collected::Memory(set) => (set.rows.len() as u64, set.truncated),
+        Collected::Spilled(file) => (file.total_rows, file.truncated),
+    };
+    let spilled = matches!(results, Collected::Spilled(_));
+    let shaping = std::time::Instant::now();
+    let mut output = into_output(state, id, results, options.format).await?;
+    if let Some(column_meta) = column_meta {
+        output = QueryOutput::Described {
+            column_meta,
+            result: Box::new(output),
+        };
+    }
+    if let Some(truncated_by) = truncated {
+        let limits = options.limits();
+        output = QueryOutput::Truncated {
+            truncated_by,
+            cap: match truncated_by {
+                Truncation::MaxRows => limits.row_cap,
+                Truncation::MaxBytes => limits.byte_cap,
+            },
+            result: Box::new(output),
+        };
+    }
+    if let Some((_, injected_limit)) = limited {
+        output = QueryOutput::LimitInjected {
+            injected_limit,
+            truncated: returned >= injected_limit,
+            result: Box::new(output),
+        };
+    }
+    // Spilled results are read back once through their spill id, so they aren't kept.
+    if let Some((key, ttl)) = cache {
+        if !spilled && returned <= result_cache::MAX_CACHED_ROWS {
+            state.result_cache.store(key, output.clone(), ttl);
+        }
+    }
+    if retries > 0 {
+        output = QueryOutput::Retried {
+            retries,
+            result: Box::new(output),
+        };
+    }
+    Ok(match timer {
+        Some(timer) => QueryOutput::Timed {
+            timings: timer.timings(shaping),
+            result: Box::new(output),
+        },
+        None => output,
+    })
+}
 
-    Ok(results)
+/// The metadata of `query`'s columns, described on the connection `route` names.
+async fn describe_via(
+    conn: &Connection,
+    route: &mut Route<'_>,
+    query: &str,
+) -> Result<Vec<ColumnMeta>, String> {
+    match route {
+        Route::Pool => column_meta::describe(conn, query).await,
+        Route::Transaction(transaction) => transaction.describe(query).await,
+        Route::Dedicated(dedicated) => dedicated.describe(query).await,
+    }
 }
 
-#[tauri::command]
-pub async fn get_tables(state: State<'_, AppState>, id: String) -> Result<Vec<String>, String> {
-    // Clone the connection (pool + kind) out of the mutex to avoid holding the guard across awaits.
-    let conn = {
-        let guard = state.connections.lock().unwrap();
-        guard.get(&id).cloned().ok_or("Connection not found")?
-    };
+/// Keep the counts `collect_rows` summed only for scripts that write rows: a SELECT's
+/// completion counts its rows too, and SQLite's last rowid outlives the INSERT that
+/// set it.
+fn keep_affected(set: &mut RowSet, sql: &str) {
+    let kinds: Vec<StatementKind> = sql::split_statements(sql)
+        .into_iter()
+        .map(sql::classify)
+        .collect();
+    let writes = kinds.iter().any(|kind| {
+        matches!(
+            kind,
+            StatementKind::Insert
+                | StatementKind::Update
+                | StatementKind::Delete
+                | StatementKind::Merge
+        )
+    });
+    if !writes {
+        set.affected = None;
+    } else if !kinds.contains(&StatementKind::Insert) {
+        if let Some(affected) = &mut set.affected {
+            affected.last_insert_id = None;
+        }
+    }
+}
 
-    let tables = match conn {
-        Connection::Postgres(pool) => {
-            let rows = sqlx::query(
-                "SELECT table_name FROM information_schema.tables WHERE table_schema='public'",
-            )
-            .fetch_all(&pool)
-            .await
-            .map_err(|e| e.to_string())?;
+async fn into_output(
+    state: &AppState,
+    id: &str,
+    collected: Collected,
+    format: ResultFormat,
+) -> Result<QueryOutput, String> {
+    match collected {
+        Collected::Memory(set) => Ok(set.into_output(format)),
+        Collected::Spilled(file) => spill::register(state, id, file, format).await,
+    }
+}
 
-            let mut tables = Vec::new();
-            for row in rows {
-                let name: String = row.try_get(0).unwrap_or_default();
-                tables.push(name);
-            }
-            tables
+/// Stream rows from the appropriate typed pool, converting incrementally and
+/// spilling to disk past `limits` rather than buffering the whole result first.
+///
+/// Ad-hoc SQL is not kept in the statement cache, so one-off queries don't evict the
+/// statements the grid re-runs while paging. `binds` are bound to positional
+/// placeholders on Postgres, MySQL and SQLite. With a `query_id`, the session the
+/// query runs on is registered for `cancel_query` until the query is done with it;
+/// a `timeout_ms` is passed on to the server where it can be set for one query.
+async fn run_query(
+    state: &AppState,
+    id: &str,
+    conn: &Connection,
+    sql: &str,
+    binds: &[serde_json::Value],
+    limits: CollectLimits,
+    control: QueryControl<'_>,
+) -> Result<Collected, String> {
+    if !binds.is_empty()
+        && !matches!(
+            conn,
+            Connection::Postgres(_) | Connection::MySql(_) | Connection::Sqlite(_)
+        )
+    {
+        return Err(conn.unsupported("Bind parameters"));
+    }
+    let timeout_ms = state.query_timeout_ms(id, control.timeout_ms);
+    match conn {
+        Connection::Postgres(pool) => {
+            let conn = state.acquire(id, pool).await?;
+            limits.acquired();
+            // The connection already has the sandbox timeout, if any.
+            let timeout_ms = control.timeout_ms.and(timeout_ms);
+            let mut conn = PgStatementTimeout::set(conn, timeout_ms).await?;
+            let _interrupt = state.interruptible_pg(control.query_id, &mut conn).await?;
+            let query = binds
+                .iter()
+                .try_fold(sqlx::query(sql).persistent(false), bind_value)?;
+            let results = collect_rows(sqlx::Executor::fetch_many(&mut *conn, query), limits).await;
+            conn.reset().await;
+            results
         }
         Connection::MySql(pool) => {
-            let rows = sqlx::query("SHOW TABLES")
-                .fetch_all(&pool)
-                .await
-                .map_err(|e| e.to_string())?;
-
-            let mut tables = Vec::new();
-            for row in rows {
-                let name: String = row.try_get(0).unwrap_or_default();
-                tables.push(name);
-            }
-            tables
+            let mut conn = state.acquire(id, pool).await?;
+            limits.acquired();
+            let _interrupt = state
+                .interruptible_mysql(control.query_id, &mut conn)
+                .await?;
+            let query = binds
+                .iter()
+                .try_fold(sqlx::query(sql).persistent(false), bind_value)?;
+            collect_rows(sqlx::Executor::fetch_many(&mut *conn, query), limits).await
         }
         Connection::Sqlite(pool) => {
-            let rows = sqlx::query(
-                "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%'",
-            )
-            .fetch_all(&pool)
-            .await
-            .map_err(|e| e.to_string())?;
-
-            let mut tables = Vec::new();
-            for row in rows {
-                let name: String = row.try_get(0).unwrap_or_default();
-                tables.push(name);
-            }
-            tables
+            let mut conn = state.acquire(id, pool).await?;
+            limits.acquired();
+            let _interrupt = state
+                .interruptible_sqlite(control.query_id, &mut conn)
+                .await?;
+            let query = binds
+                .iter()
+                .try_fold(sqlx::query(sql).persistent(false), bind_value)?;
+            collect_rows(sqlx::Executor::fetch_many(&mut *conn, query), limits).await
         }
-    };
-
-    Ok(tables)
+        Connection::Oracle(pool) => oracle::run_query(pool, sql, timeout_ms, limits).await,
+        Connection::DuckDb(db) => duckdb::run_query(db, sql, timeout_ms, limits).await,
+        Connection::ClickHouse(client) => {
+            clickhouse::run_query(client, sql, timeout_ms, limits).await
+        }
+        Connection::Cassandra(session) => {
+            cassandra::run_query(session, sql, timeout_ms, limits).await
+        }
+        Connection::BigQuery(client) => bigquery::run_query(client, sql, timeout_ms, limits).await,
+        Connection::Firebird(pool) => firebird::run_query(pool, sql, timeout_ms, limits).await,
+        Connection::Odbc(pool) => odbc::run_query(pool, sql, limits).await,
+        Connection::Mongo(_) | Connection::Redis(_) | Connection::Surreal(_) => {
+            Err(conn.unsupported("SQL"))
+        }
+    }
 }
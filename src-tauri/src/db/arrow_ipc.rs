@@ -0,0 +1,93 @@
+//! Arrow IPC encoding for `execute_binary`. The result's rows become one record batch
+//! in the IPC stream format, which arrow-js reads with `tableFromIPC` and Parquet
+//! writers take as-is. Column types are inferred from the values: booleans, 64-bit
+//! integers, doubles and strings map to their Arrow types, a column with no values is
+//! `Null`, and mixed or nested values are sent as JSON text.
+
+use super::convert::QueryOutput;
+use arrow::array::{ArrayRef, BooleanArray, Float64Array, Int64Array, NullArray, StringArray};
+use arrow::datatypes::{Field, Schema};
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::{RecordBatch, RecordBatchOptions};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Schema metadata key holding the rest of the result as JSON, its rows left out: the
+/// wrappers around them such as `LimitInjected`, or an output with no rows at all
+/// such as `NeedsConfirmation`.
+pub const OUTPUT_METADATA_KEY: &str = "query_output";
+
+/// Encode `output`, requested in the `table` format, as an Arrow IPC stream.
+pub fn encode(mut output: QueryOutput) -> Result<Vec<u8>, String> {
+    let (columns, rows) = output.take_table().unwrap_or_default();
+    let rest = serde_json::to_string(&output).map_err(|e| e.to_string())?;
+    let arrays: Vec<ArrayRef> = (0..columns.len()).map(|i| column_array(&rows, i)).collect();
+    let fields: Vec<Field> = columns
+        .iter()
+        .zip(&arrays)
+        .map(|(name, array)| Field::new(name, array.data_type().clone(), true))
+        .collect();
+    let schema = Arc::new(Schema::new_with_metadata(
+        fields,
+        HashMap::from([(OUTPUT_METADATA_KEY.to_string(), rest)]),
+    ));
+    let batch = RecordBatch::try_new_with_options(
+        schema.clone(),
+        arrays,
+        &RecordBatchOptions::new().with_row_count(Some(rows.len())),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let mut bytes = Vec::new();
+    let mut writer = StreamWriter::try_new(&mut bytes, &schema).map_err(|e| e.to_string())?;
+    writer.write(&batch).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    drop(writer);
+    Ok(bytes)
+}
+
+/// Column `i` of `rows` as an Arrow array of the narrowest type that holds its values.
+fn column_array(rows: &[Vec<Value>], i: usize) -> ArrayRef {
+    let values: Vec<&Value> = rows
+        .iter()
+        .map(|row| row.get(i).unwrap_or(&Value::Null))
+        .collect();
+    let present = || values.iter().filter(|value| !value.is_null());
+
+    if present().next().is_none() {
+        Arc::new(NullArray::new(values.len()))
+    } else if present().all(|value| value.is_boolean()) {
+        Arc::new(
+            values
+                .iter()
+                .map(|value| value.as_bool())
+                .collect::<BooleanArray>(),
+        )
+    } else if present().all(|value| value.is_i64()) {
+        Arc::new(
+            values
+                .iter()
+                .map(|value| value.as_i64())
+                .collect::<Int64Array>(),
+        )
+    } else if present().all(|value| value.is_number()) {
+        Arc::new(
+            values
+                .iter()
+                .map(|value| value.as_f64())
+                .collect::<Float64Array>(),
+        )
+    } else {
+        Arc::new(
+            values
+                .iter()
+                .map(|value| match value {
+                    Value::Null => None,
+                    Value::String(s) => Some(s.clone()),
+                    other => Some(other.to_string()),
+                })
+                .collect::<StringArray>(),
+        )
+    }
+}
@@ -0,0 +1,344 @@
+//! Audit log of statements run on audited connections. Entries are appended to
+//! `audit.jsonl` in the app data directory, each carrying the hash of the previous one,
+//! so editing or deleting an entry breaks the chain. Exports are signed with an
+//! Ed25519 key kept next to the log.
+
+use super::{redact, AppState};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::State;
+
+const LOG_FILE: &str = "audit.jsonl";
+const KEY_FILE: &str = "audit-signing.key";
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp_ms: u64,
+    pub connection_id: String,
+    /// The statement with credentials redacted.
+    pub statement: String,
+    pub ok: bool,
+    pub error: Option<String>,
+    pub prev_hash: String,
+    /// SHA-256 of this entry serialized with `hash` empty.
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn compute_hash(&self) -> String {
+        let mut unhashed = self.clone();
+        unhashed.hash = String::new();
+        let bytes = serde_json::to_vec(&unhashed).unwrap_or_default();
+        hex(&Sha256::digest(bytes))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditExport {
+    pub entries: Vec<AuditEntry>,
+    pub head_hash: String,
+    /// Hex Ed25519 public key and signature over `"<entry count>:<head_hash>"`.
+    pub public_key: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditVerification {
+    pub valid: bool,
+    pub entries: u64,
+    /// Sequence number of the first entry that doesn't match the chain.
+    pub first_invalid: Option<u64>,
+    pub reason: Option<String>,
+}
+
+/// The connections being audited and the head of the chain once it has been read.
+#[derive(Default)]
+pub struct AuditLog {
+    audited: Mutex<HashSet<String>>,
+    head: Mutex<Option<(u64, String)>>,
+}
+
+impl AuditLog {
+    pub fn enable(&self, id: &str) {
+        self.audited.lock().unwrap().insert(id.to_string());
+    }
+
+    pub fn disable(&self, id: &str) {
+        self.audited.lock().unwrap().remove(id);
+    }
+
+    pub fn is_audited(&self, id: &str) -> bool {
+        self.audited.lock().unwrap().contains(id)
+    }
+}
+
+/// Emitted as `db://audit-failed` when an entry could not be written.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditFailure {
+    pub id: String,
+    /// The statement with credentials redacted.
+    pub statement: String,
+    pub error: String,
+}
+
+impl AppState {
+    /// Append an entry if connection `id` is audited. The statement has already run by
+    /// now, so a failure to write doesn't fail the command, which would invite a retry
+    /// of a committed write; it is emitted as `db://audit-failed` instead, so an audited
+    /// connection never runs statements silently unlogged.
+    pub fn audit(&self, id: &str, statement: &str, error: Option<&str>) {
+        if let Err(e) = self.append_audit_entry(id, statement, error) {
+            self.emit(
+                "db://audit-failed",
+                AuditFailure {
+                    id: id.to_string(),
+                    statement: redact::redact(statement),
+                    error: e,
+                },
+            );
+        }
+    }
+
+    fn append_audit_entry(
+        &self,
+        id: &str,
+        statement: &str,
+        error: Option<&str>,
+    ) -> Result<(), String> {
+        if !self.audit_log.is_audited(id) {
+            return Ok(());
+        }
+        let path = self.data_path(LOG_FILE)?;
+        let mut head = self.audit_log.head.lock().unwrap();
+        let (last_seq, prev_hash) = match head.take() {
+            Some(head) => head,
+            None => read_head(&path)?,
+        };
+
+        let mut entry = AuditEntry {
+            seq: last_seq + 1,
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+            connection_id: id.to_string(),
+            statement: redact::redact(statement),
+            ok: error.is_none(),
+            error: error.map(redact::redact),
+            prev_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        let mut line = serde_json::to_vec(&entry).map_err(|e| e.to_string())?;
+        line.push(b'\n');
+        let written = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(&line));
+        match written {
+            Ok(()) => {
+                *head = Some((entry.seq, entry.hash));
+                Ok(())
+            }
+            Err(e) => Err(format!("Could not write the audit log: {}", e)),
+        }
+    }
+}
+
+/// Check the local audit log, or a signed export when `path` is given. An export must
+/// be signed with `public_key` (hex), or with this app's own key when that is absent;
+/// the key the export carries is not trusted, as whoever rewrote it could re-sign it.
+#[tauri::command]
+pub fn verify_audit_log(
+    state: State<'_, AppState>,
+    path: Option<String>,
+    public_key: Option<String>,
+) -> Result<AuditVerification, String> {
+    match path {
+        None => {
+            let _head = state.audit_log.head.lock().unwrap();
+            let entries = read_entries(&state.data_path(LOG_FILE)?)?;
+            Ok(verify_chain(&entries))
+        }
+        Some(path) => {
+            let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+            let export: AuditExport = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+            let mut verification = verify_chain(&export.entries);
+            let head = export
+                .entries
+                .last()
+                .map_or(GENESIS_HASH, |entry| entry.hash.as_str());
+            if verification.valid && head != export.head_hash {
+                verification.valid = false;
+                verification.reason = Some("Head hash does not match the last entry".to_string());
+            }
+            if verification.valid {
+                let trusted = match public_key {
+                    Some(public_key) => parse_public_key(&public_key)?,
+                    None => signing_key(&state)?.verifying_key(),
+                };
+                if let Err(reason) = verify_signature(&export, &trusted) {
+                    verification.valid = false;
+                    verification.reason = Some(reason);
+                }
+            }
+            Ok(verification)
+        }
+    }
+}
+
+/// Write a signed copy of the audit log to `path`.
+#[tauri::command]
+pub fn export_audit_log(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<AuditVerification, String> {
+    let entries = {
+        let _head = state.audit_log.head.lock().unwrap();
+        read_entries(&state.data_path(LOG_FILE)?)?
+    };
+    let verification = verify_chain(&entries);
+    if !verification.valid {
+        return Err(format!(
+            "The audit log is not intact ({}); refusing to sign it",
+            verification.reason.as_deref().unwrap_or("unknown")
+        ));
+    }
+
+    let key = signing_key(&state)?;
+    let head_hash = entries
+        .last()
+        .map_or(GENESIS_HASH.to_string(), |entry| entry.hash.clone());
+    let signature = key.sign(signed_message(entries.len(), &head_hash).as_bytes());
+    let export = AuditExport {
+        entries,
+        head_hash,
+        public_key: hex(key.verifying_key().as_bytes()),
+        signature: hex(&signature.to_bytes()),
+    };
+    let bytes = serde_json::to_vec_pretty(&export).map_err(|e| e.to_string())?;
+    std::fs::write(&path, bytes).map_err(|e| e.to_string())?;
+    Ok(verification)
+}
+
+fn verify_chain(entries: &[AuditEntry]) -> AuditVerification {
+    let mut prev_hash = GENESIS_HASH;
+    let mut prev_seq = 0;
+    for entry in entries {
+        let reason = if entry.seq != prev_seq + 1 {
+            Some("Sequence numbers are not contiguous")
+        } else if entry.prev_hash != prev_hash {
+            Some("Entry does not link to the previous entry")
+        } else if entry.hash != entry.compute_hash() {
+            Some("Entry contents do not match its hash")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            return AuditVerification {
+                valid: false,
+                entries: entries.len() as u64,
+                first_invalid: Some(entry.seq),
+                reason: Some(reason.to_string()),
+            };
+        }
+        prev_hash = &entry.hash;
+        prev_seq = entry.seq;
+    }
+    AuditVerification {
+        valid: true,
+        entries: entries.len() as u64,
+        first_invalid: None,
+        reason: None,
+    }
+}
+
+fn parse_public_key(text: &str) -> Result<VerifyingKey, String> {
+    let key: [u8; 32] = unhex(text)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or("Malformed public key")?;
+    VerifyingKey::from_bytes(&key).map_err(|e| e.to_string())
+}
+
+fn verify_signature(export: &AuditExport, trusted: &VerifyingKey) -> Result<(), String> {
+    if export.public_key != hex(trusted.as_bytes()) {
+        return Err("Export is signed with a different key than the trusted one".to_string());
+    }
+    let signature: [u8; 64] = unhex(&export.signature)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or("Malformed signature")?;
+    trusted
+        .verify(
+            signed_message(export.entries.len(), &export.head_hash).as_bytes(),
+            &Signature::from_bytes(&signature),
+        )
+        .map_err(|_| "Signature does not match".to_string())
+}
+
+fn signed_message(count: usize, head_hash: &str) -> String {
+    format!("{}:{}", count, head_hash)
+}
+
+/// The signing key, generated on first use.
+fn signing_key(state: &AppState) -> Result<SigningKey, String> {
+    let path = state.data_path(KEY_FILE)?;
+    match std::fs::read(&path) {
+        Ok(bytes) => {
+            let bytes: [u8; 32] = bytes
+                .try_into()
+                .map_err(|_| "Audit signing key is corrupt".to_string())?;
+            Ok(SigningKey::from_bytes(&bytes))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let key = SigningKey::generate(&mut OsRng);
+            std::fs::write(&path, key.to_bytes()).map_err(|e| e.to_string())?;
+            Ok(key)
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn read_entries(path: &Path) -> Result<Vec<AuditEntry>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.to_string()),
+    };
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(|e| format!("Malformed audit entry: {}", e)))
+        .collect()
+}
+
+fn read_head(path: &Path) -> Result<(u64, String), String> {
+    Ok(read_entries(path)?
+        .pop()
+        .map_or((0, GENESIS_HASH.to_string()), |entry| {
+            (entry.seq, entry.hash)
+        }))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,110 @@
+//! Close every connection, lock the credential store and forget the Vault token after a
+//! period of inactivity. The frontend receives `db://locked` so it can blank sensitive
+//! data and ask for the master password again.
+
+use super::secrets::VaultConfig;
+use super::{close_connection, AppState};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+pub struct AutoLock {
+    last_activity: Mutex<Instant>,
+    /// Disabled when `None`.
+    timeout: Mutex<Option<Duration>>,
+}
+
+impl AutoLock {
+    pub fn new() -> Self {
+        Self {
+            last_activity: Mutex::new(Instant::now()),
+            timeout: Mutex::new(None),
+        }
+    }
+
+    pub fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    fn is_idle(&self) -> bool {
+        match *self.timeout.lock().unwrap() {
+            Some(timeout) => self.last_activity.lock().unwrap().elapsed() >= timeout,
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockReason {
+    Inactivity,
+    Manual,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LockEvent {
+    pub reason: LockReason,
+    pub closed_connections: Vec<String>,
+}
+
+/// Enable auto-lock after `minutes` without activity; `None` or `0` disables it.
+#[tauri::command]
+pub fn set_auto_lock(state: State<'_, AppState>, minutes: Option<u32>) {
+    let timeout = minutes
+        .filter(|m| *m > 0)
+        .map(|m| Duration::from_secs(u64::from(m) * 60));
+    *state.auto_lock.timeout.lock().unwrap() = timeout;
+    state.auto_lock.touch();
+}
+
+/// Record user activity that doesn't reach the backend otherwise (typing, scrolling).
+#[tauri::command]
+pub fn touch_activity(state: State<'_, AppState>) {
+    state.auto_lock.touch();
+}
+
+#[tauri::command]
+pub async fn lock_now(state: State<'_, AppState>) -> Result<(), String> {
+    lock(&state, LockReason::Manual).await;
+    Ok(())
+}
+
+/// Check for inactivity periodically for the lifetime of the app.
+pub fn spawn_monitor(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let state = app.state::<AppState>();
+            if state.auto_lock.is_idle() {
+                lock(&state, LockReason::Inactivity).await;
+            }
+        }
+    });
+}
+
+async fn lock(state: &AppState, reason: LockReason) {
+    let ids: Vec<String> = state.connections.lock().unwrap().keys().cloned().collect();
+    if ids.is_empty()
+        && !state.credentials.is_unlocked()
+        && state.session_secrets.is_empty()
+        && state.vault.lock().unwrap().token.is_none()
+    {
+        return;
+    }
+    for id in &ids {
+        close_connection(state, id).await;
+    }
+    state.credentials.lock();
+    state.session_secrets.clear();
+    *state.vault.lock().unwrap() = VaultConfig::default();
+    state.emit(
+        "db://locked",
+        LockEvent {
+            reason,
+            closed_connections: ids,
+        },
+    );
+}
@@ -0,0 +1,335 @@
+//! Microsoft Entra ID (Azure AD) sign-in for Azure Database for PostgreSQL and MySQL.
+//! The access token is the password; a background task fetches a new one before it
+//! expires and hands it to the pool, so connections opened later still sign in.
+
+use super::{secrets, AppState};
+use serde::{Deserialize, Serialize};
+use sqlx::{Database, Pool};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use zeroize::Zeroizing;
+
+const SCOPE: &str = "https://ossrdbms-aad.database.windows.net/.default";
+/// The Azure CLI's public client, used for device code sign-in when no app is named.
+const AZURE_CLI_CLIENT_ID: &str = "04b07795-8ddb-461a-bbee-02f9e1bf7b46";
+/// Fetch a new token when the current one expires within this long.
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+/// How long to wait before retrying a failed refresh.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "flow", rename_all = "snake_case")]
+pub enum AzureAdFlow {
+    /// Sign in as an app registration (service principal).
+    ClientSecret {
+        client_id: String,
+        /// May be a `vault:path#field` reference.
+        client_secret: String,
+    },
+    /// Sign in as a user on another device; the code to enter is sent in a
+    /// `db://azure-device-code` event.
+    DeviceCode {
+        #[serde(default)]
+        client_id: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AzureAdOptions {
+    /// Directory (tenant) id or domain.
+    pub tenant: String,
+    #[serde(flatten)]
+    pub flow: AzureAdFlow,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceCodeEvent {
+    pub user_code: String,
+    pub verification_uri: String,
+    /// Instructions for the user, as worded by Entra ID.
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+struct DeviceCodeResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    expires_in: u64,
+    #[serde(default = "default_poll_interval")]
+    interval: u64,
+    message: String,
+}
+
+fn default_poll_interval() -> u64 {
+    5
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_description: String,
+}
+
+/// An access token and when it expires.
+pub struct Token {
+    pub value: Zeroizing<String>,
+    pub expires: Instant,
+}
+
+enum Grant {
+    ClientSecret {
+        client_id: String,
+        client_secret: Zeroizing<String>,
+    },
+    /// Signed in by device code; later tokens come from the refresh token.
+    Refresh {
+        client_id: String,
+        refresh_token: Mutex<Zeroizing<String>>,
+    },
+}
+
+/// A signed-in identity that can fetch new tokens without the user.
+#[derive(Clone)]
+pub struct Credential {
+    http: reqwest::Client,
+    token_url: String,
+    grant: Arc<Grant>,
+}
+
+impl Credential {
+    /// Sign in, returning the credential and its first token. Device code sign-in
+    /// waits until the user has entered the code or it expires.
+    pub async fn sign_in(
+        state: &AppState,
+        options: &AzureAdOptions,
+    ) -> Result<(Credential, Token), String> {
+        let http = reqwest::Client::new();
+        let authority = format!(
+            "https://login.microsoftonline.com/{}/oauth2/v2.0",
+            options.tenant
+        );
+        let token_url = format!("{}/token", authority);
+        match &options.flow {
+            AzureAdFlow::ClientSecret {
+                client_id,
+                client_secret,
+            } => {
+                let credential = Credential {
+                    http,
+                    token_url,
+                    grant: Arc::new(Grant::ClientSecret {
+                        client_id: client_id.clone(),
+                        client_secret: secrets::resolve(state, client_secret).await?,
+                    }),
+                };
+                let token = credential.token().await?;
+                Ok((credential, token))
+            }
+            AzureAdFlow::DeviceCode { client_id } => {
+                let client_id = client_id.as_deref().unwrap_or(AZURE_CLI_CLIENT_ID);
+                let (token, refresh_token) =
+                    device_code(state, &http, &authority, client_id).await?;
+                let credential = Credential {
+                    http,
+                    token_url,
+                    grant: Arc::new(Grant::Refresh {
+                        client_id: client_id.to_string(),
+                        refresh_token: Mutex::new(refresh_token),
+                    }),
+                };
+                Ok((credential, token))
+            }
+        }
+    }
+
+    /// A new access token.
+    pub async fn token(&self) -> Result<Token, String> {
+        match self.grant.as_ref() {
+            Grant::ClientSecret {
+                client_id,
+                client_secret,
+            } => {
+                let form = [
+                    ("grant_type", "client_credentials"),
+                    ("client_id", client_id),
+                    ("client_secret", client_secret),
+                    ("scope", SCOPE),
+                ];
+                let (token, _) = request_token(&self.http, &self.token_url, &form).await?;
+                Ok(token)
+            }
+            Grant::Refresh {
+                client_id,
+                refresh_token,
+            } => {
+                let mut refresh_token = refresh_token.lock().await;
+                let scope = format!("{} offline_access", SCOPE);
+                let form = [
+                    ("grant_type", "refresh_token"),
+                    ("client_id", client_id),
+                    ("refresh_token", &refresh_token),
+                    ("scope", &scope),
+                ];
+                let (token, rotated) = request_token(&self.http, &self.token_url, &form).await?;
+                if let Some(rotated) = rotated {
+                    *refresh_token = rotated;
+                }
+                Ok(token)
+            }
+        }
+    }
+}
+
+/// Post a token request, returning the token and any new refresh token.
+async fn request_token(
+    http: &reqwest::Client,
+    url: &str,
+    form: &[(&str, &str)],
+) -> Result<(Token, Option<Zeroizing<String>>), String> {
+    let response = http
+        .post(url)
+        .form(form)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(sign_in_error(response).await);
+    }
+    let granted: TokenResponse = response.json().await.map_err(|e| e.to_string())?;
+    let token = Token {
+        value: Zeroizing::new(granted.access_token),
+        expires: Instant::now() + Duration::from_secs(granted.expires_in),
+    };
+    Ok((token, granted.refresh_token.map(Zeroizing::new)))
+}
+
+async fn sign_in_error(response: reqwest::Response) -> String {
+    let body = response.text().await.unwrap_or_default();
+    match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(error) => format!(
+            "Azure AD sign-in failed ({}): {}",
+            error.error,
+            error.error_description.lines().next().unwrap_or_default()
+        ),
+        Err(_) => format!("Azure AD sign-in failed: {}", body.trim()),
+    }
+}
+
+/// Run the device code flow: emit the code for the user, then poll until they have
+/// signed in.
+async fn device_code(
+    state: &AppState,
+    http: &reqwest::Client,
+    authority: &str,
+    client_id: &str,
+) -> Result<(Token, Zeroizing<String>), String> {
+    let scope = format!("{} offline_access", SCOPE);
+    let response = http
+        .post(format!("{}/devicecode", authority))
+        .form(&[("client_id", client_id), ("scope", &scope)])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(sign_in_error(response).await);
+    }
+    let code: DeviceCodeResponse = response.json().await.map_err(|e| e.to_string())?;
+    state.emit(
+        "db://azure-device-code",
+        DeviceCodeEvent {
+            user_code: code.user_code.clone(),
+            verification_uri: code.verification_uri.clone(),
+            message: code.message.clone(),
+        },
+    );
+
+    let deadline = Instant::now() + Duration::from_secs(code.expires_in);
+    let mut interval = Duration::from_secs(code.interval);
+    let token_url = format!("{}/token", authority);
+    while Instant::now() < deadline {
+        tokio::time::sleep(interval).await;
+        let response = http
+            .post(&token_url)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                ("client_id", client_id),
+                ("device_code", &code.device_code),
+            ])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            let granted: TokenResponse = response.json().await.map_err(|e| e.to_string())?;
+            let refresh_token = granted
+                .refresh_token
+                .ok_or("Azure AD returned no refresh token")?;
+            let token = Token {
+                value: Zeroizing::new(granted.access_token),
+                expires: Instant::now() + Duration::from_secs(granted.expires_in),
+            };
+            return Ok((token, Zeroizing::new(refresh_token)));
+        }
+        let body = response.text().await.unwrap_or_default();
+        match serde_json::from_str::<ErrorResponse>(&body) {
+            Ok(error) if error.error == "authorization_pending" => {}
+            Ok(error) if error.error == "slow_down" => interval += Duration::from_secs(5),
+            Ok(error) if error.error == "authorization_declined" => {
+                return Err("Azure AD sign-in was declined".to_string())
+            }
+            Ok(error) if error.error != "expired_token" => {
+                return Err(format!(
+                    "Azure AD sign-in failed ({}): {}",
+                    error.error,
+                    error.error_description.lines().next().unwrap_or_default()
+                ))
+            }
+            Ok(_) => break,
+            Err(_) => return Err(format!("Azure AD sign-in failed: {}", body.trim())),
+        }
+    }
+    Err("The Azure AD device code expired before sign-in finished".to_string())
+}
+
+/// Keep the pool's password a valid token until the pool is closed. `with_token`
+/// sets the password on the pool's connect options.
+pub fn keep_fresh<DB: Database>(
+    pool: Pool<DB>,
+    credential: Credential,
+    mut expires: Instant,
+    with_token: fn(ConnectOptions<DB>, &str) -> ConnectOptions<DB>,
+) {
+    tauri::async_runtime::spawn(async move {
+        let closed = pool.close_event();
+        tokio::pin!(closed);
+        loop {
+            let refresh_at = expires.checked_sub(REFRESH_MARGIN).unwrap_or(expires);
+            tokio::select! {
+                _ = &mut closed => return,
+                _ = tokio::time::sleep_until(refresh_at.into()) => {}
+            }
+            match credential.token().await {
+                Ok(token) => {
+                    let options = (*pool.connect_options()).clone();
+                    pool.set_connect_options(with_token(options, &token.value));
+                    expires = token.expires;
+                }
+                // Connections already open are unaffected; new ones fail until a
+                // refresh succeeds.
+                Err(_) => expires = Instant::now() + REFRESH_MARGIN + RETRY_DELAY,
+            }
+        }
+    });
+}
+
+type ConnectOptions<DB> = <<DB as Database>::Connection as sqlx::Connection>::Options;
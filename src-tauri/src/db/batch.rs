@@ -0,0 +1,133 @@
+use super::convert::QueryOutput;
+use super::{execute_on, generate_id, AppState, ExecuteOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+
+/// Either one connection for every query, or one connection per query.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ConnectionTarget {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Emitted as `db://query-result` once per query, in completion order.
+#[derive(Clone, Serialize)]
+pub struct BatchQueryResult {
+    pub batch_id: String,
+    pub index: usize,
+    pub connection_id: String,
+    pub result: Option<QueryOutput>,
+    pub error: Option<String>,
+}
+
+/// Emitted as `db://batch-complete` after every query in the batch has finished.
+#[derive(Clone, Serialize)]
+pub struct BatchComplete {
+    pub batch_id: String,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// Run independent queries concurrently and report each result as it completes.
+///
+/// At most `concurrency` queries run at once overall, and never more against a
+/// connection than its pool can serve. Returns the batch id immediately; results
+/// arrive as `db://query-result` events followed by one `db://batch-complete`.
+#[tauri::command]
+pub async fn execute_many(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id_or_ids: ConnectionTarget,
+    queries: Vec<String>,
+    concurrency: Option<usize>,
+    options: Option<ExecuteOptions>,
+) -> Result<String, String> {
+    let targets = match id_or_ids {
+        ConnectionTarget::One(id) => vec![id; queries.len()],
+        ConnectionTarget::Many(ids) if ids.len() == queries.len() => ids,
+        ConnectionTarget::Many(ids) => {
+            return Err(format!(
+                "Expected one connection id per query, got {} ids for {} queries",
+                ids.len(),
+                queries.len()
+            ))
+        }
+    };
+
+    // One permit per pooled connection, so queued queries wait here rather than
+    // timing out inside the pool's acquire.
+    let mut pool_slots: HashMap<String, Arc<Semaphore>> = HashMap::new();
+    for id in &targets {
+        if !pool_slots.contains_key(id) {
+            let conn = state.connection(id)?;
+            let slots = state.max_connections(id, &conn) as usize;
+            pool_slots.insert(id.clone(), Arc::new(Semaphore::new(slots)));
+        }
+    }
+
+    let batch_id = generate_id("batch");
+    let overall = Arc::new(Semaphore::new(
+        concurrency.unwrap_or(DEFAULT_BATCH_CONCURRENCY).max(1),
+    ));
+    let options = options.unwrap_or_default();
+
+    let mut tasks = JoinSet::new();
+    for (index, (id, sql)) in targets.into_iter().zip(queries).enumerate() {
+        let app = app.clone();
+        let batch_id = batch_id.clone();
+        let overall = overall.clone();
+        let slots = pool_slots[&id].clone();
+        let options = options.clone();
+
+        tasks.spawn(async move {
+            let _overall = overall.acquire_owned().await;
+            let _slot = slots.acquire_owned().await;
+
+            let state = app.state::<AppState>();
+            let (result, error) = match execute_on(&state, &id, &sql, &options).await {
+                Ok(output) => (Some(output), None),
+                Err(e) => (None, Some(e)),
+            };
+            let succeeded = error.is_none();
+            let _ = app.emit(
+                "db://query-result",
+                BatchQueryResult {
+                    batch_id,
+                    index,
+                    connection_id: id,
+                    result,
+                    error,
+                },
+            );
+            succeeded
+        });
+    }
+
+    let complete_id = batch_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let (mut succeeded, mut failed) = (0, 0);
+        while let Some(outcome) = tasks.join_next().await {
+            match outcome {
+                Ok(true) => succeeded += 1,
+                _ => failed += 1,
+            }
+        }
+        let _ = app.emit(
+            "db://batch-complete",
+            BatchComplete {
+                batch_id: complete_id,
+                succeeded,
+                failed,
+            },
+        );
+    });
+
+    Ok(batch_id)
+}
@@ -0,0 +1,420 @@
+//! Google BigQuery over its REST API, signed in as a service account. Queries run as
+//! jobs: `run_query` inserts one, then polls it (each poll waits server-side, so no
+//! thread is held while a long query runs) and pages through the results.
+
+use super::convert::{CollectLimits, Collected, RowSink};
+use super::gcp::{self, ServiceAccount};
+use super::introspect::ColumnInfo;
+use super::proxy::Proxy;
+use chrono::{DateTime, SecondsFormat};
+use reqwest::{Method, Url};
+use serde_json::{json, Map, Number, Value};
+use std::time::Duration;
+
+const API: &str = "https://bigquery.googleapis.com/bigquery/v2";
+const SCOPE: &str = "https://www.googleapis.com/auth/bigquery";
+/// How long each `getQueryResults` call waits for the job before reporting progress.
+const POLL_WAIT_MS: u64 = 10_000;
+/// Rows requested per result page.
+const PAGE_SIZE: usize = 10_000;
+/// Datasets or tables requested per listing page.
+const LIST_PAGE_SIZE: usize = 1000;
+
+/// A client for one project, and optionally a default dataset. Cheap to clone: clones
+/// share the HTTP connection pool and the access token.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    project: String,
+    dataset: Option<String>,
+    location: Option<String>,
+    account: ServiceAccount,
+}
+
+/// Connect to `bigquery://project[/dataset][?location=EU&credentials=/path/key.json]`.
+///
+/// The service account key is the password (the key file's JSON, so it can be kept in
+/// the vault), or the file named by `credentials`, or the one in
+/// `GOOGLE_APPLICATION_CREDENTIALS`. With a dataset, unqualified table names in queries
+/// resolve against it and `get_tables` lists only its tables.
+pub async fn connect(
+    conn_string: &str,
+    password: Option<&str>,
+    proxy: Option<&Proxy>,
+) -> Result<Client, String> {
+    let url = Url::parse(conn_string).map_err(|e| e.to_string())?;
+    let project = url
+        .host_str()
+        .filter(|project| !project.is_empty())
+        .ok_or("The connection string has no project: bigquery://project/dataset")?
+        .to_string();
+    let dataset = url
+        .path_segments()
+        .and_then(|mut segments| segments.next())
+        .filter(|dataset| !dataset.is_empty())
+        .map(str::to_string);
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+
+    let key = gcp::load_key(
+        password,
+        param("credentials").as_deref(),
+        "pass it as the password or name the file with ?credentials=",
+    )?;
+
+    let mut builder = reqwest::Client::builder().connect_timeout(Duration::from_secs(5));
+    if let Some(proxy) = proxy {
+        builder = proxy.apply_http(builder)?;
+    }
+    let http = builder.build().map_err(|e| e.to_string())?;
+    let client = Client {
+        http: http.clone(),
+        project,
+        dataset,
+        location: param("location"),
+        account: ServiceAccount::new(http, key, SCOPE),
+    };
+    client
+        .request(
+            Method::GET,
+            &["datasets"],
+            &[("maxResults", "1".to_string())],
+            None,
+        )
+        .await?;
+    Ok(client)
+}
+
+impl Client {
+    /// Call `projects/<project>/<path...>` and return the JSON response.
+    async fn request(
+        &self,
+        method: Method,
+        path: &[&str],
+        query: &[(&str, String)],
+        body: Option<&Value>,
+    ) -> Result<Value, String> {
+        let mut url = Url::parse(API).map_err(|e| e.to_string())?;
+        url.path_segments_mut()
+            .map_err(|_| "Invalid API URL".to_string())?
+            .extend(["projects", self.project.as_str()])
+            .extend(path);
+        let mut request = self
+            .http
+            .request(method, url)
+            .bearer_auth(self.account.token().await?)
+            .query(query);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        if status.is_success() {
+            Ok(body)
+        } else {
+            Err(body["error"]["message"]
+                .as_str()
+                .map_or_else(|| status.to_string(), str::to_string))
+        }
+    }
+
+    /// Every item of a paged listing, by the id under `reference`.
+    async fn list(
+        &self,
+        path: &[&str],
+        items: &str,
+        reference: &str,
+        id: &str,
+    ) -> Result<Vec<String>, String> {
+        let mut names = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut query = vec![("maxResults", LIST_PAGE_SIZE.to_string())];
+            if let Some(token) = page_token.take() {
+                query.push(("pageToken", token));
+            }
+            let page = self.request(Method::GET, path, &query, None).await?;
+            names.extend(
+                page[items]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|item| item[reference][id].as_str())
+                    .map(str::to_string),
+            );
+            match page["nextPageToken"].as_str() {
+                Some(token) => page_token = Some(token.to_string()),
+                None => return Ok(names),
+            }
+        }
+    }
+}
+
+/// Run a script as a query job and collect the rows of its result (the last statement's,
+/// for a script). `timeout_ms` becomes the job timeout, after which BigQuery cancels it.
+pub async fn run_query(
+    client: &Client,
+    sql: &str,
+    timeout_ms: Option<u64>,
+    limits: CollectLimits,
+) -> Result<Collected, String> {
+    let mut query = json!({ "query": sql, "useLegacySql": false });
+    if let Some(dataset) = &client.dataset {
+        query["defaultDataset"] = json!({ "projectId": client.project, "datasetId": dataset });
+    }
+    let mut job = json!({ "configuration": { "query": query } });
+    if let Some(ms) = timeout_ms {
+        job["configuration"]["jobTimeoutMs"] = json!(ms.to_string());
+    }
+    if let Some(location) = &client.location {
+        job["jobReference"] = json!({ "location": location });
+    }
+    let job = client
+        .request(Method::POST, &["jobs"], &[], Some(&job))
+        .await?;
+    let job_id = job["jobReference"]["jobId"]
+        .as_str()
+        .ok_or("BigQuery did not return a job id")?
+        .to_string();
+    let location = job["jobReference"]["location"].as_str().map(str::to_string);
+
+    let mut sink = RowSink::new(limits);
+    let mut fields: Option<Vec<Value>> = None;
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut query = vec![
+            ("timeoutMs", POLL_WAIT_MS.to_string()),
+            ("maxResults", PAGE_SIZE.to_string()),
+            ("formatOptions.useInt64Timestamp", "true".to_string()),
+        ];
+        if let Some(location) = &location {
+            query.push(("location", location.clone()));
+        }
+        if let Some(token) = &page_token {
+            query.push(("pageToken", token.clone()));
+        }
+        let page = client
+            .request(Method::GET, &["queries", &job_id], &query, None)
+            .await?;
+        if page["jobComplete"] != Value::Bool(true) {
+            continue;
+        }
+
+        let fields = fields.get_or_insert_with(|| {
+            page["schema"]["fields"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default()
+        });
+        if page_token.is_none() {
+            sink.set_columns(
+                fields
+                    .iter()
+                    .map(|field| field["name"].as_str().unwrap_or_default().to_string())
+                    .collect(),
+            );
+        }
+        let rows = page["rows"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|row| to_row(fields, row))
+            .collect();
+        sink.push(rows).await?;
+        match page["pageToken"].as_str() {
+            Some(token) if !sink.is_full() => page_token = Some(token.to_string()),
+            _ => return Ok(sink.finish()),
+        }
+    }
+}
+
+/// A result row, `{"f": [{"v": ...}, ...]}`, as values in schema order.
+fn to_row(fields: &[Value], row: &Value) -> Vec<Value> {
+    let cells = row["f"].as_array().map(Vec::as_slice).unwrap_or_default();
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            cells
+                .get(i)
+                .map_or(Value::Null, |cell| to_json(field, &cell["v"]))
+        })
+        .collect()
+}
+
+/// INT64 becomes a number, FLOAT64 a number unless it is NaN or infinite, TIMESTAMP an
+/// RFC 3339 string, JSON the parsed value, STRUCT an object and ARRAY an array.
+/// NUMERIC, dates and times, BYTES (base64) and GEOGRAPHY stay as BigQuery's text.
+fn to_json(field: &Value, value: &Value) -> Value {
+    if field["mode"] == "REPEATED" {
+        let element = {
+            let mut element = field.clone();
+            element["mode"] = json!("NULLABLE");
+            element
+        };
+        return Value::Array(
+            value
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|item| to_json(&element, &item["v"]))
+                .collect(),
+        );
+    }
+    if let Value::Object(record) = value {
+        let fields = field["fields"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let cells = record
+            .get("f")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let object: Map<String, Value> = fields
+            .iter()
+            .zip(cells)
+            .map(|(field, cell)| {
+                (
+                    field["name"].as_str().unwrap_or_default().to_string(),
+                    to_json(field, &cell["v"]),
+                )
+            })
+            .collect();
+        return Value::Object(object);
+    }
+    let Some(text) = value.as_str() else {
+        return Value::Null;
+    };
+    let as_text = || Value::String(text.to_string());
+    match field["type"].as_str().unwrap_or_default() {
+        "INTEGER" | "INT64" => text.parse::<i64>().map_or_else(|_| as_text(), Value::from),
+        "FLOAT" | "FLOAT64" => text
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map_or_else(as_text, Value::Number),
+        "BOOLEAN" | "BOOL" => Value::Bool(text == "true"),
+        "TIMESTAMP" => text
+            .parse::<i64>()
+            .ok()
+            .and_then(DateTime::from_timestamp_micros)
+            .map_or_else(as_text, |t| {
+                Value::String(t.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            }),
+        "JSON" => serde_json::from_str(text).unwrap_or_else(|_| as_text()),
+        _ => as_text(),
+    }
+}
+
+/// Tables of the default dataset, or `dataset.table` for every dataset in the project
+/// when the connection has none.
+pub async fn fetch_tables(client: &Client) -> Result<Vec<String>, String> {
+    let tables_of = |dataset: String| async move {
+        client
+            .list(
+                &["datasets", &dataset, "tables"],
+                "tables",
+                "tableReference",
+                "tableId",
+            )
+            .await
+    };
+    let mut names = match &client.dataset {
+        Some(dataset) => tables_of(dataset.clone()).await?,
+        None => {
+            let datasets = client
+                .list(&["datasets"], "datasets", "datasetReference", "datasetId")
+                .await?;
+            let mut names = Vec::new();
+            for dataset in datasets {
+                let prefix = format!("{}.", dataset);
+                names.extend(
+                    tables_of(dataset)
+                        .await?
+                        .into_iter()
+                        .map(|table| format!("{}{}", prefix, table)),
+                );
+            }
+            names
+        }
+    };
+    names.sort();
+    Ok(names)
+}
+
+/// Columns from the table's schema. Only NULLABLE columns are nullable (REPEATED ones
+/// are empty arrays rather than NULL), and primary keys come from the table's
+/// unenforced key constraint.
+pub async fn fetch_columns(client: &Client, table: &str) -> Result<Vec<ColumnInfo>, String> {
+    let (dataset, table) = match (table.split_once('.'), &client.dataset) {
+        (Some((dataset, table)), _) => (dataset, table),
+        (None, Some(dataset)) => (dataset.as_str(), table),
+        (None, None) => {
+            return Err("Name the table as dataset.table, or set a default dataset".to_string())
+        }
+    };
+    let info = client
+        .request(
+            Method::GET,
+            &["datasets", dataset, "tables", table],
+            &[],
+            None,
+        )
+        .await?;
+    let primary_key: Vec<&str> = info["tableConstraints"]["primaryKey"]["columns"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+
+    Ok(info["schema"]["fields"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|field| {
+            let name = field["name"].as_str().unwrap_or_default().to_string();
+            ColumnInfo {
+                data_type: type_name(field),
+                nullable: field["mode"].as_str().unwrap_or("NULLABLE") == "NULLABLE",
+                default: field["defaultValueExpression"].as_str().map(str::to_string),
+                primary_key: primary_key.contains(&name.as_str()),
+                name,
+            }
+        })
+        .collect())
+}
+
+/// GoogleSQL spelling of a schema field's type, e.g. `ARRAY<STRUCT<id INT64>>`.
+fn type_name(field: &Value) -> String {
+    let base = match field["type"].as_str().unwrap_or_default() {
+        "RECORD" | "STRUCT" => format!(
+            "STRUCT<{}>",
+            field["fields"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|sub| format!(
+                    "{} {}",
+                    sub["name"].as_str().unwrap_or_default(),
+                    type_name(sub)
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        "INTEGER" => "INT64".to_string(),
+        "FLOAT" => "FLOAT64".to_string(),
+        "BOOLEAN" => "BOOL".to_string(),
+        other => other.to_string(),
+    };
+    if field["mode"] == "REPEATED" {
+        format!("ARRAY<{}>", base)
+    } else {
+        base
+    }
+}
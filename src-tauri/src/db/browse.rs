@@ -0,0 +1,244 @@
+use super::convert::{collect_bounded, QueryOutput, ResultFormat, RowSet};
+use super::introspect::{columns_for, ColumnInfo};
+use super::masking::ColumnMasks;
+use super::params::bind_value;
+use super::sql::{self, placeholder, quote_ident, StatementKind};
+use super::{redact, AppState, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::State;
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 10_000;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PageRequest {
+    pub limit: Option<usize>,
+    /// `next_cursor` from the previous page, for keyset pagination.
+    pub after: Option<Vec<Value>>,
+    /// Row offset. Forces OFFSET pagination even when the table has a usable key.
+    pub offset: Option<u64>,
+    /// Show masked columns in clear, if the connection allows it.
+    pub unmask: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TablePage {
+    pub columns: Vec<String>,
+    pub rows: Vec<Map<String, Value>>,
+    /// Columns the page is ordered and keyed by; empty when the table has no usable key.
+    pub key_columns: Vec<String>,
+    pub has_more: bool,
+    /// Pass as `after` to fetch the next page with keyset pagination.
+    pub next_cursor: Option<Vec<Value>>,
+    /// Pass as `offset` to fetch the next page when keyset pagination isn't available.
+    pub next_offset: Option<u64>,
+}
+
+/// Fetch one page of a table for the data grid.
+///
+/// When the table's primary key has types that round-trip through JSON, pages are
+/// fetched with `WHERE (key) > (cursor) ORDER BY key LIMIT n`, which stays fast at any
+/// depth. Otherwise, or when an explicit `offset` is given, it falls back to OFFSET.
+#[tauri::command]
+pub async fn get_table_data(
+    state: State<'_, AppState>,
+    id: String,
+    table: String,
+    page: Option<PageRequest>,
+) -> Result<TablePage, String> {
+    let page = page.unwrap_or_default();
+    let conn = state.connection(&id)?;
+    let masking = state.masking_rules(&id, page.unmask)?;
+    let kind = conn.kind();
+    let limit = page
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let columns = columns_for(&state, &id, &conn, &table).await?;
+    let key_columns = keyset_columns(&columns);
+    let order = key_columns
+        .iter()
+        .map(|k| quote_ident(kind, k))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut sql = format!("SELECT * FROM {}", quote_ident(kind, &table));
+    let mut binds = Vec::new();
+    let keyset = !key_columns.is_empty() && page.offset.is_none();
+
+    if keyset {
+        if let Some(after) = page.after {
+            if after.len() != key_columns.len() {
+                return Err(format!(
+                    "Cursor has {} values but the table key has {} columns",
+                    after.len(),
+                    key_columns.len()
+                ));
+            }
+            let placeholders = (1..=after.len())
+                .map(|n| placeholder(kind, n))
+                .collect::<Vec<_>>()
+                .join(", ");
+            sql.push_str(&format!(" WHERE ({}) > ({})", order, placeholders));
+            binds = after;
+        }
+    }
+    if !order.is_empty() {
+        sql.push_str(&format!(" ORDER BY {}", order));
+    }
+    // One extra row tells us whether another page exists.
+    sql.push_str(&format!(" LIMIT {}", limit + 1));
+    let offset = page.offset.unwrap_or(0);
+    if !keyset {
+        sql.push_str(&format!(" OFFSET {}", offset));
+    }
+
+    let mut set = fetch_page(&state, &id, &conn, &sql, &binds).await?;
+    let has_more = set.rows.len() > limit;
+    set.rows.truncate(limit);
+
+    let (next_cursor, next_offset) = match (has_more, keyset) {
+        (false, _) => (None, None),
+        (true, true) => (cursor_of(&set, &key_columns), None),
+        (true, false) => (None, Some(offset + limit as u64)),
+    };
+    ColumnMasks::new(&masking, &set.columns).apply(&mut set.rows);
+
+    Ok(TablePage {
+        columns: set.columns.clone(),
+        rows: set.into_maps(),
+        key_columns: if keyset { key_columns } else { Vec::new() },
+        has_more,
+        next_cursor,
+        next_offset,
+    })
+}
+
+/// Primary key columns, if every one of them has a type that survives the JSON
+/// round trip exactly (integers and strings); otherwise none.
+fn keyset_columns(columns: &[ColumnInfo]) -> Vec<String> {
+    let key: Vec<&ColumnInfo> = columns.iter().filter(|c| c.primary_key).collect();
+    let usable = key.iter().all(|c| {
+        let ty = c.data_type.to_lowercase();
+        ty.contains("int") || ty.contains("char") || ty.contains("text")
+    });
+    if usable {
+        key.into_iter().map(|c| c.name.clone()).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+fn cursor_of(set: &RowSet, key_columns: &[String]) -> Option<Vec<Value>> {
+    let last = set.rows.last()?;
+    key_columns
+        .iter()
+        .map(|k| {
+            let idx = set.columns.iter().position(|c| c == k)?;
+            Some(last[idx].clone())
+        })
+        .collect()
+}
+
+/// Run one generated SELECT for the data grid inside a read-only transaction, so a
+/// function with side effects called from a view can't change data. `sql` must be a
+/// single SELECT, bounded by the caller (as with `LIMIT`); `binds` fill its positional
+/// placeholders.
+#[tauri::command]
+pub async fn safe_select(
+    state: State<'_, AppState>,
+    id: String,
+    sql: String,
+    binds: Option<Vec<Value>>,
+    format: Option<ResultFormat>,
+    unmask: Option<bool>,
+) -> Result<QueryOutput, String> {
+    let conn = state.connection(&id)?;
+    match sql::split_statements(&sql)[..] {
+        [stmt] if sql::classify(stmt) == StatementKind::Select => {}
+        _ => return Err("Only a single SELECT can be run read-only".to_string()),
+    }
+    let masking = state.masking_rules(&id, unmask.unwrap_or(false))?;
+    state.check_policy(&id, &sql)?;
+    let set = fetch_page(&state, &id, &conn, &sql, &binds.unwrap_or_default()).await;
+    state.audit(&id, &sql, set.as_ref().err().map(String::as_str));
+    let mut set = set.map_err(|e| redact::redact(&e))?;
+    ColumnMasks::new(&masking, &set.columns).apply(&mut set.rows);
+    Ok(set.into_output(format.unwrap_or_default()))
+}
+
+/// Page queries repeat with only the cursor changing, so they are always prepared and
+/// cached server-side. They run in a read-only transaction, committed once the rows
+/// are read; SQLite has none, so the connection is set `query_only` for the query.
+async fn fetch_page(
+    state: &AppState,
+    id: &str,
+    conn: &Connection,
+    sql: &str,
+    binds: &[Value],
+) -> Result<RowSet, String> {
+    match conn {
+        Connection::Postgres(pool) => {
+            let mut conn = state.acquire(id, pool).await?;
+            let mut tx = sqlx::Connection::begin(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+            sqlx::Executor::execute(&mut *tx, "SET TRANSACTION READ ONLY")
+                .await
+                .map_err(|e| e.to_string())?;
+            let query = binds
+                .iter()
+                .try_fold(sqlx::query(sql).persistent(true), bind_value)?;
+            let set = collect_bounded(query.fetch(&mut *tx)).await?;
+            tx.commit().await.map_err(|e| e.to_string())?;
+            Ok(set)
+        }
+        Connection::MySql(pool) => {
+            let mut conn = state.acquire(id, pool).await?;
+            // Without a scope, this applies to the next transaction only.
+            sqlx::Executor::execute(&mut *conn, "SET TRANSACTION READ ONLY")
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut tx = sqlx::Connection::begin(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+            let query = binds
+                .iter()
+                .try_fold(sqlx::query(sql).persistent(true), bind_value)?;
+            let set = collect_bounded(query.fetch(&mut *tx)).await?;
+            tx.commit().await.map_err(|e| e.to_string())?;
+            Ok(set)
+        }
+        Connection::Sqlite(pool) => {
+            let mut conn = state.acquire(id, pool).await?;
+            sqlx::Executor::execute(&mut *conn, "PRAGMA query_only = ON")
+                .await
+                .map_err(|e| e.to_string())?;
+            let query = binds
+                .iter()
+                .try_fold(sqlx::query(sql).persistent(true), bind_value);
+            let set = match query {
+                Ok(query) => collect_bounded(query.fetch(&mut *conn)).await,
+                Err(e) => Err(e),
+            };
+            // The connection goes back to the pool, so it is always made writable again.
+            sqlx::Executor::execute(&mut *conn, "PRAGMA query_only = OFF")
+                .await
+                .map_err(|e| e.to_string())?;
+            set
+        }
+        Connection::Oracle(_)
+        | Connection::Mongo(_)
+        | Connection::DuckDb(_)
+        | Connection::ClickHouse(_)
+        | Connection::Redis(_)
+        | Connection::Cassandra(_)
+        | Connection::Surreal(_)
+        | Connection::BigQuery(_)
+        | Connection::Firebird(_)
+        | Connection::Odbc(_) => Err(conn.unsupported("Table browsing")),
+    }
+}
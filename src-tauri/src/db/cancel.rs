@@ -0,0 +1,213 @@
+//! Cancelling queries while they run. A script run with a `query_id` is registered
+//! under it, along with what identifies its session to the server; `cancel_query`
+//! then stops it there (`pg_cancel_backend`, `KILL QUERY`, `sqlite3_interrupt`) and
+//! makes the waiting `execute` return at once. Other backends' queries are abandoned
+//! rather than stopped. Identifying the session takes a round trip, so queries without
+//! a `query_id` are not registered.
+
+use super::{AppState, Connection};
+use libsqlite3_sys::sqlite3;
+use sqlx::mysql::MySqlConnection;
+use sqlx::postgres::PgConnection;
+use sqlx::sqlite::SqliteConnection;
+use std::collections::hash_map::Entry;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use tauri::State;
+use tokio::sync::Notify;
+
+pub const CANCELLED: &str = "The query was cancelled";
+
+/// How the server is told to stop a query.
+enum Interrupt {
+    /// The backend pid.
+    Postgres(i32),
+    /// The connection id.
+    MySql(u64),
+    Sqlite(SqliteHandle),
+}
+
+struct SqliteHandle(NonNull<sqlite3>);
+
+// SAFETY: `sqlite3_interrupt` may be called from any thread. The handle is only used
+// while the query it was registered for holds its connection: `InterruptGuard` clears
+// it, under the same lock `cancel_query` uses, before the connection is released.
+unsafe impl Send for SqliteHandle {}
+
+pub struct RunningQuery {
+    id: String,
+    interrupt: Option<Interrupt>,
+    cancelled: Arc<Notify>,
+}
+
+/// Removes a query's registration when it ends.
+pub struct Tracked<'a> {
+    state: &'a AppState,
+    query_id: String,
+}
+
+impl Drop for Tracked<'_> {
+    fn drop(&mut self) {
+        self.state.running.lock().unwrap().remove(&self.query_id);
+    }
+}
+
+/// Forgets a query's session when the query releases its connection, so a late
+/// `cancel_query` can't reach whatever the connection runs next.
+pub struct InterruptGuard<'a> {
+    state: &'a AppState,
+    query_id: String,
+}
+
+impl Drop for InterruptGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(running) = self.state.running.lock().unwrap().get_mut(&self.query_id) {
+            running.interrupt = None;
+        }
+    }
+}
+
+impl AppState {
+    /// Register `query_id` as running on connection `id`. The returned `Notify` fires
+    /// when it is cancelled.
+    pub fn track(&self, query_id: &str, id: &str) -> Result<(Tracked<'_>, Arc<Notify>), String> {
+        let cancelled = Arc::new(Notify::new());
+        match self.running.lock().unwrap().entry(query_id.to_string()) {
+            Entry::Occupied(_) => return Err(format!("Query {} is already running", query_id)),
+            Entry::Vacant(slot) => slot.insert(RunningQuery {
+                id: id.to_string(),
+                interrupt: None,
+                cancelled: cancelled.clone(),
+            }),
+        };
+        let tracked = Tracked {
+            state: self,
+            query_id: query_id.to_string(),
+        };
+        Ok((tracked, cancelled))
+    }
+
+    fn set_interrupt(&self, query_id: &str, interrupt: Interrupt) -> InterruptGuard<'_> {
+        if let Some(running) = self.running.lock().unwrap().get_mut(query_id) {
+            running.interrupt = Some(interrupt);
+        }
+        InterruptGuard {
+            state: self,
+            query_id: query_id.to_string(),
+        }
+    }
+
+    /// Register the session of `conn` for query `query_id`, if there is one. Keep the
+    /// guard until the query is done with the connection.
+    pub async fn interruptible_pg(
+        &self,
+        query_id: Option<&str>,
+        conn: &mut PgConnection,
+    ) -> Result<Option<InterruptGuard<'_>>, String> {
+        let Some(query_id) = query_id else {
+            return Ok(None);
+        };
+        let pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+            .fetch_one(conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Some(self.set_interrupt(query_id, Interrupt::Postgres(pid))))
+    }
+
+    pub async fn interruptible_mysql(
+        &self,
+        query_id: Option<&str>,
+        conn: &mut MySqlConnection,
+    ) -> Result<Option<InterruptGuard<'_>>, String> {
+        let Some(query_id) = query_id else {
+            return Ok(None);
+        };
+        let connection_id: u64 = sqlx::query_scalar("SELECT CONNECTION_ID()")
+            .fetch_one(conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Some(
+            self.set_interrupt(query_id, Interrupt::MySql(connection_id)),
+        ))
+    }
+
+    pub async fn interruptible_sqlite(
+        &self,
+        query_id: Option<&str>,
+        conn: &mut SqliteConnection,
+    ) -> Result<Option<InterruptGuard<'_>>, String> {
+        let Some(query_id) = query_id else {
+            return Ok(None);
+        };
+        let handle = conn
+            .lock_handle()
+            .await
+            .map_err(|e| e.to_string())?
+            .as_raw_handle();
+        Ok(Some(self.set_interrupt(
+            query_id,
+            Interrupt::Sqlite(SqliteHandle(handle)),
+        )))
+    }
+
+    /// Stop query `query_id`. Returns `false` if it isn't running.
+    pub async fn cancel(&self, query_id: &str) -> Result<bool, String> {
+        let (id, interrupt, cancelled) = {
+            let running = self.running.lock().unwrap();
+            let Some(query) = running.get(query_id) else {
+                return Ok(false);
+            };
+            let interrupt = match &query.interrupt {
+                Some(Interrupt::Sqlite(handle)) => {
+                    // SAFETY: see `SqliteHandle`; the lock is held.
+                    unsafe { libsqlite3_sys::sqlite3_interrupt(handle.0.as_ptr()) };
+                    None
+                }
+                Some(Interrupt::Postgres(pid)) => Some(Interrupt::Postgres(*pid)),
+                Some(Interrupt::MySql(connection_id)) => Some(Interrupt::MySql(*connection_id)),
+                None => None,
+            };
+            (query.id.clone(), interrupt, query.cancelled.clone())
+        };
+        // Stop the query on the server before `execute` lets go of its connection, so
+        // the session isn't reused in between.
+        let stopped = match interrupt {
+            Some(interrupt) => interrupt_on_server(self, &id, interrupt).await,
+            None => Ok(()),
+        };
+        cancelled.notify_one();
+        stopped.map(|_| true)
+    }
+}
+
+async fn interrupt_on_server(
+    state: &AppState,
+    id: &str,
+    interrupt: Interrupt,
+) -> Result<(), String> {
+    match (interrupt, state.connection(id)?) {
+        (Interrupt::Postgres(pid), Connection::Postgres(pool)) => {
+            sqlx::query("SELECT pg_cancel_backend($1)")
+                .bind(pid)
+                .execute(&pool)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        (Interrupt::MySql(connection_id), Connection::MySql(pool)) => {
+            // KILL takes no bind parameters; the id is an integer.
+            sqlx::query(&format!("KILL QUERY {}", connection_id))
+                .execute(&pool)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Cancel the query `execute` is running under `query_id`; that `execute` returns an
+/// error saying so. Returns `false` if no such query is running.
+#[tauri::command]
+pub async fn cancel_query(state: State<'_, AppState>, query_id: String) -> Result<bool, String> {
+    state.cancel(&query_id).await
+}
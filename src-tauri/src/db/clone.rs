@@ -0,0 +1,47 @@
+//! Opening a second connection from an existing one's connection string and options,
+//! e.g. to work in another database on the same server side by side.
+
+use super::{open_connection, AppState};
+use serde::Deserialize;
+use tauri::State;
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct CloneOverrides {
+    /// Database (or schema, keyspace, dataset) to open instead of the original's.
+    pub database: Option<String>,
+    /// Open the copy read-only, whatever the original may do.
+    pub read_only: bool,
+    /// Id for the copy; one is generated otherwise.
+    pub alias: Option<String>,
+    /// Display name for the copy; the original's otherwise.
+    pub name: Option<String>,
+}
+
+/// Open a new connection configured like `id`, with `overrides` applied, and return
+/// its id. Secrets are resolved again, so a rotated vault password is picked up.
+#[tauri::command]
+pub async fn clone_connection(
+    state: State<'_, AppState>,
+    id: String,
+    overrides: Option<CloneOverrides>,
+) -> Result<String, String> {
+    let overrides = overrides.unwrap_or_default();
+    let (conn_string, mut options) = state
+        .connect_specs
+        .lock()
+        .unwrap()
+        .get(&id)
+        .cloned()
+        .ok_or("Connection not found")?;
+
+    if overrides.database.is_some() {
+        options.database = overrides.database;
+    }
+    options.alias = overrides.alias;
+    if overrides.name.is_some() {
+        options.name = overrides.name;
+    }
+    options.read_only |= overrides.read_only;
+    open_connection(&state, &conn_string, options).await
+}
@@ -0,0 +1,410 @@
+//! Connecting to Cloud SQL the way the Cloud SQL Auth Proxy does: the instance is
+//! named by its connection name, a service account fetches its address and CA from
+//! the Admin API and signs a short-lived client certificate for a local key, and the
+//! database protocol runs over TLS to the instance's server-side proxy on port 3307.
+//! The driver reaches it through a `Tunnel`.
+
+use super::gcp::{self, ServiceAccount};
+use super::secrets;
+use super::tunnel::Tunnel;
+use super::{AppState, DbKind};
+use rsa::pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::RsaPrivateKey;
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, CertificateError, ClientConfig, PrivateKey, RootCertStore, ServerName};
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::{MySqlConnectOptions, MySqlSslMode};
+use sqlx::postgres::{PgConnectOptions, PgSslMode};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use x509_parser::pem::Pem;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+const SCOPE: &str = "https://www.googleapis.com/auth/sqlservice.admin";
+const ADMIN_API: &str = "https://sqladmin.googleapis.com/sql/v1beta4";
+/// The port of the server-side proxy on every instance.
+const SERVER_PROXY_PORT: u16 = 3307;
+/// Fetch a new client certificate when the current one expires within this long.
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpType {
+    #[default]
+    Public,
+    /// The instance's VPC address, for hosts inside the same network.
+    Private,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloudSqlOptions {
+    /// Instance connection name, `project:region:instance`. The host and port in the
+    /// connection string are ignored.
+    pub instance: String,
+    /// Service account key JSON, or a `vault:path#field` reference to one.
+    #[serde(default)]
+    pub credentials: Option<String>,
+    /// Path to a service account key file; `GOOGLE_APPLICATION_CREDENTIALS` is used
+    /// when neither this nor `credentials` is set.
+    #[serde(default)]
+    pub credentials_file: Option<String>,
+    #[serde(default)]
+    pub ip_type: IpType,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConnectSettings {
+    server_ca_cert: SslCert,
+    #[serde(default)]
+    ip_addresses: Vec<IpMapping>,
+    region: String,
+    database_version: String,
+    #[serde(default)]
+    dns_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SslCert {
+    cert: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IpMapping {
+    #[serde(rename = "type")]
+    kind: String,
+    ip_address: IpAddr,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EphemeralCertResponse {
+    ephemeral_cert: SslCert,
+}
+
+/// TLS settings for the instance, valid until the client certificate expires.
+struct Session {
+    config: Arc<ClientConfig>,
+    address: IpAddr,
+    server_name: ServerName,
+    expires: SystemTime,
+}
+
+/// Dials one instance, renewing the client certificate as it nears expiry.
+struct Connector {
+    http: reqwest::Client,
+    account: ServiceAccount,
+    kind: DbKind,
+    project: String,
+    region: String,
+    instance: String,
+    ip_type: IpType,
+    key: Arc<RsaPrivateKey>,
+    session: Mutex<Option<Session>>,
+}
+
+impl Connector {
+    async fn new(
+        state: &AppState,
+        options: &CloudSqlOptions,
+        kind: DbKind,
+    ) -> Result<Self, String> {
+        // The project may be domain-scoped (`example.com:project`), so split from the end.
+        let mut parts = options.instance.rsplitn(3, ':');
+        let (Some(instance), Some(region), Some(project)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!(
+                "Invalid instance connection name {}; expected project:region:instance",
+                options.instance
+            ));
+        };
+        let credentials = match &options.credentials {
+            Some(credentials) => Some(secrets::resolve(state, credentials).await?),
+            None => None,
+        };
+        let key = gcp::load_key(
+            credentials.as_deref().map(String::as_str),
+            options.credentials_file.as_deref(),
+            "set credentials or credentials_file",
+        )?;
+        let http = reqwest::Client::new();
+        // Key generation takes a moment; keep it off the runtime threads.
+        let private_key = tauri::async_runtime::spawn_blocking(|| {
+            RsaPrivateKey::new(&mut rand_core::OsRng, 2048)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+        Ok(Self {
+            account: ServiceAccount::new(http.clone(), key, SCOPE),
+            http,
+            kind,
+            project: project.to_string(),
+            region: region.to_string(),
+            instance: instance.to_string(),
+            ip_type: options.ip_type,
+            key: Arc::new(private_key),
+            session: Mutex::new(None),
+        })
+    }
+
+    fn instance_url(&self) -> String {
+        format!(
+            "{}/projects/{}/instances/{}",
+            ADMIN_API, self.project, self.instance
+        )
+    }
+
+    async fn admin_error(response: reqwest::Response) -> String {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        format!("Cloud SQL Admin API ({}): {}", status, body.trim())
+    }
+
+    /// Look up the instance and have its CA sign a certificate for our key.
+    async fn refresh(&self) -> Result<Session, String> {
+        let token = self.account.token().await?;
+        let response = self
+            .http
+            .get(format!("{}/connectSettings", self.instance_url()))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(Self::admin_error(response).await);
+        }
+        let settings: ConnectSettings = response.json().await.map_err(|e| e.to_string())?;
+        if settings.region != self.region {
+            return Err(format!(
+                "Instance {} is in region {}, not {}",
+                self.instance, settings.region, self.region
+            ));
+        }
+        let engine = match self.kind {
+            DbKind::Postgres => "POSTGRES",
+            _ => "MYSQL",
+        };
+        if !settings.database_version.starts_with(engine) {
+            return Err(format!(
+                "Instance {} runs {}, which doesn't match the connection string",
+                self.instance, settings.database_version
+            ));
+        }
+        let wanted = match self.ip_type {
+            IpType::Public => "PRIMARY",
+            IpType::Private => "PRIVATE",
+        };
+        let address = settings
+            .ip_addresses
+            .iter()
+            .find(|ip| ip.kind == wanted)
+            .map(|ip| ip.ip_address)
+            .ok_or_else(|| {
+                format!(
+                    "Instance {} has no {:?} IP address",
+                    self.instance, self.ip_type
+                )
+            })?;
+
+        let public_key = self
+            .key
+            .to_public_key()
+            .to_public_key_pem(LineEnding::LF)
+            .map_err(|e| e.to_string())?;
+        let response = self
+            .http
+            .post(format!("{}:generateEphemeralCert", self.instance_url()))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "public_key": public_key }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(Self::admin_error(response).await);
+        }
+        let issued: EphemeralCertResponse = response.json().await.map_err(|e| e.to_string())?;
+
+        let client_cert = pem_certificates(&issued.ephemeral_cert.cert)?
+            .into_iter()
+            .next()
+            .ok_or("The Admin API returned no client certificate")?;
+        let expires = not_after(&client_cert)?;
+        let mut roots = RootCertStore::empty();
+        for ca in pem_certificates(&settings.server_ca_cert.cert)? {
+            roots.add(&ca).map_err(|e| e.to_string())?;
+        }
+        let verifier = InstanceVerifier {
+            inner: WebPkiVerifier::new(roots, None),
+            common_name: format!("{}:{}", self.project, self.instance),
+        };
+        let private_key = self.key.to_pkcs8_der().map_err(|e| e.to_string())?;
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(verifier))
+            .with_client_auth_cert(
+                vec![client_cert],
+                PrivateKey(private_key.as_bytes().to_vec()),
+            )
+            .map_err(|e| e.to_string())?;
+        // Newer instances have certificates for their DNS name; older ones are checked
+        // by common name in `InstanceVerifier`.
+        let server_name = match settings.dns_name.filter(|name| !name.is_empty()) {
+            Some(name) => {
+                ServerName::try_from(name.trim_end_matches('.')).map_err(|e| e.to_string())?
+            }
+            None => ServerName::IpAddress(address),
+        };
+        Ok(Session {
+            config: Arc::new(config),
+            address,
+            server_name,
+            expires,
+        })
+    }
+
+    /// A TLS connection to the instance's server-side proxy.
+    async fn dial(&self) -> Result<TlsStream<TcpStream>, String> {
+        let (config, address, server_name) = {
+            let mut session = self.session.lock().await;
+            let fresh = session
+                .as_ref()
+                .is_some_and(|s| s.expires > SystemTime::now() + REFRESH_MARGIN);
+            if !fresh {
+                *session = Some(self.refresh().await?);
+            }
+            let session = session.as_ref().expect("session was just refreshed");
+            (
+                session.config.clone(),
+                session.address,
+                session.server_name.clone(),
+            )
+        };
+        let stream = TcpStream::connect((address, SERVER_PROXY_PORT))
+            .await
+            .map_err(|e| format!("Could not reach Cloud SQL instance at {}: {}", address, e))?;
+        TlsConnector::from(config)
+            .connect(server_name, stream)
+            .await
+            .map_err(|e| format!("Cloud SQL TLS handshake failed: {}", e))
+    }
+}
+
+fn pem_certificates(pem: &str) -> Result<Vec<Certificate>, String> {
+    Pem::iter_from_buffer(pem.as_bytes())
+        .map(|block| {
+            block
+                .map(|block| Certificate(block.contents))
+                .map_err(|e| format!("Invalid certificate from the Admin API: {}", e))
+        })
+        .collect()
+}
+
+fn not_after(cert: &Certificate) -> Result<SystemTime, String> {
+    let (_, parsed) = X509Certificate::from_der(&cert.0).map_err(|e| e.to_string())?;
+    let seconds = parsed.validity().not_after.timestamp();
+    Ok(UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).unwrap_or_default()))
+}
+
+/// Verifies the server certificate against the instance's CA. Instances without a DNS
+/// name present a certificate whose common name is `project:instance` instead.
+struct InstanceVerifier {
+    inner: WebPkiVerifier,
+    common_name: String,
+}
+
+impl ServerCertVerifier for InstanceVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        // The chain is checked before the name, so a name mismatch means the
+        // certificate was issued by the instance's CA.
+        match self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        ) {
+            Err(rustls::Error::InvalidCertificate(CertificateError::NotValidForName))
+                if common_name(end_entity).as_deref() == Some(self.common_name.as_str()) =>
+            {
+                Ok(ServerCertVerified::assertion())
+            }
+            result => result,
+        }
+    }
+}
+
+fn common_name(cert: &Certificate) -> Option<String> {
+    let (_, parsed) = X509Certificate::from_der(&cert.0).ok()?;
+    let name = parsed.subject().iter_common_name().next()?;
+    name.as_str().ok().map(str::to_string)
+}
+
+/// A tunnel to the instance. The instance is dialled once first, so bad credentials or
+/// a wrong instance name fail here rather than in the driver.
+async fn open_tunnel(
+    state: &AppState,
+    options: &CloudSqlOptions,
+    kind: DbKind,
+) -> Result<Tunnel, String> {
+    let connector = Arc::new(Connector::new(state, options, kind).await?);
+    drop(connector.dial().await?);
+    Tunnel::serve(move || {
+        let connector = connector.clone();
+        async move { connector.dial().await }
+    })
+    .await
+}
+
+const UNIX_SOCKET: &str = "Cloud SQL connections can't use a Unix socket";
+
+/// Point Postgres options at a tunnel to the instance. The tunnel encrypts, so the
+/// driver's own TLS is turned off.
+pub async fn tunnel_pg(
+    state: &AppState,
+    options: &CloudSqlOptions,
+    connect_options: PgConnectOptions,
+) -> Result<(PgConnectOptions, Tunnel), String> {
+    if connect_options.get_socket().is_some() {
+        return Err(UNIX_SOCKET.to_string());
+    }
+    let tunnel = open_tunnel(state, options, DbKind::Postgres).await?;
+    let connect_options = connect_options
+        .host("127.0.0.1")
+        .port(tunnel.port())
+        .ssl_mode(PgSslMode::Disable);
+    Ok((connect_options, tunnel))
+}
+
+/// Point MySQL options at a tunnel to the instance.
+pub async fn tunnel_mysql(
+    state: &AppState,
+    options: &CloudSqlOptions,
+    connect_options: MySqlConnectOptions,
+) -> Result<(MySqlConnectOptions, Tunnel), String> {
+    if connect_options.get_socket().is_some() {
+        return Err(UNIX_SOCKET.to_string());
+    }
+    let tunnel = open_tunnel(state, options, DbKind::MySql).await?;
+    let connect_options = connect_options
+        .host("127.0.0.1")
+        .port(tunnel.port())
+        .ssl_mode(MySqlSslMode::Disabled);
+    Ok((connect_options, tunnel))
+}
@@ -0,0 +1,88 @@
+//! Column metadata for `execute` results, read by describing the statement: each
+//! column's database type, the JSON type its values are converted to, and whether it
+//! can be NULL where the driver can tell.
+
+use super::Connection;
+use serde::Serialize;
+use sqlx::{Column, Database, Describe, Type, TypeInfo};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnMeta {
+    pub name: String,
+    /// The type name as the database reports it, e.g. `INT8` or `VARCHAR`.
+    pub db_type: String,
+    pub json_type: JsonType,
+    /// `None` where the driver can't tell, as for computed columns.
+    pub nullable: Option<bool>,
+}
+
+/// What a column's values look like once converted to JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonType {
+    Number,
+    Boolean,
+    String,
+    /// Values of this type aren't converted and come back as `null`.
+    Null,
+}
+
+/// The metadata of a described statement's columns. Types are judged the way
+/// `JsonRow::to_values` converts them.
+pub fn from_describe<DB>(describe: &Describe<DB>) -> Vec<ColumnMeta>
+where
+    DB: Database,
+    i64: Type<DB>,
+    i32: Type<DB>,
+    i16: Type<DB>,
+    f64: Type<DB>,
+    f32: Type<DB>,
+    bool: Type<DB>,
+    String: Type<DB>,
+{
+    describe
+        .columns()
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            let ty = column.type_info();
+            let json_type = if <i64 as Type<DB>>::compatible(ty)
+                || <i32 as Type<DB>>::compatible(ty)
+                || <i16 as Type<DB>>::compatible(ty)
+                || <f64 as Type<DB>>::compatible(ty)
+                || <f32 as Type<DB>>::compatible(ty)
+            {
+                JsonType::Number
+            } else if <bool as Type<DB>>::compatible(ty) {
+                JsonType::Boolean
+            } else if <String as Type<DB>>::compatible(ty) {
+                JsonType::String
+            } else {
+                JsonType::Null
+            };
+            ColumnMeta {
+                name: column.name().to_string(),
+                db_type: ty.name().to_string(),
+                json_type,
+                nullable: describe.nullable(i),
+            }
+        })
+        .collect()
+}
+
+/// The metadata of `sql`'s columns, described on one of `conn`'s pooled connections.
+pub async fn describe(conn: &Connection, sql: &str) -> Result<Vec<ColumnMeta>, String> {
+    match conn {
+        Connection::Postgres(pool) => sqlx::Executor::describe(pool, sql)
+            .await
+            .map(|describe| from_describe(&describe)),
+        Connection::MySql(pool) => sqlx::Executor::describe(pool, sql)
+            .await
+            .map(|describe| from_describe(&describe)),
+        Connection::Sqlite(pool) => sqlx::Executor::describe(pool, sql)
+            .await
+            .map(|describe| from_describe(&describe)),
+        _ => return Err(conn.unsupported("Column metadata")),
+    }
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,125 @@
+//! Connections described field by field. `connect` builds the connection string from
+//! a `DbConfig`, escaping each part, and passes the password alongside it rather than
+//! in the URL.
+
+use super::{DbConfig, DbKind};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+/// Everything but the characters RFC 3986 leaves unreserved.
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+/// As `COMPONENT`, but keeping `/` so paths and `namespace/database` stay intact.
+const PATH: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/');
+
+impl DbConfig {
+    /// The connection string for this config, without the password.
+    pub fn to_conn_string(&self) -> Result<String, String> {
+        let scheme = match self.kind {
+            DbKind::Postgres => "postgres",
+            DbKind::MySql => "mysql",
+            DbKind::Oracle => "oracle",
+            DbKind::Mongo => "mongodb",
+            DbKind::ClickHouse => "clickhouse",
+            DbKind::Redis => "redis",
+            DbKind::Cassandra => "cassandra",
+            DbKind::Surreal => "surreal",
+            DbKind::Firebird => "firebird",
+            // The project stands where the host would.
+            DbKind::BigQuery => "bigquery",
+            DbKind::Sqlite => return self.file("sqlite:", true),
+            DbKind::DuckDb => return self.file("duckdb:", false),
+            DbKind::Odbc => return Ok(self.odbc()),
+        };
+
+        let host = self
+            .host
+            .as_deref()
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| format!("A {:?} connection needs a host", self.kind))?;
+        let mut url = format!("{}://", scheme);
+        if let Some(user) = self.user.as_deref().filter(|user| !user.is_empty()) {
+            url.push_str(&format!("{}@", utf8_percent_encode(user, COMPONENT)));
+        }
+        if host.contains(':') && !host.starts_with('[') {
+            url.push_str(&format!("[{}]", host));
+        } else {
+            url.push_str(&utf8_percent_encode(host, COMPONENT).to_string());
+        }
+        if let Some(port) = self.port {
+            url.push_str(&format!(":{}", port));
+        }
+        if let Some(database) = self.database.as_deref().filter(|db| !db.is_empty()) {
+            url.push_str(&format!(
+                "/{}",
+                utf8_percent_encode(database.trim_start_matches('/'), PATH)
+            ));
+        }
+        url.push_str(&self.query());
+        Ok(url)
+    }
+
+    /// `prefix` and the file path in `database`. sqlx decodes the path, so it is
+    /// escaped there; DuckDB takes it as is and has no parameters.
+    fn file(&self, prefix: &str, escape: bool) -> Result<String, String> {
+        let path = self
+            .database
+            .as_deref()
+            .filter(|path| !path.is_empty())
+            .ok_or_else(|| format!("A {:?} connection needs a database file", self.kind))?;
+        if !escape {
+            return Ok(format!("{}{}", prefix, path));
+        }
+        Ok(format!(
+            "{}{}{}",
+            prefix,
+            utf8_percent_encode(path, PATH),
+            self.query()
+        ))
+    }
+
+    /// `?key=value&...`, or nothing without params.
+    fn query(&self) -> String {
+        if self.params.is_empty() {
+            return String::new();
+        }
+        let pairs: Vec<String> = self
+            .params
+            .iter()
+            .map(|(key, value)| {
+                format!(
+                    "{}={}",
+                    utf8_percent_encode(key, COMPONENT),
+                    utf8_percent_encode(value, COMPONENT)
+                )
+            })
+            .collect();
+        format!("?{}", pairs.join("&"))
+    }
+
+    /// `odbc:` and `key=value;` attributes: `Server`, `Port`, `UID` and `Database` from
+    /// the fields, then `params` (e.g. `Driver` or `DSN`) as given.
+    fn odbc(&self) -> String {
+        let fields = [
+            ("Server", self.host.clone()),
+            ("Port", self.port.map(|port| port.to_string())),
+            ("UID", self.user.clone()),
+            ("Database", self.database.clone()),
+        ];
+        let attributes: Vec<String> = fields
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value?)))
+            .chain(self.params.clone())
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| format!("{}={}", key, odbc_api::escape_attribute_value(&value)))
+            .collect();
+        format!("odbc:{}", attributes.join(";"))
+    }
+}
@@ -0,0 +1,627 @@
+use super::column_meta::ColumnMeta;
+use super::masking::{ColumnMasks, MaskingRule};
+use super::policy::DestructiveStatement;
+use super::spill::SpillFile;
+use super::timing::{QueryTimer, Timings};
+use futures_util::{Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::mysql::MySqlQueryResult;
+use sqlx::postgres::PgQueryResult;
+use sqlx::sqlite::SqliteQueryResult;
+use sqlx::{Column, ColumnIndex, Decode, Either, Row, Type};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Rows a single `execute` call keeps in memory before spilling to disk.
+pub const MAX_RESULT_ROWS: usize = 100_000;
+
+/// Approximate bytes of converted values kept in memory before spilling to disk.
+pub const DEFAULT_MEMORY_BUDGET: usize = 64 * 1024 * 1024;
+
+/// Rows a single `execute` call returns at most, in memory or spilled; the rest of the
+/// result is dropped.
+pub const DEFAULT_MAX_ROWS: u64 = 1_000_000;
+
+/// Approximate bytes of converted values a single `execute` call returns at most.
+pub const DEFAULT_MAX_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// A database row that can be converted into JSON values.
+pub trait JsonRow: Row {
+    fn column_names(&self) -> Vec<String>;
+
+    /// Whether the row's columns are `names`, in order.
+    fn has_columns(&self, names: &[String]) -> bool {
+        self.columns().len() == names.len()
+            && self
+                .columns()
+                .iter()
+                .zip(names)
+                .all(|(col, name)| col.name() == name)
+    }
+
+    fn to_values(&self) -> Vec<Value>;
+
+    fn to_json(&self) -> Map<String, Value> {
+        self.column_names()
+            .into_iter()
+            .zip(self.to_values())
+            .collect()
+    }
+}
+
+impl<R> JsonRow for R
+where
+    R: Row,
+    usize: ColumnIndex<R>,
+    for<'r> i64: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> i32: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> i16: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> f64: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> f32: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> bool: Decode<'r, R::Database> + Type<R::Database>,
+    for<'r> String: Decode<'r, R::Database> + Type<R::Database>,
+{
+    fn column_names(&self) -> Vec<String> {
+        self.columns()
+            .iter()
+            .map(|col| col.name().to_string())
+            .collect()
+    }
+
+    fn to_values(&self) -> Vec<Value> {
+        let mut values = Vec::with_capacity(self.columns().len());
+        for col in self.columns() {
+            let idx = col.ordinal();
+
+            // Postgres only decodes integers and floats into their exact width.
+            let val: Value = if let Ok(v) = self.try_get::<i64, _>(idx) {
+                Value::Number(v.into())
+            } else if let Ok(v) = self.try_get::<i32, _>(idx) {
+                Value::Number(v.into())
+            } else if let Ok(v) = self.try_get::<i16, _>(idx) {
+                Value::Number(v.into())
+            } else if let Ok(v) = self.try_get::<f64, _>(idx) {
+                if let Some(n) = serde_json::Number::from_f64(v) {
+                    Value::Number(n)
+                } else {
+                    Value::Null
+                }
+            } else if let Ok(v) = self.try_get::<f32, _>(idx) {
+                if let Some(n) = serde_json::Number::from_f64(v as f64) {
+                    Value::Number(n)
+                } else {
+                    Value::Null
+                }
+            } else if let Ok(v) = self.try_get::<bool, _>(idx) {
+                Value::Bool(v)
+            } else if let Ok(v) = self.try_get::<String, _>(idx) {
+                Value::String(v)
+            } else {
+                Value::Null
+            };
+
+            values.push(val);
+        }
+        values
+    }
+}
+
+/// Converted rows with the column names stored once rather than per row.
+#[derive(Debug, Default)]
+pub struct RowSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+    /// What the statements changed, where the driver reports it.
+    pub affected: Option<Affected>,
+    /// Set when rows were dropped to stay within `CollectLimits`.
+    pub truncated: Option<Truncation>,
+}
+
+/// Which cap a result was cut short at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Truncation {
+    MaxRows,
+    MaxBytes,
+}
+
+/// Totals over the statements of a script.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct Affected {
+    pub rows_affected: u64,
+    /// The id generated by the last insert: MySQL's `AUTO_INCREMENT` value or SQLite's
+    /// rowid. Postgres returns generated values through `RETURNING` instead.
+    pub last_insert_id: Option<i64>,
+}
+
+/// The completion of one statement, as a sqlx driver reports it.
+pub trait Outcome {
+    fn rows_affected(&self) -> u64;
+    fn last_insert_id(&self) -> Option<i64>;
+}
+
+impl Outcome for PgQueryResult {
+    fn rows_affected(&self) -> u64 {
+        PgQueryResult::rows_affected(self)
+    }
+
+    fn last_insert_id(&self) -> Option<i64> {
+        None
+    }
+}
+
+impl Outcome for MySqlQueryResult {
+    fn rows_affected(&self) -> u64 {
+        MySqlQueryResult::rows_affected(self)
+    }
+
+    fn last_insert_id(&self) -> Option<i64> {
+        Some(MySqlQueryResult::last_insert_id(self) as i64).filter(|id| *id != 0)
+    }
+}
+
+impl Outcome for SqliteQueryResult {
+    fn rows_affected(&self) -> u64 {
+        SqliteQueryResult::rows_affected(self)
+    }
+
+    fn last_insert_id(&self) -> Option<i64> {
+        Some(self.last_insert_rowid()).filter(|id| *id != 0)
+    }
+}
+
+impl RowSet {
+    pub fn into_maps(self) -> Vec<Map<String, Value>> {
+        let columns = self.columns;
+        self.rows
+            .into_iter()
+            .map(|row| columns.iter().cloned().zip(row).collect())
+            .collect()
+    }
+
+    pub fn into_columnar(self) -> Map<String, Value> {
+        let mut data: Vec<Vec<Value>> = self
+            .columns
+            .iter()
+            .map(|_| Vec::with_capacity(self.rows.len()))
+            .collect();
+        for row in self.rows {
+            for (i, value) in row.into_iter().enumerate() {
+                data[i].push(value);
+            }
+        }
+        self.columns
+            .into_iter()
+            .zip(data.into_iter().map(Value::Array))
+            .collect()
+    }
+
+    pub fn into_output(mut self, format: ResultFormat) -> QueryOutput {
+        let affected = self.affected.take();
+        let output = match format {
+            ResultFormat::Rows => QueryOutput::Rows(self.into_maps()),
+            ResultFormat::Columnar => QueryOutput::Columnar {
+                columns: self.columns.clone(),
+                data: self.into_columnar(),
+            },
+            ResultFormat::Table => QueryOutput::Table {
+                columns: self.columns,
+                rows: self.rows,
+            },
+        };
+        match affected {
+            Some(affected) => QueryOutput::Executed {
+                rows_affected: affected.rows_affected,
+                last_insert_id: affected.last_insert_id,
+                result: Box::new(output),
+            },
+            None => output,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultFormat {
+    /// One JSON object per row (the default).
+    #[default]
+    Rows,
+    /// `{ columns, data: { column: [values...] } }`, far smaller for wide or long results.
+    Columnar,
+    /// `{ columns, rows: [[values...]] }`: column names once, then one array per row.
+    Table,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum QueryOutput {
+    Rows(Vec<Map<String, Value>>),
+    Columnar {
+        columns: Vec<String>,
+        data: Map<String, Value>,
+    },
+    Table {
+        columns: Vec<String>,
+        rows: Vec<Vec<Value>>,
+    },
+    Spilled {
+        spill_id: String,
+        total_rows: u64,
+        columns: Vec<String>,
+        page: Box<QueryOutput>,
+    },
+    /// `LIMIT injected_limit` was added to the query, by the connection's sandbox or
+    /// because the caller didn't pass `no_limit`. `truncated` is set when the limit was
+    /// reached, so more rows may exist; run again with `no_limit` to get them all.
+    LimitInjected {
+        injected_limit: u64,
+        truncated: bool,
+        result: Box<QueryOutput>,
+    },
+    /// The script wrote rows; `result` holds any that its statements returned, as
+    /// with `RETURNING`.
+    Executed {
+        rows_affected: u64,
+        last_insert_id: Option<i64>,
+        result: Box<QueryOutput>,
+    },
+    /// The result reached the `max_rows` or `max_bytes` cap named by `truncated_by`
+    /// and the rows after it were dropped; `cap` is that cap's value.
+    Truncated {
+        truncated_by: Truncation,
+        cap: u64,
+        result: Box<QueryOutput>,
+    },
+    /// `result` with the metadata of its columns, asked for with the `column_meta`
+    /// option.
+    Described {
+        column_meta: Vec<ColumnMeta>,
+        result: Box<QueryOutput>,
+    },
+    /// `result` with how long each phase of the query took, asked for with the
+    /// `timings` option.
+    Timed {
+        timings: Timings,
+        result: Box<QueryOutput>,
+    },
+    /// A result served from the result cache, stored `cached_age_ms` ago, asked for
+    /// with the `cache_ttl_ms` option.
+    Cached {
+        cached_age_ms: u64,
+        result: Box<QueryOutput>,
+    },
+    /// The script failed with a transient error and succeeded after `retries` more
+    /// attempts, under the connection's or the call's retry policy.
+    Retried {
+        retries: u32,
+        result: Box<QueryOutput>,
+    },
+    /// Nothing was run: the script contains destructive statements and the caller did
+    /// not pass a valid `confirmation_token`. `needs_confirmation` is always `true`.
+    NeedsConfirmation {
+        needs_confirmation: bool,
+        statements: Vec<DestructiveStatement>,
+    },
+    /// The script did not finish within the `timeout_ms` it was run with and was
+    /// cancelled. `timed_out` is always `true`.
+    TimedOut {
+        timed_out: bool,
+        timeout_ms: u64,
+    },
+}
+
+impl QueryOutput {
+    /// Take the rows of a `Table` result out from whatever wraps them, leaving an
+    /// empty table in their place. `None` if the output has no table.
+    pub fn take_table(&mut self) -> Option<(Vec<String>, Vec<Vec<Value>>)> {
+        match self {
+            QueryOutput::Table { columns, rows } => {
+                Some((std::mem::take(columns), std::mem::take(rows)))
+            }
+            QueryOutput::Spilled { page, .. } => page.take_table(),
+            QueryOutput::LimitInjected { result, .. }
+            | QueryOutput::Executed { result, .. }
+            | QueryOutput::Truncated { result, .. }
+            | QueryOutput::Described { result, .. }
+            | QueryOutput::Timed { result, .. }
+            | QueryOutput::Retried { result, .. }
+            | QueryOutput::Cached { result, .. } => result.take_table(),
+            QueryOutput::Rows(_)
+            | QueryOutput::Columnar { .. }
+            | QueryOutput::NeedsConfirmation { .. }
+            | QueryOutput::TimedOut { .. } => None,
+        }
+    }
+}
+
+/// Batches at least this large are converted on the blocking thread pool so that
+/// big results don't monopolise an async worker; smaller ones are converted inline.
+pub const BLOCKING_CONVERT_BATCH: usize = 1024;
+
+/// Convert a batch of rows, off the async runtime when the batch is large.
+pub async fn convert_batch<R: JsonRow>(batch: Vec<R>) -> Result<Vec<Vec<Value>>, String> {
+    if batch.len() < BLOCKING_CONVERT_BATCH {
+        return Ok(batch.iter().map(JsonRow::to_values).collect());
+    }
+    tokio::task::spawn_blocking(move || batch.iter().map(JsonRow::to_values).collect())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Clone)]
+pub struct CollectLimits {
+    /// Rows kept in memory before spilling.
+    pub max_rows: usize,
+    pub memory_budget: usize,
+    /// Rows kept in all; later rows are dropped and the result marked truncated.
+    pub row_cap: u64,
+    /// Approximate bytes kept in all, as with `row_cap`.
+    pub byte_cap: u64,
+    /// Applied to each batch as it is converted.
+    pub masking: Vec<MaskingRule>,
+    /// Marks the phases of a query run with `timings`.
+    pub timer: Option<Arc<QueryTimer>>,
+}
+
+impl CollectLimits {
+    /// Mark the connection as acquired, for a query run with `timings`.
+    pub fn acquired(&self) {
+        if let Some(timer) = &self.timer {
+            timer.acquired();
+        }
+    }
+
+    fn first_result(&self) {
+        if let Some(timer) = &self.timer {
+            timer.first_result();
+        }
+    }
+}
+
+impl Default for CollectLimits {
+    fn default() -> Self {
+        Self {
+            max_rows: MAX_RESULT_ROWS,
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+            row_cap: DEFAULT_MAX_ROWS,
+            byte_cap: DEFAULT_MAX_BYTES,
+            masking: Vec::new(),
+            timer: None,
+        }
+    }
+}
+
+pub enum Collected {
+    Memory(RowSet),
+    Spilled(SpillFile),
+}
+
+/// Drain a row stream, converting rows in batches as they arrive so that only the
+/// JSON form is kept in memory. Each full batch is converted on a blocking worker
+/// while the next one is fetched. Once the result exceeds `limits` it is moved to a
+/// temporary on-disk store and the remaining rows are appended there; once it reaches
+/// the caps, the rest of the stream is left unread. Statement completions are summed
+/// into the result's `affected` counts, which a spilled result doesn't keep. A script
+/// whose statements return rows with different columns is an error, as the result
+/// has one set of columns.
+pub async fn collect_rows<Q, R, S>(mut steps: S, limits: CollectLimits) -> Result<Collected, String>
+where
+    Q: Outcome,
+    R: JsonRow,
+    S: Stream<Item = Result<Either<Q, R>, sqlx::Error>> + Unpin,
+{
+    let mut sink = RowSink::new(limits);
+    let mut pending = Vec::with_capacity(BLOCKING_CONVERT_BATCH);
+    let mut converting = None;
+
+    while let Some(step) = steps.try_next().await.map_err(|e| e.to_string())? {
+        if pending.is_empty() && converting.is_none() {
+            sink.limits.first_result();
+        }
+        if sink.is_full() {
+            break;
+        }
+        let row = match step {
+            Either::Left(done) => {
+                let affected = sink.set.affected.get_or_insert_with(Affected::default);
+                affected.rows_affected += done.rows_affected();
+                if let Some(id) = done.last_insert_id() {
+                    affected.last_insert_id = Some(id);
+                }
+                continue;
+            }
+            Either::Right(row) => row,
+        };
+        if sink.set.columns.is_empty() {
+            sink.set.columns = row.column_names();
+        } else if !row.has_columns(&sink.set.columns) {
+            return Err(
+                "The script's statements return rows with different columns; run them separately"
+                    .to_string(),
+            );
+        }
+        pending.push(row);
+
+        if pending.len() == BLOCKING_CONVERT_BATCH {
+            let batch = std::mem::replace(&mut pending, Vec::with_capacity(BLOCKING_CONVERT_BATCH));
+            if let Some(previous) = converting.replace(tokio::spawn(convert_batch(batch))) {
+                sink.push(join_batch(previous).await?).await?;
+            }
+        }
+    }
+
+    if let Some(previous) = converting {
+        sink.push(join_batch(previous).await?).await?;
+    }
+    sink.push(convert_batch(pending).await?).await?;
+    Ok(sink.finish())
+}
+
+/// Output of a driver outside sqlx, produced on a blocking thread. `Columns` is sent
+/// once, before any rows.
+pub enum Batch {
+    Columns(Vec<String>),
+    Rows(Vec<Vec<Value>>),
+}
+
+/// The blocking-driver counterpart of `collect_rows`: `produce` runs on the blocking
+/// thread pool and sends converted batches, which are masked and spilled as they arrive.
+/// A send fails once collection has given up, which should stop the producer.
+pub async fn collect_blocking<F>(limits: CollectLimits, produce: F) -> Result<Collected, String>
+where
+    F: FnOnce(&mpsc::Sender<Batch>) -> Result<(), String> + Send + 'static,
+{
+    let (tx, mut rx) = mpsc::channel(2);
+    let producer = tokio::task::spawn_blocking(move || produce(&tx));
+
+    let mut sink = RowSink::new(limits);
+    while let Some(batch) = rx.recv().await {
+        match batch {
+            Batch::Columns(columns) => sink.set_columns(columns),
+            Batch::Rows(rows) => sink.push(rows).await?,
+        }
+        if sink.is_full() {
+            break;
+        }
+    }
+    // A full sink stops the producer by hanging up, which fails its next send.
+    drop(rx);
+    let produced = producer.await.map_err(|e| e.to_string())?;
+    if !sink.is_full() {
+        produced?;
+    }
+    Ok(sink.finish())
+}
+
+/// Masks, buffers and spills converted rows as a result is collected. Drivers with
+/// an async row source feed it directly; the others go through `collect_blocking`.
+pub struct RowSink {
+    set: RowSet,
+    /// Bytes held in memory.
+    bytes: usize,
+    /// Rows and bytes collected in all, counted against the caps.
+    total_rows: u64,
+    total_bytes: u64,
+    limits: CollectLimits,
+    masks: Option<ColumnMasks>,
+    spill: Option<SpillFile>,
+}
+
+impl RowSink {
+    pub fn new(limits: CollectLimits) -> Self {
+        Self {
+            set: RowSet::default(),
+            bytes: 0,
+            total_rows: 0,
+            total_bytes: 0,
+            limits,
+            masks: None,
+            spill: None,
+        }
+    }
+
+    /// Set the column names. Must be called before the first `push`.
+    pub fn set_columns(&mut self, columns: Vec<String>) {
+        self.limits.first_result();
+        self.set.columns = columns;
+    }
+
+    /// Whether a cap has been reached; rows pushed after that are dropped.
+    pub fn is_full(&self) -> bool {
+        self.set.truncated.is_some()
+    }
+
+    pub async fn push(&mut self, mut batch: Vec<Vec<Value>>) -> Result<(), String> {
+        self.limits.first_result();
+        if self.is_full() {
+            return Ok(());
+        }
+        let (rules, columns) = (&self.limits.masking, &self.set.columns);
+        self.masks
+            .get_or_insert_with(|| ColumnMasks::new(rules, columns))
+            .apply(&mut batch);
+
+        let mut bytes = 0;
+        let mut kept = 0;
+        for row in &batch {
+            let size = row.iter().map(approx_size).sum::<usize>();
+            if self.total_rows + kept as u64 >= self.limits.row_cap {
+                self.set.truncated = Some(Truncation::MaxRows);
+                break;
+            }
+            if self.total_bytes + (bytes + size) as u64 > self.limits.byte_cap {
+                self.set.truncated = Some(Truncation::MaxBytes);
+                break;
+            }
+            bytes += size;
+            kept += 1;
+        }
+        batch.truncate(kept);
+        self.total_rows += kept as u64;
+        self.total_bytes += bytes as u64;
+
+        if let Some(spill) = &mut self.spill {
+            return spill.append(batch).await;
+        }
+
+        self.bytes += bytes;
+        self.set.rows.extend(batch);
+
+        if self.bytes > self.limits.memory_budget || self.set.rows.len() > self.limits.max_rows {
+            let mut spill = SpillFile::create(self.set.columns.clone()).await?;
+            spill.append(std::mem::take(&mut self.set.rows)).await?;
+            self.spill = Some(spill);
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Collected {
+        if let Some(timer) = &self.limits.timer {
+            timer.fetched();
+        }
+        match self.spill {
+            Some(mut spill) => {
+                spill.truncated = self.set.truncated;
+                Collected::Spilled(spill)
+            }
+            None => Collected::Memory(self.set),
+        }
+    }
+}
+
+/// Rough in-memory footprint of a converted value, used against the memory budget.
+fn approx_size(value: &Value) -> usize {
+    std::mem::size_of::<Value>()
+        + match value {
+            Value::String(s) => s.len(),
+            Value::Array(items) => items.iter().map(approx_size).sum(),
+            Value::Object(map) => map.iter().map(|(k, v)| k.len() + approx_size(v)).sum(),
+            _ => 0,
+        }
+}
+
+/// Convert every row of a stream whose size is already bounded by the query itself,
+/// such as a page fetched with `LIMIT`.
+pub async fn collect_bounded<R, S>(mut rows: S) -> Result<RowSet, String>
+where
+    R: JsonRow,
+    S: Stream<Item = Result<R, sqlx::Error>> + Unpin,
+{
+    let mut set = RowSet::default();
+    let mut pending = Vec::new();
+    while let Some(row) = rows.try_next().await.map_err(|e| e.to_string())? {
+        if set.columns.is_empty() {
+            set.columns = row.column_names();
+        }
+        pending.push(row);
+    }
+    set.rows = convert_batch(pending).await?;
+    Ok(set)
+}
+
+async fn join_batch(
+    handle: tokio::task::JoinHandle<Result<Vec<Vec<Value>>, String>>,
+) -> Result<Vec<Vec<Value>>, String> {
+    handle.await.map_err(|e| e.to_string())?
+}
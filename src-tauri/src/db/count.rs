@@ -0,0 +1,229 @@
+use super::flavor::Flavor;
+use super::sql::{leading_keyword, quote_ident, split_statements};
+use super::{AppState, Connection};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::Row;
+use tauri::State;
+
+#[derive(Debug, Serialize)]
+pub struct RowCount {
+    pub count: i64,
+    /// False when `count` is a planner/statistics estimate.
+    pub exact: bool,
+}
+
+/// Row count for a table name or a SELECT statement.
+///
+/// By default this returns the planner's estimate (`reltuples`/`EXPLAIN` on Postgres,
+/// `TABLE_ROWS`/`EXPLAIN` on MySQL, `sqlite_stat1` on SQLite, table statistics/`EXPLAIN`
+/// on CockroachDB), which is instant even on
+/// huge tables. Pass `exact: true` to run a real `COUNT(*)`; SQLite falls back to an
+/// exact count when no statistics have been gathered.
+#[tauri::command]
+pub async fn estimate_count(
+    state: State<'_, AppState>,
+    id: String,
+    table_or_sql: String,
+    exact: Option<bool>,
+) -> Result<RowCount, String> {
+    let conn = state.connection(&id)?;
+    let target = CountTarget::parse(&table_or_sql)?;
+
+    if !exact.unwrap_or(false) {
+        if let Some(count) = estimate(&conn, state.flavor(&id), &target).await? {
+            return Ok(RowCount {
+                count,
+                exact: false,
+            });
+        }
+    }
+
+    let sql = match &target {
+        CountTarget::Table(table) => {
+            format!("SELECT COUNT(*) FROM {}", quote_ident(conn.kind(), table))
+        }
+        CountTarget::Query(query) => format!("SELECT COUNT(*) FROM ({}) AS counted", query),
+    };
+    let count = match &conn {
+        Connection::Postgres(pool) => sqlx::query_scalar(&sql).fetch_one(pool).await,
+        Connection::MySql(pool) => sqlx::query_scalar(&sql).fetch_one(pool).await,
+        Connection::Sqlite(pool) => sqlx::query_scalar(&sql).fetch_one(pool).await,
+        Connection::Oracle(_)
+        | Connection::Mongo(_)
+        | Connection::DuckDb(_)
+        | Connection::ClickHouse(_)
+        | Connection::Redis(_)
+        | Connection::Cassandra(_)
+        | Connection::Surreal(_)
+        | Connection::BigQuery(_)
+        | Connection::Firebird(_)
+        | Connection::Odbc(_) => return Err(conn.unsupported("Row counting")),
+    }
+    .map_err(|e| e.to_string())?;
+
+    Ok(RowCount { count, exact: true })
+}
+
+enum CountTarget {
+    Table(String),
+    Query(String),
+}
+
+impl CountTarget {
+    fn parse(input: &str) -> Result<Self, String> {
+        let statements = split_statements(input);
+        match statements.as_slice() {
+            [stmt] if matches!(leading_keyword(stmt).as_deref(), Some("SELECT" | "WITH")) => {
+                Ok(CountTarget::Query(stmt.to_string()))
+            }
+            [_, _, ..] => Err("Only a single statement can be counted".to_string()),
+            _ => Ok(CountTarget::Table(input.trim().to_string())),
+        }
+    }
+}
+
+async fn estimate(
+    conn: &Connection,
+    flavor: Option<Flavor>,
+    target: &CountTarget,
+) -> Result<Option<i64>, String> {
+    let cockroach = flavor == Some(Flavor::CockroachDb);
+    match (conn, target) {
+        (Connection::Postgres(pool), CountTarget::Table(table)) if cockroach => {
+            // Statistics are collected automatically; the newest set has the row count.
+            let table = quote_ident(conn.kind(), table);
+            let rows: Option<i64> = sqlx::query_scalar(&format!(
+                "SELECT row_count FROM [SHOW STATISTICS FOR TABLE {}] \
+                 ORDER BY created DESC LIMIT 1",
+                table
+            ))
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            match rows {
+                Some(n) => Ok(Some(n)),
+                None => cockroach_plan_rows(pool, &format!("SELECT * FROM {}", table)).await,
+            }
+        }
+        (Connection::Postgres(pool), CountTarget::Query(query)) if cockroach => {
+            cockroach_plan_rows(pool, query).await
+        }
+        (Connection::Postgres(pool), CountTarget::Table(table)) => {
+            // reltuples is -1 until the table has been vacuumed or analyzed.
+            let reltuples: Option<f32> =
+                sqlx::query_scalar("SELECT reltuples FROM pg_class WHERE oid = to_regclass($1)")
+                    .bind(quote_ident(conn.kind(), table))
+                    .fetch_optional(pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            match reltuples {
+                Some(n) if n >= 0.0 => Ok(Some(n as i64)),
+                _ => {
+                    let query = format!("SELECT * FROM {}", quote_ident(conn.kind(), table));
+                    pg_plan_rows(pool, &query).await.map(Some)
+                }
+            }
+        }
+        (Connection::Postgres(pool), CountTarget::Query(query)) => {
+            pg_plan_rows(pool, query).await.map(Some)
+        }
+        (Connection::MySql(pool), CountTarget::Table(table)) => {
+            let rows: Option<Option<u64>> = sqlx::query_scalar(
+                "SELECT TABLE_ROWS FROM information_schema.TABLES \
+                 WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?",
+            )
+            .bind(table)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows.flatten().map(|n| n as i64))
+        }
+        (Connection::MySql(pool), CountTarget::Query(query)) => {
+            // Nested-loop estimate: the product of rows * filtered% across the plan.
+            let rows = sqlx::query(&format!("EXPLAIN {}", query))
+                .fetch_all(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut estimate = 1.0;
+            for row in rows {
+                let scanned = row
+                    .try_get::<Option<u64>, _>("rows")
+                    .ok()
+                    .flatten()
+                    .map(|n| n as f64)
+                    .unwrap_or(1.0);
+                let filtered = row
+                    .try_get::<Option<f64>, _>("filtered")
+                    .ok()
+                    .flatten()
+                    .or_else(|| {
+                        row.try_get::<Option<f32>, _>("filtered")
+                            .ok()
+                            .flatten()
+                            .map(f64::from)
+                    })
+                    .unwrap_or(100.0);
+                estimate *= scanned * filtered / 100.0;
+            }
+            Ok(Some(estimate.round() as i64))
+        }
+        (Connection::Sqlite(pool), CountTarget::Table(table)) => {
+            // The first number in sqlite_stat1.stat is the table's row count at the
+            // last ANALYZE. The table doesn't exist until ANALYZE has run once.
+            let stat: Result<Option<String>, _> =
+                sqlx::query_scalar("SELECT stat FROM sqlite_stat1 WHERE tbl = ? LIMIT 1")
+                    .bind(table)
+                    .fetch_optional(pool)
+                    .await;
+            Ok(stat
+                .ok()
+                .flatten()
+                .and_then(|s| s.split_whitespace().next()?.parse().ok()))
+        }
+        (Connection::Sqlite(_), CountTarget::Query(_))
+        | (
+            Connection::Oracle(_)
+            | Connection::Mongo(_)
+            | Connection::DuckDb(_)
+            | Connection::ClickHouse(_)
+            | Connection::Redis(_)
+            | Connection::Cassandra(_)
+            | Connection::Surreal(_)
+            | Connection::BigQuery(_)
+            | Connection::Firebird(_)
+            | Connection::Odbc(_),
+            _,
+        ) => Ok(None),
+    }
+}
+
+async fn pg_plan_rows(pool: &sqlx::PgPool, query: &str) -> Result<i64, String> {
+    let plan: Value = sqlx::query_scalar(&format!("EXPLAIN (FORMAT JSON) {}", query))
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    plan[0]["Plan"]["Plan Rows"]
+        .as_f64()
+        .map(|n| n as i64)
+        .ok_or_else(|| "Could not read a row estimate from the query plan".to_string())
+}
+
+/// CockroachDB has no `FORMAT JSON`; its text plan carries `estimated row count: 1,234`
+/// on the root node once the table has statistics.
+async fn cockroach_plan_rows(pool: &sqlx::PgPool, query: &str) -> Result<Option<i64>, String> {
+    let lines: Vec<String> = sqlx::query_scalar(&format!("EXPLAIN {}", query))
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(lines.iter().find_map(|line| {
+        let (_, rest) = line.split_once("estimated row count:")?;
+        let digits: String = rest
+            .trim_start()
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == ',')
+            .filter(char::is_ascii_digit)
+            .collect();
+        digits.parse().ok()
+    }))
+}
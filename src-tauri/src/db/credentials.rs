@@ -0,0 +1,301 @@
+//! Saved connection passwords, encrypted at rest with a key derived from an app-level
+//! master password. For users who can't or won't use the OS keychain.
+
+use super::AppState;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::State;
+use zeroize::Zeroizing;
+
+const STORE_FILE: &str = "credentials.json";
+/// Encrypted under the master key so a wrong password is detected on unlock.
+const CHECK_PLAINTEXT: &[u8] = b"db-connector credential store";
+
+type Key = Zeroizing<[u8; 32]>;
+
+#[derive(Serialize, Deserialize)]
+struct StoreFile {
+    version: u32,
+    kdf: KdfParams,
+    check: Sealed,
+    entries: BTreeMap<String, Sealed>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct KdfParams {
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CredentialStatus {
+    /// A master password has been set.
+    pub initialized: bool,
+    pub unlocked: bool,
+}
+
+/// The derived master key while unlocked. File access happens with the lock held so
+/// read-modify-write cycles don't interleave.
+#[derive(Default)]
+pub struct CredentialStore {
+    key: Mutex<Option<Key>>,
+}
+
+impl CredentialStore {
+    pub fn is_unlocked(&self) -> bool {
+        self.key.lock().unwrap().is_some()
+    }
+
+    pub fn lock(&self) {
+        self.key.lock().unwrap().take();
+    }
+
+    pub fn get(&self, path: &Path, profile_id: &str) -> Result<Option<String>, String> {
+        let key = self.key.lock().unwrap();
+        let key = key.as_ref().ok_or(LOCKED)?;
+        let Some(store) = load(path)? else {
+            return Ok(None);
+        };
+        store
+            .entries
+            .get(profile_id)
+            .map(|sealed| {
+                let plain = open(key, sealed)?;
+                String::from_utf8(plain.to_vec()).map_err(|e| e.to_string())
+            })
+            .transpose()
+    }
+
+    pub fn put(&self, path: &Path, profile_id: &str, secret: &str) -> Result<(), String> {
+        let key = self.key.lock().unwrap();
+        let key = key.as_ref().ok_or(LOCKED)?;
+        let mut store = load(path)?.ok_or(LOCKED)?;
+        store
+            .entries
+            .insert(profile_id.to_string(), seal(key, secret.as_bytes())?);
+        save(path, &store)
+    }
+
+    pub fn remove(&self, path: &Path, profile_id: &str) -> Result<bool, String> {
+        let key = self.key.lock().unwrap();
+        key.as_ref().ok_or(LOCKED)?;
+        let Some(mut store) = load(path)? else {
+            return Ok(false);
+        };
+        let removed = store.entries.remove(profile_id).is_some();
+        save(path, &store)?;
+        Ok(removed)
+    }
+}
+
+impl AppState {
+    /// The saved secret for `profile_id`, if the store is unlocked and has one.
+    pub fn saved_credential(&self, profile_id: &str) -> Result<Option<String>, String> {
+        let path = self.data_path(STORE_FILE)?;
+        self.credentials.get(&path, profile_id)
+    }
+}
+
+const LOCKED: &str = "Credential store is locked; unlock it with the master password first";
+
+/// Report whether a master password exists and whether the store is unlocked.
+#[tauri::command]
+pub fn credentials_status(state: State<'_, AppState>) -> Result<CredentialStatus, String> {
+    let path = state.data_path(STORE_FILE)?;
+    Ok(CredentialStatus {
+        initialized: path.exists(),
+        unlocked: state.credentials.is_unlocked(),
+    })
+}
+
+/// Unlock the store with the master password. The first call sets the master password.
+#[tauri::command]
+pub async fn unlock_credentials(
+    state: State<'_, AppState>,
+    master_password: String,
+) -> Result<(), String> {
+    let master_password = Zeroizing::new(master_password);
+    let path = state.data_path(STORE_FILE)?;
+
+    let key = match load(&path)? {
+        Some(store) => {
+            let key = derive_key(&master_password, &store.kdf).await?;
+            open(&key, &store.check).map_err(|_| "Incorrect master password".to_string())?;
+            key
+        }
+        None => {
+            let kdf = new_kdf_params();
+            let key = derive_key(&master_password, &kdf).await?;
+            let store = StoreFile {
+                version: 1,
+                check: seal(&key, CHECK_PLAINTEXT)?,
+                kdf,
+                entries: BTreeMap::new(),
+            };
+            save(&path, &store)?;
+            key
+        }
+    };
+
+    *state.credentials.key.lock().unwrap() = Some(key);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn lock_credentials(state: State<'_, AppState>) {
+    state.credentials.lock();
+}
+
+/// Re-encrypt every saved credential under a key derived from `new_password`.
+#[tauri::command]
+pub async fn change_master_password(
+    state: State<'_, AppState>,
+    current_password: String,
+    new_password: String,
+) -> Result<(), String> {
+    let current_password = Zeroizing::new(current_password);
+    let new_password = Zeroizing::new(new_password);
+    let path = state.data_path(STORE_FILE)?;
+    let store = load(&path)?.ok_or("No master password has been set")?;
+
+    let old_key = derive_key(&current_password, &store.kdf).await?;
+    open(&old_key, &store.check).map_err(|_| "Incorrect master password".to_string())?;
+    let kdf = new_kdf_params();
+    let new_key = derive_key(&new_password, &kdf).await?;
+
+    let mut key = state.credentials.key.lock().unwrap();
+    // Re-read under the lock in case an entry was saved while the keys were derived.
+    let store = load(&path)?.ok_or("No master password has been set")?;
+    let mut entries = BTreeMap::new();
+    for (profile_id, sealed) in &store.entries {
+        let plain = open(&old_key, sealed)?;
+        entries.insert(profile_id.clone(), seal(&new_key, &plain)?);
+    }
+    save(
+        &path,
+        &StoreFile {
+            version: 1,
+            check: seal(&new_key, CHECK_PLAINTEXT)?,
+            kdf,
+            entries,
+        },
+    )?;
+    *key = Some(new_key);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn save_credential(
+    state: State<'_, AppState>,
+    profile_id: String,
+    secret: String,
+) -> Result<(), String> {
+    let secret = Zeroizing::new(secret);
+    let path = state.data_path(STORE_FILE)?;
+    state.credentials.put(&path, &profile_id, &secret)
+}
+
+#[tauri::command]
+pub fn get_credential(
+    state: State<'_, AppState>,
+    profile_id: String,
+) -> Result<Option<String>, String> {
+    let path = state.data_path(STORE_FILE)?;
+    state.credentials.get(&path, &profile_id)
+}
+
+#[tauri::command]
+pub fn delete_credential(state: State<'_, AppState>, profile_id: String) -> Result<bool, String> {
+    let path = state.data_path(STORE_FILE)?;
+    state.credentials.remove(&path, &profile_id)
+}
+
+fn new_kdf_params() -> KdfParams {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    KdfParams {
+        salt: BASE64.encode(salt),
+        m_cost: Params::DEFAULT_M_COST,
+        t_cost: Params::DEFAULT_T_COST,
+        p_cost: Params::DEFAULT_P_COST,
+    }
+}
+
+/// Argon2id is deliberately slow, so it runs on the blocking pool.
+async fn derive_key(password: &str, kdf: &KdfParams) -> Result<Key, String> {
+    let password = Zeroizing::new(password.as_bytes().to_vec());
+    let salt = BASE64.decode(&kdf.salt).map_err(|e| e.to_string())?;
+    let params =
+        Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32)).map_err(|e| e.to_string())?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(&password, &salt, key.as_mut())
+            .map_err(|e| e.to_string())?;
+        Ok(key)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn seal(key: &Key, plaintext: &[u8]) -> Result<Sealed, String> {
+    let cipher = XChaCha20Poly1305::new(key.as_ref().into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| "Encryption failed".to_string())?;
+    Ok(Sealed {
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+}
+
+fn open(key: &Key, sealed: &Sealed) -> Result<Zeroizing<Vec<u8>>, String> {
+    let cipher = XChaCha20Poly1305::new(key.as_ref().into());
+    let nonce = BASE64.decode(&sealed.nonce).map_err(|e| e.to_string())?;
+    if nonce.len() != 24 {
+        return Err("Corrupt credential entry".to_string());
+    }
+    let ciphertext = BASE64
+        .decode(&sealed.ciphertext)
+        .map_err(|e| e.to_string())?;
+    cipher
+        .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+        .map(Zeroizing::new)
+        .map_err(|_| "Could not decrypt credential".to_string())
+}
+
+fn load(path: &Path) -> Result<Option<StoreFile>, String> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| e.to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Write via a temporary file and rename so a crash never leaves a truncated store.
+fn save(path: &Path, store: &StoreFile) -> Result<(), String> {
+    let bytes = serde_json::to_vec_pretty(store).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
@@ -0,0 +1,229 @@
+//! Cursors for paging through large results one batch at a time. On Postgres the
+//! query runs as a `DECLARE`d cursor and each batch is a `FETCH`; on MySQL and SQLite
+//! the result is streamed and left unread until the next batch is asked for. Either
+//! way the cursor holds one pooled connection until it is exhausted, closed, or left
+//! idle for `CURSOR_IDLE_TIMEOUT`.
+
+use super::convert::{convert_batch, JsonRow, RowSet};
+use super::masking::{ColumnMasks, MaskingRule};
+use super::sql::{self, StatementKind};
+use super::{generate_id, redact, AppState, Connection};
+use futures_util::{Stream, TryStreamExt};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::postgres::Postgres;
+use sqlx::Transaction;
+use std::time::Duration;
+use tauri::State;
+use tokio::sync::{mpsc, oneshot};
+
+pub const DEFAULT_CURSOR_BATCH_SIZE: usize = 1000;
+
+/// How long an open cursor waits for `fetch_next` before closing itself.
+const CURSOR_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Serialize)]
+pub struct CursorBatch {
+    pub rows: Vec<Map<String, Value>>,
+    /// No rows are left; the cursor has been closed.
+    pub done: bool,
+}
+
+type Reply = oneshot::Sender<Result<CursorBatch, String>>;
+
+/// The connection id a cursor was opened on, and where to ask it for batches.
+pub type CursorSlot = (String, mpsc::Sender<Reply>);
+
+impl AppState {
+    /// Close the cursors open on connection `id`, so its pool can close.
+    pub fn end_cursors(&self, id: &str) {
+        self.cursors
+            .lock()
+            .unwrap()
+            .retain(|_, (connection_id, _)| connection_id != id);
+    }
+}
+
+/// Open a cursor over the single SELECT `sql` and return its id for `fetch_next`.
+#[tauri::command]
+pub async fn open_cursor(
+    state: State<'_, AppState>,
+    id: String,
+    sql: String,
+    batch_size: Option<usize>,
+    unmask: Option<bool>,
+) -> Result<String, String> {
+    let conn = state.connection(&id)?;
+    state.check_policy(&id, &sql)?;
+    let single_select = matches!(
+        &sql::split_statements(&sql)[..],
+        [stmt] if sql::classify(stmt) == StatementKind::Select
+    );
+    if !single_select {
+        return Err("Cursors can only be opened on a single SELECT".to_string());
+    }
+    let masking = state.masking_rules(&id, unmask.unwrap_or(false))?;
+    let sql = state
+        .sandboxed(&id, &sql)
+        .map_or(sql, |(rewritten, _)| rewritten);
+    let batch_size = batch_size.unwrap_or(DEFAULT_CURSOR_BATCH_SIZE).max(1);
+    let cursor_id = generate_id("cursor");
+    let (sender, requests) = mpsc::channel(1);
+    let server = Server {
+        batch_size,
+        masking,
+        requests,
+    };
+
+    match &conn {
+        Connection::Postgres(pool) => {
+            let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+            let declare = format!("DECLARE {} NO SCROLL CURSOR FOR {}", cursor_id, sql);
+            let declared = sqlx::Executor::execute(&mut *tx, declare.as_str())
+                .await
+                .map(drop)
+                .map_err(|e| e.to_string());
+            state.audit(&id, &sql, declared.as_ref().err().map(String::as_str));
+            declared.map_err(|e| redact::redact(&e))?;
+            let name = cursor_id.clone();
+            tauri::async_runtime::spawn(server.serve_pg(tx, name));
+        }
+        Connection::MySql(pool) => {
+            let mut conn = state.acquire(&id, pool).await?;
+            state.audit(&id, &sql, None);
+            tauri::async_runtime::spawn(async move {
+                let rows = sqlx::query(&sql).persistent(false).fetch(&mut *conn);
+                server.serve_stream(rows).await
+            });
+        }
+        Connection::Sqlite(pool) => {
+            let mut conn = state.acquire(&id, pool).await?;
+            state.audit(&id, &sql, None);
+            tauri::async_runtime::spawn(async move {
+                let rows = sqlx::query(&sql).persistent(false).fetch(&mut *conn);
+                server.serve_stream(rows).await
+            });
+        }
+        _ => return Err(conn.unsupported("Cursors")),
+    }
+
+    state
+        .cursors
+        .lock()
+        .unwrap()
+        .insert(cursor_id.clone(), (id, sender));
+    Ok(cursor_id)
+}
+
+/// The next batch of cursor `cursor_id`'s rows. Once a batch comes back `done`, the
+/// cursor is gone.
+#[tauri::command]
+pub async fn fetch_next(
+    state: State<'_, AppState>,
+    cursor_id: String,
+) -> Result<CursorBatch, String> {
+    let sender = match state.cursors.lock().unwrap().get(&cursor_id) {
+        Some((_, sender)) => sender.clone(),
+        None => return Err("Cursor not found".to_string()),
+    };
+    let (reply, batch) = oneshot::channel();
+    let batch = match sender.send(reply).await {
+        Ok(()) => batch
+            .await
+            .unwrap_or_else(|_| Err("The cursor has closed".to_string())),
+        Err(_) => Err("The cursor has closed".to_string()),
+    };
+    if !matches!(batch, Ok(CursorBatch { done: false, .. })) {
+        state.cursors.lock().unwrap().remove(&cursor_id);
+    }
+    batch.map_err(|e| redact::redact(&e))
+}
+
+/// Close cursor `cursor_id` and return its connection to the pool. Returns `false` if
+/// it was already closed.
+#[tauri::command]
+pub fn close_cursor(state: State<'_, AppState>, cursor_id: String) -> bool {
+    state.cursors.lock().unwrap().remove(&cursor_id).is_some()
+}
+
+/// Answers `fetch_next` for one cursor until it is exhausted, closed or left idle.
+struct Server {
+    batch_size: usize,
+    masking: Vec<MaskingRule>,
+    requests: mpsc::Receiver<Reply>,
+}
+
+impl Server {
+    async fn next_request(&mut self) -> Option<Reply> {
+        tokio::time::timeout(CURSOR_IDLE_TIMEOUT, self.requests.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    async fn serve_pg(mut self, mut tx: Transaction<'static, Postgres>, name: String) {
+        let fetch = format!("FETCH FORWARD {} FROM {}", self.batch_size, name);
+        while let Some(reply) = self.next_request().await {
+            let batch = match sqlx::Executor::fetch_all(&mut *tx, fetch.as_str()).await {
+                Ok(rows) => {
+                    let done = rows.len() < self.batch_size;
+                    self.batch(rows, done).await
+                }
+                Err(e) => Err(e.to_string()),
+            };
+            let last = !matches!(batch, Ok(CursorBatch { done: false, .. }));
+            let _ = reply.send(batch);
+            if last {
+                break;
+            }
+        }
+        // Dropping the transaction rolls it back, closing the cursor.
+    }
+
+    async fn serve_stream<R, S>(mut self, mut rows: S)
+    where
+        R: JsonRow,
+        S: Stream<Item = Result<R, sqlx::Error>> + Unpin,
+    {
+        while let Some(reply) = self.next_request().await {
+            let mut chunk = Vec::with_capacity(self.batch_size);
+            let mut done = false;
+            let batch = loop {
+                if chunk.len() == self.batch_size {
+                    break Ok(());
+                }
+                match rows.try_next().await {
+                    Ok(Some(row)) => chunk.push(row),
+                    Ok(None) => {
+                        done = true;
+                        break Ok(());
+                    }
+                    Err(e) => break Err(e.to_string()),
+                }
+            };
+            let batch = match batch {
+                Ok(()) => self.batch(chunk, done).await,
+                Err(e) => Err(e),
+            };
+            let last = !matches!(batch, Ok(CursorBatch { done: false, .. }));
+            let _ = reply.send(batch);
+            if last {
+                break;
+            }
+        }
+    }
+
+    async fn batch<R: JsonRow>(&self, rows: Vec<R>, done: bool) -> Result<CursorBatch, String> {
+        let columns = rows.first().map(JsonRow::column_names).unwrap_or_default();
+        let mut rows = convert_batch(rows).await?;
+        ColumnMasks::new(&self.masking, &columns).apply(&mut rows);
+        let rows = RowSet {
+            columns,
+            rows,
+            affected: None,
+            truncated: None,
+        }
+        .into_maps();
+        Ok(CursorBatch { rows, done })
+    }
+}
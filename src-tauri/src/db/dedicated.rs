@@ -0,0 +1,157 @@
+//! Dedicated connections for editor tabs. Pooled queries may each land on a different
+//! connection, so temp tables, `SET` variables and advisory locks don't carry over from
+//! one to the next; a dedicated connection is taken out of the pool for one tab, and
+//! `execute` runs on it when given its session id. It is closed rather than returned
+//! to the pool when the session ends, so its state doesn't leak into other queries.
+
+use super::cancel::InterruptGuard;
+use super::column_meta::{self, ColumnMeta};
+use super::convert::{collect_rows, CollectLimits, Collected};
+use super::params::bind_value;
+use super::{generate_id, AppState, Connection};
+use serde_json::Value;
+use sqlx::mysql::MySql;
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::Postgres;
+use sqlx::sqlite::Sqlite;
+use std::sync::Arc;
+use tauri::State;
+use tokio::sync::Mutex;
+
+pub enum DedicatedConnection {
+    Postgres(PoolConnection<Postgres>),
+    MySql(PoolConnection<MySql>),
+    Sqlite(PoolConnection<Sqlite>),
+}
+
+/// The connection id a session was opened on, and its connection until it closes.
+pub type SessionSlot = (String, Arc<Mutex<Option<DedicatedConnection>>>);
+
+impl DedicatedConnection {
+    pub async fn run(
+        &mut self,
+        sql: &str,
+        binds: &[Value],
+        limits: CollectLimits,
+    ) -> Result<Collected, String> {
+        match self {
+            DedicatedConnection::Postgres(conn) => {
+                let query = binds
+                    .iter()
+                    .try_fold(sqlx::query(sql).persistent(false), bind_value)?;
+                collect_rows(sqlx::Executor::fetch_many(&mut **conn, query), limits).await
+            }
+            DedicatedConnection::MySql(conn) => {
+                let query = binds
+                    .iter()
+                    .try_fold(sqlx::query(sql).persistent(false), bind_value)?;
+                collect_rows(sqlx::Executor::fetch_many(&mut **conn, query), limits).await
+            }
+            DedicatedConnection::Sqlite(conn) => {
+                let query = binds
+                    .iter()
+                    .try_fold(sqlx::query(sql).persistent(false), bind_value)?;
+                collect_rows(sqlx::Executor::fetch_many(&mut **conn, query), limits).await
+            }
+        }
+    }
+
+    /// The metadata of `sql`'s columns, described in the session's connection.
+    pub async fn describe(&mut self, sql: &str) -> Result<Vec<ColumnMeta>, String> {
+        match self {
+            DedicatedConnection::Postgres(conn) => sqlx::Executor::describe(&mut **conn, sql)
+                .await
+                .map(|describe| column_meta::from_describe(&describe)),
+            DedicatedConnection::MySql(conn) => sqlx::Executor::describe(&mut **conn, sql)
+                .await
+                .map(|describe| column_meta::from_describe(&describe)),
+            DedicatedConnection::Sqlite(conn) => sqlx::Executor::describe(&mut **conn, sql)
+                .await
+                .map(|describe| column_meta::from_describe(&describe)),
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    /// Register the session's connection for `cancel_query` under `query_id`, if given.
+    pub async fn interruptible<'s>(
+        &mut self,
+        state: &'s AppState,
+        query_id: Option<&str>,
+    ) -> Result<Option<InterruptGuard<'s>>, String> {
+        match self {
+            DedicatedConnection::Postgres(conn) => state.interruptible_pg(query_id, conn).await,
+            DedicatedConnection::MySql(conn) => state.interruptible_mysql(query_id, conn).await,
+            DedicatedConnection::Sqlite(conn) => state.interruptible_sqlite(query_id, conn).await,
+        }
+    }
+
+    async fn close(self) -> Result<(), sqlx::Error> {
+        match self {
+            DedicatedConnection::Postgres(conn) => conn.close().await,
+            DedicatedConnection::MySql(conn) => conn.close().await,
+            DedicatedConnection::Sqlite(conn) => conn.close().await,
+        }
+    }
+}
+
+impl AppState {
+    /// Session `session_id`, which must belong to connection `id`.
+    pub fn dedicated_session(
+        &self,
+        session_id: &str,
+        id: &str,
+    ) -> Result<Arc<Mutex<Option<DedicatedConnection>>>, String> {
+        match self.dedicated.lock().unwrap().get(session_id) {
+            Some((connection_id, slot)) if connection_id == id => Ok(slot.clone()),
+            Some(_) => Err(format!(
+                "Session {} belongs to another connection",
+                session_id
+            )),
+            None => Err("Session not found".to_string()),
+        }
+    }
+
+    /// Drop the sessions open on connection `id`, so its pool can close.
+    pub fn end_sessions(&self, id: &str) {
+        self.dedicated
+            .lock()
+            .unwrap()
+            .retain(|_, (connection_id, _)| connection_id != id);
+    }
+}
+
+/// Take one of `id`'s pooled connections for a tab and return the session id to pass
+/// to `execute` as `session`.
+#[tauri::command]
+pub async fn open_session(state: State<'_, AppState>, id: String) -> Result<String, String> {
+    let conn = state.connection(&id)?;
+    let dedicated = match &conn {
+        Connection::Postgres(pool) => {
+            DedicatedConnection::Postgres(state.acquire(&id, pool).await?)
+        }
+        Connection::MySql(pool) => DedicatedConnection::MySql(state.acquire(&id, pool).await?),
+        Connection::Sqlite(pool) => DedicatedConnection::Sqlite(state.acquire(&id, pool).await?),
+        _ => return Err(conn.unsupported("Dedicated sessions")),
+    };
+    let session_id = generate_id("session");
+    state.dedicated.lock().unwrap().insert(
+        session_id.clone(),
+        (id, Arc::new(Mutex::new(Some(dedicated)))),
+    );
+    Ok(session_id)
+}
+
+/// Close session `session_id`'s connection once any script running on it finishes;
+/// the pool opens a fresh one in its place when needed. Returns `false` if there was
+/// no such session.
+#[tauri::command]
+pub async fn close_session(state: State<'_, AppState>, session_id: String) -> Result<bool, String> {
+    let Some((_, slot)) = state.dedicated.lock().unwrap().remove(&session_id) else {
+        return Ok(false);
+    };
+    let dedicated = slot.lock().await.take();
+    if let Some(dedicated) = dedicated {
+        dedicated.close().await.map_err(|e| e.to_string())?;
+    }
+    Ok(true)
+}
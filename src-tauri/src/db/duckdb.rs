@@ -0,0 +1,275 @@
+//! DuckDB connections, for analytical queries and for querying local Parquet, CSV and
+//! JSON files directly (`SELECT * FROM 'events/*.parquet'`). DuckDB runs in-process and
+//! its API is blocking, so every call runs on the blocking thread pool.
+
+use super::convert::{collect_blocking, Batch, CollectLimits, Collected, BLOCKING_CONVERT_BATCH};
+use super::introspect::ColumnInfo;
+use super::sql::split_statements;
+use ::duckdb::types::{TimeUnit, Value as DuckValue};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, NaiveTime};
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The connection opened by `connect`. Queries run on clones of it, which share the
+/// database but can run concurrently.
+pub type Database = Arc<Mutex<::duckdb::Connection>>;
+
+/// `duckdb://path/to/file.duckdb`, a bare `*.duckdb` path, or `duckdb://:memory:` (or
+/// just `duckdb://`) for an in-memory database.
+fn database_path(conn_string: &str) -> Option<&str> {
+    let path = conn_string
+        .strip_prefix("duckdb://")
+        .or_else(|| conn_string.strip_prefix("duckdb:"))
+        .unwrap_or(conn_string);
+    Some(path).filter(|path| !path.is_empty() && *path != ":memory:")
+}
+
+/// Open the database; `read_only` opens a file in read-only mode, which also lets
+/// other processes open it. In-memory databases are always writable.
+pub async fn connect(conn_string: &str, read_only: bool) -> Result<Database, String> {
+    let path = database_path(conn_string).map(str::to_string);
+    let conn = tokio::task::spawn_blocking(move || match path {
+        Some(path) if read_only => {
+            let config = ::duckdb::Config::default().access_mode(::duckdb::AccessMode::ReadOnly)?;
+            ::duckdb::Connection::open_with_flags(path, config)
+        }
+        Some(path) => ::duckdb::Connection::open(path),
+        None => ::duckdb::Connection::open_in_memory(),
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+    Ok(Arc::new(Mutex::new(conn)))
+}
+
+fn clone_connection(db: &Database) -> Result<::duckdb::Connection, String> {
+    db.lock().unwrap().try_clone().map_err(|e| e.to_string())
+}
+
+/// Run a script, returning the rows of its last statement. `timeout_ms` interrupts the
+/// query once it has run that long.
+pub async fn run_query(
+    db: &Database,
+    sql: &str,
+    timeout_ms: Option<u64>,
+    limits: CollectLimits,
+) -> Result<Collected, String> {
+    let conn = clone_connection(db)?;
+    let statements: Vec<String> = split_statements(sql)
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let timer = timeout_ms.map(|ms| {
+        let interrupt = conn.interrupt_handle();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            interrupt.interrupt();
+        })
+    });
+
+    let result = collect_blocking(limits, move |tx| {
+        let Some((last, rest)) = statements.split_last() else {
+            return Ok(());
+        };
+        for text in rest {
+            conn.execute_batch(text).map_err(|e| e.to_string())?;
+        }
+
+        let mut stmt = conn.prepare(last).map_err(|e| e.to_string())?;
+        let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+        let Some(stmt) = rows.as_ref() else {
+            return Ok(());
+        };
+        let (columns, count) = (stmt.column_names(), stmt.column_count());
+        if tx.blocking_send(Batch::Columns(columns)).is_err() {
+            return Ok(());
+        }
+        let mut batch = Vec::with_capacity(BLOCKING_CONVERT_BATCH);
+        while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+            let values = (0..count)
+                .map(|i| row.get::<_, DuckValue>(i).map(to_json))
+                .collect::<Result<_, _>>()
+                .map_err(|e| e.to_string())?;
+            batch.push(values);
+            if batch.len() == BLOCKING_CONVERT_BATCH {
+                let full =
+                    std::mem::replace(&mut batch, Vec::with_capacity(BLOCKING_CONVERT_BATCH));
+                if tx.blocking_send(Batch::Rows(full)).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+        let _ = tx.blocking_send(Batch::Rows(batch));
+        Ok(())
+    })
+    .await;
+
+    if let Some(timer) = timer {
+        timer.abort();
+    }
+    result
+}
+
+/// Integers that fit JSON numbers stay numbers, wider ones become strings. Dates and
+/// times are rendered as ISO 8601 text, blobs as base64, and nested types as JSON.
+fn to_json(value: DuckValue) -> Value {
+    match value {
+        DuckValue::Null => Value::Null,
+        DuckValue::Boolean(b) => Value::Bool(b),
+        DuckValue::TinyInt(n) => n.into(),
+        DuckValue::SmallInt(n) => n.into(),
+        DuckValue::Int(n) => n.into(),
+        DuckValue::BigInt(n) => n.into(),
+        DuckValue::UTinyInt(n) => n.into(),
+        DuckValue::USmallInt(n) => n.into(),
+        DuckValue::UInt(n) => n.into(),
+        DuckValue::UBigInt(n) => n.into(),
+        DuckValue::HugeInt(n) => {
+            i64::try_from(n).map_or_else(|_| n.to_string().into(), Value::from)
+        }
+        DuckValue::UHugeInt(n) => {
+            u64::try_from(n).map_or_else(|_| n.to_string().into(), Value::from)
+        }
+        DuckValue::Float(n) => float(n as f64),
+        DuckValue::Double(n) => float(n),
+        DuckValue::Decimal(d) => {
+            let text = d.to_string();
+            text.parse().map_or(Value::String(text), float)
+        }
+        DuckValue::Timestamp(unit, n) => {
+            DateTime::from_timestamp_micros(unit.to_micros(n)).map_or(Value::Null, |t| {
+                t.naive_utc()
+                    .format("%Y-%m-%d %H:%M:%S%.f")
+                    .to_string()
+                    .into()
+            })
+        }
+        DuckValue::Date32(days) => NaiveDate::from_num_days_from_ce_opt(days + UNIX_EPOCH_DAYS)
+            .map_or(Value::Null, |d| d.to_string().into()),
+        DuckValue::Time64(unit, n) => {
+            time_of_day(unit, n).map_or(Value::Null, |t| t.to_string().into())
+        }
+        DuckValue::Interval {
+            months,
+            days,
+            nanos,
+        } => format!("P{}M{}DT{}S", months, days, nanos as f64 / 1e9).into(),
+        DuckValue::Text(s) | DuckValue::Enum(s) => Value::String(s),
+        DuckValue::Blob(bytes) | DuckValue::Geometry(bytes) => BASE64.encode(bytes).into(),
+        DuckValue::List(items) | DuckValue::Array(items) => {
+            Value::Array(items.into_iter().map(to_json).collect())
+        }
+        DuckValue::Struct(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(k, v)| (k.clone(), to_json(v.clone())))
+                .collect(),
+        ),
+        DuckValue::Map(entries) => Value::Object(
+            entries
+                .iter()
+                .map(|(k, v)| {
+                    let key = match to_json(k.clone()) {
+                        Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    (key, to_json(v.clone()))
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        DuckValue::Union(inner) => to_json(*inner),
+        _ => Value::Null,
+    }
+}
+
+/// Days from 0001-01-01 to 1970-01-01, the epoch `DATE` values count from.
+const UNIX_EPOCH_DAYS: i32 = 719_163;
+
+fn float(n: f64) -> Value {
+    serde_json::Number::from_f64(n).map_or(Value::Null, Value::Number)
+}
+
+fn time_of_day(unit: TimeUnit, n: i64) -> Option<NaiveTime> {
+    let micros = unit.to_micros(n);
+    let secs = u32::try_from(micros / 1_000_000).ok()?;
+    let nanos = u32::try_from(micros % 1_000_000 * 1000).ok()?;
+    NaiveTime::from_num_seconds_from_midnight_opt(secs, nanos)
+}
+
+/// Run a catalog query on a fresh clone and return every value as text.
+async fn query_strings(
+    db: &Database,
+    sql: &'static str,
+    binds: Vec<String>,
+) -> Result<Vec<Vec<Option<String>>>, String> {
+    let conn = clone_connection(db)?;
+    tokio::task::spawn_blocking(move || {
+        let mut stmt = conn.prepare(sql)?;
+        let mut rows = stmt.query(::duckdb::params_from_iter(binds))?;
+        let count = rows.as_ref().map_or(0, |stmt| stmt.column_count());
+        let mut result = Vec::new();
+        while let Some(row) = rows.next()? {
+            result.push(
+                (0..count)
+                    .map(|i| row.get::<_, Option<String>>(i))
+                    .collect::<Result<_, _>>()?,
+            );
+        }
+        Ok::<_, ::duckdb::Error>(result)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+/// Tables and views in the current schema.
+pub async fn fetch_tables(db: &Database) -> Result<Vec<String>, String> {
+    let rows = query_strings(
+        db,
+        "SELECT table_name FROM information_schema.tables \
+         WHERE table_schema = current_schema() \
+         ORDER BY table_name",
+        Vec::new(),
+    )
+    .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| row.into_iter().next().flatten())
+        .collect())
+}
+
+pub async fn fetch_columns(db: &Database, table: &str) -> Result<Vec<ColumnInfo>, String> {
+    let rows = query_strings(
+        db,
+        "SELECT c.column_name, c.data_type, CAST(c.is_nullable AS VARCHAR), c.column_default, \
+                CAST(EXISTS (SELECT 1 FROM duckdb_constraints() k \
+                              WHERE k.constraint_type = 'PRIMARY KEY' \
+                                AND k.schema_name = c.schema_name \
+                                AND k.table_name = c.table_name \
+                                AND list_contains(k.constraint_column_names, c.column_name)) \
+                     AS VARCHAR) \
+         FROM duckdb_columns() c \
+         WHERE c.schema_name = current_schema() AND c.table_name = ? \
+         ORDER BY c.column_index",
+        vec![table.to_string()],
+    )
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|mut row| {
+            row.resize(5, None);
+            let text = |i: usize| row[i].clone().unwrap_or_default();
+            ColumnInfo {
+                name: text(0),
+                data_type: text(1),
+                nullable: text(2) != "false",
+                default: row[3].clone(),
+                primary_key: text(4) == "true",
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,548 @@
+//! Query plans for the plan visualizer. `explain` runs the dialect's own EXPLAIN and
+//! normalizes what comes back into one tree of `PlanNode`s: `EXPLAIN (FORMAT JSON)`
+//! on Postgres, the text plan on CockroachDB, `EXPLAIN FORMAT=JSON` on MySQL and
+//! MariaDB, and `EXPLAIN QUERY PLAN` on SQLite.
+//!
+//! In analyze mode the statement is also run, through `EXPLAIN ANALYZE` (`ANALYZE
+//! FORMAT=JSON` on MariaDB), and the nodes carry what it actually did. Plans are
+//! always fetched inside a transaction that is rolled back afterwards, so analyzing
+//! an UPDATE or DELETE doesn't change any data. Writes to non-transactional tables,
+//! such as MyISAM's, are not undone.
+
+use super::flavor::Flavor;
+use super::sql::{self, StatementKind};
+use super::{redact, AppState, Connection};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use sqlx::mysql::MySqlConnection;
+use sqlx::postgres::PgConnection;
+use sqlx::sqlite::SqliteConnection;
+use sqlx::Row;
+use tauri::State;
+
+#[derive(Debug, Default, Serialize)]
+pub struct PlanNode {
+    /// What the node does, e.g. `Seq Scan`, `SEARCH` or `nested_loop`.
+    pub operation: String,
+    /// The table the node reads, if any.
+    pub relation: Option<String>,
+    /// The database's own one-line description of the node, where it gives one.
+    pub detail: Option<String>,
+    pub estimated_rows: Option<f64>,
+    /// The planner's cost for the node and its children, in the database's own units.
+    pub estimated_cost: Option<f64>,
+    /// Rows the node returned over all its loops; only set in analyze mode.
+    pub actual_rows: Option<f64>,
+    /// Milliseconds spent in the node and its children over all its loops; only set in
+    /// analyze mode.
+    pub actual_time_ms: Option<f64>,
+    /// Everything else the database reported about the node, as it reported it.
+    pub properties: Map<String, Value>,
+    pub children: Vec<PlanNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueryPlan {
+    pub root: PlanNode,
+    /// The plan as the database returned it.
+    pub raw: Value,
+}
+
+/// The plan of the single statement `sql` on connection `id`. The statement itself
+/// is only run when `analyze` is set, and its changes are then rolled back.
+#[tauri::command]
+pub async fn explain(
+    state: State<'_, AppState>,
+    id: String,
+    sql: String,
+    analyze: Option<bool>,
+) -> Result<QueryPlan, String> {
+    let conn = state.connection(&id)?;
+    let [statement] = sql::split_statements(&sql)[..] else {
+        return Err("Only a single statement can be explained".to_string());
+    };
+    let analyze = analyze.unwrap_or(false);
+    let flavor = state.flavor(&id);
+    let explain = if analyze {
+        // DDL commits implicitly on MySQL, which the rollback couldn't undo.
+        if !matches!(
+            sql::classify(statement),
+            StatementKind::Select
+                | StatementKind::Insert
+                | StatementKind::Update
+                | StatementKind::Delete
+                | StatementKind::Merge
+        ) {
+            return Err("Only queries and data changes can be analyzed".to_string());
+        }
+        match &conn {
+            Connection::Postgres(_) if flavor == Some(Flavor::CockroachDb) => {
+                format!("EXPLAIN ANALYZE {}", statement)
+            }
+            Connection::Postgres(_) => format!("EXPLAIN (ANALYZE, FORMAT JSON) {}", statement),
+            Connection::MySql(_) if flavor == Some(Flavor::MariaDb) => {
+                format!("ANALYZE FORMAT=JSON {}", statement)
+            }
+            Connection::MySql(_) => format!("EXPLAIN ANALYZE {}", statement),
+            _ => return Err(conn.unsupported("Analyzed query plans")),
+        }
+    } else {
+        match &conn {
+            Connection::Postgres(_) if flavor == Some(Flavor::CockroachDb) => {
+                format!("EXPLAIN {}", statement)
+            }
+            Connection::Postgres(_) => format!("EXPLAIN (FORMAT JSON) {}", statement),
+            Connection::MySql(_) => format!("EXPLAIN FORMAT=JSON {}", statement),
+            Connection::Sqlite(_) => format!("EXPLAIN QUERY PLAN {}", statement),
+            _ => return Err(conn.unsupported("Query plans")),
+        }
+    };
+    // Analyzing runs the statement, so it is held to the policy for the statement
+    // itself; MariaDB's `ANALYZE` prefix would otherwise hide what it runs.
+    state.check_policy(&id, if analyze { statement } else { &explain })?;
+    let plan = fetch_plan(&conn, flavor, analyze, &explain).await;
+    state.audit(&id, &explain, plan.as_ref().err().map(String::as_str));
+    plan.map_err(|e| redact::redact(&e))
+}
+
+/// Run `explain` in a transaction that is rolled back once the plan has been read.
+async fn fetch_plan(
+    conn: &Connection,
+    flavor: Option<Flavor>,
+    analyze: bool,
+    explain: &str,
+) -> Result<QueryPlan, String> {
+    match conn {
+        Connection::Postgres(pool) => {
+            let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+            let plan = postgres_plan(&mut tx, flavor, explain).await;
+            tx.rollback().await.map_err(|e| e.to_string())?;
+            plan
+        }
+        Connection::MySql(pool) => {
+            let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+            let plan = mysql_plan(&mut tx, flavor, analyze, explain).await;
+            tx.rollback().await.map_err(|e| e.to_string())?;
+            plan
+        }
+        Connection::Sqlite(pool) => {
+            let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+            let plan = sqlite_plan(&mut tx, explain).await;
+            tx.rollback().await.map_err(|e| e.to_string())?;
+            plan
+        }
+        _ => Err(conn.unsupported("Query plans")),
+    }
+}
+
+async fn postgres_plan(
+    conn: &mut PgConnection,
+    flavor: Option<Flavor>,
+    explain: &str,
+) -> Result<QueryPlan, String> {
+    if flavor == Some(Flavor::CockroachDb) {
+        let lines: Vec<String> = sqlx::query_scalar(explain)
+            .fetch_all(conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        return Ok(QueryPlan {
+            root: cockroach_tree(&lines),
+            raw: Value::String(lines.join("\n")),
+        });
+    }
+    let raw: Value = sqlx::query_scalar(explain)
+        .fetch_one(conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    let plan = raw[0]["Plan"]
+        .as_object()
+        .ok_or("The server returned no plan")?;
+    Ok(QueryPlan {
+        root: pg_node(plan),
+        raw,
+    })
+}
+
+async fn mysql_plan(
+    conn: &mut MySqlConnection,
+    flavor: Option<Flavor>,
+    analyze: bool,
+    explain: &str,
+) -> Result<QueryPlan, String> {
+    let text: String = sqlx::query_scalar(explain)
+        .fetch_one(conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    // MySQL only analyzes into its text tree.
+    if analyze && flavor != Some(Flavor::MariaDb) {
+        return Ok(QueryPlan {
+            root: mysql_tree(&text),
+            raw: Value::String(text),
+        });
+    }
+    let raw: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    let root = match raw.as_object() {
+        Some(top) if top.len() == 1 => {
+            let (name, block) = top.iter().next().unwrap();
+            mysql_node(name, block.as_object().unwrap_or(&Map::new()))
+        }
+        Some(top) => mysql_node("query", top),
+        None => return Err("The server returned no plan".to_string()),
+    };
+    Ok(QueryPlan { root, raw })
+}
+
+async fn sqlite_plan(conn: &mut SqliteConnection, explain: &str) -> Result<QueryPlan, String> {
+    let rows = sqlx::query(explain)
+        .fetch_all(conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    let steps: Vec<(i64, i64, String)> = rows
+        .iter()
+        .map(|row| {
+            Ok((
+                row.try_get("id")?,
+                row.try_get("parent")?,
+                row.try_get("detail")?,
+            ))
+        })
+        .collect::<Result<_, sqlx::Error>>()
+        .map_err(|e| e.to_string())?;
+    let raw = steps
+        .iter()
+        .map(|(id, parent, detail)| json!({ "id": id, "parent": parent, "detail": detail }))
+        .collect();
+    let mut top = sqlite_children(&steps, 0);
+    let root = if top.len() == 1 {
+        top.remove(0)
+    } else {
+        PlanNode {
+            operation: "QUERY PLAN".to_string(),
+            children: top,
+            ..PlanNode::default()
+        }
+    };
+    Ok(QueryPlan { root, raw })
+}
+
+/// A node of a Postgres JSON plan; the keys read into the node's own fields are left
+/// out of its `properties`.
+fn pg_node(plan: &Map<String, Value>) -> PlanNode {
+    let mut properties = plan.clone();
+    let children = match properties.remove("Plans") {
+        Some(Value::Array(plans)) => plans
+            .iter()
+            .filter_map(Value::as_object)
+            .map(pg_node)
+            .collect(),
+        _ => Vec::new(),
+    };
+    let relation = ["Relation Name", "CTE Name", "Function Name"]
+        .iter()
+        .find_map(|key| properties.remove(*key)?.as_str().map(str::to_string));
+    // Actual figures are per loop; `Actual Loops` is left in the properties.
+    let loops = properties
+        .get("Actual Loops")
+        .and_then(number)
+        .unwrap_or(1.0);
+    let actual_rows = properties.remove("Actual Rows").as_ref().and_then(number);
+    let actual_time = properties
+        .remove("Actual Total Time")
+        .as_ref()
+        .and_then(number);
+    PlanNode {
+        operation: properties
+            .remove("Node Type")
+            .and_then(|node| node.as_str().map(str::to_string))
+            .unwrap_or_default(),
+        relation,
+        detail: None,
+        estimated_rows: properties.remove("Plan Rows").as_ref().and_then(number),
+        estimated_cost: properties.remove("Total Cost").as_ref().and_then(number),
+        actual_rows: actual_rows.map(|rows| rows * loops),
+        actual_time_ms: actual_time.map(|time| time * loops),
+        properties,
+        children,
+    }
+}
+
+/// A node of a MySQL or MariaDB JSON plan, named by the key it was found under.
+/// Nested objects become children, except `cost_info`, whose costs are read into the
+/// node itself.
+fn mysql_node(name: &str, node: &Map<String, Value>) -> PlanNode {
+    let mut plan = PlanNode {
+        operation: name.to_string(),
+        ..PlanNode::default()
+    };
+    for (key, value) in node {
+        match value {
+            Value::Object(costs) if key == "cost_info" => plan.properties.extend(costs.clone()),
+            Value::Object(child) => plan.children.push(mysql_node(key, child)),
+            Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_object) => {
+                // `nested_loop: [{"table": {...}}, ...]` wraps each step in a one-key object.
+                for item in items.iter().filter_map(Value::as_object) {
+                    plan.children.push(match single_object(item) {
+                        Some((inner_name, inner)) => mysql_node(inner_name, inner),
+                        None => mysql_node(key, item),
+                    });
+                }
+            }
+            _ => {
+                plan.properties.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    let properties = &mut plan.properties;
+    plan.relation = properties
+        .remove("table_name")
+        .and_then(|table| table.as_str().map(str::to_string));
+    plan.detail = properties
+        .remove("access_type")
+        .and_then(|access| access.as_str().map(str::to_string));
+    plan.estimated_rows = ["rows_examined_per_scan", "rows"]
+        .iter()
+        .find_map(|key| properties.remove(*key).as_ref().and_then(number));
+    plan.estimated_cost = ["prefix_cost", "query_cost", "cost"]
+        .iter()
+        .find_map(|key| properties.remove(*key).as_ref().and_then(number));
+    // MariaDB's `ANALYZE`: `r_rows` is per loop, `r_total_time_ms` over all of them.
+    let loops = properties.get("r_loops").and_then(number).unwrap_or(1.0);
+    plan.actual_rows = properties
+        .remove("r_rows")
+        .as_ref()
+        .and_then(number)
+        .map(|rows| rows * loops);
+    plan.actual_time_ms = properties
+        .remove("r_total_time_ms")
+        .as_ref()
+        .and_then(number);
+    plan
+}
+
+/// Parse MySQL's `EXPLAIN ANALYZE` tree, where each node is a `-> description` line
+/// indented under its parent, followed by `(cost=... rows=...)` and `(actual
+/// time=first..last rows=... loops=...)`.
+fn mysql_tree(text: &str) -> PlanNode {
+    let mut top = PlanNode {
+        operation: "query".to_string(),
+        ..PlanNode::default()
+    };
+    let mut open: Vec<(usize, PlanNode)> = Vec::new();
+    for line in text.lines() {
+        let Some(at) = line.find("-> ") else {
+            continue;
+        };
+        let depth = line[..at].len();
+        while open
+            .last()
+            .is_some_and(|(open_depth, _)| *open_depth >= depth)
+        {
+            let (_, node) = open.pop().unwrap();
+            attach(&mut open, &mut top, node);
+        }
+        let description = line[at + 3..].trim();
+        let (operation, figures) = match description.find("  (") {
+            Some(split) => description.split_at(split),
+            None => (description, ""),
+        };
+        let mut node = PlanNode {
+            operation: operation.to_string(),
+            relation: operation
+                .split_once(" on ")
+                .and_then(|(_, rest)| rest.split_whitespace().next())
+                .map(str::to_string),
+            detail: Some(description.to_string()),
+            ..PlanNode::default()
+        };
+        for group in figures
+            .split('(')
+            .filter_map(|group| group.split(')').next())
+        {
+            let actual = group.starts_with("actual ");
+            let mut loops: f64 = 1.0;
+            let mut rows = None;
+            let mut last = None;
+            for pair in group.trim_start_matches("actual ").split_whitespace() {
+                let Some((key, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                match key {
+                    "cost" => node.estimated_cost = value.parse().ok(),
+                    "rows" => rows = value.parse::<f64>().ok(),
+                    "loops" => loops = value.parse().unwrap_or(1.0),
+                    "time" => {
+                        last = value
+                            .rsplit("..")
+                            .next()
+                            .and_then(|t| t.parse::<f64>().ok())
+                    }
+                    _ => {}
+                }
+                node.properties.insert(
+                    format!("{}{}", if actual { "actual " } else { "" }, key),
+                    Value::String(value.to_string()),
+                );
+            }
+            if actual {
+                node.actual_rows = rows.map(|rows| rows * loops);
+                node.actual_time_ms = last.map(|time| time * loops);
+            } else {
+                node.estimated_rows = rows;
+            }
+        }
+        open.push((depth, node));
+    }
+    while let Some((_, node)) = open.pop() {
+        attach(&mut open, &mut top, node);
+    }
+    if top.children.len() == 1 {
+        top.children.remove(0)
+    } else {
+        top
+    }
+}
+
+fn single_object(item: &Map<String, Value>) -> Option<(&str, &Map<String, Value>)> {
+    match item.iter().next() {
+        Some((name, Value::Object(inner))) if item.len() == 1 => Some((name, inner)),
+        _ => None,
+    }
+}
+
+/// The steps of a SQLite `EXPLAIN QUERY PLAN` whose parent is `parent`, as nodes.
+fn sqlite_children(steps: &[(i64, i64, String)], parent: i64) -> Vec<PlanNode> {
+    steps
+        .iter()
+        .filter(|(_, step_parent, _)| *step_parent == parent)
+        .map(|(id, _, detail)| {
+            let mut words = detail.split_whitespace();
+            let first = words.next().unwrap_or_default();
+            let (operation, relation) = match first {
+                // `SCAN t` and `SEARCH t USING ...`; before 3.36, `SCAN TABLE t`.
+                "SCAN" | "SEARCH" => {
+                    let table = words.find(|word| *word != "TABLE");
+                    (first.to_string(), table.map(str::to_string))
+                }
+                _ => (detail.clone(), None),
+            };
+            PlanNode {
+                operation,
+                relation,
+                detail: Some(detail.clone()),
+                children: sqlite_children(steps, *id),
+                ..PlanNode::default()
+            }
+        })
+        .collect()
+}
+
+/// Parse CockroachDB's text plan, where each node is a `• name` line indented under
+/// its parent and followed by `key: value` lines. Lines before the first node describe
+/// the whole plan.
+fn cockroach_tree(lines: &[String]) -> PlanNode {
+    let mut top = PlanNode {
+        operation: "plan".to_string(),
+        ..PlanNode::default()
+    };
+    // Open nodes, with the column of their bullet.
+    let mut open: Vec<(usize, PlanNode)> = Vec::new();
+    for line in lines {
+        if let Some(at) = line.find('•') {
+            let depth = line[..at].chars().count();
+            while open
+                .last()
+                .is_some_and(|(open_depth, _)| *open_depth >= depth)
+            {
+                let (_, node) = open.pop().unwrap();
+                attach(&mut open, &mut top, node);
+            }
+            let operation = line[at + '•'.len_utf8()..].trim().to_string();
+            open.push((
+                depth,
+                PlanNode {
+                    operation,
+                    ..PlanNode::default()
+                },
+            ));
+            continue;
+        }
+        let text = line.trim_start_matches(|c: char| c.is_whitespace() || "│├└─".contains(c));
+        if let Some((key, value)) = text.split_once(": ") {
+            let node = open.last_mut().map_or(&mut top, |(_, node)| node);
+            node.properties.insert(
+                key.trim().to_string(),
+                Value::String(value.trim().to_string()),
+            );
+        }
+    }
+    while let Some((_, node)) = open.pop() {
+        attach(&mut open, &mut top, node);
+    }
+    let mut root = if top.children.len() == 1 {
+        let mut root = top.children.remove(0);
+        for (key, value) in top.properties {
+            root.properties.entry(key).or_insert(value);
+        }
+        root
+    } else {
+        top
+    };
+    cockroach_fields(&mut root);
+    root
+}
+
+fn attach(open: &mut [(usize, PlanNode)], top: &mut PlanNode, node: PlanNode) {
+    match open.last_mut() {
+        Some((_, parent)) => parent.children.push(node),
+        None => top.children.push(node),
+    }
+}
+
+/// Read `table: t@index`, `estimated row count: 1,234 (...)` and, when analyzed,
+/// `actual row count: 1,234` and `execution time: 1.2ms` into the node's fields.
+fn cockroach_fields(node: &mut PlanNode) {
+    if let Some(Value::String(table)) = node.properties.get("table") {
+        node.relation = table.split('@').next().map(str::to_string);
+    }
+    let count = |key: &str| -> Option<f64> {
+        let Some(Value::String(count)) = node.properties.get(key) else {
+            return None;
+        };
+        let digits: String = count
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == ',')
+            .filter(char::is_ascii_digit)
+            .collect();
+        digits.parse().ok()
+    };
+    node.estimated_rows = count("estimated row count");
+    node.actual_rows = count("actual row count");
+    if let Some(Value::String(time)) = node.properties.get("execution time") {
+        node.actual_time_ms = duration_ms(time);
+    }
+    node.children.iter_mut().for_each(cockroach_fields);
+}
+
+/// A CockroachDB duration such as `1.2ms`, `850µs` or `2s`, in milliseconds.
+fn duration_ms(text: &str) -> Option<f64> {
+    let split = text.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let (value, unit) = text.split_at(split);
+    let scale = match unit.trim() {
+        "ns" => 1e-6,
+        "µs" | "us" => 1e-3,
+        "ms" => 1.0,
+        "s" => 1e3,
+        "m" => 6e4,
+        _ => return None,
+    };
+    value.parse::<f64>().ok().map(|value| value * scale)
+}
+
+/// A number, or a string holding one as MySQL writes its costs.
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
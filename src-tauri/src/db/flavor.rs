@@ -0,0 +1,67 @@
+//! Servers that speak another database's wire protocol but not its dialect. The
+//! connection keeps the protocol's `DbKind`; the flavor, detected from `version()` when
+//! the connection opens, picks the catalog queries, plan commands and syntax that work
+//! there.
+
+use super::sql::{leading_keyword, split_statements, top_level_words};
+use super::{AppState, Connection};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Flavor {
+    /// CockroachDB, over the Postgres protocol.
+    CockroachDb,
+    /// MariaDB, over the MySQL protocol. Adds sequences and `RETURNING`.
+    MariaDb,
+}
+
+/// The flavor of the server behind `conn`, or `None` when it is the database its
+/// protocol belongs to.
+pub async fn detect(conn: &Connection) -> Result<Option<Flavor>, String> {
+    match conn {
+        Connection::Postgres(pool) => {
+            let version: String = sqlx::query_scalar("SELECT version()")
+                .fetch_one(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(version
+                .contains("CockroachDB")
+                .then_some(Flavor::CockroachDb))
+        }
+        Connection::MySql(pool) => {
+            let version: String = sqlx::query_scalar("SELECT VERSION()")
+                .fetch_one(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(version.contains("MariaDB").then_some(Flavor::MariaDb))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Reject MariaDB-only syntax sent to MySQL with an explanation, rather than the
+/// server's bare syntax error.
+pub fn check_script(conn: &Connection, flavor: Option<Flavor>, script: &str) -> Result<(), String> {
+    if !matches!(conn, Connection::MySql(_)) || flavor == Some(Flavor::MariaDb) {
+        return Ok(());
+    }
+    for stmt in split_statements(script) {
+        let returning = matches!(
+            leading_keyword(stmt).as_deref(),
+            Some("INSERT" | "REPLACE" | "DELETE")
+        ) && top_level_words(stmt)
+            .iter()
+            .any(|(word, depth)| *depth == 0 && word == "RETURNING");
+        if returning {
+            return Err("RETURNING is only supported by MariaDB; this server is MySQL".to_string());
+        }
+    }
+    Ok(())
+}
+
+impl AppState {
+    pub fn flavor(&self, id: &str) -> Option<Flavor> {
+        self.flavors.lock().unwrap().get(id).copied()
+    }
+}
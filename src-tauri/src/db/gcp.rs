@@ -0,0 +1,133 @@
+//! Signing in to Google Cloud APIs as a service account, for BigQuery and the Cloud
+//! SQL connector.
+
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+/// The fields of a service account key file that signing in needs.
+#[derive(Deserialize)]
+pub struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    #[serde(default = "default_token_uri")]
+    token_uri: String,
+}
+
+fn default_token_uri() -> String {
+    "https://oauth2.googleapis.com/token".to_string()
+}
+
+/// The key in `json` (a key file's contents), or in the file at `path`, or in the one
+/// named by `GOOGLE_APPLICATION_CREDENTIALS`. `hint` says how to pass one when there
+/// is none.
+pub fn load_key(
+    json: Option<&str>,
+    path: Option<&str>,
+    hint: &str,
+) -> Result<ServiceAccountKey, String> {
+    let read = |path: &str| {
+        std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read credentials file {}: {}", path, e))
+    };
+    let key = match (json, path) {
+        (Some(json), _) => json.to_string(),
+        (None, Some(path)) => read(path)?,
+        (None, None) => {
+            let path = std::env::var("GOOGLE_APPLICATION_CREDENTIALS")
+                .map_err(|_| format!("No service account key: {}", hint))?;
+            read(&path)?
+        }
+    };
+    serde_json::from_str(&key).map_err(|e| format!("Invalid service account key: {}", e))
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+struct Token {
+    value: String,
+    expires: Instant,
+}
+
+/// A service account signed in for one OAuth scope. Cheap to clone: clones share the
+/// access token.
+#[derive(Clone)]
+pub struct ServiceAccount {
+    http: reqwest::Client,
+    key: Arc<ServiceAccountKey>,
+    scope: &'static str,
+    token: Arc<Mutex<Option<Token>>>,
+}
+
+impl ServiceAccount {
+    pub fn new(http: reqwest::Client, key: ServiceAccountKey, scope: &'static str) -> Self {
+        Self {
+            http,
+            key: Arc::new(key),
+            scope,
+            token: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// An access token, exchanging a freshly signed assertion for a new one when the
+    /// cached token is about to expire.
+    pub async fn token(&self) -> Result<String, String> {
+        let mut token = self.token.lock().await;
+        if let Some(token) = token.as_ref().filter(|t| t.expires > Instant::now()) {
+            return Ok(token.value.clone());
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let claims = Claims {
+            iss: &self.key.client_email,
+            scope: self.scope,
+            aud: &self.key.token_uri,
+            iat: now,
+            exp: now + 3600,
+        };
+        let signing_key = EncodingKey::from_rsa_pem(self.key.private_key.as_bytes())
+            .map_err(|e| format!("Invalid service account key: {}", e))?;
+        let assertion = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &signing_key)
+            .map_err(|e| e.to_string())?;
+        let response = self
+            .http
+            .post(&self.key.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &assertion),
+            ])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("Service account sign-in failed: {}", body.trim()));
+        }
+        let granted: TokenResponse = response.json().await.map_err(|e| e.to_string())?;
+        let value = granted.access_token.clone();
+        // Renew a minute early so a token doesn't expire mid-request.
+        *token = Some(Token {
+            value: granted.access_token,
+            expires: Instant::now() + Duration::from_secs(granted.expires_in.saturating_sub(60)),
+        });
+        Ok(value)
+    }
+}
@@ -0,0 +1,113 @@
+//! Connection health: `ping` on demand, and an optional background monitor that pings
+//! every open connection and emits `db://health` whenever one goes up or down, so the
+//! connection list can show live status.
+
+use super::probe::round_trip;
+use super::{redact, AppState};
+use futures_util::future::join_all;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+/// A ping slower than this counts as a failure.
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often a disabled monitor checks whether it has been enabled.
+const IDLE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+pub struct HealthMonitor {
+    /// Disabled when `None`.
+    interval: Mutex<Option<Duration>>,
+    /// Whether each connection was up at its last ping.
+    status: Mutex<HashMap<String, bool>>,
+}
+
+impl HealthMonitor {
+    pub fn forget(&self, id: &str) {
+        self.status.lock().unwrap().remove(id);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthEvent {
+    pub id: String,
+    pub up: bool,
+    pub latency_ms: Option<u64>,
+    /// Why the ping failed, when it did.
+    pub error: Option<String>,
+}
+
+/// Make one round trip on `id`, returning its latency, and emit `db://health` if the
+/// connection's status changed. Pinging doesn't count as activity for auto-lock.
+async fn check(state: &AppState, id: &str) -> Result<u64, String> {
+    let conn = state
+        .connections
+        .lock()
+        .unwrap()
+        .get(id)
+        .cloned()
+        .ok_or("Connection not found")?;
+    let started = Instant::now();
+    let result = match tokio::time::timeout(PING_TIMEOUT, round_trip(state, id, &conn)).await {
+        Ok(result) => result.map(|()| started.elapsed().as_millis() as u64),
+        Err(_) => Err(format!("No response within {}s", PING_TIMEOUT.as_secs())),
+    }
+    .map_err(|e| redact::redact(&e));
+
+    // Skip connections closed while the ping ran.
+    if !state.connections.lock().unwrap().contains_key(id) {
+        return result;
+    }
+    let up = result.is_ok();
+    let previous = state
+        .health
+        .status
+        .lock()
+        .unwrap()
+        .insert(id.to_string(), up);
+    if previous != Some(up) {
+        state.emit(
+            "db://health",
+            HealthEvent {
+                id: id.to_string(),
+                up,
+                latency_ms: result.as_ref().ok().copied(),
+                error: result.as_ref().err().cloned(),
+            },
+        );
+    }
+    result
+}
+
+/// Round-trip latency of `id` in milliseconds.
+#[tauri::command]
+pub async fn ping(state: State<'_, AppState>, id: String) -> Result<u64, String> {
+    check(&state, &id).await
+}
+
+/// Ping every connection each `seconds`; `None` or `0` disables the monitor.
+#[tauri::command]
+pub fn set_health_monitor(state: State<'_, AppState>, seconds: Option<u32>) {
+    let interval = seconds
+        .filter(|s| *s > 0)
+        .map(|s| Duration::from_secs(u64::from(s)));
+    *state.health.interval.lock().unwrap() = interval;
+}
+
+/// Run the health monitor for the lifetime of the app.
+pub fn spawn_monitor(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let state = app.state::<AppState>();
+            let Some(interval) = *state.health.interval.lock().unwrap() else {
+                tokio::time::sleep(IDLE_INTERVAL).await;
+                continue;
+            };
+            tokio::time::sleep(interval).await;
+            let ids: Vec<String> = state.connections.lock().unwrap().keys().cloned().collect();
+            join_all(ids.iter().map(|id| check(&state, id))).await;
+        }
+    });
+}
@@ -0,0 +1,322 @@
+use super::flavor::Flavor;
+use super::{registry, AppState, Connection};
+use futures_util::FutureExt;
+use serde::Serialize;
+use sqlx::Row;
+use tauri::State;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+    pub primary_key: bool,
+}
+
+#[tauri::command]
+pub async fn get_tables(state: State<'_, AppState>, id: String) -> Result<Vec<String>, String> {
+    let conn = state.connection(&id)?;
+    if let Some(tables) = state.schema_cache.tables(&id) {
+        return Ok(tables);
+    }
+    registry::describe_on_first_use(&state, &id, &conn).await?;
+    let flavor = state.flavor(&id);
+
+    let tables = state
+        .table_flights
+        .run(id.clone(), || {
+            async move { fetch_tables(&conn, flavor).await }.boxed()
+        })
+        .await?;
+    state.schema_cache.store_tables(&id, tables.clone());
+    Ok(tables)
+}
+
+#[tauri::command]
+pub async fn get_columns(
+    state: State<'_, AppState>,
+    id: String,
+    table: String,
+) -> Result<Vec<ColumnInfo>, String> {
+    let conn = state.connection(&id)?;
+    columns_for(&state, &id, &conn, &table).await
+}
+
+/// Columns of `table`, served from the schema cache when fresh.
+pub async fn columns_for(
+    state: &AppState,
+    id: &str,
+    conn: &Connection,
+    table: &str,
+) -> Result<Vec<ColumnInfo>, String> {
+    if let Some(columns) = state.schema_cache.columns(id, table) {
+        return Ok(columns);
+    }
+
+    let key = format!("{}\0{}", id, table);
+    let flavor = state.flavor(id);
+    let conn = conn.clone();
+    let lookup = table.to_string();
+    let columns = state
+        .column_flights
+        .run(key, || {
+            async move { fetch_columns(&conn, &lookup, flavor).await }.boxed()
+        })
+        .await?;
+    state.schema_cache.store_columns(id, table, columns.clone());
+    Ok(columns)
+}
+
+/// Sequences in the connection's schema: Postgres, CockroachDB and MariaDB have them;
+/// MySQL and SQLite don't, so they return an empty list.
+#[tauri::command]
+pub async fn get_sequences(state: State<'_, AppState>, id: String) -> Result<Vec<String>, String> {
+    let conn = state.connection(&id)?;
+    match (&conn, state.flavor(&id)) {
+        (Connection::Postgres(pool), _) => sqlx::query_scalar(
+            "SELECT sequence_name::text FROM information_schema.sequences \
+             WHERE sequence_schema = current_schema() ORDER BY sequence_name",
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string()),
+        (Connection::MySql(pool), Some(Flavor::MariaDb)) => sqlx::query_scalar(
+            "SELECT CAST(TABLE_NAME AS CHAR) FROM information_schema.TABLES \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_TYPE = 'SEQUENCE' ORDER BY TABLE_NAME",
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string()),
+        (Connection::MySql(_) | Connection::Sqlite(_), _) => Ok(Vec::new()),
+        (
+            Connection::Oracle(_)
+            | Connection::Mongo(_)
+            | Connection::DuckDb(_)
+            | Connection::ClickHouse(_)
+            | Connection::Redis(_)
+            | Connection::Cassandra(_)
+            | Connection::Surreal(_)
+            | Connection::BigQuery(_)
+            | Connection::Firebird(_)
+            | Connection::Odbc(_),
+            _,
+        ) => Err(conn.unsupported("Sequences")),
+    }
+}
+
+/// Drop cached schema metadata for a connection so the next introspection call
+/// reads fresh data from the server.
+#[tauri::command]
+pub fn refresh_schema(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    state.connection(&id)?;
+    state.schema_cache.invalidate(&id);
+    Ok(())
+}
+
+pub async fn fetch_tables(
+    conn: &Connection,
+    flavor: Option<Flavor>,
+) -> Result<Vec<String>, String> {
+    let tables = match conn {
+        Connection::Postgres(pool) => {
+            let sql = match flavor {
+                // Cockroach lists sequences in information_schema.tables as well.
+                Some(Flavor::CockroachDb) => {
+                    "SELECT table_name FROM information_schema.tables \
+                     WHERE table_schema = current_schema() AND table_type <> 'SEQUENCE'"
+                }
+                _ => {
+                    "SELECT table_name FROM information_schema.tables \
+                     WHERE table_schema = current_schema()"
+                }
+            };
+            let rows = sqlx::query(sql)
+                .fetch_all(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let mut tables = Vec::new();
+            for row in rows {
+                let name: String = row.try_get(0).unwrap_or_default();
+                tables.push(name);
+            }
+            tables
+        }
+        Connection::MySql(pool) => {
+            let sql = match flavor {
+                // MariaDB sequences are tables of type SEQUENCE.
+                Some(Flavor::MariaDb) => "SHOW FULL TABLES WHERE Table_type <> 'SEQUENCE'",
+                _ => "SHOW TABLES",
+            };
+            let rows = sqlx::query(sql)
+                .fetch_all(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let mut tables = Vec::new();
+            for row in rows {
+                let name: String = row.try_get(0).unwrap_or_default();
+                tables.push(name);
+            }
+            tables
+        }
+        Connection::Sqlite(pool) => {
+            let rows = sqlx::query(
+                "SELECT name FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%'",
+            )
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            let mut tables = Vec::new();
+            for row in rows {
+                let name: String = row.try_get(0).unwrap_or_default();
+                tables.push(name);
+            }
+            tables
+        }
+        Connection::Oracle(pool) => super::oracle::fetch_tables(pool).await?,
+        Connection::Mongo(db) => super::mongo::fetch_tables(db).await?,
+        Connection::DuckDb(db) => super::duckdb::fetch_tables(db).await?,
+        Connection::ClickHouse(client) => super::clickhouse::fetch_tables(client).await?,
+        Connection::Redis(conn) => super::redis::fetch_tables(conn).await?,
+        Connection::Cassandra(session) => super::cassandra::fetch_tables(session).await?,
+        Connection::Surreal(client) => super::surreal::fetch_tables(client).await?,
+        Connection::BigQuery(client) => super::bigquery::fetch_tables(client).await?,
+        Connection::Firebird(pool) => super::firebird::fetch_tables(pool).await?,
+        Connection::Odbc(pool) => super::odbc::fetch_tables(pool).await?,
+    };
+
+    Ok(tables)
+}
+
+pub async fn fetch_columns(
+    conn: &Connection,
+    table: &str,
+    flavor: Option<Flavor>,
+) -> Result<Vec<ColumnInfo>, String> {
+    let columns = match conn {
+        Connection::Postgres(pool) => {
+            // Cockroach adds a hidden `rowid` key to tables created without a primary key.
+            let hidden = match flavor {
+                Some(Flavor::CockroachDb) => "AND c.is_hidden = 'NO' ",
+                _ => "",
+            };
+            let rows = sqlx::query(&format!(
+                "SELECT c.column_name::text, c.data_type::text, c.is_nullable = 'YES', \
+                        c.column_default::text, \
+                        EXISTS (SELECT 1 FROM information_schema.table_constraints tc \
+                                JOIN information_schema.key_column_usage k \
+                                  ON k.constraint_name = tc.constraint_name \
+                                 AND k.table_schema = tc.table_schema \
+                                 AND k.table_name = tc.table_name \
+                                WHERE tc.constraint_type = 'PRIMARY KEY' \
+                                  AND tc.table_schema = c.table_schema \
+                                  AND tc.table_name = c.table_name \
+                                  AND k.column_name = c.column_name) \
+                 FROM information_schema.columns c \
+                 WHERE c.table_schema = current_schema() AND c.table_name = $1 {}\
+                 ORDER BY c.ordinal_position",
+                hidden
+            ))
+            .bind(table)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            let mut columns = Vec::new();
+            for row in rows {
+                columns.push(ColumnInfo {
+                    name: row.try_get(0).unwrap_or_default(),
+                    data_type: row.try_get(1).unwrap_or_default(),
+                    nullable: row.try_get(2).unwrap_or(true),
+                    default: row.try_get(3).unwrap_or_default(),
+                    primary_key: row.try_get(4).unwrap_or(false),
+                });
+            }
+            columns
+        }
+        Connection::MySql(pool) => {
+            let rows = sqlx::query(
+                "SELECT CAST(COLUMN_NAME AS CHAR), CAST(COLUMN_TYPE AS CHAR), \
+                        IS_NULLABLE = 'YES', CAST(COLUMN_DEFAULT AS CHAR), COLUMN_KEY = 'PRI' \
+                 FROM information_schema.COLUMNS \
+                 WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? \
+                 ORDER BY ORDINAL_POSITION",
+            )
+            .bind(table)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            let mut columns = Vec::new();
+            for row in rows {
+                columns.push(ColumnInfo {
+                    name: row.try_get(0).unwrap_or_default(),
+                    data_type: row.try_get(1).unwrap_or_default(),
+                    nullable: row.try_get::<i64, _>(2).map_or(true, |v| v != 0),
+                    default: match flavor {
+                        Some(Flavor::MariaDb) => {
+                            mariadb_default(row.try_get(3).unwrap_or_default())
+                        }
+                        _ => row.try_get(3).unwrap_or_default(),
+                    },
+                    primary_key: row.try_get::<i64, _>(4).is_ok_and(|v| v != 0),
+                });
+            }
+            columns
+        }
+        Connection::Sqlite(pool) => {
+            let rows = sqlx::query(
+                "SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?)",
+            )
+            .bind(table)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            let mut columns = Vec::new();
+            for row in rows {
+                columns.push(ColumnInfo {
+                    name: row.try_get(0).unwrap_or_default(),
+                    data_type: row.try_get(1).unwrap_or_default(),
+                    nullable: row.try_get::<i64, _>(2).map_or(true, |v| v == 0),
+                    default: row.try_get(3).unwrap_or_default(),
+                    primary_key: row.try_get::<i64, _>(4).is_ok_and(|v| v > 0),
+                });
+            }
+            columns
+        }
+        Connection::Oracle(pool) => super::oracle::fetch_columns(pool, table).await?,
+        Connection::Mongo(db) => super::mongo::fetch_columns(db, table).await?,
+        Connection::DuckDb(db) => super::duckdb::fetch_columns(db, table).await?,
+        Connection::ClickHouse(client) => super::clickhouse::fetch_columns(client, table).await?,
+        Connection::Redis(conn) => super::redis::fetch_columns(conn, table).await?,
+        Connection::Cassandra(session) => super::cassandra::fetch_columns(session, table).await?,
+        Connection::Surreal(client) => super::surreal::fetch_columns(client, table).await?,
+        Connection::BigQuery(client) => super::bigquery::fetch_columns(client, table).await?,
+        Connection::Firebird(pool) => super::firebird::fetch_columns(pool, table).await?,
+        Connection::Odbc(pool) => super::odbc::fetch_columns(pool, table).await?,
+    };
+
+    Ok(columns)
+}
+
+/// MariaDB reports column defaults as SQL expressions: `NULL` for none, and string
+/// literals in quotes. Bring them in line with MySQL, which gives the bare value.
+fn mariadb_default(default: Option<String>) -> Option<String> {
+    let default = default?;
+    if default == "NULL" {
+        return None;
+    }
+    Some(
+        match default
+            .strip_prefix('\'')
+            .and_then(|d| d.strip_suffix('\''))
+        {
+            Some(literal) => literal.replace("''", "'"),
+            None => default,
+        },
+    )
+}
@@ -0,0 +1,262 @@
+//! JDBC URLs, as found in application configs and shared by Java tooling. `connect`
+//! accepts one in place of a connection string; it is read into a `DbConfig`, mapping
+//! the driver properties this app understands and dropping JDBC-only ones such as
+//! `loginTimeout` or `prepareThreshold`.
+
+use super::{DbConfig, DbKind};
+use percent_encoding::percent_decode_str;
+use std::collections::BTreeMap;
+
+const SUPPORTED: &str = "postgresql, redshift, mysql, mariadb, sqlite, duckdb, oracle:thin, \
+                         clickhouse, firebirdsql and sqlserver";
+
+pub fn is_jdbc(conn_string: &str) -> bool {
+    conn_string
+        .get(..5)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("jdbc:"))
+}
+
+/// The connection `url` describes, with any `user` and `password` properties moved
+/// into the config's fields.
+#[tauri::command]
+pub fn parse_jdbc_url(url: String) -> Result<DbConfig, String> {
+    parse(&url)
+}
+
+pub fn parse(url: &str) -> Result<DbConfig, String> {
+    if !is_jdbc(url) {
+        return Err("JDBC URLs start with jdbc:".to_string());
+    }
+    let rest = &url[5..];
+    let (subprotocol, rest) = rest
+        .split_once(':')
+        .ok_or("The JDBC URL has no subprotocol")?;
+    match subprotocol.to_ascii_lowercase().as_str() {
+        "postgresql" | "redshift" => url_style(DbKind::Postgres, rest, postgres_property),
+        "mysql" | "mariadb" => url_style(DbKind::MySql, rest, mysql_property),
+        "clickhouse" | "ch" => url_style(DbKind::ClickHouse, rest, |_, _| Param::Ignored),
+        "firebirdsql" | "firebird" => url_style(DbKind::Firebird, rest, |_, _| Param::Ignored),
+        "sqlite" => file(DbKind::Sqlite, rest),
+        "duckdb" => file(DbKind::DuckDb, rest),
+        "oracle" => oracle(rest),
+        "sqlserver" => sqlserver(rest),
+        other => Err(format!(
+            "Unsupported JDBC driver {}; supported are {}",
+            other, SUPPORTED
+        )),
+    }
+}
+
+fn decode(text: &str) -> String {
+    percent_decode_str(text).decode_utf8_lossy().into_owned()
+}
+
+fn empty(kind: DbKind) -> DbConfig {
+    DbConfig {
+        kind,
+        host: None,
+        port: None,
+        user: None,
+        password: None,
+        database: None,
+        params: BTreeMap::new(),
+    }
+}
+
+/// Split `host`, `host:port` or `[v6]:port`.
+fn host_port(authority: &str) -> Result<(String, Option<u16>), String> {
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(rest) => {
+            let (host, after) = rest.split_once(']').ok_or("Unclosed [ in the JDBC URL")?;
+            (host, after.strip_prefix(':'))
+        }
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    let port = match port.filter(|port| !port.is_empty()) {
+        Some(port) => Some(
+            port.parse()
+                .map_err(|_| format!("Invalid port {} in the JDBC URL", port))?,
+        ),
+        None => None,
+    };
+    Ok((decode(host), port))
+}
+
+/// `//[user[:password]@]host[:port][/database][?key=value&...]`, the form shared by
+/// most drivers. `property` maps a driver property to a connection string parameter.
+fn url_style(kind: DbKind, rest: &str, property: PropertyMap) -> Result<DbConfig, String> {
+    let mut config = empty(kind);
+    let (rest, query) = rest.split_once('?').unwrap_or((rest, ""));
+    // `jdbc:postgresql:database` connects to localhost.
+    let Some(rest) = rest.strip_prefix("//") else {
+        config.host = Some("localhost".to_string());
+        config.database = Some(decode(rest)).filter(|db| !db.is_empty());
+        return with_properties(config, query, '&', property);
+    };
+    let (authority, database) = rest.split_once('/').unwrap_or((rest, ""));
+    let authority = match authority.rsplit_once('@') {
+        Some((credentials, authority)) => {
+            let (user, password) = match credentials.split_once(':') {
+                Some((user, password)) => (user, Some(password)),
+                None => (credentials, None),
+            };
+            config.user = Some(decode(user));
+            config.password = password.map(decode);
+            authority
+        }
+        None => authority,
+    };
+    if authority.contains(',') {
+        return Err("JDBC URLs with several hosts are not supported; keep one".to_string());
+    }
+    let (host, port) = host_port(authority)?;
+    config.host = Some(host).filter(|host| !host.is_empty());
+    config.port = port;
+    config.database = Some(decode(database)).filter(|db| !db.is_empty());
+    with_properties(config, query, '&', property)
+}
+
+/// A driver property as a connection string parameter.
+enum Param {
+    Set(&'static str, String),
+    /// Derived from a shorthand such as `ssl=true`; an explicit setting wins.
+    Default(&'static str, String),
+    /// JDBC-only; dropped.
+    Ignored,
+}
+
+type PropertyMap = fn(&str, &str) -> Param;
+
+/// Apply `key=value` pairs separated by `separator`, taking `user` and `password` into
+/// their fields.
+fn with_properties(
+    mut config: DbConfig,
+    query: &str,
+    separator: char,
+    property: PropertyMap,
+) -> Result<DbConfig, String> {
+    for pair in query.split(separator).filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let (key, value) = (decode(key), decode(value));
+        match key.to_ascii_lowercase().as_str() {
+            "user" => config.user = Some(value),
+            "password" => config.password = Some(value),
+            _ => match property(&key, &value) {
+                Param::Set(key, value) => {
+                    config.params.insert(key.to_string(), value);
+                }
+                Param::Default(key, value) => {
+                    config.params.entry(key.to_string()).or_insert(value);
+                }
+                Param::Ignored => {}
+            },
+        }
+    }
+    Ok(config)
+}
+
+fn postgres_property(key: &str, value: &str) -> Param {
+    let value = value.to_string();
+    match key {
+        "sslmode" => Param::Set("sslmode", value),
+        "sslrootcert" => Param::Set("sslrootcert", value),
+        "sslcert" => Param::Set("sslcert", value),
+        "sslkey" => Param::Set("sslkey", value),
+        "ssl" if value.eq_ignore_ascii_case("true") => Param::Default("sslmode", "require".into()),
+        "ApplicationName" => Param::Set("application_name", value),
+        "currentSchema" => Param::Set("options", format!("-c search_path={}", value)),
+        _ => Param::Ignored,
+    }
+}
+
+fn mysql_property(key: &str, value: &str) -> Param {
+    match key {
+        "sslMode" => Param::Set("ssl-mode", value.to_ascii_lowercase().replace('_', "-")),
+        "useSSL" | "useSsl" if value.eq_ignore_ascii_case("false") => {
+            Param::Default("ssl-mode", "disabled".into())
+        }
+        "useSSL" | "useSsl" => Param::Default("ssl-mode", "required".into()),
+        "characterEncoding" if value.to_ascii_lowercase().starts_with("utf") => {
+            Param::Set("charset", "utf8mb4".into())
+        }
+        "connectionCollation" => Param::Set("collation", value.to_string()),
+        _ => Param::Ignored,
+    }
+}
+
+/// `jdbc:sqlite:path` and `jdbc:duckdb:path`.
+fn file(kind: DbKind, path: &str) -> Result<DbConfig, String> {
+    let path = path.split_once('?').map_or(path, |(path, _)| path);
+    if path.is_empty() {
+        return Err("The JDBC URL names no database file".to_string());
+    }
+    let mut config = empty(kind);
+    config.database = Some(path.to_string());
+    Ok(config)
+}
+
+/// `jdbc:oracle:thin:[user/password]@[//]host[:port]/service`.
+fn oracle(rest: &str) -> Result<DbConfig, String> {
+    let rest = rest
+        .strip_prefix("thin:")
+        .ok_or("Only the Oracle thin driver's URLs are supported")?;
+    let (credentials, target) = rest
+        .rsplit_once('@')
+        .ok_or("Oracle JDBC URLs name the server after @")?;
+    let mut config = empty(DbKind::Oracle);
+    if let Some((user, password)) = credentials.split_once('/') {
+        config.user = Some(user.to_string()).filter(|user| !user.is_empty());
+        config.password = Some(password.to_string()).filter(|p| !p.is_empty());
+    }
+    if target.starts_with('(') {
+        return Err(
+            "Oracle connect descriptors are not supported; use host:port/service".to_string(),
+        );
+    }
+    let target = target.trim_start_matches("//");
+    let Some((authority, service)) = target.split_once('/') else {
+        return Err(
+            "Oracle SIDs are not supported; use the service name: host:port/service".to_string(),
+        );
+    };
+    let (host, port) = host_port(authority)?;
+    config.host = Some(host);
+    config.port = port;
+    config.database = Some(service.to_string());
+    Ok(config)
+}
+
+/// `jdbc:sqlserver://host[\instance][:port][;key=value...]`, connected through ODBC
+/// with Microsoft's driver.
+fn sqlserver(rest: &str) -> Result<DbConfig, String> {
+    let rest = rest
+        .strip_prefix("//")
+        .ok_or("SQL Server JDBC URLs start with jdbc:sqlserver://")?;
+    let (authority, properties) = rest.split_once(';').unwrap_or((rest, ""));
+    let (host, port) = host_port(authority)?;
+    let mut config = empty(DbKind::Odbc);
+    // The driver takes the port after a comma in `Server`.
+    config.host = Some(match port {
+        Some(port) => format!("{},{}", host, port),
+        None => host,
+    });
+    config.params.insert(
+        "Driver".to_string(),
+        "ODBC Driver 18 for SQL Server".to_string(),
+    );
+    let mut config = with_properties(config, properties, ';', |key, value| {
+        let value = value.to_string();
+        match key.to_ascii_lowercase().as_str() {
+            "databasename" | "database" => Param::Set("Database", value),
+            "encrypt" => Param::Set("Encrypt", value),
+            "trustservercertificate" => Param::Set("TrustServerCertificate", value),
+            "applicationname" => Param::Set("APP", value),
+            _ => Param::Ignored,
+        }
+    })?;
+    config.database = config.params.remove("Database");
+    Ok(config)
+}
@@ -0,0 +1,234 @@
+//! Background query jobs. `submit_query` queues a script and returns its job id at
+//! once; a fixed number of workers run queued jobs through `execute`, and the UI
+//! follows them through `db://job` events and `job_status`, then collects the output
+//! with `job_result`. A job's result is kept until it is collected.
+
+use super::cancel::CANCELLED;
+use super::convert::QueryOutput;
+use super::{execute_on, generate_id, AppState, ExecuteOptions};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::{Notify, Semaphore};
+
+/// Jobs run at the same time, across all connections.
+pub const DEFAULT_JOB_WORKERS: usize = 4;
+
+/// How often a running job reports progress.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+struct Job {
+    id: String,
+    status: JobStatus,
+    submitted: Instant,
+    started: Option<Instant>,
+    finished: Option<Instant>,
+    result: Option<Result<QueryOutput, String>>,
+    cancelled: Arc<Notify>,
+}
+
+/// A job's progress, as returned by `job_status` and sent with `db://job` events.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub job_id: String,
+    /// The connection the job runs on.
+    pub id: String,
+    pub status: JobStatus,
+    /// Time spent waiting for a worker.
+    pub queued_ms: u64,
+    /// Time spent running so far, or in all once finished.
+    pub running_ms: u64,
+    pub error: Option<String>,
+}
+
+pub struct JobQueue {
+    jobs: Mutex<HashMap<String, Job>>,
+    workers: Arc<Semaphore>,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        JobQueue {
+            jobs: Mutex::new(HashMap::new()),
+            workers: Arc::new(Semaphore::new(DEFAULT_JOB_WORKERS)),
+        }
+    }
+}
+
+impl JobQueue {
+    fn info(&self, job_id: &str) -> Result<JobInfo, String> {
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(job_id).ok_or("Job not found")?;
+        let now = Instant::now();
+        let started = job.started.unwrap_or(now);
+        Ok(JobInfo {
+            job_id: job_id.to_string(),
+            id: job.id.clone(),
+            status: job.status,
+            queued_ms: started.duration_since(job.submitted).as_millis() as u64,
+            running_ms: job.started.map_or(0, |started| {
+                job.finished
+                    .unwrap_or(now)
+                    .duration_since(started)
+                    .as_millis()
+            }) as u64,
+            error: match &job.result {
+                Some(Err(e)) => Some(e.clone()),
+                _ => None,
+            },
+        })
+    }
+
+    /// Mark job `job_id` as running, unless it was cancelled while queued.
+    fn start(&self, job_id: &str) -> bool {
+        match self.jobs.lock().unwrap().get_mut(job_id) {
+            Some(job) if job.status == JobStatus::Queued => {
+                job.status = JobStatus::Running;
+                job.started = Some(Instant::now());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn finish(&self, job_id: &str, result: Result<QueryOutput, String>) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(job_id) {
+            if job.status == JobStatus::Running {
+                job.status = match &result {
+                    Ok(_) => JobStatus::Succeeded,
+                    Err(_) => JobStatus::Failed,
+                };
+            }
+            job.finished = Some(Instant::now());
+            job.result = Some(result);
+        }
+    }
+}
+
+impl AppState {
+    fn emit_job(&self, job_id: &str) {
+        if let Ok(info) = self.jobs.info(job_id) {
+            self.emit("db://job", info);
+        }
+    }
+}
+
+/// Queue `sql` to run on connection `id` as `execute` would and return its job id.
+/// Progress is reported through `db://job` events carrying a `JobInfo`: on each change
+/// of status, and every second while the job runs.
+#[tauri::command]
+pub async fn submit_query(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+    sql: String,
+    options: Option<ExecuteOptions>,
+) -> Result<String, String> {
+    state.connection(&id)?;
+    let mut options = options.unwrap_or_default();
+    if options.query_id.is_some() {
+        return Err("Jobs are cancelled by job id; leave out query_id".to_string());
+    }
+    let job_id = generate_id("job");
+    options.query_id = Some(job_id.clone());
+    let cancelled = Arc::new(Notify::new());
+    state.jobs.jobs.lock().unwrap().insert(
+        job_id.clone(),
+        Job {
+            id: id.clone(),
+            status: JobStatus::Queued,
+            submitted: Instant::now(),
+            started: None,
+            finished: None,
+            result: None,
+            cancelled: cancelled.clone(),
+        },
+    );
+    state.emit_job(&job_id);
+
+    let task_job_id = job_id.clone();
+    let workers = state.jobs.workers.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let Ok(_worker) = workers.acquire_owned().await else {
+            return;
+        };
+        if !state.jobs.start(&task_job_id) {
+            return;
+        }
+        state.emit_job(&task_job_id);
+        let run = execute_on(&state, &id, &sql, &options);
+        tokio::pin!(run);
+        let mut progress = tokio::time::interval(PROGRESS_INTERVAL);
+        progress.tick().await;
+        let result = loop {
+            tokio::select! {
+                result = &mut run => break result,
+                _ = cancelled.notified() => break Err(CANCELLED.to_string()),
+                _ = progress.tick() => state.emit_job(&task_job_id),
+            }
+        };
+        state.jobs.finish(&task_job_id, result);
+        state.emit_job(&task_job_id);
+    });
+    Ok(job_id)
+}
+
+#[tauri::command]
+pub fn job_status(state: State<'_, AppState>, job_id: String) -> Result<JobInfo, String> {
+    state.jobs.info(&job_id)
+}
+
+/// The output of finished job `job_id`, or its error. The job is forgotten once its
+/// result has been collected.
+#[tauri::command]
+pub fn job_result(state: State<'_, AppState>, job_id: String) -> Result<QueryOutput, String> {
+    let mut jobs = state.jobs.jobs.lock().unwrap();
+    let job = jobs.get(&job_id).ok_or("Job not found")?;
+    if job.result.is_none() {
+        return Err(format!("Job {} has not finished", job_id));
+    }
+    let job = jobs.remove(&job_id).unwrap();
+    job.result.unwrap()
+}
+
+/// Cancel job `job_id`: a queued job never runs, and a running one is stopped as
+/// `cancel_query` would stop it. Returns `false` if the job has already finished.
+#[tauri::command]
+pub async fn cancel_job(state: State<'_, AppState>, job_id: String) -> Result<bool, String> {
+    let (was, cancelled) = {
+        let mut jobs = state.jobs.jobs.lock().unwrap();
+        let job = jobs.get_mut(&job_id).ok_or("Job not found")?;
+        let was = job.status;
+        if matches!(was, JobStatus::Queued | JobStatus::Running) {
+            job.status = JobStatus::Cancelled;
+        }
+        (was, job.cancelled.clone())
+    };
+    match was {
+        JobStatus::Queued => {
+            state.jobs.finish(&job_id, Err(CANCELLED.to_string()));
+            state.emit_job(&job_id);
+            Ok(true)
+        }
+        JobStatus::Running => {
+            // Stop the query on the server first; the job then stops waiting for it.
+            let stopped = state.cancel(&job_id).await;
+            cancelled.notify_one();
+            stopped.map(|_| true)
+        }
+        _ => Ok(false),
+    }
+}
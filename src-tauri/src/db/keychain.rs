@@ -0,0 +1,55 @@
+//! Profile passwords in the platform keychain: Keychain on macOS, the Credential
+//! Manager (DPAPI) on Windows and the Secret Service on Linux. Unlike the credential
+//! store, nothing is written to the app's data directory and no master password is
+//! needed.
+
+use keyring::Entry;
+use tauri::async_runtime::spawn_blocking;
+use zeroize::Zeroizing;
+
+/// The keychain service name entries are filed under, one per profile id.
+const SERVICE: &str = "db-connector";
+
+/// Run a keychain call on the blocking pool; the Secret Service client deadlocks if
+/// driven from a runtime thread.
+async fn with_entry<T: Send + 'static>(
+    profile_id: &str,
+    f: impl FnOnce(Entry) -> keyring::Result<T> + Send + 'static,
+) -> Result<T, String> {
+    let profile_id = profile_id.to_string();
+    spawn_blocking(move || f(Entry::new(SERVICE, &profile_id)?))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Keychain: {}", e))
+}
+
+pub async fn get(profile_id: &str) -> Result<Option<Zeroizing<String>>, String> {
+    with_entry(profile_id, |entry| match entry.get_password() {
+        Ok(secret) => Ok(Some(Zeroizing::new(secret))),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e),
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn save_keychain_secret(profile_id: String, secret: String) -> Result<(), String> {
+    let secret = Zeroizing::new(secret);
+    with_entry(&profile_id, move |entry| entry.set_password(&secret)).await
+}
+
+#[tauri::command]
+pub async fn get_keychain_secret(profile_id: String) -> Result<Option<String>, String> {
+    Ok(get(&profile_id).await?.map(|secret| secret.to_string()))
+}
+
+/// Returns `false` if the keychain held nothing for the profile.
+#[tauri::command]
+pub async fn delete_keychain_secret(profile_id: String) -> Result<bool, String> {
+    with_entry(&profile_id, |entry| match entry.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(e),
+    })
+    .await
+}
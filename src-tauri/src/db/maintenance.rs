@@ -0,0 +1,209 @@
+//! Routine maintenance without remembering dialect syntax: VACUUM/ANALYZE/REINDEX on
+//! Postgres, ANALYZE/OPTIMIZE/CHECK TABLE on MySQL, VACUUM/ANALYZE/`PRAGMA
+//! integrity_check` on SQLite, and ANALYZE on CockroachDB. Each table is a step;
+//! `db://maintenance-progress` is emitted as steps finish.
+
+use super::convert::JsonRow;
+use super::flavor::Flavor;
+use super::introspect::fetch_tables;
+use super::policy::Capability;
+use super::sql::quote_ident;
+use super::{generate_id, AppState, Connection, DbKind};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::Executor;
+use std::time::Instant;
+use tauri::State;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceAction {
+    Vacuum,
+    Analyze,
+    Reindex,
+    Optimize,
+    IntegrityCheck,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MaintenanceStep {
+    /// The table, or `None` for a database-wide command.
+    pub target: Option<String>,
+    pub statement: String,
+    pub elapsed_ms: u64,
+    /// Rows the command returned, e.g. MySQL's `Msg_type`/`Msg_text` or SQLite's
+    /// integrity check findings.
+    pub output: Vec<Map<String, Value>>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MaintenanceResult {
+    pub maintenance_id: String,
+    pub action: MaintenanceAction,
+    pub steps: Vec<MaintenanceStep>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceProgress {
+    pub maintenance_id: String,
+    pub completed: usize,
+    pub total: usize,
+    pub target: Option<String>,
+    pub ok: bool,
+}
+
+/// Run `action` on `target` (a table), or on the whole database when `target` is
+/// omitted. MySQL and CockroachDB have no database-wide form, so every table is
+/// processed in turn.
+/// A failing step is recorded and the remaining steps still run.
+#[tauri::command]
+pub async fn run_maintenance(
+    state: State<'_, AppState>,
+    id: String,
+    target: Option<String>,
+    action: MaintenanceAction,
+) -> Result<MaintenanceResult, String> {
+    let conn = state.connection(&id)?;
+    state.require_capability(&id, Capability::Ddl)?;
+    let kind = conn.kind();
+    let flavor = state.flavor(&id);
+
+    let targets = match (&target, kind, flavor) {
+        (Some(table), _, _) => vec![Some(table.clone())],
+        (None, DbKind::MySql, _) | (None, _, Some(Flavor::CockroachDb)) => {
+            fetch_tables(&conn, flavor)
+                .await?
+                .into_iter()
+                .map(Some)
+                .collect()
+        }
+        (None, _, _) => vec![None],
+    };
+    let mut statements = Vec::with_capacity(targets.len());
+    for table in &targets {
+        statements.push(statement(&conn, flavor, action, table.as_deref()).await?);
+    }
+
+    let maintenance_id = generate_id("maintenance");
+    let total = statements.len();
+    let mut steps = Vec::with_capacity(total);
+    for (target, statement) in targets.into_iter().zip(statements) {
+        let started = Instant::now();
+        let result = run(&conn, &statement).await;
+        state.audit(&id, &statement, result.as_ref().err().map(String::as_str));
+        let ok = result.is_ok();
+        steps.push(MaintenanceStep {
+            target: target.clone(),
+            statement,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            output: result.as_ref().cloned().unwrap_or_default(),
+            error: result.err(),
+        });
+        state.emit(
+            "db://maintenance-progress",
+            MaintenanceProgress {
+                maintenance_id: maintenance_id.clone(),
+                completed: steps.len(),
+                total,
+                target,
+                ok,
+            },
+        );
+    }
+
+    if matches!(
+        action,
+        MaintenanceAction::Vacuum | MaintenanceAction::Reindex
+    ) {
+        state.schema_cache.invalidate(&id);
+    }
+
+    Ok(MaintenanceResult {
+        maintenance_id,
+        action,
+        steps,
+    })
+}
+
+async fn statement(
+    conn: &Connection,
+    flavor: Option<Flavor>,
+    action: MaintenanceAction,
+    table: Option<&str>,
+) -> Result<String, String> {
+    use MaintenanceAction::*;
+    let kind = conn.kind();
+    let on = |keyword: &str| match table {
+        Some(table) => format!("{} {}", keyword, quote_ident(kind, table)),
+        None => keyword.to_string(),
+    };
+    let unsupported = || Err(format!("{:?} is not available on {:?}", action, kind));
+
+    if flavor == Some(Flavor::CockroachDb) {
+        // Cockroach garbage-collects and rebuilds indexes on its own.
+        return match action {
+            Analyze => Ok(on("ANALYZE")),
+            _ => Err(format!("{:?} is not available on CockroachDB", action)),
+        };
+    }
+
+    match (kind, action) {
+        (DbKind::Postgres, Vacuum) => Ok(on("VACUUM")),
+        (DbKind::Postgres, Analyze) => Ok(on("ANALYZE")),
+        (DbKind::Postgres, Reindex) => match (table, conn) {
+            (Some(_), _) => Ok(on("REINDEX TABLE")),
+            (None, Connection::Postgres(pool)) => {
+                let database: String = sqlx::query_scalar("SELECT current_database()")
+                    .fetch_one(pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok(format!("REINDEX DATABASE {}", quote_ident(kind, &database)))
+            }
+            _ => unsupported(),
+        },
+        (DbKind::MySql, Analyze) => Ok(on("ANALYZE TABLE")),
+        (DbKind::MySql, Optimize) => Ok(on("OPTIMIZE TABLE")),
+        (DbKind::MySql, IntegrityCheck) => Ok(on("CHECK TABLE")),
+        (DbKind::Sqlite, Vacuum) if table.is_none() => Ok("VACUUM".to_string()),
+        (DbKind::Sqlite, Vacuum) => Err("SQLite can only VACUUM the whole database".to_string()),
+        (DbKind::Sqlite, Analyze) => Ok(on("ANALYZE")),
+        (DbKind::Sqlite, Reindex) => Ok(on("REINDEX")),
+        (DbKind::Sqlite, Optimize) => Ok("PRAGMA optimize".to_string()),
+        (DbKind::Sqlite, IntegrityCheck) => Ok(match table {
+            Some(table) => format!("PRAGMA integrity_check({})", quote_ident(kind, table)),
+            None => "PRAGMA integrity_check".to_string(),
+        }),
+        _ => unsupported(),
+    }
+}
+
+/// Run over the simple query protocol: VACUUM refuses to run inside the implicit
+/// transaction of a prepared statement on some servers.
+async fn run(conn: &Connection, statement: &str) -> Result<Vec<Map<String, Value>>, String> {
+    let rows = match conn {
+        Connection::Postgres(pool) => pool
+            .fetch_all(statement)
+            .await
+            .map(|rows| rows.iter().map(JsonRow::to_json).collect()),
+        Connection::MySql(pool) => pool
+            .fetch_all(statement)
+            .await
+            .map(|rows| rows.iter().map(JsonRow::to_json).collect()),
+        Connection::Sqlite(pool) => pool
+            .fetch_all(statement)
+            .await
+            .map(|rows| rows.iter().map(JsonRow::to_json).collect()),
+        Connection::Oracle(_)
+        | Connection::Mongo(_)
+        | Connection::DuckDb(_)
+        | Connection::ClickHouse(_)
+        | Connection::Redis(_)
+        | Connection::Cassandra(_)
+        | Connection::Surreal(_)
+        | Connection::BigQuery(_)
+        | Connection::Firebird(_)
+        | Connection::Odbc(_) => return Err(conn.unsupported("Maintenance")),
+    };
+    rows.map_err(|e| e.to_string())
+}
@@ -0,0 +1,130 @@
+//! Column masking rules, so that screen-sharing against production data doesn't expose
+//! e-mail addresses or national ids. Values are replaced as rows are converted, before
+//! they are held in memory, spilled, streamed or sent to the webview.
+
+use super::convert::RowSet;
+use super::AppState;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub const DEFAULT_MASK: &str = "***";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskingRule {
+    /// Case-insensitive column name pattern; `*` matches any run of characters and
+    /// `?` a single one, e.g. `*email*` or `ssn`.
+    pub column: String,
+    /// Shown instead of the value. Defaults to `DEFAULT_MASK`.
+    pub replacement: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaskingOptions {
+    pub rules: Vec<MaskingRule>,
+    /// Whether `unmask: true` on a request is honoured for this connection.
+    pub allow_unmask: bool,
+}
+
+/// Replacement values by column index for one result's columns.
+#[derive(Debug, Default)]
+pub struct ColumnMasks(Vec<(usize, Value)>);
+
+impl ColumnMasks {
+    pub fn new(rules: &[MaskingRule], columns: &[String]) -> Self {
+        let masks = columns
+            .iter()
+            .enumerate()
+            .filter_map(|(index, column)| {
+                let rule = rules.iter().find(|rule| matches(&rule.column, column))?;
+                let replacement = rule.replacement.as_deref().unwrap_or(DEFAULT_MASK);
+                Some((index, Value::String(replacement.to_string())))
+            })
+            .collect();
+        Self(masks)
+    }
+
+    /// Mask every non-null value in the masked columns. NULLs stay visible.
+    pub fn apply(&self, rows: &mut [Vec<Value>]) {
+        if self.0.is_empty() {
+            return;
+        }
+        for row in rows {
+            for (index, replacement) in &self.0 {
+                if let Some(value) = row.get_mut(*index).filter(|v| !v.is_null()) {
+                    *value = replacement.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Mask the matching top-level fields of a document, for backends whose rows don't
+/// share one column list. NULLs stay visible.
+pub fn mask_fields(rules: &[MaskingRule], document: &mut Map<String, Value>) {
+    for (key, value) in document.iter_mut().filter(|(_, v)| !v.is_null()) {
+        if let Some(replacement) = replacement_for(rules, key) {
+            *value = Value::String(replacement.to_string());
+        }
+    }
+}
+
+/// What values named `name` are masked with, if any rule matches it.
+pub fn replacement_for<'a>(rules: &'a [MaskingRule], name: &str) -> Option<&'a str> {
+    rules
+        .iter()
+        .find(|rule| matches(&rule.column, name))
+        .map(|rule| rule.replacement.as_deref().unwrap_or(DEFAULT_MASK))
+}
+
+impl AppState {
+    /// The masking rules to apply to results from connection `id`; none when `unmask`
+    /// is requested and the connection allows it.
+    pub fn masking_rules(&self, id: &str, unmask: bool) -> Result<Vec<MaskingRule>, String> {
+        let masking = self.masking.lock().unwrap();
+        let Some(options) = masking.get(id) else {
+            return Ok(Vec::new());
+        };
+        match (unmask, options.allow_unmask) {
+            (false, _) => Ok(options.rules.clone()),
+            (true, true) => Ok(Vec::new()),
+            (true, false) => Err("Unmasking is not permitted on this connection".to_string()),
+        }
+    }
+
+    pub fn mask(&self, id: &str, set: &mut RowSet, unmask: bool) -> Result<(), String> {
+        let rules = self.masking_rules(id, unmask)?;
+        ColumnMasks::new(&rules, &set.columns).apply(&mut set.rows);
+        Ok(())
+    }
+}
+
+/// Case-insensitive glob match supporting `*` and `?`.
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
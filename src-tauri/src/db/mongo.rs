@@ -0,0 +1,294 @@
+//! MongoDB connections. There is no SQL here: `execute` takes a JSON document query,
+//! either a find or an aggregation pipeline on one collection, and returns documents.
+
+use super::convert::{QueryOutput, ResultFormat, RowSet, MAX_RESULT_ROWS};
+use super::introspect::ColumnInfo;
+use super::masking::mask_fields;
+use super::policy::{production_write, Capability, DestructiveStatement};
+use super::pooling::PoolSettings;
+use super::{AppState, ExecuteOptions};
+use futures_util::future::try_join_all;
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::options::ClientOptions;
+use mongodb::{Client, Database};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::future::IntoFuture;
+
+/// Documents sampled per collection when inferring its fields for `get_columns`.
+const FIELD_SAMPLE_SIZE: i64 = 100;
+
+/// What `execute` accepts on a MongoDB connection, as JSON. Filters, projections,
+/// sorts and pipeline stages may use extended JSON (`{"$oid": ...}`, `{"$date": ...}`).
+///
+/// `{"collection": "orders", "filter": {"status": "open"}, "sort": {"created": -1}, "limit": 50}`
+/// runs a find; `{"collection": "orders", "pipeline": [{"$group": ...}]}` an aggregation.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DocumentQuery {
+    pub collection: String,
+    /// Run this aggregation pipeline instead of a find.
+    pub pipeline: Option<Vec<Map<String, Value>>>,
+    #[serde(default)]
+    pub filter: Map<String, Value>,
+    pub projection: Option<Map<String, Value>>,
+    pub sort: Option<Map<String, Value>>,
+    pub skip: Option<u64>,
+    pub limit: Option<i64>,
+}
+
+impl DocumentQuery {
+    /// Pipelines ending in `$out` or `$merge` write to a collection.
+    fn required_capability(&self) -> Capability {
+        let writes = self
+            .pipeline
+            .iter()
+            .flatten()
+            .any(|stage| stage.contains_key("$out") || stage.contains_key("$merge"));
+        if writes {
+            Capability::ReadWrite
+        } else {
+            Capability::ReadOnly
+        }
+    }
+}
+
+/// Connect to the database named in the connection string.
+pub async fn connect(
+    conn_string: &str,
+    password: Option<&str>,
+    settings: &PoolSettings,
+) -> Result<Database, String> {
+    let mut options = ClientOptions::parse(conn_string)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let (Some(password), Some(credential)) = (password, options.credential.as_mut()) {
+        credential.password = Some(password.to_string());
+    }
+    options.min_pool_size = Some(settings.min_connections);
+    options.max_pool_size = Some(settings.max_connections);
+    if let Some(idle) = settings.idle_timeout {
+        options.max_idle_time = Some(idle);
+    }
+
+    let client = Client::with_options(options).map_err(|e| e.to_string())?;
+    let db = client
+        .default_database()
+        .ok_or("Name the database in the connection string, e.g. mongodb://host:27017/mydb")?;
+    // The driver connects lazily; fail here rather than on the first query.
+    db.run_command(doc! { "ping": 1 })
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(db)
+}
+
+/// Ping from `n` tasks at once so the driver opens that many connections.
+pub async fn warm(db: &Database, n: u32) -> Result<u32, String> {
+    try_join_all((0..n).map(|_| db.run_command(doc! { "ping": 1 }).into_future()))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(n)
+}
+
+/// Run a `DocumentQuery` given as JSON. Capability, masking and auditing apply as they
+/// do for SQL; statement policies, sandboxing and destructive-statement confirmation
+/// don't, since a document query can only read (or write through `$out`/`$merge`).
+/// Writes on a `production` connection still need confirming.
+pub async fn execute(
+    state: &AppState,
+    id: &str,
+    db: &Database,
+    query: &str,
+    options: &ExecuteOptions,
+) -> Result<QueryOutput, String> {
+    let parsed: DocumentQuery = serde_json::from_str(query)
+        .map_err(|e| format!("Expected a MongoDB document query: {}", e))?;
+    let required = parsed.required_capability();
+    state.require_capability(id, required)?;
+    if let Some(reason) = production_write(state.is_production(id), required) {
+        if !state.confirmed(id, query, options) {
+            return Ok(QueryOutput::NeedsConfirmation {
+                needs_confirmation: true,
+                statements: vec![DestructiveStatement {
+                    index: 0,
+                    reason,
+                    statement: query.to_string(),
+                }],
+            });
+        }
+    }
+    let masking = state.masking_rules(id, options.unmask)?;
+
+    let result = run(db, parsed).await;
+    state.audit(id, query, result.as_ref().err().map(String::as_str));
+    let mut documents = result?;
+    for document in &mut documents {
+        mask_fields(&masking, document);
+    }
+
+    Ok(match options.format {
+        ResultFormat::Rows => QueryOutput::Rows(documents),
+        format => to_row_set(documents).into_output(format),
+    })
+}
+
+async fn run(db: &Database, query: DocumentQuery) -> Result<Vec<Map<String, Value>>, String> {
+    let collection = db.collection::<Document>(&query.collection);
+    let mut cursor = match query.pipeline {
+        Some(pipeline) => {
+            let stages = pipeline
+                .into_iter()
+                .map(to_document)
+                .collect::<Result<Vec<_>, _>>()?;
+            collection.aggregate(stages).await
+        }
+        None => {
+            let mut find = collection.find(to_document(query.filter)?);
+            if let Some(projection) = query.projection {
+                find = find.projection(to_document(projection)?);
+            }
+            if let Some(sort) = query.sort {
+                find = find.sort(to_document(sort)?);
+            }
+            if let Some(skip) = query.skip {
+                find = find.skip(skip);
+            }
+            if let Some(limit) = query.limit {
+                find = find.limit(limit);
+            }
+            find.await
+        }
+    }
+    .map_err(|e| e.to_string())?;
+
+    let mut documents = Vec::new();
+    while let Some(document) = cursor.try_next().await.map_err(|e| e.to_string())? {
+        if documents.len() == MAX_RESULT_ROWS {
+            return Err(format!(
+                "The query returned more than {} documents; add a limit or a $limit stage",
+                MAX_RESULT_ROWS
+            ));
+        }
+        documents.push(to_json(document));
+    }
+    Ok(documents)
+}
+
+fn to_document(map: Map<String, Value>) -> Result<Document, String> {
+    Document::try_from(map).map_err(|e| e.to_string())
+}
+
+/// Relaxed extended JSON: numbers stay numbers, ObjectIds become `{"$oid": ...}`.
+fn to_json(document: Document) -> Map<String, Value> {
+    match Bson::Document(document).into_relaxed_extjson() {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    }
+}
+
+/// Documents as rows, with a column for every field seen, in first-seen order.
+fn to_row_set(documents: Vec<Map<String, Value>>) -> RowSet {
+    let mut columns: Vec<String> = Vec::new();
+    for document in &documents {
+        for key in document.keys() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+    let rows = documents
+        .into_iter()
+        .map(|mut document| {
+            columns
+                .iter()
+                .map(|c| document.remove(c).unwrap_or(Value::Null))
+                .collect()
+        })
+        .collect();
+    RowSet {
+        columns,
+        rows,
+        affected: None,
+        truncated: None,
+    }
+}
+
+pub async fn fetch_tables(db: &Database) -> Result<Vec<String>, String> {
+    let mut names = db
+        .list_collection_names()
+        .await
+        .map_err(|e| e.to_string())?;
+    names.sort();
+    Ok(names)
+}
+
+/// Collections have no fixed schema, so fields are inferred from a sample of documents.
+/// A field is nullable when it is missing or null in any sampled document, and its type
+/// lists every BSON type seen.
+pub async fn fetch_columns(db: &Database, collection: &str) -> Result<Vec<ColumnInfo>, String> {
+    let documents: Vec<Document> = db
+        .collection::<Document>(collection)
+        .find(doc! {})
+        .limit(FIELD_SAMPLE_SIZE)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut fields: Vec<(String, Vec<String>, usize, bool)> = Vec::new();
+    for document in &documents {
+        for (key, value) in document {
+            let index = match fields.iter().position(|(name, ..)| name == key) {
+                Some(index) => index,
+                None => {
+                    fields.push((key.clone(), Vec::new(), 0, false));
+                    fields.len() - 1
+                }
+            };
+            let (_, types, seen, has_null) = &mut fields[index];
+            *seen += 1;
+            if matches!(value, Bson::Null) {
+                *has_null = true;
+            } else {
+                let name = type_name(value).to_string();
+                if !types.contains(&name) {
+                    types.push(name);
+                }
+            }
+        }
+    }
+
+    Ok(fields
+        .into_iter()
+        .map(|(name, types, seen, has_null)| ColumnInfo {
+            primary_key: name == "_id",
+            nullable: has_null || seen < documents.len(),
+            data_type: types.join(" | "),
+            default: None,
+            name,
+        })
+        .collect())
+}
+
+fn type_name(value: &Bson) -> &'static str {
+    match value {
+        Bson::Double(_) => "double",
+        Bson::String(_) => "string",
+        Bson::Array(_) => "array",
+        Bson::Document(_) => "object",
+        Bson::Boolean(_) => "bool",
+        Bson::Null => "null",
+        Bson::RegularExpression(_) => "regex",
+        Bson::JavaScriptCode(_) | Bson::JavaScriptCodeWithScope(_) => "javascript",
+        Bson::Int32(_) => "int",
+        Bson::Int64(_) => "long",
+        Bson::Timestamp(_) => "timestamp",
+        Bson::Binary(_) => "binData",
+        Bson::ObjectId(_) => "objectId",
+        Bson::DateTime(_) => "date",
+        Bson::Decimal128(_) => "decimal",
+        _ => "other",
+    }
+}
@@ -0,0 +1,272 @@
+//! Fallback for databases without a native backend, through whatever ODBC driver is
+//! installed. Results are fetched as text and converted back to JSON numbers and
+//! booleans using the column types the driver reports. The ODBC API is blocking, so
+//! every call runs on the blocking thread pool.
+
+use super::convert::{collect_blocking, Batch, CollectLimits, Collected};
+use super::introspect::ColumnInfo;
+use super::pooling::{IdlePool, PoolSettings, Pooled};
+use super::sql::split_statements;
+use odbc_api::buffers::TextRowSet;
+use odbc_api::{escape_attribute_value, ConnectionOptions, Cursor, DataType, ResultSetMetadata};
+use serde_json::{Number, Value};
+use std::sync::Arc;
+
+/// Rows fetched per round trip.
+const FETCH_BATCH: usize = 256;
+/// Longest text value read per cell; longer values are cut off by the driver.
+const MAX_TEXT_BYTES: usize = 16 * 1024;
+
+type OdbcConnection = odbc_api::Connection<'static>;
+
+/// Connections are opened on demand and kept idle for reuse by later calls.
+#[derive(Clone)]
+pub struct Pool {
+    conn_string: Arc<str>,
+    idle: Arc<IdlePool<OdbcConnection>>,
+}
+
+impl Pool {
+    /// An idle connection the driver doesn't report as dead, or a new one.
+    fn get(&self) -> Result<Pooled<OdbcConnection>, odbc_api::Error> {
+        while let Some(conn) = self.idle.take() {
+            if !conn.is_dead().unwrap_or(true) {
+                return Ok(conn);
+            }
+        }
+        let conn = odbc_api::environment()?
+            .connect_with_connection_string(&self.conn_string, ConnectionOptions::default())?;
+        Ok(Pooled::new(conn))
+    }
+
+    /// Keep `conn` for reuse. Only call this after a successful call on it.
+    fn put(&self, conn: Pooled<OdbcConnection>) {
+        self.idle.put(conn);
+    }
+}
+
+/// Connect with `odbc:` followed by an ODBC connection string, e.g.
+/// `odbc:DSN=warehouse;UID=reporting` or `odbc:Driver={Ingres};Server=...;Database=...`.
+/// A stored password is added as `PWD`.
+pub async fn connect(
+    conn_string: &str,
+    password: Option<&str>,
+    settings: &PoolSettings,
+) -> Result<Pool, String> {
+    let mut odbc_string = conn_string
+        .get(5..)
+        .filter(|_| conn_string[..5].eq_ignore_ascii_case("odbc:"))
+        .ok_or("ODBC connection strings start with odbc:")?
+        .trim()
+        .trim_end_matches(';')
+        .to_string();
+    if let Some(password) = password {
+        odbc_string.push_str(&format!(";PWD={}", escape_attribute_value(password)));
+    }
+    let pool = Pool {
+        conn_string: odbc_string.into(),
+        idle: Arc::new(IdlePool::new(settings)),
+    };
+    let first = pool.clone();
+    blocking(move || {
+        let conn = first.get()?;
+        first.put(conn);
+        Ok(())
+    })
+    .await?;
+    Ok(pool)
+}
+
+/// Open `n` connections at once and keep them idle, returning how many are held.
+pub async fn warm(pool: &Pool, n: u32) -> Result<u32, String> {
+    let pool = pool.clone();
+    blocking(move || {
+        let held = (0..n.min(pool.idle.max_idle() as u32))
+            .map(|_| pool.get())
+            .collect::<Result<Vec<_>, _>>()?;
+        for conn in held {
+            pool.put(conn);
+        }
+        Ok(pool.idle.idle_count() as u32)
+    })
+    .await
+}
+
+/// Disconnect idle connections that have expired.
+pub async fn reap(pool: &Pool) {
+    let expired = pool.idle.take_expired();
+    if !expired.is_empty() {
+        let _ = tokio::task::spawn_blocking(move || drop(expired)).await;
+    }
+}
+
+/// Run a script, returning the rows of its last statement. Statements are sent one at
+/// a time with the driver's default autocommit. ODBC has no portable statement timeout,
+/// so none is applied.
+pub async fn run_query(pool: &Pool, sql: &str, limits: CollectLimits) -> Result<Collected, String> {
+    let pool = pool.clone();
+    let statements: Vec<String> = split_statements(sql)
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    collect_blocking(limits, move |tx| {
+        let conn = pool.get().map_err(|e| e.to_string())?;
+        {
+            let mut cursor = None;
+            for text in &statements {
+                cursor = conn.execute(text, ()).map_err(|e| e.to_string())?;
+            }
+            if let Some(mut cursor) = cursor {
+                let (columns, types) = describe(&mut cursor).map_err(|e| e.to_string())?;
+                if tx.blocking_send(Batch::Columns(columns)).is_err() {
+                    return Ok(());
+                }
+                let mut buffer =
+                    TextRowSet::for_cursor(FETCH_BATCH, &mut cursor, Some(MAX_TEXT_BYTES))
+                        .map_err(|e| e.to_string())?;
+                let mut rows = cursor.bind_buffer(&mut buffer).map_err(|e| e.to_string())?;
+                while let Some(batch) = rows.fetch().map_err(|e| e.to_string())? {
+                    let converted = (0..batch.num_rows())
+                        .map(|row| {
+                            types
+                                .iter()
+                                .enumerate()
+                                .map(|(col, data_type)| {
+                                    let text = batch.at(col, row).map(String::from_utf8_lossy);
+                                    to_json(text.as_deref(), data_type)
+                                })
+                                .collect()
+                        })
+                        .collect();
+                    if tx.blocking_send(Batch::Rows(converted)).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        pool.put(conn);
+        Ok(())
+    })
+    .await
+}
+
+fn describe(
+    cursor: &mut impl ResultSetMetadata,
+) -> Result<(Vec<String>, Vec<DataType>), odbc_api::Error> {
+    let count = cursor.num_result_cols()? as u16;
+    let mut columns = Vec::with_capacity(count as usize);
+    let mut types = Vec::with_capacity(count as usize);
+    for i in 1..=count {
+        columns.push(cursor.col_name(i)?);
+        types.push(cursor.col_data_type(i)?);
+    }
+    Ok((columns, types))
+}
+
+/// Integer, floating point and bit columns become numbers and booleans; everything
+/// else, including exact decimals, keeps the driver's text.
+fn to_json(text: Option<&str>, data_type: &DataType) -> Value {
+    let Some(text) = text else {
+        return Value::Null;
+    };
+    let parsed = match data_type {
+        DataType::TinyInt | DataType::SmallInt | DataType::Integer | DataType::BigInt => {
+            text.trim().parse::<i64>().ok().map(Value::from)
+        }
+        DataType::Real | DataType::Float { .. } | DataType::Double => text
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number),
+        DataType::Bit => match text.trim() {
+            "1" => Some(Value::Bool(true)),
+            "0" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| Value::String(text.to_string()))
+}
+
+/// Read a whole catalog-function result as text.
+fn read_all(mut cursor: impl Cursor) -> Result<Vec<Vec<Option<String>>>, odbc_api::Error> {
+    let mut buffer = TextRowSet::for_cursor(FETCH_BATCH, &mut cursor, Some(MAX_TEXT_BYTES))?;
+    let mut rows = cursor.bind_buffer(&mut buffer)?;
+    let mut out = Vec::new();
+    while let Some(batch) = rows.fetch()? {
+        for row in 0..batch.num_rows() {
+            out.push(
+                (0..batch.num_cols())
+                    .map(|col| {
+                        batch
+                            .at(col, row)
+                            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+                    })
+                    .collect(),
+            );
+        }
+    }
+    Ok(out)
+}
+
+/// Tables and views, best effort: `SQLTables` across everything the connection's user
+/// can see, in whatever order the driver returns them.
+pub async fn fetch_tables(pool: &Pool) -> Result<Vec<String>, String> {
+    let pool = pool.clone();
+    blocking(move || {
+        let conn = pool.get()?;
+        // TABLE_CAT, TABLE_SCHEM, TABLE_NAME, TABLE_TYPE, REMARKS.
+        let rows = read_all(conn.tables("", "", "", "TABLE,VIEW")?)?;
+        pool.put(conn);
+        let mut tables: Vec<String> = rows
+            .into_iter()
+            .filter_map(|row| row.into_iter().nth(2).flatten())
+            .collect();
+        tables.dedup();
+        Ok(tables)
+    })
+    .await
+}
+
+/// Columns from `SQLColumns`. ODBC reports keys separately, so no column is marked as
+/// part of the primary key.
+pub async fn fetch_columns(pool: &Pool, table: &str) -> Result<Vec<ColumnInfo>, String> {
+    let pool = pool.clone();
+    let table = table.to_string();
+    blocking(move || {
+        let conn = pool.get()?;
+        // TABLE_CAT, TABLE_SCHEM, TABLE_NAME, COLUMN_NAME, DATA_TYPE, TYPE_NAME,
+        // COLUMN_SIZE, BUFFER_LENGTH, DECIMAL_DIGITS, NUM_PREC_RADIX, NULLABLE, REMARKS,
+        // COLUMN_DEF, ...
+        let rows = read_all(conn.columns("", "", &table, "")?)?;
+        pool.put(conn);
+        // The table name is a search pattern, so `_` in it matches any character.
+        Ok(rows
+            .into_iter()
+            .filter(|row| row.get(2).cloned().flatten().as_deref() == Some(table.as_str()))
+            .map(|mut row| {
+                row.resize(13, None);
+                ColumnInfo {
+                    name: row[3].take().unwrap_or_default(),
+                    data_type: row[5].take().unwrap_or_default(),
+                    nullable: row[10].as_deref() != Some("0"),
+                    default: row[12].take(),
+                    primary_key: false,
+                }
+            })
+            .collect())
+    })
+    .await
+}
+
+async fn blocking<T, F>(f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, odbc_api::Error> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,231 @@
+//! Binding JSON parameters. A bare JSON value is bound as the closest native type; a
+//! `{"value": ..., "type": "..."}` object names the type, for values JSON can't tell
+//! apart (a date from a string, a UUID, a decimal, bytes) and for typed NULLs.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::database::HasArguments;
+use sqlx::mysql::MySql;
+use sqlx::postgres::Postgres;
+use sqlx::query::Query;
+use sqlx::sqlite::Sqlite;
+use sqlx::types::chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use sqlx::types::{Decimal, Json, Uuid};
+use sqlx::{Database, Encode, Type};
+use std::str::FromStr;
+
+pub type DbQuery<'q, DB> = Query<'q, DB, <DB as HasArguments<'q>>::Arguments>;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamType {
+    #[serde(alias = "string")]
+    Text,
+    #[serde(alias = "int", alias = "bigint")]
+    Integer,
+    #[serde(alias = "double")]
+    Float,
+    #[serde(alias = "bool")]
+    Boolean,
+    #[serde(alias = "numeric")]
+    Decimal,
+    Date,
+    Time,
+    /// Without a time zone, e.g. `2024-01-01 12:00:00`.
+    Timestamp,
+    /// RFC 3339 with an offset, e.g. `2024-01-01T12:00:00+02:00`; bound in UTC.
+    Timestamptz,
+    Uuid,
+    /// Base64-encoded.
+    #[serde(alias = "bytea", alias = "blob")]
+    Bytes,
+    Json,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Tagged {
+    value: Value,
+    #[serde(rename = "type")]
+    ty: ParamType,
+}
+
+/// A parameter converted to the Rust type it is bound as; `None` binds a typed NULL.
+enum Param {
+    Text(Option<String>),
+    Integer(Option<i64>),
+    Float(Option<f64>),
+    Boolean(Option<bool>),
+    Decimal(Option<Decimal>),
+    Date(Option<NaiveDate>),
+    Time(Option<NaiveTime>),
+    Timestamp(Option<NaiveDateTime>),
+    Timestamptz(Option<DateTime<Utc>>),
+    Uuid(Option<Uuid>),
+    Bytes(Option<Vec<u8>>),
+    Json(Option<Json<Value>>),
+}
+
+impl Param {
+    fn from_json(value: &Value) -> Result<Param, String> {
+        Ok(match value {
+            Value::Null => Param::Text(None),
+            Value::Bool(b) => Param::Boolean(Some(*b)),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => Param::Integer(Some(i)),
+                None => Param::Float(n.as_f64()),
+            },
+            Value::String(s) => Param::Text(Some(s.clone())),
+            Value::Object(map) if map.contains_key("type") => {
+                let tagged: Tagged = serde_json::from_value(value.clone())
+                    .map_err(|e| format!("Invalid typed parameter: {}", e))?;
+                Param::typed(tagged.ty, &tagged.value)?
+            }
+            other => Param::Text(Some(other.to_string())),
+        })
+    }
+
+    fn typed(ty: ParamType, value: &Value) -> Result<Param, String> {
+        if let ParamType::Json = ty {
+            return Ok(match value {
+                Value::Null => Param::Json(None),
+                value => Param::Json(Some(Json(value.clone()))),
+            });
+        }
+        // Numbers and booleans may also be given as strings.
+        let text = match value {
+            Value::Null => return Ok(Param::null(ty)),
+            Value::String(s) => s.clone(),
+            Value::Number(_) | Value::Bool(_) => value.to_string(),
+            _ => return Err(format!("Invalid {:?} parameter {}", ty, value)),
+        };
+        let parsed = match ty {
+            ParamType::Text => Ok(Param::Text(Some(text.clone()))),
+            ParamType::Integer => text
+                .parse()
+                .map(|v| Param::Integer(Some(v)))
+                .map_err(|e| e.to_string()),
+            ParamType::Float => text
+                .parse()
+                .map(|v| Param::Float(Some(v)))
+                .map_err(|e| e.to_string()),
+            ParamType::Boolean => text
+                .parse()
+                .map(|v| Param::Boolean(Some(v)))
+                .map_err(|e| e.to_string()),
+            ParamType::Decimal => Decimal::from_str(&text)
+                .map(|v| Param::Decimal(Some(v)))
+                .map_err(|e| e.to_string()),
+            ParamType::Date => NaiveDate::from_str(&text)
+                .map(|v| Param::Date(Some(v)))
+                .map_err(|e| e.to_string()),
+            ParamType::Time => NaiveTime::from_str(&text)
+                .map(|v| Param::Time(Some(v)))
+                .map_err(|e| e.to_string()),
+            ParamType::Timestamp => NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S%.f")
+                .or_else(|_| NaiveDateTime::from_str(&text))
+                .map(|v| Param::Timestamp(Some(v)))
+                .map_err(|e| e.to_string()),
+            ParamType::Timestamptz => DateTime::parse_from_rfc3339(&text)
+                .map(|v| Param::Timestamptz(Some(v.with_timezone(&Utc))))
+                .map_err(|e| e.to_string()),
+            ParamType::Uuid => Uuid::parse_str(&text)
+                .map(|v| Param::Uuid(Some(v)))
+                .map_err(|e| e.to_string()),
+            ParamType::Bytes => BASE64
+                .decode(&text)
+                .map(|v| Param::Bytes(Some(v)))
+                .map_err(|e| e.to_string()),
+            ParamType::Json => unreachable!(),
+        };
+        parsed.map_err(|e| format!("Invalid {:?} parameter {:?}: {}", ty, text, e))
+    }
+
+    fn null(ty: ParamType) -> Param {
+        match ty {
+            ParamType::Text => Param::Text(None),
+            ParamType::Integer => Param::Integer(None),
+            ParamType::Float => Param::Float(None),
+            ParamType::Boolean => Param::Boolean(None),
+            ParamType::Decimal => Param::Decimal(None),
+            ParamType::Date => Param::Date(None),
+            ParamType::Time => Param::Time(None),
+            ParamType::Timestamp => Param::Timestamp(None),
+            ParamType::Timestamptz => Param::Timestamptz(None),
+            ParamType::Uuid => Param::Uuid(None),
+            ParamType::Bytes => Param::Bytes(None),
+            ParamType::Json => Param::Json(None),
+        }
+    }
+}
+
+/// Binding for the types the backends store differently. MySQL and SQLite have no
+/// UUID type, so UUIDs are bound as their hyphenated text, as `CHAR(36)` columns
+/// hold them; SQLite has no decimal type either, so decimals are bound as text.
+pub trait BindNative: Database {
+    fn bind_decimal(query: DbQuery<'_, Self>, value: Option<Decimal>) -> DbQuery<'_, Self>;
+    fn bind_uuid(query: DbQuery<'_, Self>, value: Option<Uuid>) -> DbQuery<'_, Self>;
+}
+
+impl BindNative for Postgres {
+    fn bind_decimal(query: DbQuery<'_, Self>, value: Option<Decimal>) -> DbQuery<'_, Self> {
+        query.bind(value)
+    }
+
+    fn bind_uuid(query: DbQuery<'_, Self>, value: Option<Uuid>) -> DbQuery<'_, Self> {
+        query.bind(value)
+    }
+}
+
+impl BindNative for MySql {
+    fn bind_decimal(query: DbQuery<'_, Self>, value: Option<Decimal>) -> DbQuery<'_, Self> {
+        query.bind(value)
+    }
+
+    fn bind_uuid(query: DbQuery<'_, Self>, value: Option<Uuid>) -> DbQuery<'_, Self> {
+        query.bind(value.map(|uuid| uuid.hyphenated().to_string()))
+    }
+}
+
+impl BindNative for Sqlite {
+    fn bind_decimal(query: DbQuery<'_, Self>, value: Option<Decimal>) -> DbQuery<'_, Self> {
+        query.bind(value.map(|decimal| decimal.to_string()))
+    }
+
+    fn bind_uuid(query: DbQuery<'_, Self>, value: Option<Uuid>) -> DbQuery<'_, Self> {
+        query.bind(value.map(|uuid| uuid.hyphenated().to_string()))
+    }
+}
+
+/// Bind a JSON value, typed by its tag or else as the closest native type.
+pub fn bind_value<'q, DB>(query: DbQuery<'q, DB>, value: &Value) -> Result<DbQuery<'q, DB>, String>
+where
+    DB: BindNative,
+    Option<i64>: Encode<'q, DB> + Type<DB>,
+    Option<f64>: Encode<'q, DB> + Type<DB>,
+    Option<bool>: Encode<'q, DB> + Type<DB>,
+    Option<String>: Encode<'q, DB> + Type<DB>,
+    Option<NaiveDate>: Encode<'q, DB> + Type<DB>,
+    Option<NaiveTime>: Encode<'q, DB> + Type<DB>,
+    Option<NaiveDateTime>: Encode<'q, DB> + Type<DB>,
+    Option<DateTime<Utc>>: Encode<'q, DB> + Type<DB>,
+    Option<Vec<u8>>: Encode<'q, DB> + Type<DB>,
+    Option<Json<Value>>: Encode<'q, DB> + Type<DB>,
+{
+    Ok(match Param::from_json(value)? {
+        Param::Text(v) => query.bind(v),
+        Param::Integer(v) => query.bind(v),
+        Param::Float(v) => query.bind(v),
+        Param::Boolean(v) => query.bind(v),
+        Param::Decimal(v) => DB::bind_decimal(query, v),
+        Param::Date(v) => query.bind(v),
+        Param::Time(v) => query.bind(v),
+        Param::Timestamp(v) => query.bind(v),
+        Param::Timestamptz(v) => query.bind(v),
+        Param::Uuid(v) => DB::bind_uuid(query, v),
+        Param::Bytes(v) => query.bind(v),
+        Param::Json(v) => query.bind(v),
+    })
+}
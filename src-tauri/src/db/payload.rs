@@ -0,0 +1,134 @@
+use super::arrow_ipc;
+use super::convert::{QueryOutput, ResultFormat};
+use super::{execute_on, AppState, ExecuteOptions};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use serde::Deserialize;
+use std::io::Write;
+use tauri::ipc::Response;
+use tauri::State;
+
+/// Payloads smaller than this are sent uncompressed; compressing them costs more
+/// than the transfer saves.
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 256 * 1024;
+
+/// Wire encoding for results returned as raw bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadEncoding {
+    /// UTF-8 JSON, decoded on the frontend with `TextDecoder` + `JSON.parse`.
+    #[default]
+    Json,
+    /// MessagePack, with maps keyed by name so it decodes to the same shape as JSON.
+    /// Smaller than JSON and quicker to parse for large results.
+    MessagePack,
+    /// CBOR (RFC 8949), laid out as `MessagePack` is.
+    Cbor,
+    /// An Arrow IPC stream of one record batch, for arrow-js and Parquet writers. The
+    /// rest of the result travels as JSON in the schema metadata; see `arrow_ipc`.
+    Arrow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+    /// zlib-wrapped deflate, which browsers decode natively via `DecompressionStream('deflate')`.
+    Deflate,
+    /// gzip, also decoded natively, via `DecompressionStream('gzip')`.
+    Gzip,
+    /// Zstandard: smaller and faster, but needs a JS decoder on the frontend.
+    Zstd,
+}
+
+impl Codec {
+    /// Marker byte written in front of a compressed payload.
+    fn marker(self) -> u8 {
+        match self {
+            Codec::Deflate => 1,
+            Codec::Zstd => 2,
+            Codec::Gzip => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CompressionOptions {
+    pub codec: Codec,
+    pub threshold: Option<usize>,
+}
+
+pub fn encode(output: QueryOutput, encoding: PayloadEncoding) -> Result<Vec<u8>, String> {
+    match encoding {
+        PayloadEncoding::Json => serde_json::to_vec(&output).map_err(|e| e.to_string()),
+        PayloadEncoding::MessagePack => rmp_serde::to_vec_named(&output).map_err(|e| e.to_string()),
+        PayloadEncoding::Cbor => {
+            let mut bytes = Vec::new();
+            ciborium::into_writer(&output, &mut bytes).map_err(|e| e.to_string())?;
+            Ok(bytes)
+        }
+        PayloadEncoding::Arrow => arrow_ipc::encode(output),
+    }
+}
+
+/// Frame a payload as `[marker][body]`, compressing the body when it is larger than
+/// the threshold. Marker 0 means the body is stored as-is.
+pub fn compress(bytes: Vec<u8>, options: CompressionOptions) -> Result<Vec<u8>, String> {
+    let threshold = options.threshold.unwrap_or(DEFAULT_COMPRESSION_THRESHOLD);
+    if bytes.len() < threshold {
+        let mut framed = Vec::with_capacity(bytes.len() + 1);
+        framed.push(0);
+        framed.extend_from_slice(&bytes);
+        return Ok(framed);
+    }
+
+    let mut framed = vec![options.codec.marker()];
+    match options.codec {
+        Codec::Deflate => {
+            let mut encoder = ZlibEncoder::new(framed, flate2::Compression::fast());
+            encoder.write_all(&bytes).map_err(|e| e.to_string())?;
+            framed = encoder.finish().map_err(|e| e.to_string())?;
+        }
+        Codec::Gzip => {
+            let mut encoder = GzEncoder::new(framed, flate2::Compression::fast());
+            encoder.write_all(&bytes).map_err(|e| e.to_string())?;
+            framed = encoder.finish().map_err(|e| e.to_string())?;
+        }
+        Codec::Zstd => {
+            zstd::stream::copy_encode(bytes.as_slice(), &mut framed, 3)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(framed)
+}
+
+/// Like `execute`, but hands the encoded result to the webview as an `ArrayBuffer`.
+///
+/// Regular commands serialize their return value into the IPC JSON body, so a large
+/// result is encoded twice and any byte buffer is inflated into a JSON number array.
+/// Returning a `Response` skips that step and transfers the bytes as-is.
+///
+/// When `compression` is given the payload is framed with a leading codec byte (see
+/// `compress`); `decodePayload` in `src/lib/payload.ts` undoes both steps, and decodes
+/// whichever `encoding` was asked for.
+#[tauri::command]
+pub async fn execute_binary(
+    state: State<'_, AppState>,
+    id: String,
+    sql: String,
+    options: Option<ExecuteOptions>,
+    encoding: Option<PayloadEncoding>,
+    compression: Option<CompressionOptions>,
+) -> Result<Response, String> {
+    let mut options = options.unwrap_or_default();
+    let encoding = encoding.unwrap_or_default();
+    if encoding == PayloadEncoding::Arrow {
+        options.format = ResultFormat::Table;
+    }
+    let output = execute_on(&state, &id, &sql, &options).await?;
+    let mut bytes = encode(output, encoding)?;
+    if let Some(compression) = compression {
+        bytes = tauri::async_runtime::spawn_blocking(move || compress(bytes, compression))
+            .await
+            .map_err(|e| e.to_string())??;
+    }
+    Ok(Response::new(bytes))
+}
@@ -0,0 +1,255 @@
+//! Certificate pinning. sqlx doesn't expose the peer certificate, so the server is
+//! probed with a separate TLS handshake before the pool is opened and the leaf
+//! certificate is compared with the pinned fingerprint.
+
+use super::{detect_db_kind, DbKind};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, ClientConfig, ServerName};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::mysql::MySqlConnectOptions;
+use sqlx::postgres::PgConnectOptions;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinKind {
+    /// SHA-256 of the whole certificate; changes whenever the certificate is renewed.
+    #[default]
+    Certificate,
+    /// SHA-256 of the SubjectPublicKeyInfo; survives renewals that keep the key.
+    PublicKey,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificatePin {
+    #[serde(default)]
+    pub kind: PinKind,
+    /// Hex (colons optional) or base64 SHA-256 digest.
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServerFingerprints {
+    pub certificate: String,
+    pub public_key: String,
+}
+
+/// Fetch the fingerprints of the certificate a server presents, so the UI can offer to pin it.
+/// `kind` overrides detection from the connection string, as in `connect`.
+#[tauri::command]
+pub async fn get_server_fingerprint(
+    conn_string: String,
+    kind: Option<DbKind>,
+) -> Result<ServerFingerprints, String> {
+    let kind = match kind {
+        Some(kind) => kind,
+        None => detect_db_kind(&conn_string)?,
+    };
+    let (kind, host, port) = match kind {
+        DbKind::Postgres => {
+            let options = PgConnectOptions::from_str(&conn_string).map_err(|e| e.to_string())?;
+            (
+                DbKind::Postgres,
+                options.get_host().to_string(),
+                options.get_port(),
+            )
+        }
+        DbKind::MySql => {
+            let options = MySqlConnectOptions::from_str(&conn_string).map_err(|e| e.to_string())?;
+            (
+                DbKind::MySql,
+                options.get_host().to_string(),
+                options.get_port(),
+            )
+        }
+        _ => return Err("Certificate pinning applies to network databases only".to_string()),
+    };
+    let der = probe(kind, &host, port).await?;
+    fingerprints(&der)
+}
+
+/// Fail unless the server at `host:port` presents a certificate matching `pin`.
+pub async fn verify(
+    kind: DbKind,
+    host: &str,
+    port: u16,
+    pin: &CertificatePin,
+) -> Result<(), String> {
+    let expected = decode_digest(&pin.sha256)?;
+    let der = probe(kind, host, port).await?;
+    let actual = match pin.kind {
+        PinKind::Certificate => Sha256::digest(&der).to_vec(),
+        PinKind::PublicKey => Sha256::digest(public_key_info(&der)?).to_vec(),
+    };
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!(
+            "Certificate pin mismatch: the server presented {}, expected {}",
+            hex(&actual),
+            hex(&expected)
+        ))
+    }
+}
+
+fn fingerprints(der: &[u8]) -> Result<ServerFingerprints, String> {
+    Ok(ServerFingerprints {
+        certificate: hex(&Sha256::digest(der)),
+        public_key: hex(&Sha256::digest(public_key_info(der)?)),
+    })
+}
+
+fn public_key_info(der: &[u8]) -> Result<Vec<u8>, String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der)
+        .map_err(|e| format!("Could not parse server certificate: {}", e))?;
+    Ok(cert.public_key().raw.to_vec())
+}
+
+fn decode_digest(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim();
+    let text = text.strip_prefix("sha256/").unwrap_or(text);
+    let compact: String = text.chars().filter(|c| *c != ':').collect();
+    let digest = if compact.len() == 64 && compact.chars().all(|c| c.is_ascii_hexdigit()) {
+        (0..64)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&compact[i..i + 2], 16).unwrap())
+            .collect()
+    } else {
+        BASE64
+            .decode(text)
+            .map_err(|_| "Pinned fingerprint is neither hex nor base64".to_string())?
+    };
+    if digest.len() != 32 {
+        return Err("Pinned fingerprint is not a SHA-256 digest".to_string());
+    }
+    Ok(digest)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Accepts any certificate: the probe only records what the server presents, and the
+/// real connection still verifies according to its TLS mode.
+struct RecordOnly;
+
+impl ServerCertVerifier for RecordOnly {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Negotiate TLS the way the database protocol does and return the leaf certificate.
+async fn probe(kind: DbKind, host: &str, port: u16) -> Result<Vec<u8>, String> {
+    tokio::time::timeout(PROBE_TIMEOUT, async {
+        let mut stream = TcpStream::connect((host, port))
+            .await
+            .map_err(|e| e.to_string())?;
+        match kind {
+            DbKind::Postgres => request_pg_tls(&mut stream).await?,
+            DbKind::MySql => request_mysql_tls(&mut stream).await?,
+            // HTTPS: the handshake starts straight away.
+            DbKind::ClickHouse | DbKind::Surreal => {}
+            _ => return Err("Certificate pinning applies to network databases only".to_string()),
+        }
+
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(RecordOnly))
+            .with_no_client_auth();
+        let server_name = ServerName::try_from(host).map_err(|e| e.to_string())?;
+        let tls = TlsConnector::from(Arc::new(config))
+            .connect(server_name, stream)
+            .await
+            .map_err(|e| format!("TLS handshake failed: {}", e))?;
+        let (_, session) = tls.get_ref();
+        session
+            .peer_certificates()
+            .and_then(|chain| chain.first())
+            .map(|cert| cert.0.clone())
+            .ok_or_else(|| "The server did not present a certificate".to_string())
+    })
+    .await
+    .map_err(|_| "Timed out fetching the server certificate".to_string())?
+}
+
+/// Postgres SSLRequest: the server answers `S` if it will accept a TLS handshake.
+async fn request_pg_tls(stream: &mut TcpStream) -> Result<(), String> {
+    stream
+        .write_all(&[0, 0, 0, 8, 0x04, 0xD2, 0x16, 0x2F])
+        .await
+        .map_err(|e| e.to_string())?;
+    match stream.read_u8().await.map_err(|e| e.to_string())? {
+        b'S' => Ok(()),
+        _ => Err("TLS negotiation failed: the server does not accept TLS connections".to_string()),
+    }
+}
+
+/// MySQL: read the server greeting, then send an SSLRequest packet.
+async fn request_mysql_tls(stream: &mut TcpStream) -> Result<(), String> {
+    const CLIENT_PROTOCOL_41: u32 = 0x200;
+    const CLIENT_SSL: u32 = 0x800;
+    const CLIENT_SECURE_CONNECTION: u32 = 0x8000;
+
+    let mut header = [0u8; 4];
+    stream
+        .read_exact(&mut header)
+        .await
+        .map_err(|e| e.to_string())?;
+    let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+    let mut greeting = vec![0u8; len];
+    stream
+        .read_exact(&mut greeting)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // protocol version, NUL-terminated server version, connection id, 8 bytes of
+    // auth data and a filler byte precede the lower capability flags.
+    let version_end = greeting
+        .iter()
+        .skip(1)
+        .position(|&b| b == 0)
+        .map(|p| p + 2)
+        .ok_or("Malformed MySQL greeting")?;
+    let flags_at = version_end + 4 + 8 + 1;
+    let capabilities = greeting
+        .get(flags_at..flags_at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]) as u32)
+        .ok_or("Malformed MySQL greeting")?;
+    if capabilities & CLIENT_SSL == 0 {
+        return Err(
+            "TLS negotiation failed: the server does not accept TLS connections".to_string(),
+        );
+    }
+
+    let mut packet = vec![32, 0, 0, 1];
+    packet.extend_from_slice(
+        &(CLIENT_PROTOCOL_41 | CLIENT_SSL | CLIENT_SECURE_CONNECTION).to_le_bytes(),
+    );
+    packet.extend_from_slice(&(16u32 * 1024 * 1024).to_le_bytes());
+    packet.push(0x21);
+    packet.extend_from_slice(&[0u8; 23]);
+    stream.write_all(&packet).await.map_err(|e| e.to_string())
+}
@@ -0,0 +1,308 @@
+//! Per-connection capability levels and statement policies, e.g. "only SELECT" for a
+//! shared production connection, and the destructive-statement guard. Every statement
+//! in a script is classified before anything runs.
+
+use super::registry::Environment;
+use super::sql::{self, DestructiveReason, StatementKind};
+use super::{generate_id, AppState, ConnectionOptions, ExecuteOptions};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::ipc::CommandScope;
+use tauri::State;
+
+/// How long a token from `request_confirmation` stays valid.
+const CONFIRMATION_TTL: Duration = Duration::from_secs(60);
+
+/// How dangerous a connection is allowed to be. Each level includes the ones below it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    ReadOnly,
+    ReadWrite,
+    Ddl,
+    /// Everything, including GRANT/REVOKE and unrecognised statements.
+    #[default]
+    Admin,
+}
+
+impl Capability {
+    /// The lowest level that may run a statement of `kind`.
+    pub fn required_for(kind: StatementKind) -> Capability {
+        use StatementKind::*;
+        match kind {
+            Select | Explain | Show | Set | Transaction => Capability::ReadOnly,
+            Insert | Update | Delete | Merge | Call => Capability::ReadWrite,
+            Create | Alter | Drop | Truncate | Rename | Comment => Capability::Ddl,
+            Grant | Revoke | Other => Capability::Admin,
+        }
+    }
+}
+
+/// Scope of the `connect-*` permissions: the highest capability a connection opened
+/// by a webview holding the permission may have.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ConnectScope {
+    pub capability: Capability,
+}
+
+/// Lower `options.capability` to the highest level the caller's `connect-*`
+/// permissions grant. A caller granted `allow-connect` alone gets what it asks for.
+pub fn limit_capability(options: &mut ConnectionOptions, scope: &CommandScope<ConnectScope>) {
+    let Some(granted) = scope.allows().iter().map(|scope| scope.capability).max() else {
+        return;
+    };
+    options.capability = Some(options.capability.unwrap_or_default().min(granted));
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DestructiveStatement {
+    /// Position of the statement within the script.
+    pub index: usize,
+    pub reason: DestructiveReason,
+    pub statement: String,
+}
+
+/// Statements in `script` that must be confirmed before running: destructive ones,
+/// and on a `production` connection every one that writes.
+pub fn destructive_statements(script: &str, production: bool) -> Vec<DestructiveStatement> {
+    sql::split_statements(script)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, stmt)| {
+            let required = Capability::required_for(sql::classify(stmt));
+            let reason =
+                sql::destructive_reason(stmt).or_else(|| production_write(production, required))?;
+            Some(DestructiveStatement {
+                index,
+                reason,
+                statement: stmt.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// `ProductionWrite` for a statement needing `required` on a production connection.
+pub fn production_write(production: bool, required: Capability) -> Option<DestructiveReason> {
+    (production && required > Capability::ReadOnly).then_some(DestructiveReason::ProductionWrite)
+}
+
+struct Confirmation {
+    id: String,
+    script: [u8; 32],
+    expires: Instant,
+}
+
+/// One-time tokens from `request_confirmation`, each good for one script on one
+/// connection.
+#[derive(Default)]
+pub struct Confirmations(Mutex<HashMap<String, Confirmation>>);
+
+impl Confirmations {
+    fn mint(&self, id: &str, script: &str) -> String {
+        let token = generate_id("confirm");
+        let mut tokens = self.0.lock().unwrap();
+        let now = Instant::now();
+        tokens.retain(|_, confirmation| confirmation.expires > now);
+        tokens.insert(
+            token.clone(),
+            Confirmation {
+                id: id.to_string(),
+                script: Sha256::digest(script.as_bytes()).into(),
+                expires: now + CONFIRMATION_TTL,
+            },
+        );
+        token
+    }
+
+    /// Use up `token`, returning whether it was minted for `script` on `id` and is
+    /// still valid.
+    fn redeem(&self, token: &str, id: &str, script: &str) -> bool {
+        let Some(confirmation) = self.0.lock().unwrap().remove(token) else {
+            return false;
+        };
+        confirmation.id == id
+            && confirmation.expires > Instant::now()
+            && confirmation.script == <[u8; 32]>::from(Sha256::digest(script.as_bytes()))
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatementPolicy {
+    /// When set, only these statement kinds may run.
+    pub allow: Option<Vec<StatementKind>>,
+    /// Statement kinds that are always rejected, even if listed in `allow`.
+    pub deny: Vec<StatementKind>,
+}
+
+impl StatementPolicy {
+    pub fn is_unrestricted(&self) -> bool {
+        self.allow.is_none() && self.deny.is_empty()
+    }
+
+    pub fn permits(&self, kind: StatementKind) -> bool {
+        !self.deny.contains(&kind)
+            && self
+                .allow
+                .as_ref()
+                .is_none_or(|allow| allow.contains(&kind))
+    }
+
+    /// Reject the whole script if any statement is not permitted.
+    pub fn check(&self, script: &str) -> Result<(), String> {
+        for stmt in sql::split_statements(script) {
+            let kind = sql::classify(stmt);
+            if !self.permits(kind) {
+                return Err(format!(
+                    "{:?} statements are not permitted on this connection",
+                    kind
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AppState {
+    pub fn is_production(&self, id: &str) -> bool {
+        self.connection_meta
+            .lock()
+            .unwrap()
+            .get(id)
+            .is_some_and(|meta| meta.environment == Some(Environment::Production))
+    }
+
+    /// Whether the caller confirmed running `script` with a token from
+    /// `request_confirmation`, which this uses up. The check is made here rather than
+    /// trusted to the frontend, so a caller can't skip the confirmation step.
+    pub fn confirmed(&self, id: &str, script: &str, options: &ExecuteOptions) -> bool {
+        self.redeem_confirmation(id, script, options.confirmation_token.as_deref())
+    }
+
+    /// `confirmed`, for commands that take the token on its own.
+    pub fn redeem_confirmation(&self, id: &str, script: &str, token: Option<&str>) -> bool {
+        token.is_some_and(|token| self.confirmations.redeem(token, id, script))
+    }
+
+    pub fn capability(&self, id: &str) -> Capability {
+        self.capabilities
+            .lock()
+            .unwrap()
+            .get(id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// For commands that don't run caller-supplied SQL through `check_policy`:
+    /// maintenance, killing sessions, and the Redis, MongoDB and SurrealDB backends.
+    pub fn require_capability(&self, id: &str, required: Capability) -> Result<(), String> {
+        let granted = self.capability(id);
+        if granted >= required {
+            Ok(())
+        } else {
+            Err(format!(
+                "This connection is {:?}; the operation needs {:?}",
+                granted, required
+            ))
+        }
+    }
+
+    pub fn set_policy(&self, id: &str, policy: StatementPolicy) {
+        let mut policies = self.policies.lock().unwrap();
+        if policy.is_unrestricted() {
+            policies.remove(id);
+        } else {
+            policies.insert(id.to_string(), policy);
+        }
+    }
+
+    /// Fail if connection `id` takes one statement at a time and a script has `count`.
+    pub fn check_statement_count(&self, id: &str, count: usize) -> Result<(), String> {
+        if count > 1 && self.single_statement.lock().unwrap().contains(id) {
+            return Err(format!(
+                "This connection runs one statement at a time; the script has {}",
+                count
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check `script` against the capability level and policy of connection `id`, and
+    /// its statement count if the connection takes one statement at a time.
+    pub fn check_policy(&self, id: &str, script: &str) -> Result<(), String> {
+        let granted = self.capability(id);
+        let read_only = self.read_only.lock().unwrap().contains(id);
+        let statements = sql::split_statements(script);
+        self.check_statement_count(id, statements.len())?;
+        for stmt in statements {
+            if read_only {
+                check_read_only(stmt)?;
+            }
+            let required = Capability::required_for(sql::classify(stmt));
+            if granted < required {
+                return Err(format!(
+                    "This connection is {:?}; the statement needs {:?}",
+                    granted, required
+                ));
+            }
+        }
+        match self.policies.lock().unwrap().get(id) {
+            Some(policy) => policy.check(script),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Fail if `stmt` may not run on a read-only connection: it writes, or it switches the
+/// session back to read-write. SET and transaction statements are otherwise allowed.
+pub fn check_read_only(stmt: &str) -> Result<(), String> {
+    let kind = sql::classify(stmt);
+    let required = Capability::required_for(kind);
+    if required > Capability::ReadOnly {
+        return Err(format!(
+            "This connection is read-only; the statement needs {:?}",
+            required
+        ));
+    }
+    if matches!(kind, StatementKind::Set | StatementKind::Transaction) {
+        let lower = stmt.to_lowercase();
+        if lower.contains("read_only") || lower.contains("read write") {
+            return Err("This connection is read-only".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfirmationRequest {
+    /// Pass as `confirmation_token` to run exactly this script on this connection once,
+    /// within a minute.
+    pub token: String,
+    /// The statements the token confirms, to show in the confirmation dialog; empty
+    /// when the script needs no confirmation.
+    pub statements: Vec<DestructiveStatement>,
+}
+
+/// Mint a token that confirms running exactly `sql` on `id`, once the user has agreed
+/// to the statements listed with it.
+#[tauri::command]
+pub fn request_confirmation(
+    state: State<'_, AppState>,
+    id: String,
+    sql: String,
+) -> Result<ConfirmationRequest, String> {
+    state.connection(&id)?;
+    Ok(ConfirmationRequest {
+        token: state.confirmations.mint(&id, &sql),
+        statements: destructive_statements(&sql, state.is_production(&id)),
+    })
+}
+
+#[tauri::command]
+pub fn get_capability(state: State<'_, AppState>, id: String) -> Result<Capability, String> {
+    state.connection(&id)?;
+    Ok(state.capability(&id))
+}
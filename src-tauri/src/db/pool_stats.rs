@@ -0,0 +1,194 @@
+use super::AppState;
+use serde::Serialize;
+use sqlx::pool::PoolConnection;
+use sqlx::{Database, Pool};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+/// Acquire waits kept for percentile calculations.
+const WINDOW: usize = 200;
+/// Minimum samples before the window is considered representative.
+const MIN_SAMPLES: usize = 20;
+/// Waits above this count as slow; a p90 above it triggers a warning.
+pub const DEFAULT_ACQUIRE_WARN_THRESHOLD: Duration = Duration::from_secs(1);
+/// At most one warning per connection in this interval.
+const WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Wait-time bookkeeping for one connection's pool. Only acquires made through
+/// `AppState::acquire` are counted.
+pub struct AcquireStats {
+    threshold: Duration,
+    waiting: AtomicUsize,
+    total: AtomicU64,
+    slow: AtomicU64,
+    recent: Mutex<VecDeque<Duration>>,
+    last_warning: Mutex<Option<Instant>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AcquireSnapshot {
+    /// Callers currently waiting for a pooled connection.
+    pub queue_depth: usize,
+    pub total_acquires: u64,
+    pub slow_acquires: u64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    pub max_connections: u32,
+    pub threshold_ms: u64,
+}
+
+/// Emitted as `db://pool-pressure`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolPressureWarning {
+    pub connection_id: String,
+    pub message: String,
+    pub stats: AcquireSnapshot,
+}
+
+impl AcquireStats {
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            waiting: AtomicUsize::new(0),
+            total: AtomicU64::new(0),
+            slow: AtomicU64::new(0),
+            recent: Mutex::new(VecDeque::with_capacity(WINDOW)),
+            last_warning: Mutex::new(None),
+        }
+    }
+
+    async fn acquire<DB: Database>(
+        &self,
+        pool: &Pool<DB>,
+    ) -> Result<PoolConnection<DB>, sqlx::Error> {
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        let started = Instant::now();
+        let result = pool.acquire().await;
+        self.waiting.fetch_sub(1, Ordering::Relaxed);
+        self.record(started.elapsed());
+        result
+    }
+
+    fn record(&self, wait: Duration) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        if wait > self.threshold {
+            self.slow.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == WINDOW {
+            recent.pop_front();
+        }
+        recent.push_back(wait);
+    }
+
+    /// Callers waiting for a pooled connection now.
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self, max_connections: u32) -> AcquireSnapshot {
+        let mut waits: Vec<Duration> = self.recent.lock().unwrap().iter().copied().collect();
+        waits.sort();
+        let percentile = |p: f64| -> f64 {
+            if waits.is_empty() {
+                return 0.0;
+            }
+            let idx = ((waits.len() - 1) as f64 * p).round() as usize;
+            waits[idx].as_secs_f64() * 1000.0
+        };
+
+        AcquireSnapshot {
+            queue_depth: self.waiting(),
+            total_acquires: self.total(),
+            slow_acquires: self.slow.load(Ordering::Relaxed),
+            p50_ms: percentile(0.5),
+            p90_ms: percentile(0.9),
+            p99_ms: percentile(0.99),
+            max_ms: percentile(1.0),
+            max_connections,
+            threshold_ms: self.threshold.as_millis() as u64,
+        }
+    }
+
+    /// A warning if the recent p90 wait exceeds the threshold and none was sent lately.
+    fn take_warning(&self, id: &str, max_connections: u32) -> Option<PoolPressureWarning> {
+        if self.recent.lock().unwrap().len() < MIN_SAMPLES {
+            return None;
+        }
+        let stats = self.snapshot(max_connections);
+        if stats.p90_ms <= self.threshold.as_millis() as f64 {
+            return None;
+        }
+
+        let mut last = self.last_warning.lock().unwrap();
+        if last.is_some_and(|at| at.elapsed() < WARN_INTERVAL) {
+            return None;
+        }
+        *last = Some(Instant::now());
+
+        Some(PoolPressureWarning {
+            connection_id: id.to_string(),
+            message: format!(
+                "Queries are waiting {:.0} ms (p90) for one of {} pooled connections; \
+                 consider raising max_connections",
+                stats.p90_ms, max_connections
+            ),
+            stats,
+        })
+    }
+}
+
+impl AppState {
+    pub fn acquire_stats(&self, id: &str) -> Arc<AcquireStats> {
+        self.acquire_stats
+            .lock()
+            .unwrap()
+            .entry(id.to_string())
+            .or_insert_with(|| Arc::new(AcquireStats::new(DEFAULT_ACQUIRE_WARN_THRESHOLD)))
+            .clone()
+    }
+
+    /// Acquire a pooled connection for `id`, recording how long the wait took and
+    /// warning the frontend when the pool is regularly exhausted.
+    pub async fn acquire<DB: Database>(
+        &self,
+        id: &str,
+        pool: &Pool<DB>,
+    ) -> Result<PoolConnection<DB>, String> {
+        let stats = self.acquire_stats(id);
+        let max_connections = pool.options().get_max_connections();
+        let result = stats.acquire(pool).await;
+
+        if let Some(warning) = stats.take_warning(id, max_connections) {
+            self.emit("db://pool-pressure", warning);
+        }
+
+        result.map_err(|e| match e {
+            sqlx::Error::PoolTimedOut => format!(
+                "Timed out waiting for a pooled connection; all {} connections are busy",
+                max_connections
+            ),
+            e => e.to_string(),
+        })
+    }
+}
+
+#[tauri::command]
+pub fn get_acquire_stats(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<AcquireSnapshot, String> {
+    let conn = state.connection(&id)?;
+    Ok(state
+        .acquire_stats(&id)
+        .snapshot(state.max_connections(&id, &conn)))
+}
@@ -0,0 +1,210 @@
+//! Pool sizing and connection recycling, chosen per connection at connect time.
+//! sqlx and the Oracle and MongoDB drivers recycle their own connections; the
+//! blocking drivers without a pool (Firebird, ODBC) keep theirs in an `IdlePool`,
+//! which a background reaper trims.
+
+use super::{AppState, Connection, ConnectionOptions};
+use sqlx::pool::PoolOptions;
+use sqlx::Database;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+/// Connections a pool opens at most when `connect` doesn't say.
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 5;
+/// How long a query waits for a free pooled connection when `connect` doesn't say.
+const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(5);
+/// `IdlePool` recycling when `connect` doesn't say, the same as sqlx's defaults.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_MAX_LIFETIME: Duration = Duration::from_secs(30 * 60);
+/// How often the reaper looks for expired idle connections.
+const REAP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The pool options from `ConnectionOptions`, with defaults filled in.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolSettings {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    /// Idle connections are closed after this long; the driver's default when unset.
+    pub idle_timeout: Option<Duration>,
+    /// Connections are closed once this old, when next returned to the pool; the
+    /// driver's default when unset.
+    pub max_lifetime: Option<Duration>,
+}
+
+impl PoolSettings {
+    pub fn from_options(options: &ConnectionOptions) -> Result<Self, String> {
+        let max_connections = options.max_connections.unwrap_or(DEFAULT_MAX_CONNECTIONS);
+        if max_connections == 0 {
+            return Err("max_connections must be at least 1".to_string());
+        }
+        Ok(Self {
+            max_connections,
+            min_connections: options.min_connections.unwrap_or(0).min(max_connections),
+            acquire_timeout: options
+                .acquire_timeout_ms
+                .map_or(DEFAULT_ACQUIRE_TIMEOUT, Duration::from_millis),
+            idle_timeout: options.idle_timeout_ms.map(Duration::from_millis),
+            max_lifetime: options.max_lifetime_ms.map(Duration::from_millis),
+        })
+    }
+
+    /// Apply the settings to an sqlx pool.
+    pub fn apply<DB: Database>(&self, options: PoolOptions<DB>) -> PoolOptions<DB> {
+        let mut options = options
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout);
+        if let Some(idle) = self.idle_timeout {
+            options = options.idle_timeout(idle);
+        }
+        if let Some(lifetime) = self.max_lifetime {
+            options = options.max_lifetime(lifetime);
+        }
+        options
+    }
+}
+
+impl AppState {
+    /// The most connections `id`'s pool opens at once.
+    pub fn max_connections(&self, id: &str, conn: &Connection) -> u32 {
+        match conn {
+            Connection::Postgres(pool) => pool.options().get_max_connections(),
+            Connection::MySql(pool) => pool.options().get_max_connections(),
+            Connection::Sqlite(pool) => pool.options().get_max_connections(),
+            _ => self
+                .pool_settings
+                .lock()
+                .unwrap()
+                .get(id)
+                .map_or(DEFAULT_MAX_CONNECTIONS, |settings| settings.max_connections),
+        }
+    }
+}
+
+/// A connection checked out of an `IdlePool`, remembering when it was opened.
+pub struct Pooled<C> {
+    conn: C,
+    opened: Instant,
+}
+
+impl<C> Pooled<C> {
+    pub fn new(conn: C) -> Self {
+        Self {
+            conn,
+            opened: Instant::now(),
+        }
+    }
+}
+
+impl<C> Deref for Pooled<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.conn
+    }
+}
+
+impl<C> DerefMut for Pooled<C> {
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.conn
+    }
+}
+
+/// Idle connections of a driver without a pool of its own. Callers open a connection
+/// when `take` has none; at most `max_connections` are kept once returned, each until
+/// it has been idle for the idle timeout or open for the max lifetime.
+pub struct IdlePool<C> {
+    idle: Mutex<Vec<(Pooled<C>, Instant)>>,
+    max_idle: usize,
+    idle_timeout: Duration,
+    max_lifetime: Duration,
+}
+
+impl<C> IdlePool<C> {
+    pub fn new(settings: &PoolSettings) -> Self {
+        Self {
+            idle: Mutex::new(Vec::new()),
+            max_idle: settings.max_connections as usize,
+            idle_timeout: settings.idle_timeout.unwrap_or(DEFAULT_IDLE_TIMEOUT),
+            max_lifetime: settings.max_lifetime.unwrap_or(DEFAULT_MAX_LIFETIME),
+        }
+    }
+
+    pub fn max_idle(&self) -> usize {
+        self.max_idle
+    }
+
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    fn expired(&self, conn: &Pooled<C>, idle_since: Instant) -> bool {
+        idle_since.elapsed() >= self.idle_timeout || conn.opened.elapsed() >= self.max_lifetime
+    }
+
+    /// The most recently returned idle connection. Expired ones are skipped and
+    /// dropped on the caller's (blocking) thread.
+    pub fn take(&self) -> Option<Pooled<C>> {
+        loop {
+            let (conn, idle_since) = self.idle.lock().unwrap().pop()?;
+            if !self.expired(&conn, idle_since) {
+                return Some(conn);
+            }
+        }
+    }
+
+    /// Keep `conn` for reuse. Only call this after a successful call on it.
+    pub fn put(&self, conn: Pooled<C>) {
+        if conn.opened.elapsed() >= self.max_lifetime {
+            return;
+        }
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < self.max_idle {
+            idle.push((conn, Instant::now()));
+        }
+    }
+
+    /// Remove expired idle connections, returning them to be closed.
+    pub fn take_expired(&self) -> Vec<Pooled<C>> {
+        let mut idle = self.idle.lock().unwrap();
+        let (expired, kept) = std::mem::take(&mut *idle)
+            .into_iter()
+            .partition(|(conn, idle_since)| self.expired(conn, *idle_since));
+        *idle = kept;
+        expired.into_iter().map(|(conn, _)| conn).collect()
+    }
+}
+
+/// Close expired idle connections every `REAP_INTERVAL`, for the lifetime of the app.
+pub fn spawn_reaper(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(REAP_INTERVAL).await;
+            let state = app.state::<AppState>();
+            let connections: Vec<Connection> = state
+                .connections
+                .lock()
+                .unwrap()
+                .values()
+                .cloned()
+                .collect();
+            for conn in connections {
+                conn.reap().await;
+            }
+        }
+    });
+}
+
+impl Connection {
+    /// Close this connection's expired idle connections, if it keeps an `IdlePool`.
+    async fn reap(&self) {
+        match self {
+            Connection::Firebird(pool) => super::firebird::reap(pool).await,
+            Connection::Odbc(pool) => super::odbc::reap(pool).await,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! The connection dialog's "Test" button: open a connection, make one round trip and
+//! close it again, leaving nothing registered.
+
+use super::convert::CollectLimits;
+use super::registry::server_version;
+use super::timeout::QueryControl;
+use super::{
+    connection_target, generate_id, open_pool, run_query, AppState, Connection, ConnectionOptions,
+    DbConfig, DbKind,
+};
+use serde::Serialize;
+use std::time::Instant;
+use tauri::State;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionTest {
+    pub kind: DbKind,
+    /// Opening the connection, handshakes and authentication included.
+    pub connect_ms: u64,
+    /// One round trip on the open connection.
+    pub latency_ms: u64,
+    pub server_version: Option<String>,
+}
+
+/// A trivial request: `SELECT 1` or its dialect's equivalent, or a ping.
+pub async fn round_trip(state: &AppState, id: &str, conn: &Connection) -> Result<(), String> {
+    let sql = match conn {
+        Connection::Redis(redis) => {
+            return ::redis::cmd("PING")
+                .query_async::<String>(&mut redis.clone())
+                .await
+                .map(drop)
+                .map_err(|e| e.to_string());
+        }
+        Connection::Mongo(_) | Connection::Surreal(_) => return conn.warm(1).await.map(drop),
+        Connection::Oracle(_) => "SELECT 1 FROM dual",
+        Connection::Firebird(_) => "SELECT 1 FROM rdb$database",
+        Connection::Cassandra(_) => "SELECT now() FROM system.local",
+        _ => "SELECT 1",
+    };
+    run_query(
+        state,
+        id,
+        conn,
+        sql,
+        &[],
+        CollectLimits::default(),
+        QueryControl::default(),
+    )
+    .await
+    .map(drop)
+}
+
+/// Connect as `connect` would, time a round trip and read the server version, then
+/// close the connection.
+#[tauri::command]
+pub async fn test_connection(
+    state: State<'_, AppState>,
+    conn_string: Option<String>,
+    config: Option<DbConfig>,
+    options: Option<ConnectionOptions>,
+) -> Result<ConnectionTest, String> {
+    let mut options = options.unwrap_or_default();
+    let conn_string = connection_target(conn_string, config, &mut options)?;
+
+    let started = Instant::now();
+    let (conn, _, tunnel) = open_pool(&state, &conn_string, &options).await?;
+    let connect_ms = started.elapsed().as_millis() as u64;
+
+    // Never registered; the id only keys the bookkeeping of the queries below.
+    let id = generate_id("test");
+    let started = Instant::now();
+    let result = round_trip(&state, &id, &conn).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let version = match result {
+        Ok(()) => server_version(&state, &id, &conn).await,
+        Err(_) => None,
+    };
+    state.acquire_stats.lock().unwrap().remove(&id);
+    let kind = conn.kind();
+    conn.close().await;
+    drop(tunnel);
+
+    result.map(|()| ConnectionTest {
+        kind,
+        connect_ms,
+        latency_ms,
+        server_version: version,
+    })
+}
@@ -0,0 +1,321 @@
+//! Calling stored routines with OUT and INOUT parameters. The routine's parameters are
+//! read from the catalog so the caller only passes the inputs: on Postgres OUT slots
+//! are filled with typed NULLs and a function's single output row is read back, and on
+//! MySQL OUT and INOUT parameters go through user variables that are selected once the
+//! procedure returns.
+
+use super::convert::{JsonRow, QueryOutput};
+use super::params::bind_value;
+use super::policy::{production_write, Capability};
+use super::sql::{quote_ident, StatementKind};
+use super::{redact, AppState, Connection, DbKind};
+use futures_util::{Stream, TryStreamExt};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::mysql::MySqlPool;
+use sqlx::postgres::PgPool;
+use sqlx::{Either, Row};
+use tauri::State;
+
+#[derive(Debug, Serialize)]
+pub struct ProcedureResult {
+    /// The result sets the routine returned, in order.
+    pub result_sets: Vec<QueryOutput>,
+    /// The values of the OUT and INOUT parameters, by name.
+    pub out_params: Map<String, Value>,
+}
+
+/// A routine parameter as the catalog describes it.
+struct RoutineParam {
+    /// `IN`, `OUT`, `INOUT`, or on Postgres also `VARIADIC` and `TABLE`.
+    mode: String,
+    /// The parameter's name, on MySQL only; Postgres names the columns of its output
+    /// row itself.
+    name: String,
+    /// The declared type, on Postgres only.
+    data_type: String,
+}
+
+impl RoutineParam {
+    fn takes_input(&self) -> bool {
+        matches!(self.mode.as_str(), "IN" | "INOUT" | "VARIADIC")
+    }
+
+    fn is_output(&self) -> bool {
+        matches!(self.mode.as_str(), "OUT" | "INOUT")
+    }
+}
+
+/// Call routine `name`, optionally schema-qualified, with `args` for its IN and INOUT
+/// parameters in declaration order. Postgres procedures run through `CALL` and
+/// functions through `SELECT * FROM`; a function that returns a single row through OUT
+/// parameters reports it in `out_params`, any other function's rows are a result set.
+/// On Postgres an overloaded name is refused as ambiguous.
+///
+/// A routine may write whichever statement runs it, so on a connection tagged
+/// `production` every call needs a `confirmation_token` from `request_confirmation`
+/// for the script `CALL <name>`.
+#[tauri::command]
+pub async fn call_procedure(
+    state: State<'_, AppState>,
+    id: String,
+    name: String,
+    args: Vec<Value>,
+    confirmation_token: Option<String>,
+) -> Result<ProcedureResult, String> {
+    let conn = state.connection(&id)?;
+    let required = Capability::required_for(StatementKind::Call);
+    let script = format!("CALL {}", name);
+    if production_write(state.is_production(&id), required).is_some()
+        && !state.redeem_confirmation(&id, &script, confirmation_token.as_deref())
+    {
+        return Err(format!(
+            "This connection is tagged production; confirm `{}` with a token from \
+             request_confirmation",
+            script
+        ));
+    }
+    let called = match &conn {
+        Connection::Postgres(pool) => call_postgres(&state, &id, pool, &name, &args).await,
+        Connection::MySql(pool) => call_mysql(&state, &id, pool, &name, &args).await,
+        _ => return Err(conn.unsupported("Stored procedures")),
+    };
+    called.map_err(|e| redact::redact(&e))
+}
+
+fn check_arity(params: &[RoutineParam], args: &[Value]) -> Result<(), String> {
+    let inputs = params.iter().filter(|param| param.takes_input()).count();
+    if inputs != args.len() {
+        return Err(format!(
+            "The routine takes {} input arguments; {} were given",
+            inputs,
+            args.len()
+        ));
+    }
+    Ok(())
+}
+
+async fn call_postgres(
+    state: &AppState,
+    id: &str,
+    pool: &PgPool,
+    name: &str,
+    args: &[Value],
+) -> Result<ProcedureResult, String> {
+    // `regproc` resolves the name on the search path and fails on overloads.
+    let routine = sqlx::query(
+        "SELECT p.oid::regproc::text AS name, p.prokind::text AS kind, p.proretset, \
+         COALESCE(p.proargmodes::text[], '{}') AS arg_modes, \
+         ARRAY(SELECT format_type(t, NULL) \
+               FROM unnest(COALESCE(p.proallargtypes, p.proargtypes::oid[])) \
+               WITH ORDINALITY AS a(t, n) ORDER BY n) AS arg_types \
+         FROM pg_proc p WHERE p.oid = $1::text::regproc",
+    )
+    .bind(name)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let routine_name: String = routine.try_get("name").map_err(|e| e.to_string())?;
+    let kind: String = routine.try_get("kind").map_err(|e| e.to_string())?;
+    let returns_set: bool = routine.try_get("proretset").map_err(|e| e.to_string())?;
+    let modes: Vec<String> = routine.try_get("arg_modes").map_err(|e| e.to_string())?;
+    let types: Vec<String> = routine.try_get("arg_types").map_err(|e| e.to_string())?;
+    let params: Vec<RoutineParam> = types
+        .into_iter()
+        .enumerate()
+        .map(|(i, data_type)| RoutineParam {
+            // No modes are stored when every parameter is IN.
+            mode: match modes.get(i).map(String::as_str) {
+                None | Some("i") => "IN",
+                Some("o") => "OUT",
+                Some("b") => "INOUT",
+                Some("v") => "VARIADIC",
+                Some(_) => "TABLE",
+            }
+            .to_string(),
+            name: String::new(),
+            data_type,
+        })
+        .collect();
+    check_arity(&params, args)?;
+
+    let procedure = kind == "p";
+    let mut inputs = 0;
+    let mut placeholders = Vec::new();
+    for param in &params {
+        if param.takes_input() {
+            inputs += 1;
+            placeholders.push(format!("CAST(${} AS {})", inputs, param.data_type));
+        } else if procedure && param.mode == "OUT" {
+            placeholders.push(format!("CAST(NULL AS {})", param.data_type));
+        }
+    }
+    let statement = if procedure {
+        format!("CALL {}({})", routine_name, placeholders.join(", "))
+    } else {
+        format!(
+            "SELECT * FROM {}({})",
+            routine_name,
+            placeholders.join(", ")
+        )
+    };
+
+    state.check_policy(id, &statement)?;
+    let query = args
+        .iter()
+        .try_fold(sqlx::query(&statement).persistent(false), bind_value)?;
+    let rows = query.fetch_all(pool).await.map_err(|e| e.to_string());
+    state.audit(id, &statement, rows.as_ref().err().map(String::as_str));
+    let rows = rows?;
+
+    let single_output = procedure || (!returns_set && params.iter().any(RoutineParam::is_output));
+    if single_output {
+        return Ok(ProcedureResult {
+            result_sets: Vec::new(),
+            out_params: rows.first().map(JsonRow::to_json).unwrap_or_default(),
+        });
+    }
+    Ok(ProcedureResult {
+        result_sets: vec![QueryOutput::Table {
+            columns: rows.first().map(JsonRow::column_names).unwrap_or_default(),
+            rows: rows.iter().map(JsonRow::to_values).collect(),
+        }],
+        out_params: Map::new(),
+    })
+}
+
+async fn call_mysql(
+    state: &AppState,
+    id: &str,
+    pool: &MySqlPool,
+    name: &str,
+    args: &[Value],
+) -> Result<ProcedureResult, String> {
+    let (schema, routine) = match name.split_once('.') {
+        Some((schema, routine)) => (Some(schema), routine),
+        None => (None, name),
+    };
+    let params: Vec<RoutineParam> = sqlx::query(
+        "SELECT CAST(PARAMETER_MODE AS CHAR) AS mode, CAST(PARAMETER_NAME AS CHAR) AS name \
+         FROM information_schema.PARAMETERS \
+         WHERE SPECIFIC_SCHEMA = COALESCE(?, DATABASE()) AND SPECIFIC_NAME = ? \
+         AND ROUTINE_TYPE = 'PROCEDURE' AND ORDINAL_POSITION > 0 \
+         ORDER BY ORDINAL_POSITION",
+    )
+    .bind(schema)
+    .bind(routine)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .iter()
+    .map(|row| {
+        Ok(RoutineParam {
+            mode: row.try_get("mode")?,
+            name: row.try_get("name")?,
+            data_type: String::new(),
+        })
+    })
+    .collect::<Result<_, sqlx::Error>>()
+    .map_err(|e| e.to_string())?;
+    check_arity(&params, args)?;
+
+    let variable = |i: usize| format!("@_call_{}", i + 1);
+    let slots: Vec<String> = params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| match param.mode.as_str() {
+            "IN" => "?".to_string(),
+            _ => variable(i),
+        })
+        .collect();
+    let qualified = match schema {
+        Some(schema) => format!(
+            "{}.{}",
+            quote_ident(DbKind::MySql, schema),
+            quote_ident(DbKind::MySql, routine)
+        ),
+        None => quote_ident(DbKind::MySql, routine),
+    };
+    let statement = format!("CALL {}({})", qualified, slots.join(", "));
+    state.check_policy(id, &statement)?;
+
+    // User variables belong to the session, so every step runs on one connection.
+    let mut conn = state.acquire(id, pool).await?;
+    let mut inputs = args.iter();
+    let mut call_args = Vec::new();
+    for (i, param) in params.iter().enumerate() {
+        match param.mode.as_str() {
+            "IN" => call_args.extend(inputs.next()),
+            "INOUT" => {
+                let set = format!("SET {} = ?", variable(i));
+                let value = inputs.next().unwrap_or(&Value::Null);
+                bind_value(sqlx::query(&set), value)?
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            _ => {}
+        }
+    }
+    let query = call_args
+        .into_iter()
+        .try_fold(sqlx::query(&statement).persistent(false), bind_value)?;
+    let result_sets = collect_result_sets(sqlx::Executor::fetch_many(&mut *conn, query)).await;
+    state.audit(
+        id,
+        &statement,
+        result_sets.as_ref().err().map(String::as_str),
+    );
+    let result_sets = result_sets?;
+
+    let outputs: Vec<String> = params
+        .iter()
+        .enumerate()
+        .filter(|(_, param)| param.is_output())
+        .map(|(i, param)| {
+            format!(
+                "{} AS {}",
+                variable(i),
+                quote_ident(DbKind::MySql, &param.name)
+            )
+        })
+        .collect();
+    let out_params = if outputs.is_empty() {
+        Map::new()
+    } else {
+        let row = sqlx::query(&format!("SELECT {}", outputs.join(", ")))
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        row.to_json()
+    };
+    Ok(ProcedureResult {
+        result_sets,
+        out_params,
+    })
+}
+
+/// Group the rows of a multi-result stream into one table per result set. A result set
+/// without rows is left out, as its columns are only known from a row.
+async fn collect_result_sets<Q, R, S>(mut steps: S) -> Result<Vec<QueryOutput>, String>
+where
+    R: JsonRow,
+    S: Stream<Item = Result<Either<Q, R>, sqlx::Error>> + Unpin,
+{
+    let mut sets = Vec::new();
+    let mut current: Option<(Vec<String>, Vec<Vec<Value>>)> = None;
+    while let Some(step) = steps.try_next().await.map_err(|e| e.to_string())? {
+        match step {
+            Either::Left(_) => sets.extend(current.take()),
+            Either::Right(row) => current
+                .get_or_insert_with(|| (row.column_names(), Vec::new()))
+                .1
+                .push(row.to_values()),
+        }
+    }
+    sets.extend(current);
+    Ok(sets
+        .into_iter()
+        .map(|(columns, rows)| QueryOutput::Table { columns, rows })
+        .collect())
+}
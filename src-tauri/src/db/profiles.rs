@@ -0,0 +1,207 @@
+//! Saved connection profiles. A profile's password can live in the connection string,
+//! in the encrypted credential store, in the OS keychain, or nowhere: "ask" profiles
+//! return a challenge from `connect_profile` and the password the user types is kept in
+//! memory for the rest of the session only.
+
+use super::policy::{self, ConnectScope};
+use super::{generate_id, keychain, open_connection, AppState, ConnectionOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::ipc::CommandScope;
+use tauri::State;
+use zeroize::Zeroizing;
+
+const PROFILES_FILE: &str = "profiles.json";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PasswordSource {
+    /// Whatever the connection string (or a `vault:` reference) provides.
+    #[default]
+    ConnectionString,
+    /// The credential store, under the profile id.
+    Saved,
+    /// The OS keychain, under the profile id.
+    Keychain,
+    /// Prompt on every launch; never written anywhere.
+    Ask,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionProfile {
+    /// Assigned by `save_profile` when empty.
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub conn_string: String,
+    #[serde(default)]
+    pub password: PasswordSource,
+    #[serde(default)]
+    pub options: ConnectionOptions,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ConnectOutcome {
+    Connected {
+        connection_id: String,
+    },
+    /// Call `connect_profile` again with `password`.
+    NeedsPassword {
+        needs_password: bool,
+        profile_id: String,
+        profile_name: String,
+    },
+}
+
+/// Passwords entered for "ask" profiles, by profile id. Dropped on lock.
+#[derive(Default)]
+pub struct SessionSecrets(Mutex<HashMap<String, Zeroizing<String>>>);
+
+impl SessionSecrets {
+    fn get(&self, profile_id: &str) -> Option<Zeroizing<String>> {
+        self.0.lock().unwrap().get(profile_id).cloned()
+    }
+
+    fn insert(&self, profile_id: &str, secret: Zeroizing<String>) {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(profile_id.to_string(), secret);
+    }
+
+    pub fn forget(&self, profile_id: &str) {
+        self.0.lock().unwrap().remove(profile_id);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+#[tauri::command]
+pub fn list_profiles(state: State<'_, AppState>) -> Result<Vec<ConnectionProfile>, String> {
+    load(&state)
+}
+
+/// Create or replace a profile, returning its id.
+#[tauri::command]
+pub fn save_profile(
+    state: State<'_, AppState>,
+    mut profile: ConnectionProfile,
+) -> Result<String, String> {
+    if profile.id.is_empty() {
+        profile.id = generate_id("profile");
+    }
+    // A profile whose password is supplied at connect time must not carry one.
+    if profile.password != PasswordSource::ConnectionString {
+        profile.options.password = None;
+    }
+    let mut profiles = load(&state)?;
+    let id = profile.id.clone();
+    match profiles.iter_mut().find(|p| p.id == id) {
+        Some(existing) => *existing = profile,
+        None => profiles.push(profile),
+    }
+    save(&state, &profiles)?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn delete_profile(state: State<'_, AppState>, profile_id: String) -> Result<bool, String> {
+    let mut profiles = load(&state)?;
+    let before = profiles.len();
+    profiles.retain(|p| p.id != profile_id);
+    state.session_secrets.forget(&profile_id);
+    if profiles.len() == before {
+        return Ok(false);
+    }
+    save(&state, &profiles)?;
+    Ok(true)
+}
+
+/// Connect using a saved profile. "Ask" profiles without a password for this session
+/// return `NeedsPassword`; pass the password the user entered to retry.
+#[tauri::command]
+pub async fn connect_profile(
+    state: State<'_, AppState>,
+    scope: CommandScope<ConnectScope>,
+    profile_id: String,
+    password: Option<String>,
+) -> Result<ConnectOutcome, String> {
+    let profile = load(&state)?
+        .into_iter()
+        .find(|p| p.id == profile_id)
+        .ok_or("Profile not found")?;
+    let mut options = profile.options.clone();
+    policy::limit_capability(&mut options, &scope);
+
+    match profile.password {
+        PasswordSource::ConnectionString => {}
+        PasswordSource::Saved => {
+            options.password = Some(
+                state
+                    .saved_credential(&profile.id)?
+                    .ok_or("No saved password for this profile")?,
+            );
+        }
+        PasswordSource::Keychain => {
+            let secret = keychain::get(&profile.id)
+                .await?
+                .ok_or("No password in the keychain for this profile")?;
+            options.password = Some(secret.to_string());
+        }
+        PasswordSource::Ask => {
+            let secret = match password.map(Zeroizing::new) {
+                Some(secret) => secret,
+                None => match state.session_secrets.get(&profile.id) {
+                    Some(secret) => secret,
+                    None => {
+                        return Ok(ConnectOutcome::NeedsPassword {
+                            needs_password: true,
+                            profile_id: profile.id,
+                            profile_name: profile.name,
+                        })
+                    }
+                },
+            };
+            options.password = Some(secret.to_string());
+            let connected = open_connection(&state, &profile.conn_string, options).await;
+            // Only remember a password that worked.
+            if connected.is_ok() {
+                state.session_secrets.insert(&profile.id, secret);
+            }
+            return connected.map(|connection_id| ConnectOutcome::Connected { connection_id });
+        }
+    }
+
+    let connection_id = open_connection(&state, &profile.conn_string, options).await?;
+    Ok(ConnectOutcome::Connected { connection_id })
+}
+
+/// Drop the in-memory password of an "ask" profile so the next connect prompts again.
+#[tauri::command]
+pub fn forget_session_password(state: State<'_, AppState>, profile_id: String) {
+    state.session_secrets.forget(&profile_id);
+}
+
+fn load(state: &AppState) -> Result<Vec<ConnectionProfile>, String> {
+    match std::fs::read(state.data_path(PROFILES_FILE)?) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| e.to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn save(state: &AppState, profiles: &[ConnectionProfile]) -> Result<(), String> {
+    let path = state.data_path(PROFILES_FILE)?;
+    let bytes = serde_json::to_vec_pretty(profiles).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+}
@@ -0,0 +1,298 @@
+//! Routing connections through a SOCKS5 or HTTP CONNECT proxy, for networks where
+//! database ports are blocked. HTTP backends hand the proxy to reqwest; Postgres and
+//! MySQL connect through a `Tunnel`.
+
+use super::secrets;
+use super::tunnel::Tunnel;
+use super::AppState;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::{MySqlConnectOptions, MySqlSslMode};
+use sqlx::postgres::{PgConnectOptions, PgSslMode};
+use std::net::IpAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use zeroize::Zeroizing;
+
+/// Longest HTTP CONNECT response head read before giving up.
+const MAX_RESPONSE_HEAD: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyOptions {
+    /// `socks5://host:port` (1080 by default) or `http://host:port` (8080 by default).
+    /// Host names are resolved by the proxy.
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// May be a `vault:path#field` reference.
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Socks5,
+    Http,
+}
+
+/// A proxy with its password resolved.
+#[derive(Clone)]
+pub struct Proxy {
+    protocol: Protocol,
+    host: String,
+    port: u16,
+    username: Option<String>,
+    password: Option<Zeroizing<String>>,
+}
+
+impl ProxyOptions {
+    pub async fn resolve(&self, state: &AppState) -> Result<Proxy, String> {
+        let url = Url::parse(&self.url).map_err(|e| format!("Invalid proxy URL: {}", e))?;
+        let protocol = match url.scheme() {
+            "socks5" | "socks5h" => Protocol::Socks5,
+            "http" => Protocol::Http,
+            scheme => {
+                return Err(format!(
+                    "Unsupported proxy scheme {}; use socks5:// or http://",
+                    scheme
+                ))
+            }
+        };
+        let host = url
+            .host_str()
+            .ok_or("The proxy URL has no host")?
+            .trim_matches(|c| c == '[' || c == ']')
+            .to_string();
+        let port = url.port().unwrap_or(match protocol {
+            Protocol::Socks5 => 1080,
+            Protocol::Http => 8080,
+        });
+        let password = match &self.password {
+            Some(password) => Some(secrets::resolve(state, password).await?),
+            None => None,
+        };
+        Ok(Proxy {
+            protocol,
+            host,
+            port,
+            username: self.username.clone().filter(|user| !user.is_empty()),
+            password,
+        })
+    }
+}
+
+impl Proxy {
+    /// Route an HTTP client's requests through the proxy.
+    pub fn apply_http(
+        &self,
+        builder: reqwest::ClientBuilder,
+    ) -> Result<reqwest::ClientBuilder, String> {
+        let scheme = match self.protocol {
+            Protocol::Socks5 => "socks5h",
+            Protocol::Http => "http",
+        };
+        let host = match self.host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => format!("[{}]", ip),
+            _ => self.host.clone(),
+        };
+        let mut url = Url::parse(&format!("{}://{}:{}", scheme, host, self.port))
+            .map_err(|e| format!("Invalid proxy URL: {}", e))?;
+        if let Some(user) = &self.username {
+            let password = self.password.as_deref().map(String::as_str);
+            url.set_username(user)
+                .and_then(|()| url.set_password(password))
+                .map_err(|()| "Invalid proxy credentials".to_string())?;
+        }
+        let proxy = reqwest::Proxy::all(url).map_err(|e| e.to_string())?;
+        Ok(builder.proxy(proxy))
+    }
+
+    /// A TCP connection to `host:port` through the proxy.
+    async fn dial(&self, host: &str, port: u16) -> Result<TcpStream, String> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .map_err(|e| format!("Could not reach the proxy: {}", e))?;
+        match self.protocol {
+            Protocol::Socks5 => self.socks5_connect(&mut stream, host, port).await?,
+            Protocol::Http => self.http_connect(&mut stream, host, port).await?,
+        }
+        Ok(stream)
+    }
+
+    /// RFC 1928, with RFC 1929 username/password authentication.
+    async fn socks5_connect(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), String> {
+        let io = |e: std::io::Error| format!("SOCKS5 proxy: {}", e);
+        let methods: &[u8] = match self.username {
+            Some(_) => &[5, 2, 0x00, 0x02],
+            None => &[5, 1, 0x00],
+        };
+        stream.write_all(methods).await.map_err(io)?;
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply).await.map_err(io)?;
+        match reply {
+            [5, 0x00] => {}
+            [5, 0x02] => {
+                let user = self.username.as_deref().unwrap_or_default();
+                let password = self.password.as_deref().map_or("", String::as_str);
+                if user.len() > 255 || password.len() > 255 {
+                    return Err("SOCKS5 credentials are limited to 255 bytes".to_string());
+                }
+                let mut auth = Zeroizing::new(vec![1, user.len() as u8]);
+                auth.extend_from_slice(user.as_bytes());
+                auth.push(password.len() as u8);
+                auth.extend_from_slice(password.as_bytes());
+                stream.write_all(&auth).await.map_err(io)?;
+                stream.read_exact(&mut reply).await.map_err(io)?;
+                if reply[1] != 0 {
+                    return Err("The SOCKS5 proxy rejected the credentials".to_string());
+                }
+            }
+            [5, _] => return Err("The SOCKS5 proxy requires authentication".to_string()),
+            _ => return Err("The proxy is not a SOCKS5 proxy".to_string()),
+        }
+
+        let mut request = vec![5, 1, 0];
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(1);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(4);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) if host.len() > 255 => return Err("Host name too long for SOCKS5".to_string()),
+            Err(_) => {
+                request.extend_from_slice(&[3, host.len() as u8]);
+                request.extend_from_slice(host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await.map_err(io)?;
+
+        let mut head = [0u8; 4];
+        stream.read_exact(&mut head).await.map_err(io)?;
+        if head[1] != 0 {
+            let reason = match head[1] {
+                2 => "not allowed by the proxy's rules",
+                3 => "network unreachable",
+                4 => "host unreachable",
+                5 => "connection refused",
+                6 => "TTL expired",
+                _ => "proxy failure",
+            };
+            return Err(format!(
+                "The SOCKS5 proxy could not connect to {}:{}: {}",
+                host, port, reason
+            ));
+        }
+        // Skip the bound address that follows.
+        let address_len = match head[3] {
+            1 => 4,
+            4 => 16,
+            3 => stream.read_u8().await.map_err(io)? as usize,
+            _ => return Err("Malformed SOCKS5 reply".to_string()),
+        };
+        let mut bound = vec![0u8; address_len + 2];
+        stream.read_exact(&mut bound).await.map_err(io)?;
+        Ok(())
+    }
+
+    async fn http_connect(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), String> {
+        let io = |e: std::io::Error| format!("HTTP proxy: {}", e);
+        let authority = if host.contains(':') {
+            format!("[{}]:{}", host, port)
+        } else {
+            format!("{}:{}", host, port)
+        };
+        let mut request =
+            Zeroizing::new(format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority));
+        if let Some(user) = &self.username {
+            let password = self.password.as_deref().map_or("", String::as_str);
+            let credentials = Zeroizing::new(format!("{}:{}", user, password));
+            request.push_str(&format!(
+                "Proxy-Authorization: Basic {}\r\n",
+                BASE64.encode(credentials.as_bytes())
+            ));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await.map_err(io)?;
+
+        // Byte by byte, so nothing the server sends after the head is consumed.
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            if head.len() >= MAX_RESPONSE_HEAD {
+                return Err("The HTTP proxy sent an oversized response".to_string());
+            }
+            head.push(stream.read_u8().await.map_err(io)?);
+        }
+        let head = String::from_utf8_lossy(&head);
+        let status_line = head.lines().next().unwrap_or_default();
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Ok(()),
+            Some("407") => Err("The HTTP proxy requires authentication".to_string()),
+            _ => Err(format!(
+                "The HTTP proxy could not connect to {}: {}",
+                authority, status_line
+            )),
+        }
+    }
+}
+
+/// A tunnel to `host:port` through the proxy. The proxy is tried once first, so a
+/// wrong proxy or an unreachable target fails here rather than in the driver.
+async fn open_tunnel(proxy: &Proxy, host: &str, port: u16) -> Result<Tunnel, String> {
+    drop(proxy.dial(host, port).await?);
+    let (proxy, host) = (proxy.clone(), host.to_string());
+    Tunnel::serve(move || {
+        let (proxy, host) = (proxy.clone(), host.clone());
+        async move { proxy.dial(&host, port).await }
+    })
+    .await
+}
+
+const HOST_VERIFICATION: &str =
+    "Host name verification can't be used through a proxy; use verify_ca instead";
+
+/// Point Postgres options at a tunnel to the host and port they name.
+pub async fn tunnel_pg(
+    proxy: &Proxy,
+    options: PgConnectOptions,
+) -> Result<(PgConnectOptions, Tunnel), String> {
+    if options.get_socket().is_some() {
+        return Err("A proxy can't be used with a Unix socket".to_string());
+    }
+    // The driver would check the certificate against the tunnel's address.
+    if matches!(options.get_ssl_mode(), PgSslMode::VerifyFull) {
+        return Err(HOST_VERIFICATION.to_string());
+    }
+    let tunnel = open_tunnel(proxy, options.get_host(), options.get_port()).await?;
+    Ok((options.host("127.0.0.1").port(tunnel.port()), tunnel))
+}
+
+/// Point MySQL options at a tunnel to the host and port they name.
+pub async fn tunnel_mysql(
+    proxy: &Proxy,
+    options: MySqlConnectOptions,
+) -> Result<(MySqlConnectOptions, Tunnel), String> {
+    if options.get_socket().is_some() {
+        return Err("A proxy can't be used with a Unix socket".to_string());
+    }
+    if matches!(options.get_ssl_mode(), MySqlSslMode::VerifyIdentity) {
+        return Err(HOST_VERIFICATION.to_string());
+    }
+    let tunnel = open_tunnel(proxy, options.get_host(), options.get_port()).await?;
+    Ok((options.host("127.0.0.1").port(tunnel.port()), tunnel))
+}
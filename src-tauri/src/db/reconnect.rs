@@ -0,0 +1,229 @@
+//! Rebuilding a connection's pool in place, e.g. after the machine slept and the
+//! server dropped every pooled connection. The id, and everything configured for it,
+//! stays as it was. With an `auto_reconnect` policy, `execute` does this itself when
+//! a query fails because the connection was lost.
+
+use super::convert::{CollectLimits, Collected};
+use super::sql;
+use super::timeout::QueryControl;
+use super::{flavor, open_pool, redact, registry, run_query, AppState, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use tauri::State;
+
+/// How `execute` reconnects after a connection-level error.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectPolicy {
+    /// Reconnect attempts before the error is returned.
+    pub attempts: u32,
+    /// Wait before the first attempt, doubled after each one.
+    pub initial_delay_ms: u64,
+    /// Longest wait between attempts.
+    pub max_delay_ms: u64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            initial_delay_ms: 500,
+            max_delay_ms: 10_000,
+        }
+    }
+}
+
+/// Emitted as `db://reconnecting` before each attempt.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconnectingEvent {
+    pub id: String,
+    pub attempt: u32,
+    pub attempts: u32,
+    pub delay_ms: u64,
+    /// The error that lost the connection.
+    pub error: String,
+}
+
+/// Emitted as `db://reconnected` once a new pool is in place.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconnectedEvent {
+    pub id: String,
+    pub attempt: u32,
+}
+
+/// Whether `error` means the connection was lost rather than the statement failing.
+/// Drivers only report this as text, so it is judged from the messages of the
+/// network-backed ones.
+pub fn is_connection_error(error: &str) -> bool {
+    const MARKERS: &[&str] = &[
+        "error communicating with database",
+        "closed pool",
+        "connection reset",
+        "connection refused",
+        "connection aborted",
+        "connection broken",
+        "broken pipe",
+        "unexpected eof",
+        "not connected",
+        "communication link failure",
+        "end-of-file on communication channel",
+        "ora-03113",
+        "ora-03114",
+        "ora-03135",
+        "dpi-1080",
+        "error sending request",
+        "connection closed before message completed",
+    ];
+    let error = error.to_lowercase();
+    MARKERS.iter().any(|marker| error.contains(marker))
+}
+
+/// Open a fresh pool from the connection string and options `id` was opened with,
+/// resolving secrets again, and swap it in for the old one, which is then closed.
+/// On failure the old pool stays in place.
+#[tauri::command]
+pub async fn reconnect(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    reconnect_connection(&state, &id).await
+}
+
+/// The body of `reconnect`, also used by `run_with_reconnect`.
+pub async fn reconnect_connection(state: &AppState, id: &str) -> Result<(), String> {
+    let (conn_string, options) = state
+        .connect_specs
+        .lock()
+        .unwrap()
+        .get(id)
+        .cloned()
+        .ok_or("Connection not found")?;
+
+    let (conn, resolved, tunnel) = open_pool(state, &conn_string, &options).await?;
+    let detected = match options.lazy {
+        true => Ok(None),
+        false => flavor::detect(&conn).await,
+    };
+    let detected = match detected {
+        Ok(detected) => detected,
+        Err(e) => {
+            conn.close().await;
+            return Err(e);
+        }
+    };
+    let meta = registry::describe(state, id, &conn, &resolved, &options).await;
+
+    // Disconnected while the new pool was opening: don't register it.
+    let old = {
+        let mut connections = state.connections.lock().unwrap();
+        match connections.get_mut(id) {
+            Some(slot) => Ok(std::mem::replace(slot, conn)),
+            None => Err(conn),
+        }
+    };
+    let old = match old {
+        Ok(old) => old,
+        Err(conn) => {
+            conn.close().await;
+            return Err("Connection not found".to_string());
+        }
+    };
+
+    state.schema_cache.invalidate(id);
+    state.result_cache.invalidate(id, None);
+    if options.lazy {
+        state.undescribed.lock().unwrap().insert(id.to_string());
+    }
+    match detected {
+        Some(detected) => state
+            .flavors
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), detected),
+        None => state.flavors.lock().unwrap().remove(id),
+    };
+    state
+        .connection_meta
+        .lock()
+        .unwrap()
+        .insert(id.to_string(), meta);
+    // The old pool's relay, if any, is dropped once the pool is closed.
+    let old_tunnel = match tunnel {
+        Some(tunnel) => state.tunnels.lock().unwrap().insert(id.to_string(), tunnel),
+        None => state.tunnels.lock().unwrap().remove(id),
+    };
+    // Transactions and sessions hold connections of the old pool, which waits for
+    // them to close.
+    state.end_transactions(id);
+    state.end_sessions(id);
+    state.end_cursors(id);
+    old.close().await;
+    drop(old_tunnel);
+    Ok(())
+}
+
+/// `run_query`, reconnecting under `id`'s `auto_reconnect` policy when the query
+/// fails with a connection-level error. Once reconnected, read-only scripts run again;
+/// a write may already have been applied when the connection dropped, so its error is
+/// returned instead. The error is also returned once every attempt has failed.
+pub async fn run_with_reconnect(
+    state: &AppState,
+    id: &str,
+    conn: &Connection,
+    sql: &str,
+    binds: &[Value],
+    limits: CollectLimits,
+    control: QueryControl<'_>,
+) -> Result<Collected, String> {
+    let mut error = match run_query(state, id, conn, sql, binds, limits.clone(), control).await {
+        Err(e) if is_connection_error(&e) => e,
+        result => return result,
+    };
+    let policy = state
+        .connect_specs
+        .lock()
+        .unwrap()
+        .get(id)
+        .and_then(|(_, options)| options.auto_reconnect);
+    let Some(policy) = policy else {
+        return Err(error);
+    };
+    let rerun = sql::is_read_only(sql);
+
+    let max_delay = Duration::from_millis(policy.max_delay_ms);
+    let mut delay = Duration::from_millis(policy.initial_delay_ms).min(max_delay);
+    for attempt in 1..=policy.attempts {
+        state.emit(
+            "db://reconnecting",
+            ReconnectingEvent {
+                id: id.to_string(),
+                attempt,
+                attempts: policy.attempts,
+                delay_ms: delay.as_millis() as u64,
+                error: redact::redact(&error),
+            },
+        );
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(max_delay);
+        if reconnect_connection(state, id).await.is_err() {
+            if !state.connections.lock().unwrap().contains_key(id) {
+                break;
+            }
+            continue;
+        }
+        state.emit(
+            "db://reconnected",
+            ReconnectedEvent {
+                id: id.to_string(),
+                attempt,
+            },
+        );
+        if !rerun {
+            return Err(error);
+        }
+        let conn = state.peek_connection(id)?;
+        match run_query(state, id, &conn, sql, binds, limits.clone(), control).await {
+            Err(e) if is_connection_error(&e) => error = e,
+            result => return result,
+        }
+    }
+    Err(error)
+}
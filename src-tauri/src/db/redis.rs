@@ -0,0 +1,511 @@
+//! Redis connections. `execute` takes Redis commands, one per line, as typed in
+//! `redis-cli`; keys are listed as tables by grouping them on their last `:` prefix.
+
+use super::convert::{QueryOutput, RowSet, MAX_RESULT_ROWS};
+use super::introspect::ColumnInfo;
+use super::masking::{replacement_for, MaskingRule};
+use super::policy::{production_write, Capability, DestructiveStatement};
+use super::sql::DestructiveReason;
+use super::{AppState, ExecuteOptions};
+use ::redis::aio::ConnectionManager;
+use ::redis::{IntoConnectionInfo, Value as RedisValue};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+/// Keys read by `get_tables` before it stops scanning.
+const KEY_SCAN_LIMIT: usize = 10_000;
+/// Keys sampled per pattern when inferring its fields for `get_columns`.
+const KEY_SAMPLE_SIZE: usize = 100;
+/// `COUNT` hint passed to each `SCAN`.
+const SCAN_BATCH: usize = 1000;
+
+/// Commands that only read.
+const READ_COMMANDS: &[&str] = &[
+    "BITCOUNT",
+    "BITPOS",
+    "DBSIZE",
+    "DUMP",
+    "ECHO",
+    "EXISTS",
+    "EXPIRETIME",
+    "GEODIST",
+    "GEOHASH",
+    "GEOPOS",
+    "GEOSEARCH",
+    "GET",
+    "GETBIT",
+    "GETRANGE",
+    "HEXISTS",
+    "HGET",
+    "HGETALL",
+    "HKEYS",
+    "HLEN",
+    "HMGET",
+    "HRANDFIELD",
+    "HSCAN",
+    "HSTRLEN",
+    "HVALS",
+    "INFO",
+    "KEYS",
+    "LINDEX",
+    "LLEN",
+    "LPOS",
+    "LRANGE",
+    "MEMORY",
+    "MGET",
+    "OBJECT",
+    "PEXPIRETIME",
+    "PFCOUNT",
+    "PING",
+    "PTTL",
+    "RANDOMKEY",
+    "SCAN",
+    "SCARD",
+    "SDIFF",
+    "SINTER",
+    "SINTERCARD",
+    "SISMEMBER",
+    "SMEMBERS",
+    "SMISMEMBER",
+    "SRANDMEMBER",
+    "SSCAN",
+    "STRLEN",
+    "SUNION",
+    "TIME",
+    "TTL",
+    "TYPE",
+    "XINFO",
+    "XLEN",
+    "XRANGE",
+    "XREVRANGE",
+    "ZCARD",
+    "ZCOUNT",
+    "ZLEXCOUNT",
+    "ZMSCORE",
+    "ZRANDMEMBER",
+    "ZRANGE",
+    "ZRANGEBYLEX",
+    "ZRANGEBYSCORE",
+    "ZRANK",
+    "ZREVRANGE",
+    "ZREVRANGEBYSCORE",
+    "ZREVRANK",
+    "ZSCAN",
+    "ZSCORE",
+];
+
+/// Commands that manage the server rather than data.
+const ADMIN_COMMANDS: &[&str] = &[
+    "ACL",
+    "BGREWRITEAOF",
+    "BGSAVE",
+    "CLIENT",
+    "CLUSTER",
+    "CONFIG",
+    "DEBUG",
+    "FAILOVER",
+    "FLUSHALL",
+    "FLUSHDB",
+    "FUNCTION",
+    "LATENCY",
+    "MIGRATE",
+    "MODULE",
+    "REPLICAOF",
+    "SAVE",
+    "SCRIPT",
+    "SHUTDOWN",
+    "SLAVEOF",
+    "SLOWLOG",
+    "SWAPDB",
+];
+
+/// Commands that would tie up or change the state of the shared connection.
+const CONNECTION_COMMANDS: &[&str] = &[
+    "EXEC",
+    "MONITOR",
+    "MULTI",
+    "PSUBSCRIBE",
+    "SELECT",
+    "SSUBSCRIBE",
+    "SUBSCRIBE",
+    "WATCH",
+];
+
+/// Connect to `redis://[user:password@]host:6379/db`, or `rediss://` for TLS.
+pub async fn connect(
+    conn_string: &str,
+    password: Option<&str>,
+) -> Result<ConnectionManager, String> {
+    let mut info = conn_string
+        .into_connection_info()
+        .map_err(|e| e.to_string())?;
+    if let Some(password) = password {
+        let settings = info.redis_settings().clone().set_password(password);
+        info = info.set_redis_settings(settings);
+    }
+    let client = ::redis::Client::open(info).map_err(|e| e.to_string())?;
+    client
+        .get_connection_manager()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Split a script into commands, one per line, with `redis-cli` quoting: `"..."`
+/// takes backslash escapes, `'...'` is literal. Lines starting with `#` are skipped.
+fn parse_script(script: &str) -> Result<Vec<Vec<String>>, String> {
+    script
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(tokenize)
+        .collect()
+}
+
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let unterminated = || format!("Unterminated quote in: {}", line);
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            break;
+        };
+        let mut token = String::new();
+        if first == '"' || first == '\'' {
+            chars.next();
+            loop {
+                match chars.next().ok_or_else(unterminated)? {
+                    c if c == first => break,
+                    '\\' if first == '"' => match chars.next().ok_or_else(unterminated)? {
+                        'n' => token.push('\n'),
+                        'r' => token.push('\r'),
+                        't' => token.push('\t'),
+                        c => token.push(c),
+                    },
+                    c => token.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                token.push(c);
+            }
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn required_capability(name: &str) -> Capability {
+    if READ_COMMANDS.contains(&name) {
+        Capability::ReadOnly
+    } else if ADMIN_COMMANDS.contains(&name) {
+        Capability::Admin
+    } else {
+        Capability::ReadWrite
+    }
+}
+
+/// Run Redis commands, returning the reply to the last one. Capability, masking,
+/// auditing and destructive-command confirmation apply as they do for SQL; masking
+/// rules match key names and hash fields.
+pub async fn execute(
+    state: &AppState,
+    id: &str,
+    conn: &ConnectionManager,
+    script: &str,
+    options: &ExecuteOptions,
+) -> Result<QueryOutput, String> {
+    let commands = parse_script(script)?;
+    state.check_statement_count(id, commands.len())?;
+    let mut required = Capability::ReadOnly;
+    for command in &commands {
+        let name = command[0].to_uppercase();
+        if CONNECTION_COMMANDS.contains(&name.as_str()) {
+            return Err(format!("{} is not supported on a shared connection", name));
+        }
+        required = required.max(required_capability(&name));
+    }
+    state.require_capability(id, required)?;
+    if !state.confirmed(id, script, options) {
+        let production = state.is_production(id);
+        let statements: Vec<_> = commands
+            .iter()
+            .enumerate()
+            .filter_map(|(index, command)| {
+                let name = command[0].to_uppercase();
+                let reason = match name.as_str() {
+                    "FLUSHDB" | "FLUSHALL" => DestructiveReason::Drop,
+                    _ => production_write(production, required_capability(&name))?,
+                };
+                Some(DestructiveStatement {
+                    index,
+                    reason,
+                    statement: command.join(" "),
+                })
+            })
+            .collect();
+        if !statements.is_empty() {
+            return Ok(QueryOutput::NeedsConfirmation {
+                needs_confirmation: true,
+                statements,
+            });
+        }
+    }
+    let masking = state.masking_rules(id, options.unmask)?;
+
+    let result = run(conn, &commands, state.statement_timeout_ms(id)).await;
+    state.audit(id, script, result.as_ref().err().map(String::as_str));
+    let Some((command, reply)) = result? else {
+        return Ok(RowSet::default().into_output(options.format));
+    };
+    Ok(to_row_set(&command, reply, &masking)?.into_output(options.format))
+}
+
+async fn run(
+    conn: &ConnectionManager,
+    commands: &[Vec<String>],
+    timeout_ms: Option<u64>,
+) -> Result<Option<(Vec<String>, RedisValue)>, String> {
+    let mut conn = conn.clone();
+    let mut last = None;
+    for command in commands {
+        let mut cmd = ::redis::cmd(&command[0]);
+        for arg in &command[1..] {
+            cmd.arg(arg);
+        }
+        let reply = cmd.query_async::<RedisValue>(&mut conn);
+        let reply = match timeout_ms {
+            Some(ms) => tokio::time::timeout(Duration::from_millis(ms), reply)
+                .await
+                .map_err(|_| format!("{} timed out after {} ms", command[0], ms))?,
+            None => reply.await,
+        }
+        .map_err(|e| e.to_string())?;
+        last = Some((command.clone(), reply));
+    }
+    Ok(last)
+}
+
+/// Field/value replies (RESP3 maps, `HGETALL`, `CONFIG GET`) become `field`/`value`
+/// rows, other arrays one `value` row per element, and anything else a single row.
+fn to_row_set(
+    command: &[String],
+    reply: RedisValue,
+    masking: &[MaskingRule],
+) -> Result<RowSet, String> {
+    let name = command[0].to_uppercase();
+    let pairs = match reply {
+        RedisValue::Map(pairs) => Ok(pairs),
+        RedisValue::Array(items)
+            if name == "HGETALL" || (name == "CONFIG" && items.len() % 2 == 0) =>
+        {
+            let mut items = items.into_iter();
+            let mut pairs = Vec::new();
+            while let (Some(field), Some(value)) = (items.next(), items.next()) {
+                pairs.push((field, value));
+            }
+            Ok(pairs)
+        }
+        other => Err(other),
+    };
+    // The key a command reads, which masking rules may name.
+    let key_mask = command.get(1).and_then(|key| replacement_for(masking, key));
+    let mask = |value: Value, replacement: Option<&str>| match replacement {
+        Some(replacement) if !value.is_null() => Value::String(replacement.to_string()),
+        _ => value,
+    };
+
+    let set = match pairs {
+        Ok(pairs) => RowSet {
+            columns: vec!["field".to_string(), "value".to_string()],
+            rows: pairs
+                .into_iter()
+                .map(|(field, value)| {
+                    let field = text(to_json(field));
+                    let replacement = key_mask.or_else(|| replacement_for(masking, &field));
+                    vec![Value::String(field), mask(to_json(value), replacement)]
+                })
+                .collect(),
+            affected: None,
+            truncated: None,
+        },
+        Err(RedisValue::Array(items) | RedisValue::Set(items)) => RowSet {
+            columns: vec!["value".to_string()],
+            rows: items
+                .into_iter()
+                .map(|item| vec![mask(to_json(item), key_mask)])
+                .collect(),
+            affected: None,
+            truncated: None,
+        },
+        Err(other) => RowSet {
+            columns: vec!["value".to_string()],
+            rows: vec![vec![mask(to_json(other), key_mask)]],
+            affected: None,
+            truncated: None,
+        },
+    };
+    if set.rows.len() > MAX_RESULT_ROWS {
+        return Err(format!(
+            "The reply has more than {} elements; narrow the command, e.g. with a range",
+            MAX_RESULT_ROWS
+        ));
+    }
+    Ok(set)
+}
+
+/// Bulk strings that aren't UTF-8 are base64. Nested replies become JSON arrays and
+/// objects.
+fn to_json(value: RedisValue) -> Value {
+    match value {
+        RedisValue::Nil => Value::Null,
+        RedisValue::Int(n) => n.into(),
+        RedisValue::BulkString(bytes) => match String::from_utf8(bytes) {
+            Ok(s) => Value::String(s),
+            Err(e) => BASE64.encode(e.into_bytes()).into(),
+        },
+        RedisValue::Array(items)
+        | RedisValue::Set(items)
+        | RedisValue::Push { data: items, .. } => {
+            Value::Array(items.into_iter().map(to_json).collect())
+        }
+        RedisValue::SimpleString(s) | RedisValue::VerbatimString { text: s, .. } => {
+            Value::String(s)
+        }
+        RedisValue::Okay => "OK".into(),
+        RedisValue::Map(pairs) => Value::Object(
+            pairs
+                .into_iter()
+                .map(|(k, v)| (text(to_json(k)), to_json(v)))
+                .collect::<Map<_, _>>(),
+        ),
+        RedisValue::Attribute { data, .. } => to_json(*data),
+        RedisValue::Double(n) => serde_json::Number::from_f64(n).map_or(Value::Null, Value::Number),
+        RedisValue::Boolean(b) => Value::Bool(b),
+        RedisValue::BigNumber(digits) => String::from_utf8_lossy(&digits).into_owned().into(),
+        RedisValue::ServerError(e) => Value::String(e.to_string()),
+        _ => Value::Null,
+    }
+}
+
+fn text(value: Value) -> String {
+    match value {
+        Value::String(s) => s,
+        other => other.to_string(),
+    }
+}
+
+/// `SCAN` for keys matching `pattern` (every key when `None`), stopping at `limit`.
+async fn scan(
+    conn: &ConnectionManager,
+    pattern: Option<&str>,
+    limit: usize,
+) -> Result<Vec<String>, String> {
+    let mut conn = conn.clone();
+    let mut keys = Vec::new();
+    let mut cursor = 0u64;
+    loop {
+        let mut cmd = ::redis::cmd("SCAN");
+        cmd.arg(cursor).arg("COUNT").arg(SCAN_BATCH);
+        if let Some(pattern) = pattern {
+            cmd.arg("MATCH").arg(pattern);
+        }
+        let (next, batch): (u64, Vec<Vec<u8>>) = cmd
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        keys.extend(
+            batch
+                .iter()
+                .map(|k| String::from_utf8_lossy(k).into_owned()),
+        );
+        if next == 0 || keys.len() >= limit {
+            break;
+        }
+        cursor = next;
+    }
+    keys.truncate(limit);
+    Ok(keys)
+}
+
+/// The pattern a key is listed under: `user:42:profile` becomes `user:42:*`; keys
+/// without a `:` are listed as themselves.
+fn key_pattern(key: &str) -> String {
+    match key.rsplit_once(':') {
+        Some((prefix, _)) => format!("{}:*", prefix),
+        None => key.to_string(),
+    }
+}
+
+/// Key patterns, from a scan of up to `KEY_SCAN_LIMIT` keys.
+pub async fn fetch_tables(conn: &ConnectionManager) -> Result<Vec<String>, String> {
+    let keys = scan(conn, None, KEY_SCAN_LIMIT).await?;
+    let patterns: BTreeSet<String> = keys.iter().map(|key| key_pattern(key)).collect();
+    Ok(patterns.into_iter().collect())
+}
+
+/// Fields of the keys matching `pattern`, inferred from a sample: a `key` column, a
+/// column per hash field, and a `value` column listing the other types seen.
+pub async fn fetch_columns(
+    conn: &ConnectionManager,
+    pattern: &str,
+) -> Result<Vec<ColumnInfo>, String> {
+    let keys = scan(conn, Some(pattern), KEY_SAMPLE_SIZE).await?;
+    let mut conn = conn.clone();
+    let mut hashes = 0;
+    let mut fields: Vec<(String, usize)> = Vec::new();
+    let mut types: Vec<String> = Vec::new();
+    for key in &keys {
+        let kind: String = ::redis::cmd("TYPE")
+            .arg(key)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        if kind != "hash" {
+            if !types.contains(&kind) {
+                types.push(kind);
+            }
+            continue;
+        }
+        hashes += 1;
+        let names: Vec<String> = ::redis::cmd("HKEYS")
+            .arg(key)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        for name in names {
+            match fields.iter_mut().find(|(field, _)| *field == name) {
+                Some((_, seen)) => *seen += 1,
+                None => fields.push((name, 1)),
+            }
+        }
+    }
+
+    let mut columns = vec![ColumnInfo {
+        name: "key".to_string(),
+        data_type: "string".to_string(),
+        nullable: false,
+        default: None,
+        primary_key: true,
+    }];
+    columns.extend(fields.into_iter().map(|(name, seen)| ColumnInfo {
+        name,
+        data_type: "hash field".to_string(),
+        nullable: seen < keys.len(),
+        default: None,
+        primary_key: false,
+    }));
+    if !types.is_empty() {
+        columns.push(ColumnInfo {
+            name: "value".to_string(),
+            data_type: types.join(" | "),
+            nullable: hashes > 0,
+            default: None,
+            primary_key: false,
+        });
+    }
+    Ok(columns)
+}
@@ -0,0 +1,241 @@
+//! What the frontend needs to render its connection list: the open connections with
+//! the metadata gathered when each was opened, and the current state of its pool.
+
+use super::convert::{CollectLimits, Collected};
+use super::timeout::QueryControl;
+use super::{flavor, run_query, AppState, Connection, ConnectionOptions, DbKind};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+/// Which deployment a connection points at, for the badge the connection list shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Environment {
+    #[serde(alias = "dev")]
+    Development,
+    Staging,
+    #[serde(alias = "prod")]
+    Production,
+}
+
+/// Gathered once, when the connection opens.
+#[derive(Debug, Clone)]
+pub struct ConnectionMeta {
+    pub name: Option<String>,
+    pub color: Option<String>,
+    pub environment: Option<Environment>,
+    pub server_version: Option<String>,
+    pub database: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolStatus {
+    pub max_connections: u32,
+    /// Connections currently open, where the driver reports it.
+    pub open: Option<u32>,
+    /// Open connections not in use, where the driver reports it.
+    pub idle: Option<u32>,
+    /// Callers waiting for a pooled connection.
+    pub pending: usize,
+    /// Connections handed out since the pool opened.
+    pub total_acquired: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionInfo {
+    pub id: String,
+    pub kind: DbKind,
+    /// The `name` given at connect time, or the id.
+    pub name: String,
+    pub color: Option<String>,
+    pub environment: Option<Environment>,
+    pub server_version: Option<String>,
+    pub database: Option<String>,
+    pub pool: PoolStatus,
+}
+
+/// Statement returning the server's version string as its first cell.
+fn version_query(kind: DbKind) -> Option<&'static str> {
+    match kind {
+        DbKind::Postgres | DbKind::DuckDb | DbKind::ClickHouse => Some("SELECT version()"),
+        DbKind::MySql => Some("SELECT VERSION()"),
+        DbKind::Sqlite => Some("SELECT sqlite_version()"),
+        DbKind::Oracle => Some("SELECT banner FROM v$version WHERE ROWNUM = 1"),
+        DbKind::Cassandra => Some("SELECT release_version FROM system.local"),
+        DbKind::Firebird => {
+            Some("SELECT rdb$get_context('SYSTEM', 'ENGINE_VERSION') FROM rdb$database")
+        }
+        _ => None,
+    }
+}
+
+/// Database, keyspace, file or service named in the connection string. Credentials
+/// are never part of the result.
+fn database_name(kind: DbKind, conn_string: &str) -> Option<String> {
+    let name = match kind {
+        DbKind::Sqlite | DbKind::DuckDb => {
+            let path = conn_string.split_once(':').map_or(conn_string, |(_, p)| p);
+            let path = path.trim_start_matches("//");
+            path.split('?').next().unwrap_or(path).to_string()
+        }
+        DbKind::Odbc => conn_string
+            .split(';')
+            .filter_map(|attr| attr.split_once('='))
+            .find(|(key, _)| {
+                let key = key.trim().trim_start_matches("odbc:");
+                key.eq_ignore_ascii_case("database") || key.eq_ignore_ascii_case("dsn")
+            })
+            .map(|(_, value)| value.trim_matches(|c| c == '{' || c == '}').to_string())?,
+        _ => {
+            let rest = conn_string.split_once("://")?.1;
+            let path = rest.split_once('/')?.1;
+            let path = path.split('?').next().unwrap_or(path);
+            percent_decode_str(path).decode_utf8_lossy().into_owned()
+        }
+    };
+    Some(name).filter(|name| !name.is_empty())
+}
+
+/// Read the server version and database name of a newly opened connection, alongside
+/// the labels from its options. Neither is essential, so a failed version query leaves
+/// it unset.
+pub async fn describe(
+    state: &AppState,
+    id: &str,
+    conn: &Connection,
+    conn_string: &str,
+    options: &ConnectionOptions,
+) -> ConnectionMeta {
+    ConnectionMeta {
+        name: options.name.clone(),
+        color: options.color.clone(),
+        environment: options.environment,
+        server_version: match options.lazy {
+            true => None,
+            false => server_version(state, id, conn).await,
+        },
+        database: database_name(conn.kind(), conn_string),
+    }
+}
+
+/// Read the flavor and server version of a lazy connection `id` once its pool is
+/// first used. Does nothing for other connections.
+pub async fn describe_on_first_use(
+    state: &AppState,
+    id: &str,
+    conn: &Connection,
+) -> Result<(), String> {
+    if !state.undescribed.lock().unwrap().remove(id) {
+        return Ok(());
+    }
+    match flavor::detect(conn).await {
+        Ok(Some(detected)) => {
+            state
+                .flavors
+                .lock()
+                .unwrap()
+                .insert(id.to_string(), detected);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            state.undescribed.lock().unwrap().insert(id.to_string());
+            return Err(e);
+        }
+    }
+    let version = server_version(state, id, conn).await;
+    if let Some(meta) = state.connection_meta.lock().unwrap().get_mut(id) {
+        meta.server_version = version;
+    }
+    Ok(())
+}
+
+/// The server's version string, where a query reports it.
+pub async fn server_version(state: &AppState, id: &str, conn: &Connection) -> Option<String> {
+    let sql = version_query(conn.kind())?;
+    match run_query(
+        state,
+        id,
+        conn,
+        sql,
+        &[],
+        CollectLimits::default(),
+        QueryControl::default(),
+    )
+    .await
+    {
+        Ok(Collected::Memory(rows)) => rows
+            .rows
+            .into_iter()
+            .next()
+            .and_then(|row| row.into_iter().next())
+            .and_then(|value| value.as_str().map(|v| v.trim().to_string())),
+        _ => None,
+    }
+}
+
+impl AppState {
+    /// The state of `id`'s pool. Waits and acquires are counted for the queries this
+    /// app runs, as `AppState::acquire` records them.
+    pub fn pool_status(&self, id: &str, conn: &Connection) -> PoolStatus {
+        let (open, idle) = match conn {
+            Connection::Postgres(pool) => (Some(pool.size()), Some(pool.num_idle() as u32)),
+            Connection::MySql(pool) => (Some(pool.size()), Some(pool.num_idle() as u32)),
+            Connection::Sqlite(pool) => (Some(pool.size()), Some(pool.num_idle() as u32)),
+            Connection::Oracle(pool) => match (pool.open_count(), pool.busy_count()) {
+                (Ok(open), Ok(busy)) => (Some(open), Some(open.saturating_sub(busy))),
+                _ => (None, None),
+            },
+            _ => (None, None),
+        };
+        let acquires = self.acquire_stats(id);
+        PoolStatus {
+            max_connections: self.max_connections(id, conn),
+            open,
+            idle,
+            pending: acquires.waiting(),
+            total_acquired: acquires.total(),
+        }
+    }
+}
+
+/// Size and load of `id`'s pool, e.g. to show that every connection is busy rather
+/// than wait for an acquire timeout.
+#[tauri::command]
+pub fn pool_status(state: State<'_, AppState>, id: String) -> Result<PoolStatus, String> {
+    let conn = state.connection(&id)?;
+    Ok(state.pool_status(&id, &conn))
+}
+
+/// Every open connection, ordered by id.
+#[tauri::command]
+pub fn list_connections(state: State<'_, AppState>) -> Vec<ConnectionInfo> {
+    let connections: Vec<(String, Connection)> = state
+        .connections
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(id, conn)| (id.clone(), conn.clone()))
+        .collect();
+    let meta = state.connection_meta.lock().unwrap();
+    let mut list: Vec<ConnectionInfo> = connections
+        .into_iter()
+        .map(|(id, conn)| {
+            let meta = meta.get(&id);
+            ConnectionInfo {
+                kind: conn.kind(),
+                name: meta
+                    .and_then(|m| m.name.clone())
+                    .unwrap_or_else(|| id.clone()),
+                color: meta.and_then(|m| m.color.clone()),
+                environment: meta.and_then(|m| m.environment),
+                server_version: meta.and_then(|m| m.server_version.clone()),
+                database: meta.and_then(|m| m.database.clone()),
+                pool: state.pool_status(&id, &conn),
+                id,
+            }
+        })
+        .collect();
+    list.sort_by(|a, b| a.id.cmp(&b.id));
+    list
+}
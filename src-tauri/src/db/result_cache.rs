@@ -0,0 +1,118 @@
+//! Results of read-only scripts kept in memory for callers that opt in with
+//! `cache_ttl_ms`, so dashboards and metadata panels that repeat the same query don't
+//! reach the server each time. Entries are keyed by connection, normalized SQL,
+//! parameters and the options that shape the result. A connection's entries are
+//! dropped when a script that writes runs on it through `execute` or
+//! `execute_stream`, when a transaction on it commits, when it reconnects or switches
+//! database, when it closes, and by `invalidate_cache`.
+
+use super::convert::QueryOutput;
+use super::sql;
+use super::{AppState, ExecuteOptions};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+/// Results with more rows than this are not cached.
+pub const MAX_CACHED_ROWS: u64 = 10_000;
+
+/// Entries kept in all; the oldest is dropped to make room.
+const MAX_ENTRIES: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    id: String,
+    sql: String,
+    /// The bound parameters and result-shaping options, serialized.
+    variant: String,
+}
+
+impl CacheKey {
+    pub fn new(id: &str, sql: &str, options: &ExecuteOptions) -> Self {
+        let params: Option<BTreeMap<_, _>> = options.params.as_ref().map(|p| p.iter().collect());
+        Self {
+            id: id.to_string(),
+            sql: sql::normalize(sql),
+            variant: format!(
+                "{} {:?} {} {:?} {} {:?} {:?} {}",
+                serde_json::to_string(&params).unwrap_or_default(),
+                options.format,
+                options.unmask,
+                options.row_limit,
+                options.no_limit,
+                options.max_rows,
+                options.max_bytes,
+                options.column_meta,
+            ),
+        }
+    }
+}
+
+struct CachedResult {
+    output: QueryOutput,
+    stored_at: Instant,
+    expires: Instant,
+}
+
+#[derive(Default)]
+pub struct ResultCache {
+    entries: Mutex<HashMap<CacheKey, CachedResult>>,
+}
+
+impl ResultCache {
+    /// The result stored under `key`, with its age in ms, if it is younger than `ttl`
+    /// and the TTL it was stored with.
+    pub fn get(&self, key: &CacheKey, ttl: Duration) -> Option<(QueryOutput, u64)> {
+        let entries = self.entries.lock().unwrap();
+        let cached = entries.get(key)?;
+        let age = cached.stored_at.elapsed();
+        (age < ttl && Instant::now() < cached.expires)
+            .then(|| (cached.output.clone(), age.as_millis() as u64))
+    }
+
+    pub fn store(&self, key: CacheKey, output: QueryOutput, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        entries.retain(|_, cached| cached.expires > now);
+        if entries.len() >= MAX_ENTRIES && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.stored_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key,
+            CachedResult {
+                output,
+                stored_at: now,
+                expires: now + ttl,
+            },
+        );
+    }
+
+    /// Drop the entries of connection `id`, or only those for `sql` when given.
+    /// Returns how many were dropped.
+    pub fn invalidate(&self, id: &str, sql: Option<&str>) -> usize {
+        let sql = sql.map(sql::normalize);
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|key, _| key.id != id || sql.as_ref().is_some_and(|sql| key.sql != *sql));
+        before - entries.len()
+    }
+}
+
+/// Drop cached results of connection `id`: all of them, or those of `sql` with any
+/// parameters. Returns how many were dropped.
+#[tauri::command]
+pub fn invalidate_cache(
+    state: State<'_, AppState>,
+    id: String,
+    sql: Option<String>,
+) -> Result<usize, String> {
+    state.connection(&id)?;
+    Ok(state.result_cache.invalidate(&id, sql.as_deref()))
+}
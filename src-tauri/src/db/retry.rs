@@ -0,0 +1,91 @@
+//! Retrying scripts that fail for reasons that go away on their own: serialization
+//! failures, deadlocks, lock timeouts and dropped connections. Only scripts that just
+//! read are retried unless the policy opts writes in, and only on pooled connections;
+//! inside a transaction or session the failure has already rolled back work the
+//! caller has to redo itself.
+
+use super::reconnect::is_connection_error;
+use super::sql;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+
+/// How `execute` retries a script after a transient error.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt before the error is returned.
+    pub attempts: u32,
+    /// Wait before the first retry, doubled after each one. Each wait is jittered
+    /// between half and all of it, so clients that collided don't collide again.
+    pub initial_delay_ms: u64,
+    /// Longest wait between retries.
+    pub max_delay_ms: u64,
+    /// Retry scripts that write too. A script runs again from its first statement, so
+    /// one that failed partway through repeats the statements before the failure.
+    pub writes: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            initial_delay_ms: 100,
+            max_delay_ms: 5_000,
+            writes: false,
+        }
+    }
+}
+
+/// Whether `error` is one the same script may not hit when run again. Drivers only
+/// report these as text, as with `is_connection_error`.
+pub fn is_transient(error: &str) -> bool {
+    const MARKERS: &[&str] = &[
+        // Postgres 40001 and 40P01.
+        "could not serialize access",
+        "deadlock detected",
+        // CockroachDB's 40001.
+        "restart transaction",
+        // MySQL 1213 and 1205.
+        "deadlock found when trying to get lock",
+        "lock wait timeout exceeded",
+        // SQLite's SQLITE_BUSY and SQLITE_LOCKED.
+        "database is locked",
+        "database table is locked",
+    ];
+    let lower = error.to_lowercase();
+    is_connection_error(error) || MARKERS.iter().any(|marker| lower.contains(marker))
+}
+
+/// Run `attempt` until it succeeds, fails with an error that isn't transient, or
+/// `policy` runs out of retries, counting the retries made in `retries`. Without a
+/// policy, or when `script` writes and the policy doesn't allow that, it runs once.
+pub async fn run_with_retry<T, F, Fut>(
+    policy: Option<RetryPolicy>,
+    script: &str,
+    retries: &mut u32,
+    mut attempt: F,
+) -> Result<T, String>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, String>>,
+{
+    let policy = policy.filter(|policy| policy.writes || sql::is_read_only(script));
+    let Some(policy) = policy else {
+        return attempt().await;
+    };
+    let max_delay = policy.max_delay_ms;
+    let mut delay = policy.initial_delay_ms.min(max_delay);
+    loop {
+        match attempt().await {
+            Err(e) if *retries < policy.attempts && is_transient(&e) => {
+                let jitter = OsRng.next_u64() % (delay / 2 + 1);
+                tokio::time::sleep(Duration::from_millis(delay - delay / 2 + jitter)).await;
+                delay = (delay * 2).min(max_delay);
+                *retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
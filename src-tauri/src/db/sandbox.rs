@@ -0,0 +1,99 @@
+//! Sandbox mode for exploratory connections: SELECTs without a row limit get one
+//! injected, and statements are capped by a server-side timeout. Outside a sandbox,
+//! `execute` injects a limit too unless the caller opts out.
+
+use super::sql::{self, StatementKind};
+use super::{AppState, DbKind, ExecuteOptions};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_SANDBOX_ROW_LIMIT: u64 = 1000;
+
+/// The limit `execute` injects into plain SELECTs when the caller names none.
+pub const DEFAULT_ROW_LIMIT: u64 = 1000;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SandboxOptions {
+    /// LIMIT injected into SELECTs that have none. Defaults to `DEFAULT_SANDBOX_ROW_LIMIT`.
+    pub row_limit: Option<u64>,
+    /// Server-side statement timeout: `statement_timeout` on Postgres,
+    /// `max_execution_time` (SELECTs only) on MySQL, a call timeout on Oracle, an
+    /// interrupt on DuckDB, `max_execution_time` (rounded up to whole seconds) on
+    /// ClickHouse, and a client-side timeout per command on Redis and per request on
+    /// Cassandra and SurrealDB; on BigQuery it is the job timeout, and on Firebird 4+
+    /// `SET STATEMENT TIMEOUT`. SQLite and ODBC connections have no equivalent.
+    pub statement_timeout_ms: Option<u64>,
+}
+
+impl SandboxOptions {
+    pub fn row_limit(&self) -> u64 {
+        self.row_limit.unwrap_or(DEFAULT_SANDBOX_ROW_LIMIT).max(1)
+    }
+}
+
+impl AppState {
+    /// Rewrite `script` under the sandbox of connection `id`, if it has one. Returns the
+    /// rewritten SQL and the limit when one was injected.
+    pub fn sandboxed(&self, id: &str, script: &str) -> Option<(String, u64)> {
+        let limit = self.sandboxes.lock().unwrap().get(id)?.row_limit();
+        let kind = self.connections.lock().unwrap().get(id)?.kind();
+        inject_limit(kind, script, limit).map(|sql| (sql, limit))
+    }
+
+    /// Rewrite `script` with the row limit `execute` runs it under: the sandbox's on a
+    /// sandboxed connection, otherwise `options.row_limit` unless `options.no_limit`.
+    /// Backends whose dialect isn't known to take a LIMIT are left alone outside a
+    /// sandbox.
+    pub fn limited(
+        &self,
+        id: &str,
+        script: &str,
+        options: &ExecuteOptions,
+    ) -> Option<(String, u64)> {
+        if self.sandboxes.lock().unwrap().contains_key(id) {
+            return self.sandboxed(id, script);
+        }
+        if options.no_limit {
+            return None;
+        }
+        let kind = self.connections.lock().unwrap().get(id)?.kind();
+        if kind == DbKind::Odbc {
+            return None;
+        }
+        let limit = options.row_limit.unwrap_or(DEFAULT_ROW_LIMIT).max(1);
+        inject_limit(kind, script, limit).map(|sql| (sql, limit))
+    }
+
+    /// The sandbox statement timeout of connection `id`, for backends that enforce it
+    /// per call rather than through a session setting.
+    pub fn statement_timeout_ms(&self, id: &str) -> Option<u64> {
+        self.sandboxes.lock().unwrap().get(id)?.statement_timeout_ms
+    }
+}
+
+/// Append `LIMIT n` (`FETCH FIRST n ROWS ONLY` on Oracle and Firebird) to a single
+/// SELECT that has no top-level LIMIT, FETCH or locking clause. Scripts with several
+/// statements are left alone.
+pub fn inject_limit(kind: DbKind, script: &str, limit: u64) -> Option<String> {
+    let [stmt] = sql::split_statements(script)[..] else {
+        return None;
+    };
+    if sql::classify(stmt) != StatementKind::Select {
+        return None;
+    }
+    let bounded = sql::top_level_words(stmt).iter().any(|(word, depth)| {
+        *depth == 0
+            && matches!(
+                word.as_str(),
+                "LIMIT" | "FETCH" | "FOR" | "INTO" | "LOCK" | "TOP"
+            )
+    });
+    if bounded {
+        return None;
+    }
+    // On its own line in case the statement ends with a `--` comment.
+    Some(match kind {
+        DbKind::Oracle | DbKind::Firebird => format!("{}\nFETCH FIRST {} ROWS ONLY", stmt, limit),
+        _ => format!("{}\nLIMIT {}", stmt, limit),
+    })
+}
@@ -0,0 +1,67 @@
+use super::introspect::ColumnInfo;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long introspection results are served from memory before hitting the server again.
+pub const SCHEMA_CACHE_TTL: Duration = Duration::from_secs(300);
+
+struct Cached<T> {
+    value: T,
+    fetched_at: Instant,
+}
+
+impl<T: Clone> Cached<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            fetched_at: Instant::now(),
+        }
+    }
+
+    fn fresh(&self) -> Option<T> {
+        (self.fetched_at.elapsed() < SCHEMA_CACHE_TTL).then(|| self.value.clone())
+    }
+}
+
+#[derive(Default)]
+struct SchemaEntry {
+    tables: Option<Cached<Vec<String>>>,
+    columns: HashMap<String, Cached<Vec<ColumnInfo>>>,
+}
+
+/// Per-connection schema metadata, keyed by connection id.
+#[derive(Default)]
+pub struct SchemaCache {
+    entries: Mutex<HashMap<String, SchemaEntry>>,
+}
+
+impl SchemaCache {
+    pub fn tables(&self, id: &str) -> Option<Vec<String>> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(id)?.tables.as_ref()?.fresh()
+    }
+
+    pub fn store_tables(&self, id: &str, tables: Vec<String>) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.entry(id.to_string()).or_default().tables = Some(Cached::new(tables));
+    }
+
+    pub fn columns(&self, id: &str, table: &str) -> Option<Vec<ColumnInfo>> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(id)?.columns.get(table)?.fresh()
+    }
+
+    pub fn store_columns(&self, id: &str, table: &str, columns: Vec<ColumnInfo>) {
+        let mut entries = self.entries.lock().unwrap();
+        entries
+            .entry(id.to_string())
+            .or_default()
+            .columns
+            .insert(table.to_string(), Cached::new(columns));
+    }
+
+    pub fn invalidate(&self, id: &str) {
+        self.entries.lock().unwrap().remove(id);
+    }
+}
@@ -0,0 +1,666 @@
+//! Compare the schemas of two open connections and generate the statements that bring
+//! the target in line with the source, e.g. to promote changes from dev to staging.
+
+use super::flavor::Flavor;
+use super::introspect::{fetch_columns, fetch_tables, ColumnInfo};
+use super::sql::quote_ident;
+use super::{AppState, Connection, DbKind};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::{BTreeMap, BTreeSet};
+use tauri::State;
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct DiffOptions {
+    /// Only compare these tables.
+    pub tables: Option<Vec<String>>,
+    /// Generate DROP statements for tables, columns, indexes and foreign keys that only
+    /// exist in the target. Off by default; they are still reported in the diff.
+    pub include_drops: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexInfo {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct ForeignKeyInfo {
+    /// Empty on SQLite, whose foreign keys are unnamed.
+    pub name: String,
+    pub columns: Vec<String>,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableSchema {
+    pub name: String,
+    pub columns: Vec<ColumnInfo>,
+    pub indexes: Vec<IndexInfo>,
+    pub foreign_keys: Vec<ForeignKeyInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ColumnChange {
+    pub name: String,
+    pub source: ColumnInfo,
+    pub target: ColumnInfo,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TableDiff {
+    pub table: String,
+    pub columns_added: Vec<ColumnInfo>,
+    pub columns_removed: Vec<String>,
+    pub columns_changed: Vec<ColumnChange>,
+    pub indexes_added: Vec<IndexInfo>,
+    pub indexes_removed: Vec<String>,
+    pub foreign_keys_added: Vec<ForeignKeyInfo>,
+    pub foreign_keys_removed: Vec<ForeignKeyInfo>,
+}
+
+impl TableDiff {
+    fn is_empty(&self) -> bool {
+        self.columns_added.is_empty()
+            && self.columns_removed.is_empty()
+            && self.columns_changed.is_empty()
+            && self.indexes_added.is_empty()
+            && self.indexes_removed.is_empty()
+            && self.foreign_keys_added.is_empty()
+            && self.foreign_keys_removed.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchemaDiff {
+    /// Tables only in the source, with their full definition.
+    pub tables_added: Vec<TableSchema>,
+    /// Tables only in the target.
+    pub tables_removed: Vec<String>,
+    pub tables_changed: Vec<TableDiff>,
+    /// Statements in the target's dialect. Changes the target dialect can't express
+    /// are included as `--` comments.
+    pub statements: Vec<String>,
+}
+
+/// Diff the schema of `id_a` (the source) against `id_b` (the target).
+#[tauri::command]
+pub async fn diff_schemas(
+    state: State<'_, AppState>,
+    id_a: String,
+    id_b: String,
+    options: Option<DiffOptions>,
+) -> Result<SchemaDiff, String> {
+    let options = options.unwrap_or_default();
+    let source = state.connection(&id_a)?;
+    let target = state.connection(&id_b)?;
+
+    let source_tables =
+        fetch_schema(&source, state.flavor(&id_a), options.tables.as_deref()).await?;
+    let target_tables =
+        fetch_schema(&target, state.flavor(&id_b), options.tables.as_deref()).await?;
+    Ok(diff(
+        &source_tables,
+        &target_tables,
+        target.kind(),
+        &options,
+    ))
+}
+
+async fn fetch_schema(
+    conn: &Connection,
+    flavor: Option<Flavor>,
+    only: Option<&[String]>,
+) -> Result<BTreeMap<String, TableSchema>, String> {
+    let mut schema = BTreeMap::new();
+    for table in fetch_tables(conn, flavor).await? {
+        if only.is_some_and(|only| !only.contains(&table)) {
+            continue;
+        }
+        let mut columns = fetch_columns(conn, &table, flavor).await?;
+        if let Connection::Postgres(pool) = conn {
+            // information_schema drops lengths and precision ("character varying").
+            let types: Vec<(String, String)> = sqlx::query_as(
+                "SELECT attname::text, format_type(atttypid, atttypmod) FROM pg_attribute \
+                 WHERE attrelid = to_regclass($1) AND attnum > 0 AND NOT attisdropped",
+            )
+            .bind(quote_ident(DbKind::Postgres, &table))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            for column in &mut columns {
+                if let Some((_, ty)) = types.iter().find(|(name, _)| *name == column.name) {
+                    column.data_type = ty.clone();
+                }
+            }
+        }
+        let indexes = fetch_indexes(conn, &table).await?;
+        let foreign_keys = fetch_foreign_keys(conn, &table).await?;
+        schema.insert(
+            table.clone(),
+            TableSchema {
+                name: table,
+                columns,
+                indexes,
+                foreign_keys,
+            },
+        );
+    }
+    Ok(schema)
+}
+
+/// Secondary indexes created with CREATE INDEX (or equivalent unique constraints).
+/// Primary keys and expression indexes are left out.
+async fn fetch_indexes(conn: &Connection, table: &str) -> Result<Vec<IndexInfo>, String> {
+    let rows: Vec<(String, bool, String)> = match conn {
+        Connection::Postgres(pool) => {
+            sqlx::query_as(
+                "SELECT i.relname::text, ix.indisunique, a.attname::text \
+             FROM pg_index ix \
+             JOIN pg_class i ON i.oid = ix.indexrelid \
+             CROSS JOIN LATERAL unnest(ix.indkey) WITH ORDINALITY AS k(attnum, n) \
+             JOIN pg_attribute a ON a.attrelid = ix.indrelid AND a.attnum = k.attnum \
+             WHERE ix.indrelid = to_regclass($1) AND NOT ix.indisprimary \
+               AND NOT (0 = ANY (ix.indkey::int2[])) \
+             ORDER BY i.relname, k.n",
+            )
+            .bind(quote_ident(DbKind::Postgres, table))
+            .fetch_all(pool)
+            .await
+        }
+        Connection::MySql(pool) => {
+            sqlx::query_as(
+                "SELECT CAST(INDEX_NAME AS CHAR), NON_UNIQUE = 0, CAST(COLUMN_NAME AS CHAR) \
+             FROM information_schema.STATISTICS \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND INDEX_NAME <> 'PRIMARY' \
+               AND COLUMN_NAME IS NOT NULL \
+             ORDER BY INDEX_NAME, SEQ_IN_INDEX",
+            )
+            .bind(table)
+            .fetch_all(pool)
+            .await
+        }
+        Connection::Sqlite(pool) => {
+            sqlx::query_as(
+                "SELECT l.name, l.\"unique\", i.name \
+                 FROM pragma_index_list(?1) AS l \
+                 JOIN pragma_index_info(l.name) AS i \
+                 WHERE l.origin = 'c' \
+                 ORDER BY l.name, i.seqno",
+            )
+            .bind(table)
+            .fetch_all(pool)
+            .await
+        }
+        Connection::Oracle(_)
+        | Connection::Mongo(_)
+        | Connection::DuckDb(_)
+        | Connection::ClickHouse(_)
+        | Connection::Redis(_)
+        | Connection::Cassandra(_)
+        | Connection::Surreal(_)
+        | Connection::BigQuery(_)
+        | Connection::Firebird(_)
+        | Connection::Odbc(_) => return Err(conn.unsupported("Schema diff")),
+    }
+    .map_err(|e| e.to_string())?;
+
+    let mut indexes: Vec<IndexInfo> = Vec::new();
+    for (name, unique, column) in rows {
+        match indexes.last_mut() {
+            Some(index) if index.name == name => index.columns.push(column),
+            _ => indexes.push(IndexInfo {
+                name,
+                columns: vec![column],
+                unique,
+            }),
+        }
+    }
+    Ok(indexes)
+}
+
+async fn fetch_foreign_keys(conn: &Connection, table: &str) -> Result<Vec<ForeignKeyInfo>, String> {
+    // (constraint, column, referenced table, referenced column), ordered by position.
+    let rows: Vec<(String, String, String, String)> = match conn {
+        Connection::Postgres(pool) => sqlx::query_as(
+            "SELECT c.conname::text, a.attname::text, c.confrelid::regclass::text, fa.attname::text \
+             FROM pg_constraint c \
+             CROSS JOIN LATERAL unnest(c.conkey, c.confkey) WITH ORDINALITY AS k(attnum, fattnum, n) \
+             JOIN pg_attribute a ON a.attrelid = c.conrelid AND a.attnum = k.attnum \
+             JOIN pg_attribute fa ON fa.attrelid = c.confrelid AND fa.attnum = k.fattnum \
+             WHERE c.contype = 'f' AND c.conrelid = to_regclass($1) \
+             ORDER BY c.conname, k.n",
+        )
+        .bind(quote_ident(DbKind::Postgres, table))
+        .fetch_all(pool)
+        .await,
+        Connection::MySql(pool) => sqlx::query_as(
+            "SELECT CAST(CONSTRAINT_NAME AS CHAR), CAST(COLUMN_NAME AS CHAR), \
+                    CAST(REFERENCED_TABLE_NAME AS CHAR), CAST(REFERENCED_COLUMN_NAME AS CHAR) \
+             FROM information_schema.KEY_COLUMN_USAGE \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? \
+               AND REFERENCED_TABLE_NAME IS NOT NULL \
+             ORDER BY CONSTRAINT_NAME, ORDINAL_POSITION",
+        )
+        .bind(table)
+        .fetch_all(pool)
+        .await,
+        Connection::Sqlite(pool) => {
+            let rows = sqlx::query(
+                "SELECT id, \"from\", \"table\", \"to\" FROM pragma_foreign_key_list(?) \
+                 ORDER BY id, seq",
+            )
+            .bind(table)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            return Ok(group_foreign_keys(
+                rows.iter()
+                    .map(|row| {
+                        let id: i64 = row.try_get(0).unwrap_or_default();
+                        (
+                            id.to_string(),
+                            row.try_get(1).unwrap_or_default(),
+                            row.try_get(2).unwrap_or_default(),
+                            row.try_get::<Option<String>, _>(3)
+                                .unwrap_or_default()
+                                .unwrap_or_default(),
+                        )
+                    })
+                    .collect(),
+                true,
+            ));
+        }
+        Connection::Oracle(_)
+        | Connection::Mongo(_)
+        | Connection::DuckDb(_)
+        | Connection::ClickHouse(_)
+        | Connection::Redis(_)
+        | Connection::Cassandra(_)
+        | Connection::Surreal(_)
+        | Connection::BigQuery(_)
+        | Connection::Firebird(_)
+        | Connection::Odbc(_) => return Err(conn.unsupported("Schema diff")),
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(group_foreign_keys(rows, false))
+}
+
+fn group_foreign_keys(
+    rows: Vec<(String, String, String, String)>,
+    unnamed: bool,
+) -> Vec<ForeignKeyInfo> {
+    let mut keys: Vec<(String, ForeignKeyInfo)> = Vec::new();
+    for (constraint, column, referenced_table, referenced_column) in rows {
+        match keys.last_mut() {
+            Some((id, key)) if *id == constraint => {
+                key.columns.push(column);
+                key.referenced_columns.push(referenced_column);
+            }
+            _ => keys.push((
+                constraint.clone(),
+                ForeignKeyInfo {
+                    name: if unnamed { String::new() } else { constraint },
+                    columns: vec![column],
+                    referenced_table,
+                    referenced_columns: vec![referenced_column],
+                },
+            )),
+        }
+    }
+    keys.into_iter().map(|(_, key)| key).collect()
+}
+
+fn diff(
+    source: &BTreeMap<String, TableSchema>,
+    target: &BTreeMap<String, TableSchema>,
+    kind: DbKind,
+    options: &DiffOptions,
+) -> SchemaDiff {
+    let mut result = SchemaDiff {
+        tables_added: Vec::new(),
+        tables_removed: Vec::new(),
+        tables_changed: Vec::new(),
+        statements: Vec::new(),
+    };
+
+    for (name, table) in source {
+        match target.get(name) {
+            None => {
+                result.statements.extend(create_table(kind, table));
+                result.tables_added.push(table.clone());
+            }
+            Some(existing) => {
+                let changes = diff_table(table, existing);
+                if !changes.is_empty() {
+                    result
+                        .statements
+                        .extend(alter_table(kind, &changes, options.include_drops));
+                    result.tables_changed.push(changes);
+                }
+            }
+        }
+    }
+    for name in target.keys().filter(|name| !source.contains_key(*name)) {
+        if options.include_drops {
+            result
+                .statements
+                .push(format!("DROP TABLE {}", quote_ident(kind, name)));
+        }
+        result.tables_removed.push(name.clone());
+    }
+    result
+}
+
+fn diff_table(source: &TableSchema, target: &TableSchema) -> TableDiff {
+    let mut changes = TableDiff {
+        table: source.name.clone(),
+        ..TableDiff::default()
+    };
+
+    for column in &source.columns {
+        match target.columns.iter().find(|c| c.name == column.name) {
+            None => changes.columns_added.push(column.clone()),
+            Some(existing) if !same_column(column, existing) => {
+                changes.columns_changed.push(ColumnChange {
+                    name: column.name.clone(),
+                    source: column.clone(),
+                    target: existing.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    changes.columns_removed = target
+        .columns
+        .iter()
+        .filter(|c| !source.columns.iter().any(|s| s.name == c.name))
+        .map(|c| c.name.clone())
+        .collect();
+
+    for index in &source.indexes {
+        match target.indexes.iter().find(|i| i.name == index.name) {
+            Some(existing)
+                if existing.columns == index.columns && existing.unique == index.unique => {}
+            Some(existing) => {
+                changes.indexes_removed.push(existing.name.clone());
+                changes.indexes_added.push(index.clone());
+            }
+            None => changes.indexes_added.push(index.clone()),
+        }
+    }
+    changes.indexes_removed.extend(
+        target
+            .indexes
+            .iter()
+            .filter(|i| !source.indexes.iter().any(|s| s.name == i.name))
+            .map(|i| i.name.clone()),
+    );
+
+    // Foreign keys are matched on what they reference, not their (possibly generated) names.
+    let signature = |key: &ForeignKeyInfo| {
+        (
+            key.columns.clone(),
+            key.referenced_table.clone(),
+            key.referenced_columns.clone(),
+        )
+    };
+    let source_keys: BTreeSet<_> = source.foreign_keys.iter().map(signature).collect();
+    let target_keys: BTreeSet<_> = target.foreign_keys.iter().map(signature).collect();
+    changes.foreign_keys_added = source
+        .foreign_keys
+        .iter()
+        .filter(|k| !target_keys.contains(&signature(k)))
+        .cloned()
+        .collect();
+    changes.foreign_keys_removed = target
+        .foreign_keys
+        .iter()
+        .filter(|k| !source_keys.contains(&signature(k)))
+        .cloned()
+        .collect();
+
+    changes
+}
+
+fn same_column(a: &ColumnInfo, b: &ColumnInfo) -> bool {
+    a.data_type.eq_ignore_ascii_case(&b.data_type)
+        && a.nullable == b.nullable
+        && a.default == b.default
+        && a.primary_key == b.primary_key
+}
+
+/// `name type [NOT NULL] [DEFAULT ...]`. Postgres sequence defaults become identity
+/// columns, since the sequence won't exist on the target.
+fn column_definition(kind: DbKind, column: &ColumnInfo) -> String {
+    let mut definition = format!("{} {}", quote_ident(kind, &column.name), column.data_type);
+    let sequence = column
+        .default
+        .as_deref()
+        .is_some_and(|d| d.starts_with("nextval("));
+    if kind == DbKind::Postgres && sequence {
+        definition.push_str(" GENERATED BY DEFAULT AS IDENTITY");
+    } else if let Some(default) = &column.default {
+        definition.push_str(&format!(" DEFAULT {}", default));
+    }
+    if !column.nullable {
+        definition.push_str(" NOT NULL");
+    }
+    definition
+}
+
+fn column_list(kind: DbKind, columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|c| quote_ident(kind, c))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn foreign_key_clause(kind: DbKind, key: &ForeignKeyInfo) -> String {
+    format!(
+        "FOREIGN KEY ({}) REFERENCES {} ({})",
+        column_list(kind, &key.columns),
+        quote_ident(kind, &key.referenced_table),
+        column_list(kind, &key.referenced_columns)
+    )
+}
+
+fn create_index(kind: DbKind, table: &str, index: &IndexInfo) -> String {
+    format!(
+        "CREATE {}INDEX {} ON {} ({})",
+        if index.unique { "UNIQUE " } else { "" },
+        quote_ident(kind, &index.name),
+        quote_ident(kind, table),
+        column_list(kind, &index.columns)
+    )
+}
+
+fn create_table(kind: DbKind, table: &TableSchema) -> Vec<String> {
+    let mut parts: Vec<String> = table
+        .columns
+        .iter()
+        .map(|c| column_definition(kind, c))
+        .collect();
+    let key: Vec<String> = table
+        .columns
+        .iter()
+        .filter(|c| c.primary_key)
+        .map(|c| c.name.clone())
+        .collect();
+    if !key.is_empty() {
+        parts.push(format!("PRIMARY KEY ({})", column_list(kind, &key)));
+    }
+    // SQLite can't add foreign keys later, so they go in the table definition.
+    if kind == DbKind::Sqlite {
+        parts.extend(
+            table
+                .foreign_keys
+                .iter()
+                .map(|k| foreign_key_clause(kind, k)),
+        );
+    }
+
+    let mut statements = vec![format!(
+        "CREATE TABLE {} (\n  {}\n)",
+        quote_ident(kind, &table.name),
+        parts.join(",\n  ")
+    )];
+    statements.extend(
+        table
+            .indexes
+            .iter()
+            .map(|index| create_index(kind, &table.name, index)),
+    );
+    if kind != DbKind::Sqlite {
+        statements.extend(
+            table
+                .foreign_keys
+                .iter()
+                .map(|key| add_foreign_key(kind, &table.name, key)),
+        );
+    }
+    statements
+}
+
+fn add_foreign_key(kind: DbKind, table: &str, key: &ForeignKeyInfo) -> String {
+    let constraint = if key.name.is_empty() {
+        String::new()
+    } else {
+        format!("CONSTRAINT {} ", quote_ident(kind, &key.name))
+    };
+    format!(
+        "ALTER TABLE {} ADD {}{}",
+        quote_ident(kind, table),
+        constraint,
+        foreign_key_clause(kind, key)
+    )
+}
+
+fn alter_table(kind: DbKind, changes: &TableDiff, include_drops: bool) -> Vec<String> {
+    let table = quote_ident(kind, &changes.table);
+    let mut statements = Vec::new();
+
+    if include_drops {
+        for key in &changes.foreign_keys_removed {
+            statements.push(match kind {
+                DbKind::MySql => format!(
+                    "ALTER TABLE {} DROP FOREIGN KEY {}",
+                    table,
+                    quote_ident(kind, &key.name)
+                ),
+                DbKind::Sqlite => format!(
+                    "-- SQLite cannot drop a foreign key on {} without rebuilding the table",
+                    table
+                ),
+                _ => format!(
+                    "ALTER TABLE {} DROP CONSTRAINT {}",
+                    table,
+                    quote_ident(kind, &key.name)
+                ),
+            });
+        }
+    }
+    for index in &changes.indexes_removed {
+        // Changed indexes are dropped and recreated even without `include_drops`.
+        let recreated = changes.indexes_added.iter().any(|i| i.name == *index);
+        if include_drops || recreated {
+            statements.push(match kind {
+                DbKind::MySql => format!("DROP INDEX {} ON {}", quote_ident(kind, index), table),
+                _ => format!("DROP INDEX {}", quote_ident(kind, index)),
+            });
+        }
+    }
+
+    for column in &changes.columns_added {
+        statements.push(format!(
+            "ALTER TABLE {} ADD COLUMN {}",
+            table,
+            column_definition(kind, column)
+        ));
+    }
+    for change in &changes.columns_changed {
+        statements.extend(alter_column(kind, &table, change));
+    }
+    if include_drops {
+        for column in &changes.columns_removed {
+            statements.push(format!(
+                "ALTER TABLE {} DROP COLUMN {}",
+                table,
+                quote_ident(kind, column)
+            ));
+        }
+    }
+
+    for index in &changes.indexes_added {
+        statements.push(create_index(kind, &changes.table, index));
+    }
+    for key in &changes.foreign_keys_added {
+        statements.push(match kind {
+            DbKind::Sqlite => format!(
+                "-- SQLite cannot add a foreign key to {} without rebuilding the table",
+                table
+            ),
+            _ => add_foreign_key(kind, &changes.table, key),
+        });
+    }
+    statements
+}
+
+fn alter_column(kind: DbKind, table: &str, change: &ColumnChange) -> Vec<String> {
+    let (source, target) = (&change.source, &change.target);
+    let name = quote_ident(kind, &change.name);
+    let mut statements = Vec::new();
+
+    if source.primary_key != target.primary_key {
+        statements.push(format!(
+            "-- Primary key membership of {}.{} differs; change the key by hand",
+            table, name
+        ));
+    }
+    match kind {
+        DbKind::Postgres => {
+            if !source.data_type.eq_ignore_ascii_case(&target.data_type) {
+                statements.push(format!(
+                    "ALTER TABLE {} ALTER COLUMN {} TYPE {}",
+                    table, name, source.data_type
+                ));
+            }
+            if source.nullable != target.nullable {
+                statements.push(format!(
+                    "ALTER TABLE {} ALTER COLUMN {} {} NOT NULL",
+                    table,
+                    name,
+                    if source.nullable { "DROP" } else { "SET" }
+                ));
+            }
+            if source.default != target.default {
+                statements.push(match &source.default {
+                    Some(default) => format!(
+                        "ALTER TABLE {} ALTER COLUMN {} SET DEFAULT {}",
+                        table, name, default
+                    ),
+                    None => format!("ALTER TABLE {} ALTER COLUMN {} DROP DEFAULT", table, name),
+                });
+            }
+        }
+        DbKind::MySql => {
+            if !same_column(source, target) {
+                statements.push(format!(
+                    "ALTER TABLE {} MODIFY COLUMN {}",
+                    table,
+                    column_definition(kind, source)
+                ));
+            }
+        }
+        _ => statements.push(format!(
+            "-- SQLite cannot alter column {}.{}; rebuild the table to change it",
+            table, name
+        )),
+    }
+    statements
+}
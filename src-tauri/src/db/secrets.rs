@@ -0,0 +1,132 @@
+//! `vault:<path>#<field>` references in connection settings, resolved at connect time
+//! through the HashiCorp Vault HTTP API so database passwords never live on disk.
+
+use super::AppState;
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::Duration;
+use tauri::State;
+use zeroize::Zeroizing;
+
+const VAULT_PREFIX: &str = "vault:";
+const VAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct VaultConfig {
+    /// Falls back to `VAULT_ADDR`.
+    pub address: Option<String>,
+    /// Falls back to `VAULT_TOKEN`, then `~/.vault-token` as written by `vault login`.
+    pub token: Option<String>,
+    /// Vault Enterprise namespace, sent as `X-Vault-Namespace`.
+    pub namespace: Option<String>,
+}
+
+/// Set the Vault address and token used to resolve `vault:` references. Held in memory only.
+#[tauri::command]
+pub fn set_vault_config(state: State<'_, AppState>, config: VaultConfig) {
+    *state.vault.lock().unwrap() = config;
+}
+
+/// Resolve `value` if it is a `vault:` reference; otherwise return it unchanged.
+pub async fn resolve(state: &AppState, value: &str) -> Result<Zeroizing<String>, String> {
+    let Some(reference) = value.strip_prefix(VAULT_PREFIX) else {
+        return Ok(Zeroizing::new(value.to_string()));
+    };
+    let (path, field) = reference
+        .rsplit_once('#')
+        .filter(|(path, field)| !path.is_empty() && !field.is_empty())
+        .ok_or("Vault references look like vault:secret/path#field")?;
+    let path = path.trim_matches('/');
+
+    let config = state.vault.lock().unwrap().clone();
+    let address = config
+        .address
+        .or_else(|| std::env::var("VAULT_ADDR").ok())
+        .ok_or("Vault address is not configured (set it in settings or VAULT_ADDR)")?;
+    let token = config
+        .token
+        .or_else(|| std::env::var("VAULT_TOKEN").ok())
+        .or_else(token_from_file)
+        .map(Zeroizing::new)
+        .ok_or("No Vault token (log in with `vault login` or set one in settings)")?;
+
+    let client = VaultClient {
+        http: reqwest::Client::builder()
+            .timeout(VAULT_TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?,
+        address: address.trim_end_matches('/').to_string(),
+        token,
+        namespace: config.namespace,
+    };
+
+    let (api_path, kv2) = client.kv_path(path).await;
+    let body = client.get(&api_path).await?;
+    let data = if kv2 {
+        &body["data"]["data"]
+    } else {
+        &body["data"]
+    };
+    match data.get(field) {
+        Some(Value::String(secret)) => Ok(Zeroizing::new(secret.clone())),
+        Some(other) => Ok(Zeroizing::new(other.to_string())),
+        None => Err(format!("Vault secret {} has no field \"{}\"", path, field)),
+    }
+}
+
+fn token_from_file() -> Option<String> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    let token = std::fs::read_to_string(std::path::Path::new(&home).join(".vault-token")).ok()?;
+    Some(token.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+struct VaultClient {
+    http: reqwest::Client,
+    address: String,
+    token: Zeroizing<String>,
+    namespace: Option<String>,
+}
+
+impl VaultClient {
+    async fn get(&self, path: &str) -> Result<Value, String> {
+        let mut request = self
+            .http
+            .get(format!("{}/v1/{}", self.address, path))
+            .header("X-Vault-Token", self.token.as_str());
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Could not reach Vault: {}", e))?;
+        match response.status().as_u16() {
+            200 => response.json().await.map_err(|e| e.to_string()),
+            403 => Err(format!(
+                "Vault denied access to {} (check the token and policy)",
+                path
+            )),
+            404 => Err(format!("Vault secret {} not found", path)),
+            status => Err(format!("Vault returned HTTP {} for {}", status, path)),
+        }
+    }
+
+    /// The API path for a secret and whether it lives in a KV v2 engine, whose reads go
+    /// through `<mount>/data/<path>`. Falls back to the path as given if the mount
+    /// can't be looked up.
+    async fn kv_path(&self, path: &str) -> (String, bool) {
+        let Ok(mount) = self.get(&format!("sys/internal/ui/mounts/{}", path)).await else {
+            return (path.to_string(), false);
+        };
+        let mount_path = mount["data"]["path"].as_str().unwrap_or_default();
+        let version = mount["data"]["options"]["version"].as_str();
+        match (version, path.strip_prefix(mount_path)) {
+            (Some("2"), Some(rest)) if !rest.starts_with("data/") => {
+                (format!("{}data/{}", mount_path, rest), true)
+            }
+            (Some("2"), _) => (path.to_string(), true),
+            _ => (path.to_string(), false),
+        }
+    }
+}
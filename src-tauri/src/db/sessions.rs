@@ -0,0 +1,311 @@
+//! Live server sessions: `pg_stat_activity` / the MySQL process list in one shape, the
+//! lock waits between them, and a way to cancel or terminate them.
+
+use super::policy::Capability;
+use super::{AppState, Connection};
+use serde::Serialize;
+use std::collections::HashSet;
+use tauri::State;
+
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    pub pid: i64,
+    pub user: Option<String>,
+    pub database: Option<String>,
+    /// `active`, `idle`, `idle in transaction`, ... on Postgres; the command (`query`,
+    /// `sleep`, ...) on MySQL.
+    pub state: Option<String>,
+    pub query: Option<String>,
+    /// Time since the current query (or, when idle, the session) started.
+    pub duration_ms: Option<i64>,
+    pub client: Option<String>,
+    /// The session is one of this app's own pooled connections.
+    pub is_current: bool,
+}
+
+type SessionRow = (
+    i64,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<i64>,
+    Option<String>,
+    bool,
+);
+
+#[tauri::command]
+pub async fn get_active_sessions(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<Vec<SessionInfo>, String> {
+    let rows: Vec<SessionRow> = match state.connection(&id)? {
+        Connection::Postgres(pool) => sqlx::query_as(
+            "SELECT pid::int8, usename::text, datname::text, state, query, \
+                    (EXTRACT(EPOCH FROM now() - COALESCE(query_start, backend_start)) * 1000)::int8, \
+                    client_addr::text, pid = pg_backend_pid() \
+             FROM pg_stat_activity \
+             WHERE backend_type = 'client backend' \
+             ORDER BY 6 DESC NULLS LAST",
+        )
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| e.to_string())?,
+        Connection::MySql(pool) => sqlx::query_as(
+            "SELECT CAST(ID AS SIGNED), CAST(USER AS CHAR), CAST(DB AS CHAR), \
+                    CAST(LOWER(COMMAND) AS CHAR), CAST(INFO AS CHAR), CAST(TIME AS SIGNED) * 1000, \
+                    CAST(HOST AS CHAR), ID = CONNECTION_ID() \
+             FROM information_schema.PROCESSLIST \
+             WHERE COMMAND <> 'Daemon' \
+             ORDER BY TIME DESC",
+        )
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| e.to_string())?,
+        Connection::Sqlite(_) => return Err("SQLite has no server sessions".to_string()),
+        conn @ (Connection::Oracle(_)
+        | Connection::Mongo(_)
+        | Connection::DuckDb(_)
+        | Connection::ClickHouse(_)
+        | Connection::Redis(_)
+        | Connection::Cassandra(_)
+        | Connection::Surreal(_)
+        | Connection::BigQuery(_)
+        | Connection::Firebird(_)
+        | Connection::Odbc(_)) => return Err(conn.unsupported("Session management")),
+    };
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(pid, user, database, state, query, duration_ms, client, is_current)| SessionInfo {
+                pid,
+                user,
+                database,
+                state,
+                query,
+                duration_ms,
+                client,
+                is_current,
+            },
+        )
+        .collect())
+}
+
+/// Terminate session `pid`, or with `cancel_only` just cancel its running query.
+/// Returns whether the server reported success. Requires an admin connection.
+#[tauri::command]
+pub async fn terminate_session(
+    state: State<'_, AppState>,
+    id: String,
+    pid: i64,
+    cancel_only: Option<bool>,
+) -> Result<bool, String> {
+    let conn = state.connection(&id)?;
+    state.require_capability(&id, Capability::Admin)?;
+    let cancel_only = cancel_only.unwrap_or(false);
+
+    let (statement, terminated) = match conn {
+        Connection::Postgres(pool) => {
+            let function = if cancel_only {
+                "pg_cancel_backend"
+            } else {
+                "pg_terminate_backend"
+            };
+            let terminated = sqlx::query_scalar(&format!("SELECT {}($1::int4)", function))
+                .bind(pid)
+                .fetch_one(&pool)
+                .await
+                .map_err(|e| e.to_string());
+            (format!("SELECT {}({})", function, pid), terminated)
+        }
+        Connection::MySql(pool) => {
+            // KILL can't take a bind parameter; `pid` is an integer so this is safe.
+            let sql = if cancel_only {
+                format!("KILL QUERY {}", pid)
+            } else {
+                format!("KILL {}", pid)
+            };
+            let terminated = sqlx::query(&sql)
+                .execute(&pool)
+                .await
+                .map(|_| true)
+                .map_err(|e| e.to_string());
+            (sql, terminated)
+        }
+        Connection::Sqlite(_) => return Err("SQLite has no server sessions".to_string()),
+        conn @ (Connection::Oracle(_)
+        | Connection::Mongo(_)
+        | Connection::DuckDb(_)
+        | Connection::ClickHouse(_)
+        | Connection::Redis(_)
+        | Connection::Cassandra(_)
+        | Connection::Surreal(_)
+        | Connection::BigQuery(_)
+        | Connection::Firebird(_)
+        | Connection::Odbc(_)) => return Err(conn.unsupported("Session management")),
+    };
+    state.audit(
+        &id,
+        &statement,
+        terminated.as_ref().err().map(String::as_str),
+    );
+    terminated
+}
+
+#[derive(Debug, Serialize)]
+pub struct LockWait {
+    pub waiting_pid: i64,
+    pub waiting_user: Option<String>,
+    pub waiting_query: Option<String>,
+    pub wait_duration_ms: Option<i64>,
+    pub blocking_pid: i64,
+    pub blocking_user: Option<String>,
+    pub blocking_query: Option<String>,
+    pub blocking_state: Option<String>,
+    /// e.g. `relation`/`transactionid` on Postgres, `RECORD`/`TABLE` on MySQL.
+    pub lock_type: Option<String>,
+    pub lock_mode: Option<String>,
+    /// The table being waited on, when known.
+    pub object: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LockReport {
+    /// One entry per (waiting, blocking) pair; follow `blocking_pid` to walk a chain.
+    pub waits: Vec<LockWait>,
+    /// Sessions that block others without waiting themselves: the ones to kill.
+    pub root_blockers: Vec<i64>,
+}
+
+type LockRow = (
+    i64,
+    Option<String>,
+    Option<String>,
+    Option<i64>,
+    i64,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+const PG_LOCK_WAITS: &str = "\
+    SELECT w.pid::int8, w.usename::text, w.query, \
+           (EXTRACT(EPOCH FROM now() - w.query_start) * 1000)::int8, \
+           b.pid::int8, b.usename::text, b.query, b.state, \
+           l.locktype, l.mode, l.relation::regclass::text \
+    FROM pg_stat_activity w \
+    CROSS JOIN LATERAL unnest(pg_blocking_pids(w.pid)) AS blocker(pid) \
+    JOIN pg_stat_activity b ON b.pid = blocker.pid \
+    LEFT JOIN LATERAL ( \
+        SELECT locktype, mode, relation FROM pg_locks \
+        WHERE pid = w.pid AND NOT granted LIMIT 1 \
+    ) l ON true \
+    ORDER BY 4 DESC NULLS LAST";
+
+/// MySQL 8: performance_schema.data_lock_waits.
+const MYSQL_LOCK_WAITS: &str = "\
+    SELECT CAST(r.trx_mysql_thread_id AS SIGNED), CAST(rp.USER AS CHAR), CAST(r.trx_query AS CHAR), \
+           CAST(TIMESTAMPDIFF(MICROSECOND, r.trx_wait_started, NOW()) DIV 1000 AS SIGNED), \
+           CAST(b.trx_mysql_thread_id AS SIGNED), CAST(bp.USER AS CHAR), CAST(b.trx_query AS CHAR), \
+           CAST(LOWER(bp.COMMAND) AS CHAR), CAST(dl.LOCK_TYPE AS CHAR), CAST(dl.LOCK_MODE AS CHAR), \
+           CAST(CONCAT(dl.OBJECT_SCHEMA, '.', dl.OBJECT_NAME) AS CHAR) \
+    FROM performance_schema.data_lock_waits w \
+    JOIN information_schema.INNODB_TRX r ON r.trx_id = w.REQUESTING_ENGINE_TRANSACTION_ID \
+    JOIN information_schema.INNODB_TRX b ON b.trx_id = w.BLOCKING_ENGINE_TRANSACTION_ID \
+    LEFT JOIN information_schema.PROCESSLIST rp ON rp.ID = r.trx_mysql_thread_id \
+    LEFT JOIN information_schema.PROCESSLIST bp ON bp.ID = b.trx_mysql_thread_id \
+    LEFT JOIN performance_schema.data_locks dl ON dl.ENGINE_LOCK_ID = w.REQUESTING_ENGINE_LOCK_ID \
+    ORDER BY 4 DESC";
+
+/// MySQL 5.7: information_schema.INNODB_LOCK_WAITS, removed in 8.0.
+const MYSQL57_LOCK_WAITS: &str = "\
+    SELECT CAST(r.trx_mysql_thread_id AS SIGNED), CAST(rp.USER AS CHAR), CAST(r.trx_query AS CHAR), \
+           CAST(TIMESTAMPDIFF(MICROSECOND, r.trx_wait_started, NOW()) DIV 1000 AS SIGNED), \
+           CAST(b.trx_mysql_thread_id AS SIGNED), CAST(bp.USER AS CHAR), CAST(b.trx_query AS CHAR), \
+           CAST(LOWER(bp.COMMAND) AS CHAR), CAST(l.lock_type AS CHAR), CAST(l.lock_mode AS CHAR), \
+           CAST(l.lock_table AS CHAR) \
+    FROM information_schema.INNODB_LOCK_WAITS w \
+    JOIN information_schema.INNODB_TRX r ON r.trx_id = w.requesting_trx_id \
+    JOIN information_schema.INNODB_TRX b ON b.trx_id = w.blocking_trx_id \
+    LEFT JOIN information_schema.PROCESSLIST rp ON rp.ID = r.trx_mysql_thread_id \
+    LEFT JOIN information_schema.PROCESSLIST bp ON bp.ID = b.trx_mysql_thread_id \
+    LEFT JOIN information_schema.INNODB_LOCKS l ON l.lock_id = w.requested_lock_id \
+    ORDER BY 4 DESC";
+
+/// Current lock waits and who is causing them.
+#[tauri::command]
+pub async fn get_locks(state: State<'_, AppState>, id: String) -> Result<LockReport, String> {
+    let rows: Vec<LockRow> = match state.connection(&id)? {
+        Connection::Postgres(pool) => sqlx::query_as(PG_LOCK_WAITS)
+            .fetch_all(&pool)
+            .await
+            .map_err(|e| e.to_string())?,
+        Connection::MySql(pool) => match sqlx::query_as(MYSQL_LOCK_WAITS).fetch_all(&pool).await {
+            Ok(rows) => rows,
+            Err(_) => sqlx::query_as(MYSQL57_LOCK_WAITS)
+                .fetch_all(&pool)
+                .await
+                .map_err(|e| e.to_string())?,
+        },
+        Connection::Sqlite(_) => return Err("SQLite has no server sessions".to_string()),
+        conn @ (Connection::Oracle(_)
+        | Connection::Mongo(_)
+        | Connection::DuckDb(_)
+        | Connection::ClickHouse(_)
+        | Connection::Redis(_)
+        | Connection::Cassandra(_)
+        | Connection::Surreal(_)
+        | Connection::BigQuery(_)
+        | Connection::Firebird(_)
+        | Connection::Odbc(_)) => return Err(conn.unsupported("Session management")),
+    };
+
+    let waits: Vec<LockWait> = rows
+        .into_iter()
+        .map(
+            |(
+                waiting_pid,
+                waiting_user,
+                waiting_query,
+                wait_duration_ms,
+                blocking_pid,
+                blocking_user,
+                blocking_query,
+                blocking_state,
+                lock_type,
+                lock_mode,
+                object,
+            )| LockWait {
+                waiting_pid,
+                waiting_user,
+                waiting_query,
+                wait_duration_ms,
+                blocking_pid,
+                blocking_user,
+                blocking_query,
+                blocking_state,
+                lock_type,
+                lock_mode,
+                object,
+            },
+        )
+        .collect();
+
+    let waiting: HashSet<i64> = waits.iter().map(|w| w.waiting_pid).collect();
+    let mut root_blockers: Vec<i64> = waits
+        .iter()
+        .map(|w| w.blocking_pid)
+        .filter(|pid| !waiting.contains(pid))
+        .collect();
+    root_blockers.sort_unstable();
+    root_blockers.dedup();
+
+    Ok(LockReport {
+        waits,
+        root_blockers,
+    })
+}
@@ -0,0 +1,43 @@
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+type Flight<V> = Shared<BoxFuture<'static, Result<V, String>>>;
+
+/// Coalesces identical concurrent requests: while a request for a key is in flight,
+/// further callers with the same key await its result instead of starting their own.
+pub struct SingleFlight<V> {
+    inflight: Mutex<HashMap<String, Flight<V>>>,
+}
+
+impl<V> Default for SingleFlight<V> {
+    fn default() -> Self {
+        Self {
+            inflight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<V: Clone + Send + Sync + 'static> SingleFlight<V> {
+    pub async fn run<F>(&self, key: String, start: F) -> Result<V, String>
+    where
+        F: FnOnce() -> BoxFuture<'static, Result<V, String>>,
+    {
+        let flight = {
+            let mut inflight = self.inflight.lock().unwrap();
+            inflight
+                .entry(key.clone())
+                .or_insert_with(|| start().shared())
+                .clone()
+        };
+
+        let result = flight.clone().await;
+
+        // Only clear our own flight; a newer one may already have replaced it.
+        let mut inflight = self.inflight.lock().unwrap();
+        if inflight.get(&key).is_some_and(|f| f.ptr_eq(&flight)) {
+            inflight.remove(&key);
+        }
+        result
+    }
+}
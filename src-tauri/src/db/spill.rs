@@ -0,0 +1,181 @@
+use super::convert::{QueryOutput, ResultFormat, RowSet, Truncation};
+use super::{generate_id, AppState};
+use serde_json::Value;
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous,
+};
+use sqlx::Row;
+use std::path::PathBuf;
+use tauri::State;
+
+/// Hard ceiling for a spilled result; beyond this even the disk store gives up.
+pub const MAX_SPILLED_ROWS: u64 = 20_000_000;
+
+/// Rows returned inline with a spilled result and the default page size for `fetch_spilled`.
+pub const SPILL_PAGE_SIZE: usize = 1_000;
+
+/// Converted rows that outgrew the memory budget, parked in a temporary SQLite file.
+#[derive(Clone)]
+pub struct SpillFile {
+    pool: SqlitePool,
+    path: PathBuf,
+    pub columns: Vec<String>,
+    pub total_rows: u64,
+    /// Set when the result was cut short at a cap.
+    pub truncated: Option<Truncation>,
+}
+
+impl SpillFile {
+    pub async fn create(columns: Vec<String>) -> Result<Self, String> {
+        let path =
+            std::env::temp_dir().join(format!("{}.sqlite", generate_id("db-connector-spill")));
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Off)
+            .synchronous(SqliteSynchronous::Off);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .map_err(|e| e.to_string())?;
+        sqlx::query("CREATE TABLE spill (data TEXT NOT NULL)")
+            .execute(&pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            pool,
+            path,
+            columns,
+            total_rows: 0,
+            truncated: None,
+        })
+    }
+
+    pub async fn append(&mut self, rows: Vec<Vec<Value>>) -> Result<(), String> {
+        if self.total_rows + rows.len() as u64 > MAX_SPILLED_ROWS {
+            return Err(format!(
+                "Query returned more than {} rows; add a LIMIT clause to narrow the result",
+                MAX_SPILLED_ROWS
+            ));
+        }
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        for row in &rows {
+            let data = serde_json::to_string(row).map_err(|e| e.to_string())?;
+            sqlx::query("INSERT INTO spill (data) VALUES (?)")
+                .bind(data)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.total_rows += rows.len() as u64;
+        Ok(())
+    }
+
+    pub async fn page(&self, offset: u64, limit: usize) -> Result<RowSet, String> {
+        // Rows are only ever appended, so rowid is the 1-based row position.
+        let rows = sqlx::query("SELECT data FROM spill WHERE rowid > ? ORDER BY rowid LIMIT ?")
+            .bind(offset as i64)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut set = RowSet {
+            columns: self.columns.clone(),
+            rows: Vec::with_capacity(rows.len()),
+            affected: None,
+            truncated: None,
+        };
+        for row in rows {
+            let data: String = row.try_get(0).map_err(|e| e.to_string())?;
+            set.rows
+                .push(serde_json::from_str(&data).map_err(|e| e.to_string())?);
+        }
+        Ok(set)
+    }
+
+    pub async fn remove(self) {
+        self.pool.close().await;
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Build the response for a spilled result: the first page inline plus a handle the
+/// frontend can use to page through the rest.
+pub async fn register(
+    state: &AppState,
+    conn_id: &str,
+    file: SpillFile,
+    format: ResultFormat,
+) -> Result<QueryOutput, String> {
+    let page = file.page(0, SPILL_PAGE_SIZE).await?;
+    let spill_id = generate_id("spill");
+    let output = QueryOutput::Spilled {
+        spill_id: spill_id.clone(),
+        total_rows: file.total_rows,
+        columns: file.columns.clone(),
+        page: Box::new(page.into_output(format)),
+    };
+    state
+        .spills
+        .lock()
+        .unwrap()
+        .insert(spill_id, (conn_id.to_string(), file));
+    Ok(output)
+}
+
+/// Remove every spilled result belonging to a connection.
+pub async fn release_for_connection(state: &AppState, conn_id: &str) {
+    let files: Vec<SpillFile> = {
+        let mut spills = state.spills.lock().unwrap();
+        let ids: Vec<String> = spills
+            .iter()
+            .filter(|(_, (owner, _))| owner == conn_id)
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.iter()
+            .filter_map(|id| spills.remove(id))
+            .map(|(_, file)| file)
+            .collect()
+    };
+    for file in files {
+        file.remove().await;
+    }
+}
+
+#[tauri::command]
+pub async fn fetch_spilled(
+    state: State<'_, AppState>,
+    spill_id: String,
+    offset: u64,
+    limit: Option<usize>,
+    format: Option<ResultFormat>,
+) -> Result<QueryOutput, String> {
+    let file = {
+        let spills = state.spills.lock().unwrap();
+        spills
+            .get(&spill_id)
+            .map(|(_, file)| file.clone())
+            .ok_or("Spilled result not found")?
+    };
+    let page = file.page(offset, limit.unwrap_or(SPILL_PAGE_SIZE)).await?;
+    Ok(page.into_output(format.unwrap_or_default()))
+}
+
+/// Delete a spilled result's temporary file once the frontend is done with it.
+#[tauri::command]
+pub async fn release_spilled(state: State<'_, AppState>, spill_id: String) -> Result<bool, String> {
+    let file = state.spills.lock().unwrap().remove(&spill_id);
+    match file {
+        Some((_, file)) => {
+            file.remove().await;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
@@ -0,0 +1,700 @@
+//! Lightweight SQL text helpers. These do not parse SQL; they only understand enough
+//! lexical structure (quotes, comments, dollar-quoted bodies) to find statement
+//! boundaries and leading keywords reliably.
+
+use super::DbKind;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// What a statement does, judged from its top-level keywords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementKind {
+    Select,
+    Insert,
+    Update,
+    Delete,
+    Merge,
+    Create,
+    Alter,
+    Drop,
+    Truncate,
+    Rename,
+    Comment,
+    Grant,
+    Revoke,
+    Transaction,
+    Explain,
+    Show,
+    Set,
+    Call,
+    Other,
+}
+
+/// Split a script into statements on top-level semicolons. Semicolons inside string
+/// literals, quoted identifiers, comments and Postgres dollar-quoted bodies are ignored.
+/// Statements that are empty or consist only of comments are dropped.
+pub fn split_statements(sql: &str) -> Vec<&str> {
+    let bytes = sql.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        i = match bytes[i] {
+            b'\'' | b'"' | b'`' => skip_quoted(bytes, i),
+            b'-' if bytes.get(i + 1) == Some(&b'-') => skip_line_comment(bytes, i),
+            b'/' if bytes.get(i + 1) == Some(&b'*') => skip_block_comment(bytes, i),
+            b'$' => skip_dollar_quoted(bytes, i).unwrap_or(i + 1),
+            b';' => {
+                statements.push(&sql[start..i]);
+                start = i + 1;
+                i + 1
+            }
+            _ => i + 1,
+        };
+    }
+    statements.push(&sql[start..]);
+
+    statements
+        .into_iter()
+        .map(str::trim)
+        .filter(|stmt| !skip_trivia(stmt).is_empty())
+        .collect()
+}
+
+/// `script` with each run of whitespace outside literals, quoted identifiers, comments
+/// and dollar-quoted bodies collapsed to one space, and its statements rejoined with
+/// `;`, so scripts that differ only in layout compare equal.
+pub fn normalize(script: &str) -> String {
+    let mut normalized = String::with_capacity(script.len());
+    for stmt in split_statements(script) {
+        if !normalized.is_empty() {
+            normalized.push(';');
+        }
+        let bytes = stmt.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            let end = match bytes[i] {
+                b'\'' | b'"' | b'`' => skip_quoted(bytes, i),
+                b'-' if bytes.get(i + 1) == Some(&b'-') => skip_line_comment(bytes, i),
+                b'/' if bytes.get(i + 1) == Some(&b'*') => skip_block_comment(bytes, i),
+                b'$' => skip_dollar_quoted(bytes, i).unwrap_or(i + 1),
+                b if b.is_ascii_whitespace() => {
+                    let end = bytes[i..]
+                        .iter()
+                        .position(|b| !b.is_ascii_whitespace())
+                        .map_or(bytes.len(), |p| i + p);
+                    normalized.push(' ');
+                    i = end;
+                    continue;
+                }
+                _ => i + 1,
+            };
+            normalized.push_str(&stmt[i..end]);
+            i = end;
+        }
+    }
+    normalized
+}
+
+/// Whether every statement in `script` only reads, so running it again changes nothing.
+pub fn is_read_only(script: &str) -> bool {
+    split_statements(script).iter().all(|stmt| {
+        matches!(
+            classify(stmt),
+            StatementKind::Select | StatementKind::Explain | StatementKind::Show
+        )
+    })
+}
+
+/// The first keyword of a statement, upper-cased, skipping leading whitespace and comments.
+pub fn leading_keyword(stmt: &str) -> Option<String> {
+    let rest = skip_trivia(stmt);
+    let end = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    if end == 0 {
+        None
+    } else {
+        Some(rest[..end].to_ascii_uppercase())
+    }
+}
+
+/// Add the optimizer hint `/*+ hint */` after the keyword of each statement in `script`
+/// that starts with SELECT.
+pub fn hint_selects(script: &str, hint: &str) -> String {
+    let mut hinted = String::with_capacity(script.len());
+    let mut copied = 0;
+    for stmt in split_statements(script) {
+        if leading_keyword(stmt).as_deref() != Some("SELECT") {
+            continue;
+        }
+        // Statements are slices of `script`, so this is where the keyword ends in it.
+        let at = skip_trivia(stmt).as_ptr() as usize - script.as_ptr() as usize + "SELECT".len();
+        hinted.push_str(&script[copied..at]);
+        hinted.push_str(&format!(" /*+ {} */", hint));
+        copied = at;
+    }
+    hinted.push_str(&script[copied..]);
+    hinted
+}
+
+/// Whether any statement in `sql` changes the schema.
+pub fn is_ddl(sql: &str) -> bool {
+    split_statements(sql).into_iter().any(|stmt| {
+        matches!(
+            leading_keyword(stmt).as_deref(),
+            Some("CREATE" | "ALTER" | "DROP" | "TRUNCATE" | "RENAME" | "COMMENT")
+        )
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DestructiveReason {
+    Drop,
+    Truncate,
+    DeleteWithoutWhere,
+    UpdateWithoutWhere,
+    Alter,
+    /// Any write or schema change on a connection tagged `production`.
+    ProductionWrite,
+}
+
+/// Classify a single statement. `WITH` is classified by the most dangerous statement
+/// in it, at any depth, since data-modifying CTEs run whatever the outer statement is.
+/// `EXPLAIN ANALYZE` is classified by the statement it runs, and `SELECT ... INTO` as
+/// `Create`, since it creates a table.
+pub fn classify(stmt: &str) -> StatementKind {
+    classify_words(&top_level_words(stmt))
+}
+
+fn classify_words(words: &[(String, usize)]) -> StatementKind {
+    let Some((first, _)) = words.first() else {
+        return StatementKind::Other;
+    };
+    let kind = match first.as_str() {
+        "WITH" => write_statements(words)
+            .into_iter()
+            .map(|(kind, _)| kind)
+            .max_by_key(|kind| write_rank(*kind))
+            .unwrap_or(StatementKind::Select),
+        "EXPLAIN" => {
+            let analyze = words.iter().any(|(word, _)| word == "ANALYZE");
+            let inner = words.iter().skip(1).position(|(word, depth)| {
+                *depth == 0 && (word == "WITH" || dml_kind(word).is_some())
+            });
+            match inner {
+                Some(at) if analyze => classify_words(&words[at + 1..]),
+                _ => StatementKind::Explain,
+            }
+        }
+        keyword => keyword_kind(keyword),
+    };
+    let into = words
+        .iter()
+        .any(|(word, depth)| *depth == 0 && word == "INTO");
+    if kind == StatementKind::Select && into {
+        StatementKind::Create
+    } else {
+        kind
+    }
+}
+
+/// Whether a statement needs explicit confirmation before it runs: DROP, TRUNCATE,
+/// ALTER, and DELETE or UPDATE without a WHERE clause of its own, including those in
+/// CTEs.
+pub fn destructive_reason(stmt: &str) -> Option<DestructiveReason> {
+    let unguarded = |target: StatementKind| {
+        let words = top_level_words(stmt);
+        write_statements(&words)
+            .into_iter()
+            .any(|(kind, at)| kind == target && !has_where(&words, at))
+    };
+    match classify(stmt) {
+        StatementKind::Drop => Some(DestructiveReason::Drop),
+        StatementKind::Truncate => Some(DestructiveReason::Truncate),
+        StatementKind::Alter => Some(DestructiveReason::Alter),
+        StatementKind::Delete | StatementKind::Update => {
+            if unguarded(StatementKind::Delete) {
+                Some(DestructiveReason::DeleteWithoutWhere)
+            } else if unguarded(StatementKind::Update) {
+                Some(DestructiveReason::UpdateWithoutWhere)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The INSERT, UPDATE, DELETE and MERGE statements among `words`, at any depth, with
+/// their index. `FOR UPDATE`, `ON DUPLICATE KEY UPDATE`, `DO UPDATE` and MERGE's
+/// `THEN ...` actions are clauses of another statement, not statements.
+fn write_statements(words: &[(String, usize)]) -> Vec<(StatementKind, usize)> {
+    words
+        .iter()
+        .enumerate()
+        .filter_map(|(at, (word, _))| {
+            let previous = at.checked_sub(1).map(|p| words[p].0.as_str());
+            if previous == Some("THEN") {
+                return None;
+            }
+            let kind = match word.as_str() {
+                "INSERT" => StatementKind::Insert,
+                "UPDATE" if !matches!(previous, Some("FOR" | "KEY" | "DO")) => {
+                    StatementKind::Update
+                }
+                "DELETE" => StatementKind::Delete,
+                "MERGE" => StatementKind::Merge,
+                _ => return None,
+            };
+            Some((kind, at))
+        })
+        .collect()
+}
+
+/// How much a write can destroy, for picking the kind of a `WITH` statement.
+fn write_rank(kind: StatementKind) -> u8 {
+    match kind {
+        StatementKind::Delete => 4,
+        StatementKind::Update => 3,
+        StatementKind::Merge => 2,
+        StatementKind::Insert => 1,
+        _ => 0,
+    }
+}
+
+/// Whether the statement starting at `words[at]` has a WHERE at its own depth, before
+/// the parentheses around it close.
+fn has_where(words: &[(String, usize)], at: usize) -> bool {
+    let depth = words[at].1;
+    words[at + 1..]
+        .iter()
+        .take_while(|(_, d)| *d >= depth)
+        .any(|(word, d)| *d == depth && word == "WHERE")
+}
+
+fn dml_kind(word: &str) -> Option<StatementKind> {
+    match word {
+        "SELECT" | "VALUES" | "TABLE" => Some(StatementKind::Select),
+        "INSERT" | "REPLACE" => Some(StatementKind::Insert),
+        "UPDATE" => Some(StatementKind::Update),
+        "DELETE" => Some(StatementKind::Delete),
+        "MERGE" => Some(StatementKind::Merge),
+        _ => None,
+    }
+}
+
+fn keyword_kind(keyword: &str) -> StatementKind {
+    if let Some(kind) = dml_kind(keyword) {
+        return kind;
+    }
+    match keyword {
+        "CREATE" => StatementKind::Create,
+        "ALTER" => StatementKind::Alter,
+        "DROP" => StatementKind::Drop,
+        "TRUNCATE" => StatementKind::Truncate,
+        "RENAME" => StatementKind::Rename,
+        "COMMENT" => StatementKind::Comment,
+        "GRANT" => StatementKind::Grant,
+        "REVOKE" => StatementKind::Revoke,
+        "BEGIN" | "START" | "COMMIT" | "END" | "ROLLBACK" | "SAVEPOINT" | "RELEASE" => {
+            StatementKind::Transaction
+        }
+        "SHOW" | "DESCRIBE" | "DESC" => StatementKind::Show,
+        "SET" | "RESET" | "USE" => StatementKind::Set,
+        "CALL" | "EXEC" | "EXECUTE" | "DO" => StatementKind::Call,
+        _ => StatementKind::Other,
+    }
+}
+
+/// Upper-cased bare words with their parenthesis depth, skipping literals, quoted
+/// identifiers, comments and dollar-quoted bodies.
+pub fn top_level_words(stmt: &str) -> Vec<(String, usize)> {
+    let bytes = stmt.as_bytes();
+    let mut words = Vec::new();
+    let mut depth = 0usize;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        i = match bytes[i] {
+            b'\'' | b'"' | b'`' => skip_quoted(bytes, i),
+            b'-' if bytes.get(i + 1) == Some(&b'-') => skip_line_comment(bytes, i),
+            b'/' if bytes.get(i + 1) == Some(&b'*') => skip_block_comment(bytes, i),
+            b'$' => skip_dollar_quoted(bytes, i).unwrap_or(i + 1),
+            b'(' => {
+                depth += 1;
+                i + 1
+            }
+            b')' => {
+                depth = depth.saturating_sub(1);
+                i + 1
+            }
+            b if b.is_ascii_alphabetic() || b == b'_' => {
+                let end = bytes[i..]
+                    .iter()
+                    .position(|b| !(b.is_ascii_alphanumeric() || *b == b'_'))
+                    .map_or(bytes.len(), |p| i + p);
+                words.push((stmt[i..end].to_ascii_uppercase(), depth));
+                end
+            }
+            _ => i + 1,
+        };
+    }
+    words
+}
+
+/// Quote an identifier for the given dialect, escaping embedded quote characters.
+pub fn quote_ident(kind: DbKind, name: &str) -> String {
+    match kind {
+        DbKind::MySql => format!("`{}`", name.replace('`', "``")),
+        DbKind::BigQuery => format!("`{}`", name.replace('\\', "\\\\").replace('`', "\\`")),
+        _ => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+/// Render a value as a SQL literal for the given dialect. Prefer bind parameters; this
+/// is for SQL that has to be shown or saved as text. MySQL treats backslashes in
+/// strings as escapes (unless `NO_BACKSLASH_ESCAPES` is set), so they are doubled there;
+/// BigQuery has no `''` escape, so quotes are backslash-escaped too.
+pub fn sql_literal(kind: DbKind, value: &Value) -> String {
+    let text = match value {
+        Value::Null => return "NULL".to_string(),
+        Value::Bool(b) => return if *b { "TRUE" } else { "FALSE" }.to_string(),
+        Value::Number(n) => return n.to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    match kind {
+        DbKind::MySql => format!(
+            "'{}'",
+            text.replace('\\', "\\\\")
+                .replace('\0', "\\0")
+                .replace('\'', "''")
+        ),
+        DbKind::BigQuery => format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'")),
+        _ => format!("'{}'", text.replace('\'', "''")),
+    }
+}
+
+#[tauri::command]
+pub fn quote_identifier(kind: DbKind, name: String) -> String {
+    quote_ident(kind, &name)
+}
+
+#[tauri::command]
+pub fn quote_literal(kind: DbKind, value: Value) -> String {
+    sql_literal(kind, &value)
+}
+
+/// The bind placeholder for the `n`th (1-based) parameter.
+pub fn placeholder(kind: DbKind, n: usize) -> String {
+    match kind {
+        DbKind::Postgres => format!("${}", n),
+        DbKind::Oracle => format!(":{}", n),
+        _ => "?".to_string(),
+    }
+}
+
+/// Rewrite `:name` and `@name` placeholders to the dialect's positional ones,
+/// returning the statement and the values to bind in order. `@name` is only a
+/// placeholder when `params` has it, as MySQL user variables are spelled the same;
+/// `::` casts and `@@` system variables are left alone.
+pub fn positional(
+    kind: DbKind,
+    sql: &str,
+    params: &Map<String, Value>,
+) -> Result<(String, Vec<Value>), String> {
+    let bytes = sql.as_bytes();
+    let mut rewritten = String::with_capacity(sql.len());
+    let mut binds = Vec::new();
+    // Postgres can refer to one parameter more than once.
+    let mut numbered: Vec<&str> = Vec::new();
+    let mut copied = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        i = match bytes[i] {
+            b'\'' | b'"' | b'`' => skip_quoted(bytes, i),
+            b'-' if bytes.get(i + 1) == Some(&b'-') => skip_line_comment(bytes, i),
+            b'/' if bytes.get(i + 1) == Some(&b'*') => skip_block_comment(bytes, i),
+            b'$' => skip_dollar_quoted(bytes, i).unwrap_or(i + 1),
+            sigil @ (b':' | b'@') => {
+                let starts_name = bytes
+                    .get(i + 1)
+                    .is_some_and(|b| b.is_ascii_alphabetic() || *b == b'_');
+                // `a::int`, `@@version`, and `arr[lo:hi]` or `x:y` inside a word.
+                let joined = i > 0
+                    && (bytes[i - 1] == sigil
+                        || bytes[i - 1].is_ascii_alphanumeric()
+                        || bytes[i - 1] == b'_');
+                if !starts_name || joined {
+                    i + 1
+                } else {
+                    let end = bytes[i + 1..]
+                        .iter()
+                        .position(|b| !(b.is_ascii_alphanumeric() || *b == b'_'))
+                        .map_or(bytes.len(), |p| i + 1 + p);
+                    let name = &sql[i + 1..end];
+                    let value = match params.get(name) {
+                        Some(value) => value,
+                        None if sigil == b'@' => {
+                            i = end;
+                            continue;
+                        }
+                        None => return Err(format!("No value given for parameter :{}", name)),
+                    };
+                    let n = match numbered.iter().position(|used| *used == name) {
+                        Some(index) if kind == DbKind::Postgres => index + 1,
+                        _ => {
+                            numbered.push(name);
+                            binds.push(value.clone());
+                            binds.len()
+                        }
+                    };
+                    rewritten.push_str(&sql[copied..i]);
+                    rewritten.push_str(&placeholder(kind, n));
+                    copied = end;
+                    end
+                }
+            }
+            _ => i + 1,
+        };
+    }
+    rewritten.push_str(&sql[copied..]);
+    Ok((rewritten, binds))
+}
+
+/// Strip leading whitespace and comments.
+fn skip_trivia(mut s: &str) -> &str {
+    loop {
+        s = s.trim_start();
+        let bytes = s.as_bytes();
+        if s.starts_with("--") {
+            s = &s[skip_line_comment(bytes, 0)..];
+        } else if s.starts_with("/*") {
+            s = &s[skip_block_comment(bytes, 0)..];
+        } else {
+            return s;
+        }
+    }
+}
+
+/// Index just past the closing quote. A doubled quote character is an escaped quote.
+fn skip_quoted(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        if bytes[i] == quote {
+            if bytes.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    bytes.len()
+}
+
+fn skip_line_comment(bytes: &[u8], start: usize) -> usize {
+    bytes[start..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(bytes.len(), |p| start + p + 1)
+}
+
+fn skip_block_comment(bytes: &[u8], start: usize) -> usize {
+    bytes[start + 2..]
+        .windows(2)
+        .position(|w| w == b"*/")
+        .map_or(bytes.len(), |p| start + 2 + p + 2)
+}
+
+/// If `start` opens a dollar-quoted body (`$$` or `$tag$`), return the index just past
+/// its closing delimiter. Positional parameters such as `$1` are not dollar quotes.
+fn skip_dollar_quoted(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+        i += 1;
+    }
+    if bytes.get(i) != Some(&b'$') || bytes.get(start + 1).is_some_and(u8::is_ascii_digit) {
+        return None;
+    }
+    let tag = &bytes[start..=i];
+    let body = i + 1;
+    Some(
+        bytes[body..]
+            .windows(tag.len())
+            .position(|w| w == tag)
+            .map_or(bytes.len(), |p| body + p + tag.len()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn splits_on_top_level_semicolons() {
+        let script = "SELECT ';' FROM \"a;b\"; -- c;\nSELECT $$ ; $$; /* ; */ ;\nSELECT `x;y`";
+        assert_eq!(
+            split_statements(script),
+            vec![
+                "SELECT ';' FROM \"a;b\"",
+                "-- c;\nSELECT $$ ; $$",
+                "SELECT `x;y`"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_positional_parameters_out_of_dollar_quotes() {
+        assert_eq!(
+            split_statements("SELECT $1; SELECT $tag$;$tag$"),
+            vec!["SELECT $1", "SELECT $tag$;$tag$"]
+        );
+    }
+
+    #[test]
+    fn classifies_by_leading_keyword() {
+        assert_eq!(classify("select 1"), StatementKind::Select);
+        assert_eq!(
+            classify("/* x */ INSERT INTO t VALUES (1)"),
+            StatementKind::Insert
+        );
+        assert_eq!(classify("DROP TABLE t"), StatementKind::Drop);
+        assert_eq!(classify("EXPLAIN DELETE FROM t"), StatementKind::Explain);
+        assert_eq!(
+            classify("EXPLAIN ANALYZE DELETE FROM t"),
+            StatementKind::Delete
+        );
+        assert_eq!(classify(""), StatementKind::Other);
+    }
+
+    #[test]
+    fn classifies_with_by_its_most_dangerous_statement() {
+        assert_eq!(
+            classify("WITH x AS (SELECT 1) SELECT * FROM x"),
+            StatementKind::Select
+        );
+        assert_eq!(
+            classify("WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d"),
+            StatementKind::Delete
+        );
+        assert_eq!(
+            classify("WITH x AS (UPDATE t SET a = 1 RETURNING a) SELECT 1"),
+            StatementKind::Update
+        );
+        assert_eq!(
+            classify("WITH a AS (WITH b AS (INSERT INTO t VALUES (1)) SELECT 1) SELECT 1"),
+            StatementKind::Insert
+        );
+        assert_eq!(
+            classify("WITH i AS (INSERT INTO t VALUES (1)) DELETE FROM u WHERE id = 1"),
+            StatementKind::Delete
+        );
+        assert_eq!(
+            classify("EXPLAIN ANALYZE WITH d AS (DELETE FROM t) SELECT 1"),
+            StatementKind::Delete
+        );
+    }
+
+    #[test]
+    fn ignores_clauses_that_only_look_like_writes() {
+        assert_eq!(
+            classify("WITH x AS (SELECT * FROM t FOR UPDATE) SELECT * FROM x"),
+            StatementKind::Select
+        );
+        assert_eq!(
+            classify("WITH x AS (SELECT REPLACE(a, 'b', 'c') FROM t) SELECT * FROM x"),
+            StatementKind::Select
+        );
+        assert_eq!(classify("SELECT 'DELETE FROM t'"), StatementKind::Select);
+    }
+
+    #[test]
+    fn classifies_select_into_as_create() {
+        assert_eq!(classify("SELECT * INTO t2 FROM t"), StatementKind::Create);
+        assert_eq!(
+            classify("WITH x AS (SELECT 1) SELECT * INTO t2 FROM x"),
+            StatementKind::Create
+        );
+        assert!(!is_read_only("SELECT * INTO t2 FROM t"));
+        assert!(!is_read_only("WITH d AS (DELETE FROM t) SELECT 1"));
+        assert!(is_read_only("SELECT 1; SHOW TABLES"));
+    }
+
+    #[test]
+    fn finds_destructive_statements() {
+        assert_eq!(
+            destructive_reason("DROP TABLE t"),
+            Some(DestructiveReason::Drop)
+        );
+        assert_eq!(
+            destructive_reason("TRUNCATE t"),
+            Some(DestructiveReason::Truncate)
+        );
+        assert_eq!(
+            destructive_reason("ALTER TABLE t ADD c int"),
+            Some(DestructiveReason::Alter)
+        );
+        assert_eq!(
+            destructive_reason("DELETE FROM t"),
+            Some(DestructiveReason::DeleteWithoutWhere)
+        );
+        assert_eq!(destructive_reason("DELETE FROM t WHERE id = 1"), None);
+        assert_eq!(
+            destructive_reason("UPDATE t SET a = (SELECT b FROM u WHERE u.id = 1)"),
+            Some(DestructiveReason::UpdateWithoutWhere)
+        );
+        assert_eq!(destructive_reason("UPDATE t SET a = 1 WHERE id = 1"), None);
+        assert_eq!(destructive_reason("SELECT * FROM t"), None);
+    }
+
+    #[test]
+    fn finds_destructive_statements_in_ctes() {
+        assert_eq!(
+            destructive_reason("WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d"),
+            Some(DestructiveReason::DeleteWithoutWhere)
+        );
+        assert_eq!(
+            destructive_reason(
+                "WITH d AS (DELETE FROM t WHERE id = 1 RETURNING *) SELECT * FROM d WHERE true"
+            ),
+            None
+        );
+        assert_eq!(
+            destructive_reason("WITH u AS (UPDATE t SET a = 1) SELECT * FROM x WHERE true"),
+            Some(DestructiveReason::UpdateWithoutWhere)
+        );
+    }
+
+    #[test]
+    fn rewrites_named_parameters() {
+        let params = json!({ "id": 7, "name": "x" });
+        let params = params.as_object().unwrap();
+
+        let (sql, binds) = positional(
+            DbKind::Postgres,
+            "SELECT :id::text, ':id' WHERE a = :id AND b = :name",
+            params,
+        )
+        .unwrap();
+        assert_eq!(sql, "SELECT $1::text, ':id' WHERE a = $1 AND b = $2");
+        assert_eq!(binds, vec![json!(7), json!("x")]);
+
+        let (sql, binds) =
+            positional(DbKind::MySql, "SELECT @id, @@version, @other, :id", params).unwrap();
+        assert_eq!(sql, "SELECT ?, @@version, @other, ?");
+        assert_eq!(binds, vec![json!(7), json!(7)]);
+
+        let (sql, _) = positional(DbKind::Oracle, "SELECT :name FROM dual", params).unwrap();
+        assert_eq!(sql, "SELECT :1 FROM dual");
+
+        assert!(positional(DbKind::Postgres, "SELECT :missing", params).is_err());
+    }
+}
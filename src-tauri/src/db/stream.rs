@@ -0,0 +1,247 @@
+use super::convert::{convert_batch, JsonRow, RowSet};
+use super::masking::{ColumnMasks, MaskingRule};
+use super::{generate_id, policy, redact, sql, AppState, Connection};
+use futures_util::{Stream, TryStreamExt};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Semaphore;
+
+pub const DEFAULT_CHUNK_SIZE: usize = 500;
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 4;
+
+/// How long a stream waits for the frontend to acknowledge a chunk before giving up.
+const ACK_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Serialize)]
+#[serde(tag = "event", content = "data")]
+pub enum StreamEvent {
+    Chunk {
+        stream_id: String,
+        seq: u64,
+        rows: Vec<Map<String, Value>>,
+    },
+    Done {
+        stream_id: String,
+        total_rows: u64,
+        /// Chunks sent, the last of which may hold fewer than `chunk_size` rows.
+        total_chunks: u64,
+        /// From the start of the query to its last row.
+        elapsed_ms: u64,
+        /// Set when the connection is sandboxed and a LIMIT was added to the query.
+        injected_limit: Option<u64>,
+    },
+    Error {
+        stream_id: String,
+        message: String,
+    },
+}
+
+/// Run `sql` and deliver the rows over `on_event` in chunks of `chunk_size`.
+///
+/// At most `max_in_flight` chunks may be unacknowledged at a time; the reader stops
+/// pulling rows from the database until the frontend calls `ack_stream`, so a slow
+/// webview throttles the query instead of piling rows up in memory.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_stream(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+    sql: String,
+    chunk_size: Option<usize>,
+    max_in_flight: Option<usize>,
+    unmask: Option<bool>,
+    on_event: Channel<StreamEvent>,
+) -> Result<String, String> {
+    let conn = state.connection(&id)?;
+    state.check_policy(&id, &sql)?;
+    // Streams have no confirmation step.
+    if !policy::destructive_statements(&sql, state.is_production(&id)).is_empty() {
+        return Err("Statements that need confirmation must be run with execute".to_string());
+    }
+    let masking = state.masking_rules(&id, unmask.unwrap_or(false))?;
+    let (sql, injected_limit) = match state.sandboxed(&id, &sql) {
+        Some((rewritten, limit)) => (rewritten, Some(limit)),
+        None => (sql, None),
+    };
+    let stream_id = generate_id("stream");
+    let chunk_size = chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).max(1);
+    let window = Arc::new(Semaphore::new(
+        max_in_flight.unwrap_or(DEFAULT_MAX_IN_FLIGHT).max(1),
+    ));
+
+    state
+        .streams
+        .lock()
+        .unwrap()
+        .insert(stream_id.clone(), window.clone());
+
+    let task_stream_id = stream_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let started = Instant::now();
+        let sink = ChunkSink {
+            stream_id: &task_stream_id,
+            chunk_size,
+            window: &window,
+            channel: &on_event,
+            masking: &masking,
+        };
+        let state = app.state::<AppState>();
+        let result = match conn {
+            Connection::Postgres(pool) => match state.acquire(&id, &pool).await {
+                Ok(mut conn) => {
+                    sink.pump(sqlx::query(&sql).persistent(false).fetch(&mut *conn))
+                        .await
+                }
+                Err(e) => Err(e),
+            },
+            Connection::MySql(pool) => match state.acquire(&id, &pool).await {
+                Ok(mut conn) => {
+                    sink.pump(sqlx::query(&sql).persistent(false).fetch(&mut *conn))
+                        .await
+                }
+                Err(e) => Err(e),
+            },
+            Connection::Sqlite(pool) => match state.acquire(&id, &pool).await {
+                Ok(mut conn) => {
+                    sink.pump(sqlx::query(&sql).persistent(false).fetch(&mut *conn))
+                        .await
+                }
+                Err(e) => Err(e),
+            },
+            conn @ (Connection::Oracle(_)
+            | Connection::Mongo(_)
+            | Connection::DuckDb(_)
+            | Connection::ClickHouse(_)
+            | Connection::Redis(_)
+            | Connection::Cassandra(_)
+            | Connection::Surreal(_)
+            | Connection::BigQuery(_)
+            | Connection::Firebird(_)
+            | Connection::Odbc(_)) => Err(conn.unsupported("Streaming")),
+        };
+
+        state.streams.lock().unwrap().remove(&task_stream_id);
+        state.audit(&id, &sql, result.as_ref().err().map(String::as_str));
+        if sql::is_ddl(&sql) {
+            state.schema_cache.invalidate(&id);
+        }
+        if !sql::is_read_only(&sql) {
+            state.result_cache.invalidate(&id, None);
+        }
+
+        let event = match result {
+            Ok((total_rows, total_chunks)) => StreamEvent::Done {
+                stream_id: task_stream_id,
+                total_rows,
+                total_chunks,
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                injected_limit,
+            },
+            Err(message) => StreamEvent::Error {
+                stream_id: task_stream_id,
+                message: redact::redact(&message),
+            },
+        };
+        let _ = on_event.send(event);
+    });
+
+    Ok(stream_id)
+}
+
+/// Acknowledge one delivered chunk, allowing the stream to send another.
+/// Returns `false` if the stream has already finished.
+#[tauri::command]
+pub fn ack_stream(state: State<'_, AppState>, stream_id: String) -> bool {
+    match state.streams.lock().unwrap().get(&stream_id) {
+        Some(window) => {
+            window.add_permits(1);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Stop a running stream. The reader notices on its next chunk and reports an error event.
+#[tauri::command]
+pub fn cancel_stream(state: State<'_, AppState>, stream_id: String) -> bool {
+    match state.streams.lock().unwrap().get(&stream_id) {
+        Some(window) => {
+            window.close();
+            true
+        }
+        None => false,
+    }
+}
+
+struct ChunkSink<'a> {
+    stream_id: &'a str,
+    chunk_size: usize,
+    window: &'a Semaphore,
+    channel: &'a Channel<StreamEvent>,
+    masking: &'a [MaskingRule],
+}
+
+impl ChunkSink<'_> {
+    /// Send `rows` in chunks and return how many rows and chunks were sent.
+    async fn pump<R, S>(&self, mut rows: S) -> Result<(u64, u64), String>
+    where
+        R: JsonRow,
+        S: Stream<Item = Result<R, sqlx::Error>> + Unpin,
+    {
+        let mut chunk = Vec::with_capacity(self.chunk_size);
+        let mut seq = 0;
+        let mut total_rows = 0;
+
+        while let Some(row) = rows.try_next().await.map_err(|e| e.to_string())? {
+            chunk.push(row);
+            total_rows += 1;
+            if chunk.len() == self.chunk_size {
+                let full = std::mem::replace(&mut chunk, Vec::with_capacity(self.chunk_size));
+                self.send(seq, full).await?;
+                seq += 1;
+            }
+        }
+        if !chunk.is_empty() {
+            self.send(seq, chunk).await?;
+            seq += 1;
+        }
+
+        Ok((total_rows, seq))
+    }
+
+    async fn send<R: JsonRow>(&self, seq: u64, chunk: Vec<R>) -> Result<(), String> {
+        let columns = chunk[0].column_names();
+        let mut rows = convert_batch(chunk).await?;
+        ColumnMasks::new(self.masking, &columns).apply(&mut rows);
+        let rows = RowSet {
+            columns,
+            rows,
+            affected: None,
+            truncated: None,
+        }
+        .into_maps();
+
+        match tokio::time::timeout(ACK_TIMEOUT, self.window.acquire()).await {
+            Ok(Ok(permit)) => permit.forget(),
+            Ok(Err(_)) => return Err("Stream cancelled".to_string()),
+            Err(_) => {
+                return Err(
+                    "Timed out waiting for the frontend to acknowledge result chunks".to_string(),
+                )
+            }
+        }
+
+        self.channel
+            .send(StreamEvent::Chunk {
+                stream_id: self.stream_id.to_string(),
+                seq,
+                rows,
+            })
+            .map_err(|e| e.to_string())
+    }
+}
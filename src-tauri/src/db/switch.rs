@@ -0,0 +1,48 @@
+//! Moving a connection to another database on the same server. A new pool is opened
+//! for it, as `reconnect` does: MySQL's `USE` would only move the one pooled
+//! connection it ran on, and Postgres can't change database within a session.
+
+use super::reconnect::reconnect_connection;
+use super::{AppState, DbKind};
+use reqwest::Url;
+use tauri::State;
+
+/// `conn_string` with its URL path replaced by `database`.
+pub fn with_database(kind: DbKind, conn_string: &str, database: &str) -> Result<String, String> {
+    if matches!(kind, DbKind::Sqlite | DbKind::DuckDb | DbKind::Odbc) {
+        return Err(format!(
+            "A {:?} connection has no database to switch; connect to another one instead",
+            kind
+        ));
+    }
+    let mut url = Url::parse(conn_string).map_err(|_| {
+        "The connection string is not a URL, so its database can't be changed".to_string()
+    })?;
+    url.set_path(&format!("/{}", database.trim_start_matches('/')));
+    Ok(url.to_string())
+}
+
+/// Reopen `id` against database `name`. Its id and settings are kept; on failure it
+/// stays on the database it was on.
+#[tauri::command]
+pub async fn switch_database(
+    state: State<'_, AppState>,
+    id: String,
+    name: String,
+) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Database names cannot be empty".to_string());
+    }
+    let previous = {
+        let mut specs = state.connect_specs.lock().unwrap();
+        let (_, options) = specs.get_mut(&id).ok_or("Connection not found")?;
+        options.database.replace(name)
+    };
+    let switched = reconnect_connection(&state, &id).await;
+    if switched.is_err() {
+        if let Some((_, options)) = state.connect_specs.lock().unwrap().get_mut(&id) {
+            options.database = previous;
+        }
+    }
+    switched
+}
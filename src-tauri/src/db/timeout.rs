@@ -0,0 +1,96 @@
+//! Per-query timeouts. `execute` stops waiting once a query's `timeout_ms` has passed
+//! and cancels it as `cancel_query` would. The server is also told the limit where it
+//! can be set for one query: `statement_timeout` on Postgres pooled connections, a
+//! `MAX_EXECUTION_TIME` hint on MySQL SELECTs, and the per-call timeouts the other
+//! backends take for sandboxes.
+
+use super::AppState;
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::{PgConnection, Postgres};
+use std::ops::{Deref, DerefMut};
+
+/// How a query may be stopped early: the id it is registered under for
+/// `cancel_query`, and the `timeout_ms` it was run with.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct QueryControl<'a> {
+    pub query_id: Option<&'a str>,
+    pub timeout_ms: Option<u64>,
+}
+
+impl AppState {
+    /// The timeout to enforce on connection `id` for a query asked to finish within
+    /// `requested` ms: the shorter of that and the sandbox timeout.
+    pub fn query_timeout_ms(&self, id: &str, requested: Option<u64>) -> Option<u64> {
+        match (requested, self.statement_timeout_ms(id)) {
+            (Some(requested), Some(sandbox)) => Some(requested.min(sandbox)),
+            (requested, sandbox) => requested.or(sandbox),
+        }
+    }
+}
+
+/// Whether a query that failed `elapsed_ms` into a `timeout_ms` limit ran out of
+/// time. Errors from the server's own timeout, and from the cancellation sent when
+/// the client gives up, arrive only once the limit has passed.
+pub fn timed_out(timeout_ms: Option<u64>, elapsed_ms: u128) -> bool {
+    timeout_ms.is_some_and(|ms| elapsed_ms >= ms as u128)
+}
+
+/// A pooled Postgres connection whose `statement_timeout` may be set for one query.
+/// Unless `reset` restores it, the connection is closed rather than returned to the
+/// pool, so the setting can't outlive the query.
+pub struct PgStatementTimeout {
+    conn: Option<PoolConnection<Postgres>>,
+    set: bool,
+}
+
+impl PgStatementTimeout {
+    pub async fn set(
+        mut conn: PoolConnection<Postgres>,
+        timeout_ms: Option<u64>,
+    ) -> Result<Self, String> {
+        if let Some(ms) = timeout_ms {
+            sqlx::Executor::execute(&mut *conn, &*format!("SET statement_timeout = {}", ms))
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(PgStatementTimeout {
+            conn: Some(conn),
+            set: timeout_ms.is_some(),
+        })
+    }
+
+    /// Put the connection's own `statement_timeout` back.
+    pub async fn reset(&mut self) {
+        if self.set
+            && sqlx::Executor::execute(&mut **self, "RESET statement_timeout")
+                .await
+                .is_ok()
+        {
+            self.set = false;
+        }
+    }
+}
+
+impl Deref for PgStatementTimeout {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        self.conn.as_ref().expect("connection taken")
+    }
+}
+
+impl DerefMut for PgStatementTimeout {
+    fn deref_mut(&mut self) -> &mut PgConnection {
+        self.conn.as_mut().expect("connection taken")
+    }
+}
+
+impl Drop for PgStatementTimeout {
+    fn drop(&mut self) {
+        if self.set {
+            if let Some(conn) = self.conn.take() {
+                drop(conn.detach());
+            }
+        }
+    }
+}
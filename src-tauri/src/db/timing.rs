@@ -0,0 +1,90 @@
+//! Per-phase timings for `execute`, returned with the result when asked for with the
+//! `timings` option, so a slow query can be told apart from a slow app. Phases are
+//! marked as the query runs: by `run_query` once a pooled connection is acquired, and
+//! by the row collectors when the first result arrives and when the last is read.
+
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Timings {
+    /// Waiting for a pooled connection. `None` for backends that connect inside their
+    /// driver, whose wait is counted in `execute_ms`; 0 in a transaction or session,
+    /// which hold their connection already.
+    pub acquire_ms: Option<u64>,
+    /// From sending the script until the server returned its first row or completion.
+    pub execute_ms: u64,
+    /// Reading and converting the rest of the rows, spilling included.
+    pub fetch_ms: u64,
+    /// Shaping the rows into the requested format. Encoding the result for the
+    /// webview happens after it is returned and isn't counted.
+    pub serialize_ms: u64,
+    pub total_ms: u64,
+}
+
+#[derive(Debug, Default)]
+struct Marks {
+    acquired: Option<Instant>,
+    first_result: Option<Instant>,
+    fetched: Option<Instant>,
+}
+
+/// Collects the marks of one query's phases; shared with the collectors through
+/// `CollectLimits`.
+#[derive(Debug)]
+pub struct QueryTimer {
+    started: Instant,
+    marks: Mutex<Marks>,
+}
+
+impl QueryTimer {
+    pub fn start() -> Arc<Self> {
+        Arc::new(QueryTimer {
+            started: Instant::now(),
+            marks: Mutex::new(Marks::default()),
+        })
+    }
+
+    /// A connection was acquired. A retried query starts its later phases over.
+    pub fn acquired(&self) {
+        *self.marks.lock().unwrap() = Marks {
+            acquired: Some(Instant::now()),
+            ..Marks::default()
+        };
+    }
+
+    /// The server returned something; only the first call counts.
+    pub fn first_result(&self) {
+        let mut marks = self.marks.lock().unwrap();
+        marks.first_result.get_or_insert_with(Instant::now);
+    }
+
+    /// All the rows that will be kept have been read.
+    pub fn fetched(&self) {
+        self.marks.lock().unwrap().fetched = Some(Instant::now());
+    }
+
+    /// The timings of a query whose output started being shaped at `shaping`, and is
+    /// done now.
+    pub fn timings(&self, shaping: Instant) -> Timings {
+        let marks = self.marks.lock().unwrap();
+        let now = Instant::now();
+        let fetched = marks.fetched.unwrap_or(shaping);
+        let first_result = marks.first_result.unwrap_or(fetched);
+        let sent = marks.acquired.unwrap_or(self.started);
+        Timings {
+            acquire_ms: marks
+                .acquired
+                .map(|acquired| ms(acquired.saturating_duration_since(self.started))),
+            execute_ms: ms(first_result.saturating_duration_since(sent)),
+            fetch_ms: ms(fetched.saturating_duration_since(first_result)),
+            serialize_ms: ms(now.saturating_duration_since(shaping)),
+            total_ms: ms(now.saturating_duration_since(self.started)),
+        }
+    }
+}
+
+fn ms(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
@@ -0,0 +1,168 @@
+//! Per-connection TLS settings. These override whatever the DSN specifies, so a
+//! profile's verification level can't be weakened by editing the connection string.
+
+use super::pinning::CertificatePin;
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::{MySqlConnectOptions, MySqlSslMode};
+use sqlx::postgres::{PgConnectOptions, PgSslMode};
+use std::path::PathBuf;
+use zeroize::Zeroizing;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsMode {
+    /// Never use TLS.
+    Disable,
+    /// Use TLS if the server supports it, without verifying the certificate.
+    #[default]
+    Prefer,
+    /// Require TLS, without verifying the certificate.
+    Require,
+    /// Require TLS and a certificate signed by a trusted CA.
+    VerifyCa,
+    /// Require TLS, a trusted certificate, and a host name that matches it.
+    VerifyFull,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsOptions {
+    pub mode: TlsMode,
+    /// PEM file of CA certificates to trust instead of the system roots.
+    pub ca_bundle: Option<PathBuf>,
+    /// PEM client certificate for servers that authenticate clients by certificate.
+    /// Needs `client_key`.
+    pub client_cert: Option<PathBuf>,
+    /// PEM private key of `client_cert`.
+    pub client_key: Option<PathBuf>,
+    /// Fail the connection unless the server certificate matches this fingerprint.
+    /// Implies at least `require`.
+    pub pin: Option<CertificatePin>,
+}
+
+impl TlsOptions {
+    /// The effective mode: pinning needs TLS, so it upgrades `disable`/`prefer`.
+    fn mode(&self) -> TlsMode {
+        match self.mode {
+            TlsMode::Disable | TlsMode::Prefer if self.pin.is_some() => TlsMode::Require,
+            mode => mode,
+        }
+    }
+
+    fn check_files(&self) -> Result<(), String> {
+        let files = [
+            ("CA bundle", &self.ca_bundle),
+            ("Client certificate", &self.client_cert),
+            ("Client key", &self.client_key),
+        ];
+        for (what, path) in files {
+            match path {
+                Some(path) if !path.is_file() => {
+                    return Err(format!("{} not found: {}", what, path.display()))
+                }
+                _ => {}
+            }
+        }
+        match (&self.client_cert, &self.client_key) {
+            (Some(_), None) => Err("A client certificate needs a client key".to_string()),
+            (None, Some(_)) => Err("A client key needs a client certificate".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn apply_pg(&self, options: PgConnectOptions) -> Result<PgConnectOptions, String> {
+        self.check_files()?;
+        let options = options.ssl_mode(match self.mode() {
+            TlsMode::Disable => PgSslMode::Disable,
+            TlsMode::Prefer => PgSslMode::Prefer,
+            TlsMode::Require => PgSslMode::Require,
+            TlsMode::VerifyCa => PgSslMode::VerifyCa,
+            TlsMode::VerifyFull => PgSslMode::VerifyFull,
+        });
+        let options = match &self.ca_bundle {
+            Some(path) => options.ssl_root_cert(path),
+            None => options,
+        };
+        Ok(match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => options.ssl_client_cert(cert).ssl_client_key(key),
+            _ => options,
+        })
+    }
+
+    pub fn apply_mysql(&self, options: MySqlConnectOptions) -> Result<MySqlConnectOptions, String> {
+        self.check_files()?;
+        let options = options.ssl_mode(match self.mode() {
+            TlsMode::Disable => MySqlSslMode::Disabled,
+            TlsMode::Prefer => MySqlSslMode::Preferred,
+            TlsMode::Require => MySqlSslMode::Required,
+            TlsMode::VerifyCa => MySqlSslMode::VerifyCa,
+            TlsMode::VerifyFull => MySqlSslMode::VerifyIdentity,
+        });
+        let options = match &self.ca_bundle {
+            Some(path) => options.ssl_ca(path),
+            None => options,
+        };
+        Ok(match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => options.ssl_client_cert(cert).ssl_client_key(key),
+            _ => options,
+        })
+    }
+
+    /// Configure an HTTP client. Returns the client and whether requests must use
+    /// https; `None` leaves the scheme from the DSN as is. HTTP has no in-band TLS
+    /// negotiation, so `prefer` behaves like `disable`.
+    pub fn apply_http(
+        &self,
+        builder: reqwest::ClientBuilder,
+    ) -> Result<(reqwest::ClientBuilder, Option<bool>), String> {
+        self.check_files()?;
+        let mut builder = builder;
+        if let Some(path) = &self.ca_bundle {
+            let pem = std::fs::read(path).map_err(|e| e.to_string())?;
+            for cert in reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| e.to_string())? {
+                builder = builder.add_root_certificate(cert);
+            }
+            builder = builder.tls_built_in_root_certs(false);
+        }
+        if let (Some(cert), Some(key)) = (&self.client_cert, &self.client_key) {
+            let mut pem = Zeroizing::new(std::fs::read(cert).map_err(|e| e.to_string())?);
+            pem.extend(std::fs::read(key).map_err(|e| e.to_string())?);
+            let identity = reqwest::Identity::from_pem(&pem)
+                .map_err(|e| format!("Could not load the client certificate: {}", e))?;
+            builder = builder.identity(identity);
+        }
+        Ok(match self.mode() {
+            TlsMode::Disable => (builder, Some(false)),
+            TlsMode::Prefer => (builder, None),
+            TlsMode::Require => (builder.danger_accept_invalid_certs(true), Some(true)),
+            TlsMode::VerifyCa => (builder.danger_accept_invalid_hostnames(true), Some(true)),
+            TlsMode::VerifyFull => (builder, Some(true)),
+        })
+    }
+}
+
+/// Rephrase TLS failures so they say which step failed. rustls reports certificate
+/// problems as `invalid peer certificate: <reason>`, usually wrapped in an I/O error.
+pub fn describe_error(e: &sqlx::Error) -> Option<String> {
+    let message = e.to_string();
+    let step = if message.contains("server does not support TLS") {
+        "TLS negotiation failed: the server does not accept TLS connections"
+    } else if message.contains("Invalid certificate") {
+        "TLS setup failed: the CA bundle could not be parsed"
+    } else if message.contains("UnknownIssuer") {
+        "Certificate verification failed: the server certificate is not signed by a trusted CA"
+    } else if message.contains("NotValidForName") {
+        "Host name verification failed: the server certificate does not match the host name"
+    } else if message.contains("Expired") {
+        "Certificate verification failed: the server certificate has expired"
+    } else if message.contains("NotValidYet") {
+        "Certificate verification failed: the server certificate is not valid yet"
+    } else if message.contains("invalid peer certificate") {
+        "Certificate verification failed"
+    } else if matches!(e, sqlx::Error::Tls(_)) {
+        "TLS handshake failed"
+    } else {
+        return None;
+    };
+    Some(format!("{}: {}", step, message))
+}
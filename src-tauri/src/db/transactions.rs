@@ -0,0 +1,281 @@
+//! Manual-commit mode: a transaction holds one pooled connection from `begin_transaction`
+//! until `commit` or `rollback`, and scripts run on it through `execute_in_transaction`
+//! with the same checks `execute` applies. A transaction that is never finished, or
+//! whose connection is closed or rebuilt, is rolled back when its connection returns
+//! to the pool. Savepoints within a transaction are set and rolled back to by name.
+
+use super::cancel::InterruptGuard;
+use super::column_meta::{self, ColumnMeta};
+use super::convert::{collect_rows, CollectLimits, Collected, QueryOutput};
+use super::params::bind_value;
+use super::sql::{self, StatementKind};
+use super::{execute_via, generate_id, AppState, Connection, DbKind, ExecuteOptions, Route};
+use serde_json::Value;
+use sqlx::mysql::MySql;
+use sqlx::postgres::Postgres;
+use sqlx::sqlite::Sqlite;
+use sqlx::Transaction;
+use std::sync::Arc;
+use tauri::State;
+use tokio::sync::Mutex;
+
+pub enum OpenTransaction {
+    Postgres(Transaction<'static, Postgres>),
+    MySql(Transaction<'static, MySql>),
+    Sqlite(Transaction<'static, Sqlite>),
+}
+
+/// The connection id a transaction was begun on, and the transaction until it ends.
+pub type TransactionSlot = (String, Arc<Mutex<Option<OpenTransaction>>>);
+
+impl OpenTransaction {
+    /// Run `sql` on the transaction's connection. Connection errors are returned as
+    /// they are: the transaction is lost with its connection.
+    pub async fn run(
+        &mut self,
+        sql: &str,
+        binds: &[Value],
+        limits: CollectLimits,
+    ) -> Result<Collected, String> {
+        match self {
+            OpenTransaction::Postgres(tx) => {
+                let query = binds
+                    .iter()
+                    .try_fold(sqlx::query(sql).persistent(false), bind_value)?;
+                collect_rows(sqlx::Executor::fetch_many(&mut **tx, query), limits).await
+            }
+            OpenTransaction::MySql(tx) => {
+                let query = binds
+                    .iter()
+                    .try_fold(sqlx::query(sql).persistent(false), bind_value)?;
+                collect_rows(sqlx::Executor::fetch_many(&mut **tx, query), limits).await
+            }
+            OpenTransaction::Sqlite(tx) => {
+                let query = binds
+                    .iter()
+                    .try_fold(sqlx::query(sql).persistent(false), bind_value)?;
+                collect_rows(sqlx::Executor::fetch_many(&mut **tx, query), limits).await
+            }
+        }
+    }
+
+    /// The metadata of `sql`'s columns, described in the transaction.
+    pub async fn describe(&mut self, sql: &str) -> Result<Vec<ColumnMeta>, String> {
+        match self {
+            OpenTransaction::Postgres(tx) => sqlx::Executor::describe(&mut **tx, sql)
+                .await
+                .map(|describe| column_meta::from_describe(&describe)),
+            OpenTransaction::MySql(tx) => sqlx::Executor::describe(&mut **tx, sql)
+                .await
+                .map(|describe| column_meta::from_describe(&describe)),
+            OpenTransaction::Sqlite(tx) => sqlx::Executor::describe(&mut **tx, sql)
+                .await
+                .map(|describe| column_meta::from_describe(&describe)),
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    /// Register the transaction's connection for `cancel_query` under `query_id`, if given.
+    pub async fn interruptible<'s>(
+        &mut self,
+        state: &'s AppState,
+        query_id: Option<&str>,
+    ) -> Result<Option<InterruptGuard<'s>>, String> {
+        match self {
+            OpenTransaction::Postgres(tx) => state.interruptible_pg(query_id, tx).await,
+            OpenTransaction::MySql(tx) => state.interruptible_mysql(query_id, tx).await,
+            OpenTransaction::Sqlite(tx) => state.interruptible_sqlite(query_id, tx).await,
+        }
+    }
+
+    fn kind(&self) -> DbKind {
+        match self {
+            OpenTransaction::Postgres(_) => DbKind::Postgres,
+            OpenTransaction::MySql(_) => DbKind::MySql,
+            OpenTransaction::Sqlite(_) => DbKind::Sqlite,
+        }
+    }
+
+    /// Run a statement that returns nothing, such as `SAVEPOINT`.
+    async fn run_control(&mut self, sql: &str) -> Result<(), String> {
+        match self {
+            OpenTransaction::Postgres(tx) => {
+                sqlx::Executor::execute(&mut **tx, sql).await.map(drop)
+            }
+            OpenTransaction::MySql(tx) => sqlx::Executor::execute(&mut **tx, sql).await.map(drop),
+            OpenTransaction::Sqlite(tx) => sqlx::Executor::execute(&mut **tx, sql).await.map(drop),
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    async fn commit(self) -> Result<(), sqlx::Error> {
+        match self {
+            OpenTransaction::Postgres(tx) => tx.commit().await,
+            OpenTransaction::MySql(tx) => tx.commit().await,
+            OpenTransaction::Sqlite(tx) => tx.commit().await,
+        }
+    }
+
+    async fn rollback(self) -> Result<(), sqlx::Error> {
+        match self {
+            OpenTransaction::Postgres(tx) => tx.rollback().await,
+            OpenTransaction::MySql(tx) => tx.rollback().await,
+            OpenTransaction::Sqlite(tx) => tx.rollback().await,
+        }
+    }
+}
+
+impl AppState {
+    fn transaction(&self, tx_id: &str) -> Result<TransactionSlot, String> {
+        self.transactions
+            .lock()
+            .unwrap()
+            .get(tx_id)
+            .cloned()
+            .ok_or_else(|| "Transaction not found".to_string())
+    }
+
+    /// Drop the transactions open on connection `id`, rolling them back, so its pool
+    /// can close.
+    pub fn end_transactions(&self, id: &str) {
+        self.transactions
+            .lock()
+            .unwrap()
+            .retain(|_, (connection_id, _)| connection_id != id);
+    }
+}
+
+/// Begin a transaction on one of `id`'s pooled connections and return its id.
+#[tauri::command]
+pub async fn begin_transaction(state: State<'_, AppState>, id: String) -> Result<String, String> {
+    let conn = state.connection(&id)?;
+    let tx = match &conn {
+        Connection::Postgres(pool) => pool.begin().await.map(OpenTransaction::Postgres),
+        Connection::MySql(pool) => pool.begin().await.map(OpenTransaction::MySql),
+        Connection::Sqlite(pool) => pool.begin().await.map(OpenTransaction::Sqlite),
+        _ => return Err(conn.unsupported("Transactions")),
+    }
+    .map_err(|e| e.to_string())?;
+    let tx_id = generate_id("tx");
+    state
+        .transactions
+        .lock()
+        .unwrap()
+        .insert(tx_id.clone(), (id, Arc::new(Mutex::new(Some(tx)))));
+    Ok(tx_id)
+}
+
+/// Run `sql` inside transaction `tx_id`, as `execute` would on its connection.
+/// Scripts on one transaction run one at a time. Transaction control statements are
+/// refused; the transaction ends through `commit` or `rollback`.
+#[tauri::command]
+pub async fn execute_in_transaction(
+    state: State<'_, AppState>,
+    tx_id: String,
+    sql: String,
+    options: Option<ExecuteOptions>,
+) -> Result<QueryOutput, String> {
+    if sql::split_statements(&sql)
+        .into_iter()
+        .any(|stmt| sql::classify(stmt) == StatementKind::Transaction)
+    {
+        return Err(
+            "Transaction control statements can't run inside a transaction; use commit or rollback"
+                .to_string(),
+        );
+    }
+    let options = options.unwrap_or_default();
+    if options.session.is_some() {
+        return Err("A transaction runs on its own connection; leave out session".to_string());
+    }
+    let (id, slot) = state.transaction(&tx_id)?;
+    let mut tx = slot.lock().await;
+    let tx = tx.as_mut().ok_or("The transaction has already ended")?;
+    execute_via(&state, &id, &sql, &options, Route::Transaction(tx)).await
+}
+
+/// Take transaction `tx_id` out of the state once any script running on it finishes.
+async fn finish(state: &AppState, tx_id: &str) -> Result<(String, OpenTransaction), String> {
+    let (id, slot) = state.transaction(tx_id)?;
+    state.transactions.lock().unwrap().remove(tx_id);
+    let tx = slot
+        .lock()
+        .await
+        .take()
+        .ok_or("The transaction has already ended")?;
+    Ok((id, tx))
+}
+
+#[tauri::command]
+pub async fn commit(state: State<'_, AppState>, tx_id: String) -> Result<(), String> {
+    let (id, tx) = finish(&state, &tx_id).await?;
+    let committed = tx.commit().await.map_err(|e| e.to_string());
+    // Results cached while the transaction was open don't show its writes.
+    state.result_cache.invalidate(&id, None);
+    state.audit(&id, "COMMIT", committed.as_ref().err().map(String::as_str));
+    committed
+}
+
+#[tauri::command]
+pub async fn rollback(state: State<'_, AppState>, tx_id: String) -> Result<(), String> {
+    let (id, tx) = finish(&state, &tx_id).await?;
+    let rolled_back = tx.rollback().await.map_err(|e| e.to_string());
+    state.audit(
+        &id,
+        "ROLLBACK",
+        rolled_back.as_ref().err().map(String::as_str),
+    );
+    rolled_back
+}
+
+/// Run `statement` on savepoint `name` of transaction `tx_id`.
+async fn on_savepoint(
+    state: &AppState,
+    tx_id: &str,
+    statement: &str,
+    name: &str,
+) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Savepoint names cannot be empty".to_string());
+    }
+    let (id, slot) = state.transaction(tx_id)?;
+    let mut tx = slot.lock().await;
+    let tx = tx.as_mut().ok_or("The transaction has already ended")?;
+    let sql = format!("{} {}", statement, sql::quote_ident(tx.kind(), name));
+    let ran = tx.run_control(&sql).await;
+    state.audit(&id, &sql, ran.as_ref().err().map(String::as_str));
+    ran
+}
+
+/// Mark a point in transaction `tx_id` that `rollback_to` can return to. Reusing a
+/// name moves the savepoint.
+#[tauri::command]
+pub async fn savepoint(
+    state: State<'_, AppState>,
+    tx_id: String,
+    name: String,
+) -> Result<(), String> {
+    on_savepoint(&state, &tx_id, "SAVEPOINT", &name).await
+}
+
+/// Undo what transaction `tx_id` did since savepoint `name`, keeping the savepoint and
+/// the rest of the transaction. Also recovers a Postgres transaction aborted by a
+/// failed statement.
+#[tauri::command]
+pub async fn rollback_to(
+    state: State<'_, AppState>,
+    tx_id: String,
+    name: String,
+) -> Result<(), String> {
+    on_savepoint(&state, &tx_id, "ROLLBACK TO SAVEPOINT", &name).await
+}
+
+/// Forget savepoint `name` and any set after it, keeping their changes.
+#[tauri::command]
+pub async fn release(
+    state: State<'_, AppState>,
+    tx_id: String,
+    name: String,
+) -> Result<(), String> {
+    on_savepoint(&state, &tx_id, "RELEASE SAVEPOINT", &name).await
+}
@@ -0,0 +1,54 @@
+//! Local relays for drivers that can't be handed a socket. The driver connects to a
+//! loopback port, and each connection is relayed over a stream opened another way:
+//! through a proxy, or the Cloud SQL connector.
+
+use std::future::Future;
+use std::sync::Arc;
+use tauri::async_runtime::JoinHandle;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+
+/// A listener relaying every connection to a stream from its dial function. It stops
+/// accepting when dropped; relayed connections run until either side closes.
+pub struct Tunnel {
+    port: u16,
+    accept: JoinHandle<()>,
+}
+
+impl Tunnel {
+    /// Listen on a free loopback port.
+    pub async fn serve<D, F, S>(dial: D) -> Result<Tunnel, String>
+    where
+        D: Fn() -> F + Send + Sync + 'static,
+        F: Future<Output = Result<S, String>> + Send + 'static,
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let listener = TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .map_err(|e| e.to_string())?;
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        let dial = Arc::new(dial);
+        let accept = tauri::async_runtime::spawn(async move {
+            while let Ok((mut client, _)) = listener.accept().await {
+                let upstream = dial();
+                tauri::async_runtime::spawn(async move {
+                    // A failed dial closes the client, which the driver reports.
+                    if let Ok(mut upstream) = upstream.await {
+                        let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+                    }
+                });
+            }
+        });
+        Ok(Tunnel { port, accept })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Drop for Tunnel {
+    fn drop(&mut self) {
+        self.accept.abort();
+    }
+}
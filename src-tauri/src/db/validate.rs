@@ -0,0 +1,124 @@
+//! Checking a script without running it, so the editor can underline problems before
+//! Run. Each statement is prepared on Postgres and MySQL, which parses it and (on
+//! Postgres) resolves its tables and columns, and compiled with `EXPLAIN` on SQLite.
+//! Statements are checked one by one against the current schema, so a statement that
+//! uses a table created earlier in the same script is reported as an error.
+
+use super::sql;
+use super::{AppState, Connection};
+use serde::Serialize;
+use sqlx::postgres::{PgDatabaseError, PgErrorPosition};
+use sqlx::Executor;
+use tauri::State;
+
+/// MySQL's `ER_UNSUPPORTED_PS`: the statement can't be prepared, which says nothing
+/// about whether it is valid.
+const MYSQL_UNSUPPORTED_PS: &str = "1295";
+
+#[derive(Debug, Serialize)]
+pub struct ValidationIssue {
+    /// Index of the statement in the script.
+    pub statement: usize,
+    pub message: String,
+    /// Where the database placed the problem, as a byte offset into the script, and as
+    /// a 1-based line and column (in characters). `None` when it didn't say.
+    pub offset: Option<usize>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+/// The problems found in `sql` on connection `id`; empty when every statement is valid.
+#[tauri::command]
+pub async fn validate(
+    state: State<'_, AppState>,
+    id: String,
+    sql: String,
+) -> Result<Vec<ValidationIssue>, String> {
+    let conn = state.connection(&id)?;
+    let mut issues = Vec::new();
+    for (index, stmt) in sql::split_statements(&sql).into_iter().enumerate() {
+        let start = stmt.as_ptr() as usize - sql.as_ptr() as usize;
+        let checked = match &conn {
+            Connection::Postgres(pool) => pool.prepare(stmt).await.map(drop),
+            Connection::MySql(pool) => pool.prepare(stmt).await.map(drop),
+            Connection::Sqlite(pool) => {
+                let explain = format!("EXPLAIN {}", stmt);
+                pool.execute(explain.as_str()).await.map(drop)
+            }
+            _ => return Err(conn.unsupported("Validation")),
+        };
+        let Err(error) = checked else {
+            continue;
+        };
+        let Some(db_error) = error.as_database_error() else {
+            return Err(error.to_string());
+        };
+        if matches!(&conn, Connection::MySql(_))
+            && db_error.code().as_deref() == Some(MYSQL_UNSUPPORTED_PS)
+        {
+            continue;
+        }
+        let message = db_error.message().to_string();
+        let within = match &conn {
+            Connection::Postgres(_) => db_error
+                .try_downcast_ref::<PgDatabaseError>()
+                .and_then(PgDatabaseError::position)
+                .and_then(|position| match position {
+                    // 1-based, in characters.
+                    PgErrorPosition::Original(at) => at.checked_sub(1),
+                    PgErrorPosition::Internal { .. } => None,
+                })
+                .and_then(|at| stmt.char_indices().nth(at))
+                .map(|(offset, _)| offset),
+            Connection::MySql(_) => mysql_position(stmt, &message),
+            _ => sqlite_position(stmt, &message),
+        };
+        let offset = within.map(|within| start + within);
+        let (line, column) = match offset {
+            Some(offset) => {
+                let before = &sql[..offset];
+                let line_start = before.rfind('\n').map_or(0, |at| at + 1);
+                (
+                    Some(before.matches('\n').count() + 1),
+                    Some(before[line_start..].chars().count() + 1),
+                )
+            }
+            None => (None, None),
+        };
+        issues.push(ValidationIssue {
+            statement: index,
+            message,
+            offset,
+            line,
+            column,
+        });
+    }
+    Ok(issues)
+}
+
+/// Locate MySQL's `... near 'text' at line N` within `stmt`. An empty `text` means the
+/// statement ended too soon.
+fn mysql_position(stmt: &str, message: &str) -> Option<usize> {
+    let near = &message[message.find("near '")? + "near '".len()..];
+    let (text, line) = near.rsplit_once("' at line ")?;
+    let line: usize = line.trim().parse().ok()?;
+    if text.is_empty() {
+        return Some(stmt.len());
+    }
+    let line_start = match line {
+        0 | 1 => 0,
+        _ => stmt.match_indices('\n').nth(line - 2)?.0 + 1,
+    };
+    stmt[line_start..].find(text).map(|at| line_start + at)
+}
+
+/// Locate SQLite's `near "text": ...` within `stmt`; `incomplete input` is placed at
+/// its end.
+fn sqlite_position(stmt: &str, message: &str) -> Option<usize> {
+    if message.starts_with("incomplete input") {
+        return Some(stmt.len());
+    }
+    let near = &message[message.find("near \"")? + "near \"".len()..];
+    let (text, _) = near.split_once("\":")?;
+    stmt.find(text)
+}
@@ -0,0 +1,200 @@
+//! Watched queries for monitoring dashboards. `watch_query` runs a read-only script
+//! through `execute` on an interval and emits `db://watch-update` only when its rows
+//! differ from the previous run's, with the rows added and removed, so the UI doesn't
+//! have to poll and compare results itself. A watch runs until `unwatch_query` or until
+//! its connection closes.
+
+use super::convert::ResultFormat;
+use super::sql;
+use super::{execute_on, generate_id, AppState, ExecuteOptions};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Notify;
+use tokio::time::MissedTickBehavior;
+
+/// Shortest interval a query can be watched at, so a dashboard can't hammer the server.
+pub const MIN_WATCH_INTERVAL_MS: u64 = 1000;
+
+/// The connection id a watch runs on, and the signal that stops it.
+pub type WatchSlot = (String, Arc<Notify>);
+
+/// Emitted as `db://watch-update` after the first run, whenever the rows change, and
+/// whenever a run fails with a different error than the run before it.
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchUpdate {
+    pub watch_id: String,
+    /// The connection the watch runs on.
+    pub id: String,
+    pub columns: Vec<String>,
+    /// Every row of the latest run; empty when it failed.
+    pub rows: Vec<Vec<Value>>,
+    /// Rows of the latest run that weren't in the previous one; every row on the first.
+    pub added: Vec<Vec<Value>>,
+    /// Rows of the previous run that aren't in the latest one.
+    pub removed: Vec<Vec<Value>>,
+    /// Why the latest run failed. The watch keeps running, and the next successful run
+    /// is compared with the last successful one.
+    pub error: Option<String>,
+}
+
+impl AppState {
+    /// Stop the watches running on connection `id`.
+    pub fn end_watches(&self, id: &str) {
+        self.watches
+            .lock()
+            .unwrap()
+            .retain(|_, (connection_id, stop)| {
+                if connection_id == id {
+                    stop.notify_one();
+                }
+                connection_id != id
+            });
+    }
+}
+
+/// Run the read-only `sql` on connection `id` every `interval_ms` (at least
+/// `MIN_WATCH_INTERVAL_MS`) as `execute` would with `options`, and return the watch id.
+/// Results are compared as tables whatever `options.format` says, and are capped to
+/// what fits in memory rather than spilled. Runs don't count as activity for
+/// `auto_lock`.
+#[tauri::command]
+pub async fn watch_query(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+    sql: String,
+    interval_ms: u64,
+    options: Option<ExecuteOptions>,
+) -> Result<String, String> {
+    state.connection(&id)?;
+    if !sql::is_read_only(&sql) {
+        return Err("Only scripts that just read can be watched".to_string());
+    }
+    let mut options = options.unwrap_or_default();
+    options.format = ResultFormat::Table;
+    options.background = true;
+    let interval = Duration::from_millis(interval_ms.max(MIN_WATCH_INTERVAL_MS));
+    let watch_id = generate_id("watch");
+    let stop = Arc::new(Notify::new());
+    state
+        .watches
+        .lock()
+        .unwrap()
+        .insert(watch_id.clone(), (id.clone(), stop.clone()));
+
+    let task_watch_id = watch_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut previous: Option<(Vec<String>, Vec<Vec<Value>>)> = None;
+        let mut last_error: Option<String> = None;
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = stop.notified() => break,
+            }
+            let result = execute_on(&state, &id, &sql, &options)
+                .await
+                .and_then(|mut output| {
+                    output
+                        .take_table()
+                        .ok_or_else(|| "The query returned no rows to compare".to_string())
+                });
+            let update = match result {
+                Ok((columns, rows)) => {
+                    let recovered = last_error.take().is_some();
+                    let (added, removed) = match &previous {
+                        Some((old_columns, old_rows)) if *old_columns == columns => {
+                            diff_rows(old_rows, &rows)
+                        }
+                        Some((_, old_rows)) => (rows.clone(), old_rows.clone()),
+                        None => (rows.clone(), Vec::new()),
+                    };
+                    let changed = match &previous {
+                        Some((old_columns, _)) => {
+                            *old_columns != columns || !added.is_empty() || !removed.is_empty()
+                        }
+                        None => true,
+                    };
+                    let update = (changed || recovered).then(|| WatchUpdate {
+                        watch_id: task_watch_id.clone(),
+                        id: id.clone(),
+                        columns: columns.clone(),
+                        rows: rows.clone(),
+                        added,
+                        removed,
+                        error: None,
+                    });
+                    previous = Some((columns, rows));
+                    update
+                }
+                Err(e) => {
+                    if !state.connections.lock().unwrap().contains_key(&id) {
+                        break;
+                    }
+                    let repeated = last_error.as_ref() == Some(&e);
+                    last_error = Some(e.clone());
+                    (!repeated).then(|| WatchUpdate {
+                        watch_id: task_watch_id.clone(),
+                        id: id.clone(),
+                        columns: Vec::new(),
+                        rows: Vec::new(),
+                        added: Vec::new(),
+                        removed: Vec::new(),
+                        error: Some(e),
+                    })
+                }
+            };
+            if let Some(update) = update {
+                state.emit("db://watch-update", update);
+            }
+        }
+        state.watches.lock().unwrap().remove(&task_watch_id);
+    });
+    Ok(watch_id)
+}
+
+/// Stop watch `watch_id`. Returns `false` if it had already stopped.
+#[tauri::command]
+pub fn unwatch_query(state: State<'_, AppState>, watch_id: String) -> bool {
+    match state.watches.lock().unwrap().remove(&watch_id) {
+        Some((_, stop)) => {
+            stop.notify_one();
+            true
+        }
+        None => false,
+    }
+}
+
+/// The rows of `new` not in `old` and of `old` not in `new`, counting duplicates.
+fn diff_rows(old: &[Vec<Value>], new: &[Vec<Value>]) -> (Vec<Vec<Value>>, Vec<Vec<Value>>) {
+    let key = |row: &Vec<Value>| serde_json::to_string(row).unwrap_or_default();
+    let mut remaining: HashMap<String, usize> = HashMap::new();
+    for row in old {
+        *remaining.entry(key(row)).or_default() += 1;
+    }
+    let mut added = Vec::new();
+    for row in new {
+        match remaining.get_mut(&key(row)) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => added.push(row.clone()),
+        }
+    }
+    let removed = old
+        .iter()
+        .filter(|&row| {
+            remaining.get_mut(&key(row)).is_some_and(|count| {
+                let left = *count > 0;
+                *count = count.saturating_sub(1);
+                left
+            })
+        })
+        .cloned()
+        .collect();
+    (added, removed)
+}
@@ -1,6 +1,11 @@
 mod db;
 
 use db::AppState;
+use std::time::Duration;
+use tauri::{Manager, RunEvent};
+
+/// How long closing connections may hold up exiting.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
@@ -13,13 +18,109 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .manage(AppState::new())
+        .setup(|app| {
+            let state = app.state::<AppState>();
+            state.set_app_handle(app.handle().clone());
+            if let Ok(dir) = app.path().app_data_dir() {
+                state.set_data_dir(dir);
+            }
+            db::autolock::spawn_monitor(app.handle().clone());
+            db::pooling::spawn_reaper(app.handle().clone());
+            db::health::spawn_monitor(app.handle().clone());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             greet,
             db::connect,
+            db::probe::test_connection,
             db::disconnect,
+            db::reconnect::reconnect,
+            db::clone::clone_connection,
+            db::switch::switch_database,
+            db::health::ping,
+            db::health::set_health_monitor,
+            db::warmup,
+            db::pool_stats::get_acquire_stats,
+            db::registry::list_connections,
+            db::registry::pool_status,
             db::execute,
-            db::get_tables
+            db::batch::execute_many,
+            db::transactions::begin_transaction,
+            db::transactions::execute_in_transaction,
+            db::transactions::commit,
+            db::transactions::rollback,
+            db::transactions::savepoint,
+            db::transactions::rollback_to,
+            db::transactions::release,
+            db::dedicated::open_session,
+            db::dedicated::close_session,
+            db::cancel::cancel_query,
+            db::jobs::submit_query,
+            db::jobs::job_status,
+            db::jobs::job_result,
+            db::jobs::cancel_job,
+            db::cursor::open_cursor,
+            db::cursor::fetch_next,
+            db::cursor::close_cursor,
+            db::explain::explain,
+            db::procedures::call_procedure,
+            db::validate::validate,
+            db::watch::watch_query,
+            db::watch::unwatch_query,
+            db::spill::fetch_spilled,
+            db::spill::release_spilled,
+            db::payload::execute_binary,
+            db::stream::execute_stream,
+            db::stream::ack_stream,
+            db::stream::cancel_stream,
+            db::audit::verify_audit_log,
+            db::audit::export_audit_log,
+            db::autolock::set_auto_lock,
+            db::autolock::touch_activity,
+            db::autolock::lock_now,
+            db::credentials::credentials_status,
+            db::credentials::unlock_credentials,
+            db::credentials::lock_credentials,
+            db::credentials::change_master_password,
+            db::credentials::save_credential,
+            db::credentials::get_credential,
+            db::credentials::delete_credential,
+            db::keychain::save_keychain_secret,
+            db::keychain::get_keychain_secret,
+            db::keychain::delete_keychain_secret,
+            db::jdbc::parse_jdbc_url,
+            db::pinning::get_server_fingerprint,
+            db::policy::get_capability,
+            db::policy::request_confirmation,
+            db::result_cache::invalidate_cache,
+            db::profiles::list_profiles,
+            db::profiles::save_profile,
+            db::profiles::delete_profile,
+            db::profiles::connect_profile,
+            db::profiles::forget_session_password,
+            db::secrets::set_vault_config,
+            db::sessions::get_active_sessions,
+            db::sessions::get_locks,
+            db::maintenance::run_maintenance,
+            db::schema_diff::diff_schemas,
+            db::sessions::terminate_session,
+            db::sql::quote_identifier,
+            db::sql::quote_literal,
+            db::introspect::get_tables,
+            db::introspect::get_columns,
+            db::introspect::get_sequences,
+            db::introspect::refresh_schema,
+            db::browse::get_table_data,
+            db::browse::safe_select,
+            db::count::estimate_count
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Closing the last window exits too, so this covers both.
+            if let RunEvent::Exit = event {
+                let state = app.state::<AppState>();
+                tauri::async_runtime::block_on(db::shutdown_all(&state, SHUTDOWN_TIMEOUT));
+            }
+        });
 }
@@ -21,6 +21,16 @@ export function ConnectionManager({ onConnect, activeId }: ConnectionManagerProp
       if (connString.startsWith("postgres")) name = "PostgreSQL";
       else if (connString.startsWith("mysql")) name = "MySQL";
       else if (connString.startsWith("sqlite")) name = "SQLite";
+      else if (connString.startsWith("oracle")) name = "Oracle";
+      else if (connString.startsWith("mongodb")) name = "MongoDB";
+      else if (connString.startsWith("duckdb") || connString.endsWith(".duckdb")) name = "DuckDB";
+      else if (connString.startsWith("clickhouse")) name = "ClickHouse";
+      else if (connString.startsWith("redis")) name = "Redis";
+      else if (connString.startsWith("cassandra") || connString.startsWith("scylla")) name = "Cassandra";
+      else if (connString.startsWith("surreal")) name = "SurrealDB";
+      else if (connString.startsWith("bigquery")) name = "BigQuery";
+      else if (connString.startsWith("firebird")) name = "Firebird";
+      else if (connString.startsWith("odbc")) name = "ODBC";
       
       onConnect(id, name);
     } catch (e) {
@@ -2,6 +2,7 @@ import { useState } from "preact/hooks";
 import { invoke } from "@tauri-apps/api/core";
 import { Button } from "./ui/Button";
 import { Play } from "lucide-preact";
+import { tableOf, timingsOf, type QueryOutput, type Timings } from "../lib/results";
 
 interface QueryRunnerProps {
   connectionId: string;
@@ -9,24 +10,26 @@ interface QueryRunnerProps {
 
 export function QueryRunner({ connectionId }: QueryRunnerProps) {
   const [sql, setSql] = useState("SELECT * FROM information_schema.tables LIMIT 10;");
-  // eslint-disable-next-line @typescript-eslint/no-explicit-any
-  const [results, setResults] = useState<any[]>([]);
+  const [results, setResults] = useState<unknown[][]>([]);
   const [columns, setColumns] = useState<string[]>([]);
+  const [timings, setTimings] = useState<Timings | null>(null);
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
 
   async function runQuery() {
     setLoading(true);
     setError(null);
+    setTimings(null);
     try {
-      // eslint-disable-next-line @typescript-eslint/no-explicit-any
-      const data = await invoke("execute", { id: connectionId, sql }) as any[];
-      setResults(data);
-      if (data.length > 0) {
-        setColumns(Object.keys(data[0]));
-      } else {
-        setColumns([]);
-      }
+      const output = await invoke("execute", {
+        id: connectionId,
+        sql,
+        options: { format: "table", timings: true },
+      }) as QueryOutput;
+      const table = tableOf(output);
+      setResults(table.rows);
+      setColumns(table.columns);
+      setTimings(timingsOf(output));
     } catch (e) {
       setError(String(e));
     } finally {
@@ -38,6 +41,11 @@ export function QueryRunner({ connectionId }: QueryRunnerProps) {
     <div class="flex flex-col h-full bg-white dark:bg-zinc-950">
         <div class="p-2 border-b border-zinc-200 dark:border-zinc-800 flex gap-2 bg-zinc-50 dark:bg-zinc-900 justify-between items-center">
             <h3 class="text-sm font-semibold text-zinc-600 dark:text-zinc-400 px-2">Query Editor</h3>
+            {timings && (
+                <span class="text-xs text-zinc-500 font-mono" title="acquire / execute / fetch / serialize">
+                    {timings.total_ms} ms ({timings.acquire_ms ?? "-"} / {timings.execute_ms} / {timings.fetch_ms} / {timings.serialize_ms})
+                </span>
+            )}
             <Button size="sm" onClick={runQuery} disabled={loading}>
                 <Play class="w-3 h-3 mr-2" /> 
                 {loading ? "Running..." : "Run Query"}
@@ -74,9 +82,9 @@ export function QueryRunner({ connectionId }: QueryRunnerProps) {
                     <tbody class="divide-y divide-zinc-100 dark:divide-zinc-800 bg-white dark:bg-zinc-950">
                         {results.map((row) => (
                             <tr class="hover:bg-blue-50 dark:hover:bg-blue-900/10 transition-colors">
-                                {columns.map(col => (
-                                    <td class="p-2 border-r dark:border-zinc-800 last:border-r-0 font-mono text-xs text-zinc-600 dark:text-zinc-400 max-w-[300px] truncate whitespace-nowrap" title={String(row[col])}>
-                                        {row[col] === null ? <span class="text-zinc-300 italic">null</span> : String(row[col])}
+                                {columns.map((_, i) => (
+                                    <td class="p-2 border-r dark:border-zinc-800 last:border-r-0 font-mono text-xs text-zinc-600 dark:text-zinc-400 max-w-[300px] truncate whitespace-nowrap" title={String(row[i])}>
+                                        {row[i] === null ? <span class="text-zinc-300 italic">null</span> : String(row[i])}
                                     </td>
                                 ))}
                             </tr>
@@ -0,0 +1,279 @@
+/**
+ * Minimal MessagePack and CBOR decoders for `execute_binary` payloads.
+ *
+ * Only what the backend writes is supported: integers, floats, strings, byte strings,
+ * arrays, maps keyed by strings, booleans and null. 64-bit integers become numbers,
+ * losing precision past 2^53 just as `JSON.parse` does.
+ */
+
+const utf8 = new TextDecoder();
+
+class Reader {
+	private view: DataView;
+	pos = 0;
+
+	constructor(private bytes: Uint8Array) {
+		this.view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+	}
+
+	private take(length: number): number {
+		const at = this.pos;
+		if (at + length > this.bytes.length) throw new Error('Payload ended unexpectedly');
+		this.pos += length;
+		return at;
+	}
+
+	u8(): number {
+		return this.view.getUint8(this.take(1));
+	}
+	u16(): number {
+		return this.view.getUint16(this.take(2));
+	}
+	u32(): number {
+		return this.view.getUint32(this.take(4));
+	}
+	u64(): number {
+		return this.u32() * 2 ** 32 + this.u32();
+	}
+	i8(): number {
+		return this.view.getInt8(this.take(1));
+	}
+	i16(): number {
+		return this.view.getInt16(this.take(2));
+	}
+	i32(): number {
+		return this.view.getInt32(this.take(4));
+	}
+	i64(): number {
+		return this.i32() * 2 ** 32 + this.u32();
+	}
+	f32(): number {
+		return this.view.getFloat32(this.take(4));
+	}
+	f64(): number {
+		return this.view.getFloat64(this.take(8));
+	}
+	raw(length: number): Uint8Array {
+		const at = this.take(length);
+		return this.bytes.subarray(at, at + length);
+	}
+	str(length: number): string {
+		return utf8.decode(this.raw(length));
+	}
+}
+
+function finish(reader: Reader, length: number, value: unknown): unknown {
+	if (reader.pos !== length) throw new Error('Trailing bytes after payload');
+	return value;
+}
+
+/** Decode one MessagePack value. */
+export function decodeMessagePack(bytes: Uint8Array): unknown {
+	const reader = new Reader(bytes);
+	return finish(reader, bytes.length, readMessagePack(reader));
+}
+
+function readMessagePack(r: Reader): unknown {
+	const b = r.u8();
+	if (b <= 0x7f) return b;
+	if (b >= 0xe0) return b - 0x100;
+	if ((b & 0xf0) === 0x80) return readMap(r, b & 0x0f);
+	if ((b & 0xf0) === 0x90) return readArray(r, b & 0x0f);
+	if ((b & 0xe0) === 0xa0) return r.str(b & 0x1f);
+	switch (b) {
+		case 0xc0:
+			return null;
+		case 0xc2:
+			return false;
+		case 0xc3:
+			return true;
+		case 0xc4:
+			return r.raw(r.u8()).slice();
+		case 0xc5:
+			return r.raw(r.u16()).slice();
+		case 0xc6:
+			return r.raw(r.u32()).slice();
+		case 0xca:
+			return r.f32();
+		case 0xcb:
+			return r.f64();
+		case 0xcc:
+			return r.u8();
+		case 0xcd:
+			return r.u16();
+		case 0xce:
+			return r.u32();
+		case 0xcf:
+			return r.u64();
+		case 0xd0:
+			return r.i8();
+		case 0xd1:
+			return r.i16();
+		case 0xd2:
+			return r.i32();
+		case 0xd3:
+			return r.i64();
+		case 0xd9:
+			return r.str(r.u8());
+		case 0xda:
+			return r.str(r.u16());
+		case 0xdb:
+			return r.str(r.u32());
+		case 0xdc:
+			return readArray(r, r.u16());
+		case 0xdd:
+			return readArray(r, r.u32());
+		case 0xde:
+			return readMap(r, r.u16());
+		case 0xdf:
+			return readMap(r, r.u32());
+		default:
+			throw new Error(`Unsupported MessagePack type 0x${b.toString(16)}`);
+	}
+}
+
+function readArray(r: Reader, length: number): unknown[] {
+	const items = new Array(length);
+	for (let i = 0; i < length; i++) items[i] = readMessagePack(r);
+	return items;
+}
+
+function readMap(r: Reader, length: number): Record<string, unknown> {
+	const map: Record<string, unknown> = {};
+	for (let i = 0; i < length; i++) {
+		const key = String(readMessagePack(r));
+		map[key] = readMessagePack(r);
+	}
+	return map;
+}
+
+/** Marks the end of an indefinite-length CBOR item. */
+const BREAK = Symbol('break');
+
+/** Decode one CBOR value. */
+export function decodeCbor(bytes: Uint8Array): unknown {
+	const reader = new Reader(bytes);
+	return finish(reader, bytes.length, readCbor(reader));
+}
+
+function readCbor(r: Reader): unknown {
+	const value = readCborItem(r);
+	if (value === BREAK) throw new Error('Unexpected CBOR break');
+	return value;
+}
+
+/** The argument of an item with additional info `info`, or -1 for indefinite length. */
+function cborArgument(r: Reader, info: number): number {
+	if (info < 24) return info;
+	switch (info) {
+		case 24:
+			return r.u8();
+		case 25:
+			return r.u16();
+		case 26:
+			return r.u32();
+		case 27:
+			return r.u64();
+		case 31:
+			return -1;
+		default:
+			throw new Error(`Invalid CBOR additional info ${info}`);
+	}
+}
+
+function readCborItem(r: Reader): unknown {
+	const b = r.u8();
+	const major = b >> 5;
+	const info = b & 0x1f;
+	if (major === 7) {
+		switch (info) {
+			case 20:
+				return false;
+			case 21:
+				return true;
+			case 22:
+			case 23:
+				return null;
+			case 25:
+				return halfFloat(r.u16());
+			case 26:
+				return r.f32();
+			case 27:
+				return r.f64();
+			case 31:
+				return BREAK;
+			default:
+				throw new Error(`Unsupported CBOR simple value ${info}`);
+		}
+	}
+	const arg = cborArgument(r, info);
+	switch (major) {
+		case 0:
+			return arg;
+		case 1:
+			return -1 - arg;
+		case 2:
+			return arg < 0 ? concat(chunks(r, 2)) : r.raw(arg).slice();
+		case 3:
+			return arg < 0 ? chunks(r, 3).map((c) => utf8.decode(c)).join('') : r.str(arg);
+		case 4: {
+			const items: unknown[] = [];
+			for (let i = 0; arg < 0 || i < arg; i++) {
+				const item = readCborItem(r);
+				if (item === BREAK) {
+					if (arg < 0) break;
+					throw new Error('Unexpected CBOR break');
+				}
+				items.push(item);
+			}
+			return items;
+		}
+		case 5: {
+			const map: Record<string, unknown> = {};
+			for (let i = 0; arg < 0 || i < arg; i++) {
+				const key = readCborItem(r);
+				if (key === BREAK) {
+					if (arg < 0) break;
+					throw new Error('Unexpected CBOR break');
+				}
+				map[String(key)] = readCbor(r);
+			}
+			return map;
+		}
+		default:
+			// Tags carry no meaning for these payloads; read the tagged item as-is.
+			return readCbor(r);
+	}
+}
+
+/** The chunks of an indefinite-length byte or text string of major type `major`. */
+function chunks(r: Reader, major: number): Uint8Array[] {
+	const parts: Uint8Array[] = [];
+	for (;;) {
+		const b = r.u8();
+		if (b === 0xff) return parts;
+		if (b >> 5 !== major) throw new Error('Invalid CBOR string chunk');
+		const length = cborArgument(r, b & 0x1f);
+		if (length < 0) throw new Error('Nested indefinite CBOR string');
+		parts.push(r.raw(length));
+	}
+}
+
+function concat(parts: Uint8Array[]): Uint8Array {
+	const out = new Uint8Array(parts.reduce((n, p) => n + p.length, 0));
+	let at = 0;
+	for (const part of parts) {
+		out.set(part, at);
+		at += part.length;
+	}
+	return out;
+}
+
+function halfFloat(bits: number): number {
+	const exponent = (bits >> 10) & 0x1f;
+	const fraction = bits & 0x3ff;
+	const sign = bits & 0x8000 ? -1 : 1;
+	if (exponent === 0) return sign * 2 ** -14 * (fraction / 1024);
+	if (exponent === 31) return fraction ? NaN : sign * Infinity;
+	return sign * 2 ** (exponent - 15) * (1 + fraction / 1024);
+}
@@ -0,0 +1,82 @@
+/**
+ * Decoding for results returned by the `execute_binary` command.
+ *
+ * With compression enabled the payload starts with a codec marker byte:
+ * 0 = stored, 1 = deflate (zlib), 2 = zstd, 3 = gzip.
+ */
+
+import { decodeCbor, decodeMessagePack } from './binary';
+
+/**
+ * Wire encoding of the result, as passed to `execute_binary` as `encoding`.
+ *
+ * `arrow` payloads are an Arrow IPC stream for `tableFromIPC` from apache-arrow; the
+ * rest of the result is JSON in the schema metadata under `ARROW_OUTPUT_METADATA_KEY`.
+ */
+export type PayloadEncoding = 'json' | 'message_pack' | 'cbor' | 'arrow';
+
+export const ARROW_OUTPUT_METADATA_KEY = 'query_output';
+
+export type Codec = 'deflate' | 'zstd' | 'gzip';
+
+export interface CompressionOptions {
+	codec: Codec;
+	threshold?: number;
+}
+
+/** Browsers have no native zstd support; pass a decoder (e.g. from `fzstd`) to use it. */
+export type ZstdDecoder = (data: Uint8Array) => Uint8Array;
+
+async function inflate(
+	data: Uint8Array,
+	format: 'deflate' | 'gzip' = 'deflate'
+): Promise<Uint8Array> {
+	const stream = new Blob([data]).stream().pipeThrough(new DecompressionStream(format));
+	return new Uint8Array(await new Response(stream).arrayBuffer());
+}
+
+/** Strip the codec marker and decompress the body. */
+export async function decompressPayload(
+	buffer: ArrayBuffer,
+	zstd?: ZstdDecoder
+): Promise<Uint8Array> {
+	const bytes = new Uint8Array(buffer);
+	const body = bytes.subarray(1);
+	switch (bytes[0]) {
+		case 0:
+			return body;
+		case 1:
+			return inflate(body);
+		case 2:
+			if (!zstd) throw new Error('Payload is zstd-compressed but no zstd decoder was provided');
+			return zstd(body);
+		case 3:
+			return inflate(body, 'gzip');
+		default:
+			throw new Error(`Unknown payload codec marker ${bytes[0]}`);
+	}
+}
+
+/**
+ * Decode a payload in the `encoding` it was requested in (JSON by default),
+ * decompressing it first when it was requested with compression. `arrow` payloads
+ * come back as their IPC bytes, to be read with an Arrow library.
+ */
+export async function decodePayload<T = unknown>(
+	buffer: ArrayBuffer,
+	options: { compressed?: boolean; zstd?: ZstdDecoder; encoding?: PayloadEncoding } = {}
+): Promise<T> {
+	const bytes = options.compressed
+		? await decompressPayload(buffer, options.zstd)
+		: new Uint8Array(buffer);
+	switch (options.encoding ?? 'json') {
+		case 'message_pack':
+			return decodeMessagePack(bytes) as T;
+		case 'cbor':
+			return decodeCbor(bytes) as T;
+		case 'arrow':
+			return bytes as T;
+		default:
+			return JSON.parse(new TextDecoder().decode(bytes)) as T;
+	}
+}
@@ -0,0 +1,30 @@
+/**
+ * Types for query plans returned by the `explain` command: one tree of nodes whatever
+ * the database, with the plan as the database returned it in `raw`. With `analyze`,
+ * the statement is run inside a transaction that is rolled back, and the nodes carry
+ * what it actually did.
+ */
+
+export interface PlanNode {
+	/** What the node does, e.g. `Seq Scan`, `SEARCH` or `nested_loop`. */
+	operation: string;
+	/** The table the node reads, if any. */
+	relation: string | null;
+	/** The database's own one-line description of the node, where it gives one. */
+	detail: string | null;
+	estimated_rows: number | null;
+	/** The planner's cost for the node and its children, in the database's own units. */
+	estimated_cost: number | null;
+	/** Rows the node returned over all its loops; only set in analyze mode. */
+	actual_rows: number | null;
+	/** Milliseconds spent in the node and its children over all its loops; only set in analyze mode. */
+	actual_time_ms: number | null;
+	/** Everything else the database reported about the node. */
+	properties: Record<string, unknown>;
+	children: PlanNode[];
+}
+
+export interface QueryPlan {
+	root: PlanNode;
+	raw: unknown;
+}
@@ -0,0 +1,15 @@
+/**
+ * Types for the `call_procedure` command. `args` are the routine's IN and INOUT
+ * arguments in declaration order; OUT and INOUT values come back in `out_params`.
+ * On a connection tagged `production`, pass `confirmationToken` from
+ * `request_confirmation` for the script `CALL <name>`.
+ */
+
+import type { TableOutput } from './results';
+
+export interface ProcedureResult {
+	/** The result sets the routine returned, in order. */
+	result_sets: TableOutput[];
+	/** The values of the OUT and INOUT parameters, by name. */
+	out_params: Record<string, unknown>;
+}
@@ -0,0 +1,181 @@
+/**
+ * Types and decoding for results returned by the `execute` command.
+ *
+ * `options.format` selects the shape of the rows: `rows` (one object per row, the
+ * default), `columnar` (one array per column) or `table` (column names once, then one
+ * array per row). Wrappers such as `LimitInjected` or `Truncated` carry the rows in
+ * `result`; `tableOf` unwraps them and reads any of the three shapes.
+ */
+
+export type ResultFormat = 'rows' | 'columnar' | 'table';
+
+export type RowsOutput = Record<string, unknown>[];
+
+export interface ColumnarOutput {
+	columns: string[];
+	data: Record<string, unknown[]>;
+}
+
+export interface TableOutput {
+	columns: string[];
+	rows: unknown[][];
+}
+
+export interface SpilledOutput {
+	spill_id: string;
+	total_rows: number;
+	columns: string[];
+	page: QueryOutput;
+}
+
+export interface LimitInjectedOutput {
+	injected_limit: number;
+	truncated: boolean;
+	result: QueryOutput;
+}
+
+export interface ExecutedOutput {
+	rows_affected: number;
+	last_insert_id: number | null;
+	result: QueryOutput;
+}
+
+export interface TruncatedOutput {
+	truncated_by: 'max_rows' | 'max_bytes';
+	cap: number;
+	result: QueryOutput;
+}
+
+export interface ColumnMeta {
+	name: string;
+	/** The type name as the database reports it, e.g. `INT8` or `VARCHAR`. */
+	db_type: string;
+	/** `null` for types whose values aren't converted and always come back null. */
+	json_type: 'number' | 'boolean' | 'string' | 'null';
+	/** `null` where the driver can't tell. */
+	nullable: boolean | null;
+}
+
+export interface DescribedOutput {
+	column_meta: ColumnMeta[];
+	result: QueryOutput;
+}
+
+/** Milliseconds spent in each phase of a query run with `timings: true`. */
+export interface Timings {
+	/** `null` for backends whose driver connects by itself. */
+	acquire_ms: number | null;
+	execute_ms: number;
+	fetch_ms: number;
+	serialize_ms: number;
+	total_ms: number;
+}
+
+export interface TimedOutput {
+	timings: Timings;
+	result: QueryOutput;
+}
+
+/** A result served from the cache, asked for with `cache_ttl_ms`. */
+export interface CachedOutput {
+	cached_age_ms: number;
+	result: QueryOutput;
+}
+
+/** The script succeeded after `retries` retries following transient errors. */
+export interface RetriedOutput {
+	retries: number;
+	result: QueryOutput;
+}
+
+export type DestructiveReason =
+	| 'drop'
+	| 'truncate'
+	| 'delete_without_where'
+	| 'update_without_where'
+	| 'alter'
+	| 'production_write';
+
+export interface DestructiveStatement {
+	/** Position of the statement within the script. */
+	index: number;
+	reason: DestructiveReason;
+	statement: string;
+}
+
+/**
+ * Nothing was run. Show `statements` to the user and, if they agree, run the script
+ * again with the `token` from `request_confirmation` as `options.confirmation_token`.
+ */
+export interface NeedsConfirmationOutput {
+	needs_confirmation: true;
+	statements: DestructiveStatement[];
+}
+
+/** Returned by the `request_confirmation` command. */
+export interface ConfirmationRequest {
+	token: string;
+	statements: DestructiveStatement[];
+}
+
+export interface TimedOutOutput {
+	timed_out: true;
+	timeout_ms: number;
+}
+
+export type QueryOutput =
+	| RowsOutput
+	| ColumnarOutput
+	| TableOutput
+	| SpilledOutput
+	| LimitInjectedOutput
+	| ExecutedOutput
+	| TruncatedOutput
+	| DescribedOutput
+	| TimedOutput
+	| RetriedOutput
+	| CachedOutput
+	| NeedsConfirmationOutput
+	| TimedOutOutput;
+
+/** The rows of a result as a table, whichever format it was requested in. */
+export function tableOf(output: QueryOutput): TableOutput {
+	if (Array.isArray(output)) {
+		const columns = output.length > 0 ? Object.keys(output[0]) : [];
+		return { columns, rows: output.map((row) => columns.map((column) => row[column])) };
+	}
+	if ('result' in output) return tableOf(output.result);
+	if ('page' in output) return tableOf(output.page);
+	if ('rows' in output) return output;
+	if ('data' in output) {
+		const values = output.columns.map((column) => output.data[column] ?? []);
+		const count = values.length > 0 ? values[0].length : 0;
+		const rows = Array.from({ length: count }, (_, i) => values.map((column) => column[i]));
+		return { columns: output.columns, rows };
+	}
+	return { columns: [], rows: [] };
+}
+
+/** The column metadata of a result run with `column_meta: true`, if it has any. */
+export function columnMetaOf(output: QueryOutput): ColumnMeta[] | null {
+	if (Array.isArray(output)) return null;
+	if ('column_meta' in output) return output.column_meta;
+	if ('result' in output) return columnMetaOf(output.result);
+	return null;
+}
+
+/** How many times the script was retried before it succeeded; 0 when it wasn't. */
+export function retriesOf(output: QueryOutput): number {
+	if (Array.isArray(output)) return 0;
+	if ('retries' in output) return output.retries;
+	if ('result' in output) return retriesOf(output.result);
+	return 0;
+}
+
+/** The timings of a result run with `timings: true`, if it has them. */
+export function timingsOf(output: QueryOutput): Timings | null {
+	if (Array.isArray(output)) return null;
+	if ('timings' in output) return output.timings;
+	if ('result' in output) return timingsOf(output.result);
+	return null;
+}
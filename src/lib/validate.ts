@@ -0,0 +1,15 @@
+/**
+ * Types for the `validate` command, which checks a script without running it and
+ * reports where each problem is so the editor can underline it.
+ */
+
+export interface ValidationIssue {
+	/** Index of the statement in the script. */
+	statement: number;
+	message: string;
+	/** Byte offset into the script, when the database placed the problem. */
+	offset: number | null;
+	/** 1-based line and column (in characters) of `offset`. */
+	line: number | null;
+	column: number | null;
+}
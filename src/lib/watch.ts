@@ -0,0 +1,19 @@
+/**
+ * Types for `watch_query`, which re-runs a read-only script on an interval and emits
+ * `db://watch-update` when its rows change. Stop a watch with `unwatch_query`.
+ */
+
+export interface WatchUpdate {
+	watch_id: string;
+	/** The connection the watch runs on. */
+	id: string;
+	columns: string[];
+	/** Every row of the latest run; empty when it failed. */
+	rows: unknown[][];
+	/** Rows not in the previous run; every row on the first. */
+	added: unknown[][];
+	/** Rows of the previous run missing from the latest one. */
+	removed: unknown[][];
+	/** Why the latest run failed. The watch keeps running. */
+	error: string | null;
+}